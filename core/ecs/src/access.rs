@@ -0,0 +1,183 @@
+use std::any::{type_name, TypeId};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Component and resource types a query or system reads and writes, used to reject aliasing
+/// queries and to decide which systems can run at the same time.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Access {
+  reads: BTreeMap<TypeId, &'static str>,
+  writes: BTreeMap<TypeId, &'static str>,
+  resource_reads: BTreeMap<TypeId, &'static str>,
+  resource_writes: BTreeMap<TypeId, &'static str>,
+}
+
+impl Access {
+  pub fn read<T: 'static>(&mut self) -> &mut Self {
+    self.reads.insert(TypeId::of::<T>(), type_name::<T>());
+    self
+  }
+
+  pub fn write<T: 'static>(&mut self) -> &mut Self {
+    self.writes.insert(TypeId::of::<T>(), type_name::<T>());
+    self
+  }
+
+  pub fn read_resource<R: 'static>(&mut self) -> &mut Self {
+    self.resource_reads.insert(TypeId::of::<R>(), type_name::<R>());
+    self
+  }
+
+  pub fn write_resource<R: 'static>(&mut self) -> &mut Self {
+    self.resource_writes.insert(TypeId::of::<R>(), type_name::<R>());
+    self
+  }
+
+  pub fn extend(&mut self, other: &Access) {
+    self.reads.extend(&other.reads);
+    self.writes.extend(&other.writes);
+    self.resource_reads.extend(&other.resource_reads);
+    self.resource_writes.extend(&other.resource_writes);
+  }
+
+  /// A type both read and written, which a single query can't borrow.
+  pub fn self_conflict(&self) -> Option<&'static str> {
+    self
+      .writes
+      .iter()
+      .find(|(id, _)| self.reads.contains_key(id))
+      .or_else(|| {
+        self
+          .resource_writes
+          .iter()
+          .find(|(id, _)| self.resource_reads.contains_key(id))
+      })
+      .map(|(_, name)| *name)
+  }
+
+  /// First type one side writes and the other reads or writes; `None` if both can run at once.
+  pub fn conflict(&self, other: &Access) -> Option<&'static str> {
+    fn overlap(
+      writes: &BTreeMap<TypeId, &'static str>,
+      reads: &BTreeMap<TypeId, &'static str>,
+      other_writes: &BTreeMap<TypeId, &'static str>,
+    ) -> Option<&'static str> {
+      writes
+        .iter()
+        .find(|(id, _)| reads.contains_key(id) || other_writes.contains_key(id))
+        .map(|(_, name)| *name)
+    }
+
+    overlap(&self.writes, &other.reads, &other.writes)
+      .or_else(|| overlap(&other.writes, &self.reads, &self.writes))
+      .or_else(|| overlap(&self.resource_writes, &other.resource_reads, &other.resource_writes))
+      .or_else(|| overlap(&other.resource_writes, &self.resource_reads, &self.resource_writes))
+  }
+
+  /// First type of `other` this access doesn't allow; writing allows reading too.
+  pub fn missing(&self, other: &Access) -> Option<&'static str> {
+    let covers = |writes: &BTreeMap<TypeId, &'static str>, reads: &BTreeMap<TypeId, &'static str>, id: &TypeId| {
+      writes.contains_key(id) || reads.contains_key(id)
+    };
+
+    other
+      .reads
+      .iter()
+      .find(|(id, _)| !covers(&self.writes, &self.reads, id))
+      .or_else(|| other.writes.iter().find(|(id, _)| !self.writes.contains_key(id)))
+      .or_else(|| {
+        other
+          .resource_reads
+          .iter()
+          .find(|(id, _)| !covers(&self.resource_writes, &self.resource_reads, id))
+      })
+      .or_else(|| {
+        other
+          .resource_writes
+          .iter()
+          .find(|(id, _)| !self.resource_writes.contains_key(id))
+      })
+      .map(|(_, name)| *name)
+  }
+}
+
+impl fmt::Debug for Access {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Access")
+      .field("reads", &self.reads.values().collect::<Vec<_>>())
+      .field("writes", &self.writes.values().collect::<Vec<_>>())
+      .field("resource_reads", &self.resource_reads.values().collect::<Vec<_>>())
+      .field("resource_writes", &self.resource_writes.values().collect::<Vec<_>>())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct Position;
+  struct Velocity;
+
+  fn access(build: impl FnOnce(&mut Access) -> &mut Access) -> Access {
+    let mut access = Access::default();
+    build(&mut access);
+    access
+  }
+
+  #[test]
+  fn only_writes_conflict() {
+    let reads = access(|a| a.read::<Position>().read::<Velocity>());
+    let writes = access(|a| a.write::<Position>());
+
+    assert_eq!(reads.conflict(&reads), None);
+    assert_eq!(reads.conflict(&writes), Some(type_name::<Position>()));
+    assert_eq!(writes.conflict(&reads), Some(type_name::<Position>()));
+    assert_eq!(writes.conflict(&writes), Some(type_name::<Position>()));
+    assert_eq!(writes.conflict(&access(|a| a.write::<Velocity>())), None);
+  }
+
+  #[test]
+  fn resources_and_components_dont_conflict() {
+    let component = access(|a| a.write::<Position>());
+    let resource = access(|a| a.write_resource::<Position>());
+
+    assert_eq!(component.conflict(&resource), None);
+    assert_eq!(
+      resource.conflict(&access(|a| a.read_resource::<Position>())),
+      Some(type_name::<Position>())
+    );
+  }
+
+  #[test]
+  fn missing_allows_reading_what_is_written() {
+    let declared = access(|a| a.write::<Position>().read::<Velocity>().read_resource::<u32>());
+
+    assert_eq!(declared.missing(&access(|a| a.read::<Position>())), None);
+    assert_eq!(declared.missing(&access(|a| a.read::<Velocity>())), None);
+    assert_eq!(
+      declared.missing(&access(|a| a.write::<Velocity>())),
+      Some(type_name::<Velocity>())
+    );
+    assert_eq!(
+      declared.missing(&access(|a| a.write_resource::<u32>())),
+      Some(type_name::<u32>())
+    );
+    assert_eq!(
+      declared.missing(&access(|a| a.read_resource::<Position>())),
+      Some(type_name::<Position>())
+    );
+  }
+
+  #[test]
+  fn self_conflict_finds_read_and_write_of_one_type() {
+    assert_eq!(
+      access(|a| a.write::<Position>().read::<Velocity>()).self_conflict(),
+      None
+    );
+    assert_eq!(
+      access(|a| a.write::<Position>().read::<Position>()).self_conflict(),
+      Some(type_name::<Position>())
+    );
+  }
+}
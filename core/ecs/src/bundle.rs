@@ -0,0 +1,39 @@
+use crate::entity::Entity;
+use crate::world::World;
+use crate::Component;
+
+/// Components inserted together: a single component or a tuple of up to eight bundles.
+pub trait Bundle: Send + Sync + 'static {
+  fn insert_into(self, world: &mut World, entity: Entity);
+}
+
+impl<T: Component> Bundle for T {
+  fn insert_into(self, world: &mut World, entity: Entity) {
+    world.insert_one(entity, self);
+  }
+}
+
+impl Bundle for () {
+  fn insert_into(self, _: &mut World, _: Entity) {}
+}
+
+macro_rules! tuple_bundle {
+  ($($name:ident),*) => {
+    #[allow(non_snake_case)]
+    impl<$($name: Bundle),*> Bundle for ($($name,)*) {
+      fn insert_into(self, world: &mut World, entity: Entity) {
+        let ($($name,)*) = self;
+        $($name.insert_into(world, entity);)*
+      }
+    }
+  };
+}
+
+tuple_bundle!(A);
+tuple_bundle!(A, B);
+tuple_bundle!(A, B, C);
+tuple_bundle!(A, B, C, D);
+tuple_bundle!(A, B, C, D, E);
+tuple_bundle!(A, B, C, D, E, F);
+tuple_bundle!(A, B, C, D, E, F, G);
+tuple_bundle!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// Handle of an entity. The generation tells a despawned entity apart from a later one that
+/// reuses its index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+  index: u32,
+  generation: u32,
+}
+
+impl Entity {
+  pub fn from_raw(index: u32, generation: u32) -> Self {
+    Self { index, generation }
+  }
+
+  pub fn index(self) -> u32 {
+    self.index
+  }
+
+  pub fn generation(self) -> u32 {
+    self.generation
+  }
+}
+
+impl fmt::Display for Entity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}v{}", self.index, self.generation)
+  }
+}
+
+/// Allocator of entity indices, reusing the ones freed by despawns.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Entities {
+  /// Current generation of every index ever allocated.
+  generations: Vec<u32>,
+  alive: Vec<bool>,
+  free: Vec<u32>,
+  len: usize,
+}
+
+impl Entities {
+  pub fn alloc(&mut self) -> Entity {
+    self.len += 1;
+
+    if let Some(index) = self.free.pop() {
+      self.alive[index as usize] = true;
+      return Entity::from_raw(index, self.generations[index as usize]);
+    }
+
+    self.generations.push(0);
+    self.alive.push(true);
+    Entity::from_raw(self.generations.len() as u32 - 1, 0)
+  }
+
+  /// Frees `entity`; returns false if it was already dead.
+  pub fn free(&mut self, entity: Entity) -> bool {
+    if !self.is_alive(entity) {
+      return false;
+    }
+
+    let index = entity.index as usize;
+    self.alive[index] = false;
+    self.generations[index] = self.generations[index].wrapping_add(1);
+    self.free.push(entity.index);
+    self.len -= 1;
+    true
+  }
+
+  pub fn is_alive(&self, entity: Entity) -> bool {
+    let index = entity.index as usize;
+    index < self.alive.len() && self.alive[index] && self.generations[index] == entity.generation
+  }
+
+  /// Live entity at `index`, if any.
+  pub fn at(&self, index: u32) -> Option<Entity> {
+    let i = index as usize;
+    (i < self.alive.len() && self.alive[i]).then(|| Entity::from_raw(index, self.generations[i]))
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+    (0..self.alive.len() as u32).filter_map(|i| self.at(i))
+  }
+}
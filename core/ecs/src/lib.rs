@@ -0,0 +1,38 @@
+//! Entity-component-system core: entities with components in sparse sets, typed queries with
+//! change detection, global resources and a scheduler that runs systems touching different types
+//! in parallel.
+
+pub mod access;
+pub mod bundle;
+pub mod entity;
+pub mod query;
+pub mod schedule;
+mod storage;
+pub mod world;
+
+pub use access::Access;
+pub use bundle::Bundle;
+pub use entity::Entity;
+pub use query::{Added, Changed, Query, QueryData, QueryFilter, With, Without};
+pub use schedule::{Commands, Schedule, System, SystemContext};
+pub use storage::ComponentTicks;
+pub use world::{Mut, Ref, Res, ResMut, World};
+
+/// Monotonic counter stamped on component changes; every system run gets a new one.
+pub type Tick = u32;
+
+/// Data attached to an entity. Implemented explicitly so a single component is also a `Bundle`.
+pub trait Component: Send + Sync + 'static {}
+
+/// Global data stored in the world, any thread safe type.
+pub trait Resource: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Resource for T {}
+
+/// Change ticks a query compares against: components changed after `last_run` count as changed
+/// and writes are stamped with `this_run`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemTicks {
+  pub last_run: Tick,
+  pub this_run: Tick,
+}
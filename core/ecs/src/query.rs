@@ -0,0 +1,562 @@
+use std::any::type_name;
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+
+use crate::access::Access;
+use crate::entity::Entity;
+use crate::storage::{AnyStorage, ComponentTicks, SparseSet};
+use crate::world::{downcast, downcast_mut, read, write, Mut, World};
+use crate::{Component, SystemTicks};
+
+/// What a query yields per entity: `Entity`, `&T`, `&mut T` (as `Mut<T>`), `Option` of those and
+/// tuples of up to eight.
+///
+/// # Safety
+///
+/// `access` must declare every storage `fetch` locks, and `get` may only hand out a mutable
+/// borrow for the entity it is called with.
+pub unsafe trait QueryData {
+  #[doc(hidden)]
+  type Fetch<'w>;
+  type Item<'q>;
+
+  fn access(access: &mut Access);
+
+  /// Locks the storages; `None` when a required one doesn't exist, so nothing can match.
+  #[doc(hidden)]
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>>;
+
+  /// Entities worth visiting, `None` if this term doesn't narrow them down.
+  #[doc(hidden)]
+  fn entities<'q>(fetch: &'q Self::Fetch<'_>) -> Option<&'q [Entity]>;
+
+  /// # Safety
+  ///
+  /// No other item of `entity` from the same fetch may be alive.
+  #[doc(hidden)]
+  unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> Option<Self::Item<'q>>;
+}
+
+/// Narrows a query down without yielding data: `With`, `Without`, `Added`, `Changed` and
+/// tuples of them. Filters lock what they look at for reading, so they can't name a type the
+/// query writes; `&mut T` already requires a `T`.
+pub trait QueryFilter {
+  #[doc(hidden)]
+  type Fetch<'w>;
+
+  fn access(access: &mut Access);
+
+  #[doc(hidden)]
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>>;
+
+  #[doc(hidden)]
+  fn matches(fetch: &Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> bool;
+}
+
+#[doc(hidden)]
+pub struct ReadFetch<'w, T> {
+  _guard: RwLockReadGuard<'w, Box<dyn AnyStorage>>,
+  set: *const SparseSet<T>,
+}
+
+impl<'w, T: Component> ReadFetch<'w, T> {
+  fn new(world: &'w World) -> Option<Self> {
+    let guard = read(world.storage_lock::<T>()?, type_name::<T>());
+    let set = downcast::<T>(guard.as_ref()) as *const _;
+    Some(Self { _guard: guard, set })
+  }
+
+  fn set(&self) -> &SparseSet<T> {
+    // El guard mantiene el almacenamiento bloqueado para lectura.
+    unsafe { &*self.set }
+  }
+}
+
+#[doc(hidden)]
+pub struct WriteFetch<'w, T> {
+  _guard: RwLockWriteGuard<'w, Box<dyn AnyStorage>>,
+  set: *mut SparseSet<T>,
+}
+
+unsafe impl<T: Component> QueryData for &T {
+  type Fetch<'w> = ReadFetch<'w, T>;
+  type Item<'q> = &'q T;
+
+  fn access(access: &mut Access) {
+    access.read::<T>();
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    ReadFetch::new(world)
+  }
+
+  fn entities<'q>(fetch: &'q Self::Fetch<'_>) -> Option<&'q [Entity]> {
+    Some(fetch.set().entities())
+  }
+
+  unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: Entity, _: SystemTicks) -> Option<&'q T> {
+    fetch.set().get(entity)
+  }
+}
+
+unsafe impl<T: Component> QueryData for &mut T {
+  type Fetch<'w> = WriteFetch<'w, T>;
+  type Item<'q> = Mut<'q, T>;
+
+  fn access(access: &mut Access) {
+    access.write::<T>();
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    let mut guard = write(world.storage_lock::<T>()?, type_name::<T>());
+    let set = downcast_mut::<T>(guard.as_mut()) as *mut _;
+    Some(WriteFetch { _guard: guard, set })
+  }
+
+  fn entities<'q>(fetch: &'q Self::Fetch<'_>) -> Option<&'q [Entity]> {
+    Some(unsafe { SparseSet::entities_raw(fetch.set) })
+  }
+
+  unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> Option<Mut<'q, T>> {
+    let (value, component_ticks) = SparseSet::get_raw(fetch.set, entity)?;
+    Some(Mut::new(&mut *value, &mut *component_ticks, ticks.this_run))
+  }
+}
+
+unsafe impl QueryData for Entity {
+  type Fetch<'w> = ();
+  type Item<'q> = Entity;
+
+  fn access(_: &mut Access) {}
+
+  fn fetch(_: &World) -> Option<()> {
+    Some(())
+  }
+
+  fn entities(_: &()) -> Option<&[Entity]> {
+    None
+  }
+
+  unsafe fn get(_: &(), entity: Entity, _: SystemTicks) -> Option<Entity> {
+    Some(entity)
+  }
+}
+
+unsafe impl<Q: QueryData> QueryData for Option<Q> {
+  type Fetch<'w> = Option<Q::Fetch<'w>>;
+  type Item<'q> = Option<Q::Item<'q>>;
+
+  fn access(access: &mut Access) {
+    Q::access(access);
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    Some(Q::fetch(world))
+  }
+
+  fn entities<'q>(_: &'q Self::Fetch<'_>) -> Option<&'q [Entity]> {
+    None
+  }
+
+  unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> Option<Self::Item<'q>> {
+    Some(fetch.as_ref().and_then(|fetch| Q::get(fetch, entity, ticks)))
+  }
+}
+
+macro_rules! tuple_query_data {
+  ($($name:ident),*) => {
+    #[allow(non_snake_case)]
+    unsafe impl<$($name: QueryData),*> QueryData for ($($name,)*) {
+      type Fetch<'w> = ($($name::Fetch<'w>,)*);
+      type Item<'q> = ($($name::Item<'q>,)*);
+
+      fn access(access: &mut Access) {
+        $($name::access(access);)*
+      }
+
+      fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+        Some(($($name::fetch(world)?,)*))
+      }
+
+      fn entities<'q>(fetch: &'q Self::Fetch<'_>) -> Option<&'q [Entity]> {
+        let ($($name,)*) = fetch;
+        [$($name::entities($name)),*].into_iter().flatten().min_by_key(|e| e.len())
+      }
+
+      unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> Option<Self::Item<'q>> {
+        let ($($name,)*) = fetch;
+        Some(($($name::get($name, entity, ticks)?,)*))
+      }
+    }
+
+    #[allow(non_snake_case)]
+    impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+      type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+      fn access(access: &mut Access) {
+        $($name::access(access);)*
+      }
+
+      fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+        Some(($($name::fetch(world)?,)*))
+      }
+
+      fn matches(fetch: &Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> bool {
+        let ($($name,)*) = fetch;
+        $($name::matches($name, entity, ticks))&&*
+      }
+    }
+  };
+}
+
+tuple_query_data!(A);
+tuple_query_data!(A, B);
+tuple_query_data!(A, B, C);
+tuple_query_data!(A, B, C, D);
+tuple_query_data!(A, B, C, D, E);
+tuple_query_data!(A, B, C, D, E, F);
+tuple_query_data!(A, B, C, D, E, F, G);
+tuple_query_data!(A, B, C, D, E, F, G, H);
+
+impl QueryFilter for () {
+  type Fetch<'w> = ();
+
+  fn access(_: &mut Access) {}
+
+  fn fetch(_: &World) -> Option<()> {
+    Some(())
+  }
+
+  fn matches(_: &(), _: Entity, _: SystemTicks) -> bool {
+    true
+  }
+}
+
+/// Entities that have a `T`.
+pub struct With<T>(PhantomData<T>);
+
+/// Entities without a `T`.
+pub struct Without<T>(PhantomData<T>);
+
+/// Entities whose `T` was added since the system last ran.
+pub struct Added<T>(PhantomData<T>);
+
+/// Entities whose `T` was added or mutably borrowed since the system last ran.
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T> {
+  type Fetch<'w> = ReadFetch<'w, T>;
+
+  fn access(access: &mut Access) {
+    access.read::<T>();
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    ReadFetch::new(world)
+  }
+
+  fn matches(fetch: &Self::Fetch<'_>, entity: Entity, _: SystemTicks) -> bool {
+    fetch.set().get(entity).is_some()
+  }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+  type Fetch<'w> = Option<ReadFetch<'w, T>>;
+
+  fn access(access: &mut Access) {
+    access.read::<T>();
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    Some(ReadFetch::new(world))
+  }
+
+  fn matches(fetch: &Self::Fetch<'_>, entity: Entity, _: SystemTicks) -> bool {
+    fetch.as_ref().is_none_or(|f| f.set().get(entity).is_none())
+  }
+}
+
+fn ticks_match<T: Component>(fetch: &ReadFetch<'_, T>, entity: Entity, test: impl Fn(&ComponentTicks) -> bool) -> bool {
+  fetch.set().ticks(entity).is_some_and(|t| test(&t))
+}
+
+impl<T: Component> QueryFilter for Added<T> {
+  type Fetch<'w> = ReadFetch<'w, T>;
+
+  fn access(access: &mut Access) {
+    access.read::<T>();
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    ReadFetch::new(world)
+  }
+
+  fn matches(fetch: &Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> bool {
+    ticks_match(fetch, entity, |t| t.is_added(ticks.last_run))
+  }
+}
+
+impl<T: Component> QueryFilter for Changed<T> {
+  type Fetch<'w> = ReadFetch<'w, T>;
+
+  fn access(access: &mut Access) {
+    access.read::<T>();
+  }
+
+  fn fetch(world: &World) -> Option<Self::Fetch<'_>> {
+    ReadFetch::new(world)
+  }
+
+  fn matches(fetch: &Self::Fetch<'_>, entity: Entity, ticks: SystemTicks) -> bool {
+    ticks_match(fetch, entity, |t| t.is_changed(ticks.last_run))
+  }
+}
+
+/// Typed view over the entities that have every component of `Q` and pass `F`. The storages it
+/// touches stay locked until it is dropped.
+pub struct Query<'w, Q: QueryData, F: QueryFilter = ()> {
+  world: &'w World,
+  data: Option<Q::Fetch<'w>>,
+  filter: Option<F::Fetch<'w>>,
+  ticks: SystemTicks,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Query<'w, Q, F> {
+  pub(crate) fn new(world: &'w World, ticks: SystemTicks) -> Self {
+    let (mut data, mut filter) = (Access::default(), Access::default());
+    Q::access(&mut data);
+    F::access(&mut filter);
+
+    if let Some(name) = data.self_conflict() {
+      panic!(
+        "query `{}` borrows `{}` both mutably and immutably",
+        type_name::<Q>(),
+        name
+      );
+    }
+
+    if let Some(name) = data.conflict(&filter) {
+      panic!(
+        "query `{}` writes `{}`, which its filter `{}` can't read at the same time",
+        type_name::<Q>(),
+        name,
+        type_name::<F>()
+      );
+    }
+
+    Self {
+      world,
+      data: Q::fetch(world),
+      filter: F::fetch(world),
+      ticks,
+    }
+  }
+
+  /// Types the query reads and writes.
+  pub fn access() -> Access {
+    let mut access = Access::default();
+    Q::access(&mut access);
+    F::access(&mut access);
+    access
+  }
+
+  pub fn iter(&mut self) -> QueryIter<'_, 'w, Q, F> {
+    let entities = match &self.data {
+      Some(data) if self.filter.is_some() => match Q::entities(data) {
+        Some(entities) => Cow::Borrowed(entities),
+        None => Cow::Owned(self.world.iter_entities().collect()),
+      },
+      _ => Cow::Borrowed(&[][..]),
+    };
+
+    QueryIter {
+      query: self,
+      entities,
+      next: 0,
+    }
+  }
+
+  /// Item of `entity`, `None` if it doesn't match.
+  pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+    let (data, filter) = (self.data.as_ref()?, self.filter.as_ref()?);
+
+    if !self.world.is_alive(entity) || !F::matches(filter, entity, self.ticks) {
+      return None;
+    }
+
+    // `&mut self` impide que haya otro item vivo de esta consulta.
+    unsafe { Q::get(data, entity, self.ticks) }
+  }
+
+  pub fn contains(&mut self, entity: Entity) -> bool {
+    self.get(entity).is_some()
+  }
+
+  /// The only matching item, `None` if there are none or several.
+  pub fn single(&mut self) -> Option<Q::Item<'_>> {
+    let mut iter = self.iter();
+    let item = iter.next()?;
+    iter.next().is_none().then_some(item)
+  }
+
+  pub fn count(&mut self) -> usize {
+    self.iter().count()
+  }
+}
+
+pub struct QueryIter<'q, 'w, Q: QueryData, F: QueryFilter> {
+  query: &'q Query<'w, Q, F>,
+  entities: Cow<'q, [Entity]>,
+  next: usize,
+}
+
+impl<'q, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'q, '_, Q, F> {
+  type Item = Q::Item<'q>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let query = self.query;
+    let (data, filter) = (query.data.as_ref()?, query.filter.as_ref()?);
+
+    while let Some(&entity) = self.entities.get(self.next) {
+      self.next += 1;
+
+      if !F::matches(filter, entity, query.ticks) {
+        continue;
+      }
+
+      // Cada entidad se visita una sola vez, asi que los prestamos mutables no se solapan.
+      if let Some(item) = unsafe { Q::get(data, entity, query.ticks) } {
+        return Some(item);
+      }
+    }
+
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Added, Changed, Component, Entity, With, Without, World};
+
+  #[derive(Debug, PartialEq)]
+  struct Position(i32);
+  struct Velocity(i32);
+  #[derive(Debug, PartialEq)]
+  struct Name(&'static str);
+
+  impl Component for Position {}
+  impl Component for Velocity {}
+  impl Component for Name {}
+
+  fn world() -> (World, [Entity; 3]) {
+    let mut world = World::new();
+    let a = world.spawn((Position(0), Velocity(1)));
+    let b = world.spawn((Position(10), Velocity(2), Name("b")));
+    let c = world.spawn((Position(20), Name("c")));
+    (world, [a, b, c])
+  }
+
+  fn positions<F: crate::QueryFilter>(world: &World) -> Vec<i32> {
+    let mut positions = world
+      .query_filtered::<&Position, F>()
+      .iter()
+      .map(|p| p.0)
+      .collect::<Vec<_>>();
+    positions.sort();
+    positions
+  }
+
+  #[test]
+  #[should_panic(expected = "both mutably and immutably")]
+  fn rejects_reading_and_writing_one_type() {
+    let (world, _) = world();
+    world.query::<(&mut Position, &Position)>();
+  }
+
+  #[test]
+  #[should_panic(expected = "which its filter")]
+  fn rejects_filtering_on_a_written_type() {
+    let (world, _) = world();
+    world.query_filtered::<&mut Position, With<Position>>();
+  }
+
+  #[test]
+  fn filters_can_read_what_the_query_reads() {
+    let (world, _) = world();
+
+    assert_eq!(world.query_filtered::<&Position, With<Position>>().count(), 3);
+    assert_eq!(world.query_filtered::<&mut Velocity, Changed<Position>>().count(), 2);
+  }
+
+  #[test]
+  fn iterates_mutable_and_shared_components() {
+    let (world, [a, b, c]) = world();
+
+    for (mut position, velocity) in world.query::<(&mut Position, &Velocity)>().iter() {
+      position.0 += velocity.0;
+    }
+
+    let mut query = world.query::<&Position>();
+    assert_eq!(query.get(a), Some(&Position(1)));
+    assert_eq!(query.get(b), Some(&Position(12)));
+    assert_eq!(query.get(c), Some(&Position(20)));
+  }
+
+  #[test]
+  fn optional_components_dont_narrow_the_query() {
+    let (world, [a, b, c]) = world();
+    let mut query = world.query::<(Entity, Option<&Name>)>();
+    let mut found = query.iter().collect::<Vec<_>>();
+    found.sort_by_key(|(entity, _)| *entity);
+
+    assert_eq!(found, [(a, None), (b, Some(&Name("b"))), (c, Some(&Name("c")))]);
+    assert_eq!(world.query::<(&Velocity, Option<&Name>)>().count(), 2);
+  }
+
+  #[test]
+  fn with_and_without_filter_by_presence() {
+    let (world, _) = world();
+
+    assert_eq!(positions::<With<Velocity>>(&world), [0, 10]);
+    assert_eq!(positions::<Without<Velocity>>(&world), [20]);
+    assert_eq!(positions::<(With<Name>, Without<Velocity>)>(&world), [20]);
+  }
+
+  #[test]
+  fn missing_storages_match_nothing() {
+    struct Unused;
+    impl Component for Unused {}
+
+    let (world, _) = world();
+
+    assert_eq!(world.query::<(&Position, &Unused)>().count(), 0);
+    assert_eq!(positions::<With<Unused>>(&world), Vec::<i32>::new());
+    assert_eq!(positions::<Without<Unused>>(&world).len(), 3);
+  }
+
+  #[test]
+  fn added_and_changed_reset_with_clear_trackers() {
+    let (mut world, [a, ..]) = world();
+
+    assert_eq!(positions::<Added<Position>>(&world).len(), 3);
+    assert_eq!(positions::<Changed<Position>>(&world).len(), 3);
+
+    world.clear_trackers();
+    assert!(positions::<Added<Position>>(&world).is_empty());
+    assert!(positions::<Changed<Position>>(&world).is_empty());
+
+    // Leer a traves de `Mut` no cuenta como cambio.
+    assert_eq!(world.get_mut::<Position>(a).unwrap().0, 0);
+    assert!(positions::<Changed<Position>>(&world).is_empty());
+
+    world.get_mut::<Position>(a).unwrap().0 = 5;
+    world.spawn(Position(30));
+
+    assert_eq!(positions::<Added<Position>>(&world), [30]);
+    assert_eq!(positions::<Changed<Position>>(&world), [5, 30]);
+
+    world.clear_trackers();
+    assert!(positions::<Changed<Position>>(&world).is_empty());
+  }
+}
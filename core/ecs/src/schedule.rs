@@ -0,0 +1,445 @@
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::access::Access;
+use crate::entity::Entity;
+use crate::query::{Query, QueryData, QueryFilter};
+use crate::world::{Res, ResMut, World};
+use crate::{Bundle, Component, Resource, SystemTicks, Tick};
+
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Structural changes queued by systems, applied once their batch has finished.
+#[derive(Default)]
+pub struct Commands {
+  queue: Mutex<Vec<Command>>,
+}
+
+impl Commands {
+  pub fn add(&self, command: impl FnOnce(&mut World) + Send + 'static) {
+    self
+      .queue
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .push(Box::new(command));
+  }
+
+  pub fn spawn(&self, bundle: impl Bundle) {
+    self.add(move |world| {
+      world.spawn(bundle);
+    });
+  }
+
+  pub fn despawn(&self, entity: Entity) {
+    self.add(move |world| {
+      world.despawn(entity);
+    });
+  }
+
+  pub fn insert(&self, entity: Entity, bundle: impl Bundle) {
+    self.add(move |world| {
+      world.insert(entity, bundle);
+    });
+  }
+
+  pub fn remove<T: Component>(&self, entity: Entity) {
+    self.add(move |world| {
+      world.remove::<T>(entity);
+    });
+  }
+
+  pub fn apply(&mut self, world: &mut World) {
+    let queue = std::mem::take(self.queue.get_mut().unwrap_or_else(|e| e.into_inner()));
+
+    for command in queue {
+      command(world);
+    }
+  }
+}
+
+/// What a running system sees: queries and resources limited to its declared access, its
+/// change ticks and the command queue.
+pub struct SystemContext<'w> {
+  world: &'w World,
+  name: &'static str,
+  access: &'w Access,
+  ticks: SystemTicks,
+  commands: &'w Commands,
+}
+
+impl<'w> SystemContext<'w> {
+  fn check(&self, access: &Access) {
+    if let Some(missing) = self.access.missing(access) {
+      panic!("system `{}` uses `{}` without declaring it", self.name, missing);
+    }
+  }
+
+  /// Query whose change filters see what changed since this system last ran.
+  pub fn query<Q: QueryData>(&self) -> Query<'w, Q> {
+    self.query_filtered::<Q, ()>()
+  }
+
+  pub fn query_filtered<Q: QueryData, F: QueryFilter>(&self) -> Query<'w, Q, F> {
+    self.check(&Query::<Q, F>::access());
+    Query::new(self.world, self.ticks)
+  }
+
+  pub fn resource<R: Resource>(&self) -> Option<Res<'w, R>> {
+    self.check(Access::default().read_resource::<R>());
+    self.world.resource()
+  }
+
+  pub fn resource_mut<R: Resource>(&self) -> Option<ResMut<'w, R>> {
+    self.check(Access::default().write_resource::<R>());
+    self.world.resource_mut()
+  }
+
+  pub fn commands(&self) -> &'w Commands {
+    self.commands
+  }
+
+  pub fn ticks(&self) -> SystemTicks {
+    self.ticks
+  }
+}
+
+type SystemFn = Box<dyn FnMut(&SystemContext) + Send>;
+
+/// A named function over the world and the types it touches.
+pub struct System {
+  name: &'static str,
+  access: Access,
+  run: SystemFn,
+  last_run: Tick,
+}
+
+impl System {
+  pub fn new(name: &'static str, run: impl FnMut(&SystemContext) + Send + 'static) -> Self {
+    Self {
+      name,
+      access: Access::default(),
+      run: Box::new(run),
+      last_run: 0,
+    }
+  }
+
+  pub fn reads<T: Component>(mut self) -> Self {
+    self.access.read::<T>();
+    self
+  }
+
+  pub fn writes<T: Component>(mut self) -> Self {
+    self.access.write::<T>();
+    self
+  }
+
+  pub fn reads_resource<R: Resource>(mut self) -> Self {
+    self.access.read_resource::<R>();
+    self
+  }
+
+  pub fn writes_resource<R: Resource>(mut self) -> Self {
+    self.access.write_resource::<R>();
+    self
+  }
+
+  /// Declares everything `Q` filtered by `F` touches.
+  pub fn with_query<Q: QueryData, F: QueryFilter>(mut self) -> Self {
+    self.access.extend(&Query::<Q, F>::access());
+    self
+  }
+
+  pub fn name(&self) -> &'static str {
+    self.name
+  }
+
+  pub fn access(&self) -> &Access {
+    &self.access
+  }
+
+  fn run(&mut self, world: &World, commands: &Commands) {
+    let this_run = world.increment_change_tick();
+    let context = SystemContext {
+      world,
+      name: self.name,
+      access: &self.access,
+      ticks: SystemTicks {
+        last_run: self.last_run,
+        this_run,
+      },
+      commands,
+    };
+
+    (self.run)(&context);
+    self.last_run = this_run;
+  }
+}
+
+impl fmt::Debug for System {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("System")
+      .field("name", &self.name)
+      .field("access", &self.access)
+      .finish()
+  }
+}
+
+/// Systems in insertion order. Systems that don't conflict (neither writes what the other reads
+/// or writes) are grouped into batches that run on several threads; conflicting systems keep
+/// their insertion order.
+#[derive(Debug)]
+pub struct Schedule {
+  systems: Vec<System>,
+  /// Indices into `systems`, rebuilt when a system is added.
+  batches: Vec<Vec<usize>>,
+  parallel: bool,
+}
+
+impl Default for Schedule {
+  fn default() -> Self {
+    Self {
+      systems: vec![],
+      batches: vec![],
+      parallel: true,
+    }
+  }
+}
+
+impl Schedule {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// With `true`, the default, systems that don't conflict run at the same time in batches on
+  /// worker threads. With `false` every system runs on the calling thread, in insertion order,
+  /// e.g. to debug a system or to profile it alone.
+  pub fn set_parallel(&mut self, parallel: bool) {
+    self.parallel = parallel;
+  }
+
+  pub fn add_system(&mut self, system: System) -> &mut Self {
+    self.systems.push(system);
+    self.batches.clear();
+    self
+  }
+
+  /// System names per batch, for debugging the schedule.
+  pub fn batches(&mut self) -> Vec<Vec<&'static str>> {
+    self.build();
+    self
+      .batches
+      .iter()
+      .map(|batch| batch.iter().map(|&i| self.systems[i].name).collect())
+      .collect()
+  }
+
+  /// Pairs of systems that can't run together and the first type they fight over.
+  pub fn conflicts(&self) -> Vec<(&'static str, &'static str, &'static str)> {
+    let mut conflicts = vec![];
+
+    for (i, a) in self.systems.iter().enumerate() {
+      for b in &self.systems[i + 1..] {
+        if let Some(name) = a.access.conflict(&b.access) {
+          conflicts.push((a.name, b.name, name));
+        }
+      }
+    }
+
+    conflicts
+  }
+
+  fn build(&mut self) {
+    if !self.batches.is_empty() || self.systems.is_empty() {
+      return;
+    }
+
+    let mut batch_of = Vec::<usize>::with_capacity(self.systems.len());
+
+    for (i, system) in self.systems.iter().enumerate() {
+      // Despues del ultimo lote con un sistema anterior que choque con este.
+      let batch = (0..i)
+        .filter(|&j| self.systems[j].access.conflict(&system.access).is_some())
+        .map(|j| batch_of[j] + 1)
+        .max()
+        .unwrap_or(0);
+
+      batch_of.push(batch);
+    }
+
+    let count = batch_of.iter().max().map_or(0, |b| b + 1);
+    self.batches = vec![vec![]; count];
+
+    for (i, batch) in batch_of.into_iter().enumerate() {
+      self.batches[batch].push(i);
+    }
+  }
+
+  pub fn run(&mut self, world: &mut World) {
+    self.build();
+
+    let mut commands = Commands::default();
+
+    for batch in &self.batches {
+      let mut systems = self
+        .systems
+        .iter_mut()
+        .enumerate()
+        .filter(|(i, _)| batch.contains(i))
+        .map(|(_, s)| s)
+        .collect::<Vec<_>>();
+
+      if self.parallel && systems.len() > 1 {
+        let (world, commands) = (&*world, &commands);
+
+        thread::scope(|scope| {
+          for system in systems {
+            let name = system.name;
+
+            thread::Builder::new()
+              .name(name.to_string())
+              .spawn_scoped(scope, move || system.run(world, commands))
+              .unwrap_or_else(|e| panic!("can't start a thread for system `{}`: {}", name, e));
+          }
+        });
+      } else {
+        systems.iter_mut().for_each(|system| system.run(world, &commands));
+      }
+
+      // Lo que creen los comandos es posterior a todos los sistemas del lote.
+      world.increment_change_tick();
+      commands.apply(world);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Added, Changed};
+
+  struct Position(i32);
+  struct Velocity;
+  struct Name;
+
+  impl Component for Position {}
+  impl Component for Velocity {}
+  impl Component for Name {}
+
+  /// What `watch` saw on each run: entities with `Position` added and changed.
+  #[derive(Default)]
+  struct Seen(Vec<(usize, usize)>);
+
+  fn system(name: &'static str) -> System {
+    System::new(name, |_| {})
+  }
+
+  #[test]
+  fn batches_group_systems_that_dont_conflict() {
+    let mut schedule = Schedule::new();
+    schedule
+      .add_system(system("move").writes::<Position>())
+      .add_system(system("read_velocity").reads::<Velocity>())
+      .add_system(system("read_position").reads::<Position>())
+      .add_system(system("write_velocity").writes::<Velocity>())
+      .add_system(system("names").reads::<Name>())
+      .add_system(system("teleport").writes::<Position>());
+
+    assert_eq!(
+      schedule.batches(),
+      [
+        vec!["move", "read_velocity", "names"],
+        vec!["read_position", "write_velocity"],
+        vec!["teleport"],
+      ]
+    );
+    assert_eq!(schedule.conflicts().len(), 4);
+  }
+
+  #[test]
+  fn conflicting_systems_run_in_insertion_order() {
+    let mut world = World::new();
+    world.insert_resource(Vec::<&'static str>::new());
+
+    let mut schedule = Schedule::new();
+    for name in ["first", "second", "third"] {
+      schedule.add_system(
+        System::new(name, move |context| {
+          context.resource_mut::<Vec<&str>>().unwrap().push(name)
+        })
+        .writes_resource::<Vec<&'static str>>(),
+      );
+    }
+
+    schedule.run(&mut world);
+
+    assert_eq!(schedule.batches().len(), 3);
+    assert_eq!(*world.resource::<Vec<&str>>().unwrap(), ["first", "second", "third"]);
+  }
+
+  #[test]
+  fn change_filters_see_what_changed_since_the_last_run() {
+    let mut world = World::new();
+    let a = world.spawn(Position(0));
+    world.insert_resource(Seen::default());
+
+    let mut spawned = false;
+    let mut schedule = Schedule::new();
+    schedule
+      .add_system(System::new("spawn", move |context| {
+        if !spawned {
+          context.commands().spawn(Position(1));
+          spawned = true;
+        }
+      }))
+      .add_system(
+        System::new("watch", |context| {
+          let added = context.query_filtered::<Entity, Added<Position>>().count();
+          let changed = context.query_filtered::<Entity, Changed<Position>>().count();
+          context.resource_mut::<Seen>().unwrap().0.push((added, changed));
+        })
+        .with_query::<Entity, (Added<Position>, Changed<Position>)>()
+        .writes_resource::<Seen>(),
+      );
+
+    schedule.run(&mut world);
+    schedule.run(&mut world);
+    schedule.run(&mut world);
+    world.get_mut::<Position>(a).unwrap().0 = 2;
+    schedule.run(&mut world);
+
+    assert_eq!(world.len(), 2);
+    assert_eq!(world.resource::<Seen>().unwrap().0, [(1, 1), (1, 1), (0, 0), (0, 1)]);
+  }
+
+  #[test]
+  #[should_panic(expected = "without declaring it")]
+  fn undeclared_queries_panic() {
+    let mut world = World::new();
+    world.spawn(Position(0));
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(
+      System::new("sneaky", |context| {
+        context.query::<&mut Position>().iter().for_each(|mut p| p.0 += 1);
+      })
+      .reads::<Position>(),
+    );
+
+    schedule.run(&mut world);
+  }
+
+  #[test]
+  #[should_panic(expected = "without declaring it")]
+  fn undeclared_resources_panic() {
+    let mut world = World::new();
+    world.insert_resource(Seen::default());
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(System::new("sneaky", |context| {
+      context.resource::<Seen>();
+    }));
+
+    schedule.run(&mut world);
+  }
+}
@@ -0,0 +1,171 @@
+use std::any::Any;
+use std::ptr::{addr_of, addr_of_mut};
+
+use crate::entity::Entity;
+use crate::{Component, Tick};
+
+/// When a component was added and last changed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ComponentTicks {
+  pub added: Tick,
+  pub changed: Tick,
+}
+
+impl ComponentTicks {
+  pub fn new(tick: Tick) -> Self {
+    Self {
+      added: tick,
+      changed: tick,
+    }
+  }
+
+  /// Added after `last_run`.
+  pub fn is_added(&self, last_run: Tick) -> bool {
+    self.added > last_run
+  }
+
+  /// Added or mutably accessed after `last_run`.
+  pub fn is_changed(&self, last_run: Tick) -> bool {
+    self.changed > last_run
+  }
+}
+
+/// Components of one type, packed in insertion order with an index from entity to slot.
+#[derive(Clone, Debug)]
+pub(crate) struct SparseSet<T> {
+  sparse: Vec<Option<u32>>,
+  entities: Vec<Entity>,
+  data: Vec<T>,
+  ticks: Vec<ComponentTicks>,
+}
+
+impl<T> Default for SparseSet<T> {
+  fn default() -> Self {
+    Self {
+      sparse: vec![],
+      entities: vec![],
+      data: vec![],
+      ticks: vec![],
+    }
+  }
+}
+
+impl<T> SparseSet<T> {
+  fn slot(&self, entity: Entity) -> Option<usize> {
+    let slot = (*self.sparse.get(entity.index() as usize)?)? as usize;
+    (self.entities[slot] == entity).then_some(slot)
+  }
+
+  /// Inserts or replaces the component of `entity`, returning the old one.
+  pub fn insert(&mut self, entity: Entity, value: T, tick: Tick) -> Option<T> {
+    if let Some(slot) = self.slot(entity) {
+      self.ticks[slot].changed = tick;
+      return Some(std::mem::replace(&mut self.data[slot], value));
+    }
+
+    let index = entity.index() as usize;
+
+    if self.sparse.len() <= index {
+      self.sparse.resize(index + 1, None);
+    }
+
+    // Un indice reutilizado puede apuntar a un hueco de una generacion anterior.
+    if let Some(stale) = self.sparse[index] {
+      self.swap_remove(stale as usize);
+    }
+
+    self.sparse[index] = Some(self.entities.len() as u32);
+    self.entities.push(entity);
+    self.data.push(value);
+    self.ticks.push(ComponentTicks::new(tick));
+    None
+  }
+
+  pub fn remove(&mut self, entity: Entity) -> Option<T> {
+    let slot = self.slot(entity)?;
+    Some(self.swap_remove(slot))
+  }
+
+  fn swap_remove(&mut self, slot: usize) -> T {
+    let entity = self.entities.swap_remove(slot);
+    self.ticks.swap_remove(slot);
+    let value = self.data.swap_remove(slot);
+    self.sparse[entity.index() as usize] = None;
+
+    if let Some(moved) = self.entities.get(slot) {
+      self.sparse[moved.index() as usize] = Some(slot as u32);
+    }
+
+    value
+  }
+
+  pub fn get(&self, entity: Entity) -> Option<&T> {
+    self.slot(entity).map(|slot| &self.data[slot])
+  }
+
+  pub fn get_with_ticks(&mut self, entity: Entity) -> Option<(&mut T, &mut ComponentTicks)> {
+    let slot = self.slot(entity)?;
+    Some((&mut self.data[slot], &mut self.ticks[slot]))
+  }
+
+  pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+    self.slot(entity).map(|slot| self.ticks[slot])
+  }
+
+  pub fn entities(&self) -> &[Entity] {
+    &self.entities
+  }
+
+  /// Pointers to the component of `entity` and its ticks, without borrowing the whole set.
+  ///
+  /// # Safety
+  ///
+  /// `this` must be valid, and no other reference to the same slot may be alive while the
+  /// returned pointers are used mutably.
+  pub unsafe fn get_raw(this: *mut Self, entity: Entity) -> Option<(*mut T, *mut ComponentTicks)> {
+    let sparse = &*addr_of!((*this).sparse);
+    let entities = &*addr_of!((*this).entities);
+    let slot = (*sparse.get(entity.index() as usize)?)? as usize;
+
+    if entities[slot] != entity {
+      return None;
+    }
+
+    let data = (*addr_of_mut!((*this).data)).as_mut_ptr().add(slot);
+    let ticks = (*addr_of_mut!((*this).ticks)).as_mut_ptr().add(slot);
+    Some((data, ticks))
+  }
+
+  /// # Safety
+  ///
+  /// `this` must be valid; the entity list is never written while components are borrowed.
+  pub unsafe fn entities_raw<'a>(this: *const Self) -> &'a [Entity] {
+    &*addr_of!((*this).entities)
+  }
+}
+
+/// Type erased `SparseSet`, what the world stores per component type.
+pub(crate) trait AnyStorage: Any + Send + Sync {
+  fn remove_entity(&mut self, entity: Entity);
+  fn contains(&self, entity: Entity) -> bool;
+  fn as_any(&self) -> &dyn Any;
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> AnyStorage for SparseSet<T> {
+  fn remove_entity(&mut self, entity: Entity) {
+    self.remove(entity);
+  }
+
+  fn contains(&self, entity: Entity) -> bool {
+    self.slot(entity).is_some()
+  }
+
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
@@ -0,0 +1,427 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use crate::entity::{Entities, Entity};
+use crate::query::{Query, QueryData, QueryFilter};
+use crate::storage::{AnyStorage, ComponentTicks, SparseSet};
+use crate::{Bundle, Component, Resource, SystemTicks, Tick};
+
+type StorageLock = RwLock<Box<dyn AnyStorage>>;
+type ResourceLock = RwLock<Box<dyn Any + Send + Sync>>;
+
+/// Entities, their components and the global resources.
+///
+/// Every component type lives in its own lock, so queries and resources only need `&World` and
+/// systems that touch different types can run on several threads. Spawning, despawning and
+/// adding or removing components need `&mut World`; systems queue them as `Commands`.
+pub struct World {
+  entities: Entities,
+  components: HashMap<TypeId, StorageLock>,
+  resources: HashMap<TypeId, ResourceLock>,
+  change_tick: AtomicU32,
+  /// Change tick when `clear_trackers` was last called, what `query` compares against.
+  last_change_tick: Tick,
+}
+
+impl Default for World {
+  fn default() -> Self {
+    Self {
+      entities: Entities::default(),
+      components: HashMap::new(),
+      resources: HashMap::new(),
+      // Los componentes creados antes de cualquier sistema ya cuentan como nuevos.
+      change_tick: AtomicU32::new(1),
+      last_change_tick: 0,
+    }
+  }
+}
+
+impl fmt::Debug for World {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("World")
+      .field("entities", &self.entities.len())
+      .field("components", &self.components.len())
+      .field("resources", &self.resources.len())
+      .field("change_tick", &self.change_tick())
+      .finish()
+  }
+}
+
+pub(crate) fn read<'w, T: ?Sized>(lock: &'w RwLock<T>, name: &str) -> RwLockReadGuard<'w, T> {
+  match lock.try_read() {
+    Ok(guard) => guard,
+    Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+    Err(TryLockError::WouldBlock) => panic!("`{}` is already borrowed mutably", name),
+  }
+}
+
+pub(crate) fn write<'w, T: ?Sized>(lock: &'w RwLock<T>, name: &str) -> RwLockWriteGuard<'w, T> {
+  match lock.try_write() {
+    Ok(guard) => guard,
+    Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+    Err(TryLockError::WouldBlock) => panic!("`{}` is already borrowed", name),
+  }
+}
+
+impl World {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates an entity with the components of `bundle`.
+  pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+    let entity = self.entities.alloc();
+    bundle.insert_into(self, entity);
+    entity
+  }
+
+  /// Despawns `entity` with all its components; returns false if it was already dead.
+  pub fn despawn(&mut self, entity: Entity) -> bool {
+    if !self.entities.free(entity) {
+      return false;
+    }
+
+    for storage in self.components.values_mut() {
+      storage
+        .get_mut()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove_entity(entity);
+    }
+
+    true
+  }
+
+  pub fn is_alive(&self, entity: Entity) -> bool {
+    self.entities.is_alive(entity)
+  }
+
+  /// Live entity with `index`, e.g. to resolve an id read back from the GPU.
+  pub fn entity_at(&self, index: u32) -> Option<Entity> {
+    self.entities.at(index)
+  }
+
+  pub fn len(&self) -> usize {
+    self.entities.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entities.len() == 0
+  }
+
+  pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+    self.entities.iter()
+  }
+
+  /// Adds or replaces components of `entity`; returns false if it is dead.
+  pub fn insert(&mut self, entity: Entity, bundle: impl Bundle) -> bool {
+    if !self.is_alive(entity) {
+      return false;
+    }
+
+    bundle.insert_into(self, entity);
+    true
+  }
+
+  /// Adds or replaces one component, returning the old one. Used by `Bundle` implementations.
+  pub fn insert_one<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+    let tick = self.change_tick();
+    self.storage_mut::<T>().insert(entity, component, tick)
+  }
+
+  pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+    let storage = self.components.get_mut(&TypeId::of::<T>())?;
+    let storage = storage.get_mut().unwrap_or_else(|e| e.into_inner());
+    downcast_mut::<T>(storage.as_mut()).remove(entity)
+  }
+
+  pub fn has<T: Component>(&self, entity: Entity) -> bool {
+    self
+      .components
+      .get(&TypeId::of::<T>())
+      .is_some_and(|s| read(s, type_name::<T>()).contains(entity))
+  }
+
+  /// Shared borrow of one component, `None` if `entity` doesn't have it.
+  pub fn get<T: Component>(&self, entity: Entity) -> Option<Ref<'_, T>> {
+    let guard = read(self.components.get(&TypeId::of::<T>())?, type_name::<T>());
+    let value = downcast::<T>(guard.as_ref()).get(entity)? as *const T;
+
+    Some(Ref {
+      _guard: guard,
+      value,
+      _marker: PhantomData,
+    })
+  }
+
+  /// Mutable borrow of one component, marked as changed when written.
+  pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+    let this_run = self.change_tick();
+    let storage = self.components.get_mut(&TypeId::of::<T>())?;
+    let storage = storage.get_mut().unwrap_or_else(|e| e.into_inner());
+    let (value, ticks) = downcast_mut::<T>(storage.as_mut()).get_with_ticks(entity)?;
+
+    Some(Mut::new(value, ticks, this_run))
+  }
+
+  pub fn ticks<T: Component>(&self, entity: Entity) -> Option<ComponentTicks> {
+    let guard = read(self.components.get(&TypeId::of::<T>())?, type_name::<T>());
+    downcast::<T>(guard.as_ref()).ticks(entity)
+  }
+
+  /// Query whose change filters see what changed since the last `clear_trackers`.
+  pub fn query<Q: QueryData>(&self) -> Query<'_, Q> {
+    self.query_filtered::<Q, ()>()
+  }
+
+  pub fn query_filtered<Q: QueryData, F: QueryFilter>(&self) -> Query<'_, Q, F> {
+    let ticks = SystemTicks {
+      last_run: self.last_change_tick,
+      this_run: self.change_tick(),
+    };
+
+    Query::new(self, ticks)
+  }
+
+  pub fn insert_resource<R: Resource>(&mut self, resource: R) {
+    self
+      .resources
+      .insert(TypeId::of::<R>(), RwLock::new(Box::new(resource)));
+  }
+
+  pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+    let resource = self.resources.remove(&TypeId::of::<R>())?;
+    let resource = resource.into_inner().unwrap_or_else(|e| e.into_inner());
+    resource.downcast().ok().map(|r| *r)
+  }
+
+  pub fn contains_resource<R: Resource>(&self) -> bool {
+    self.resources.contains_key(&TypeId::of::<R>())
+  }
+
+  pub fn resource<R: Resource>(&self) -> Option<Res<'_, R>> {
+    let guard = read(self.resources.get(&TypeId::of::<R>())?, type_name::<R>());
+
+    Some(Res {
+      guard,
+      _marker: PhantomData,
+    })
+  }
+
+  pub fn resource_mut<R: Resource>(&self) -> Option<ResMut<'_, R>> {
+    let guard = write(self.resources.get(&TypeId::of::<R>())?, type_name::<R>());
+
+    Some(ResMut {
+      guard,
+      _marker: PhantomData,
+    })
+  }
+
+  /// Resource, inserting `R::default()` first if it is missing.
+  pub fn resource_or_default<R: Resource + Default>(&mut self) -> &mut R {
+    self
+      .resources
+      .entry(TypeId::of::<R>())
+      .or_insert_with(|| RwLock::new(Box::new(R::default())))
+      .get_mut()
+      .unwrap_or_else(|e| e.into_inner())
+      .downcast_mut()
+      .expect("resource stored under the wrong type")
+  }
+
+  /// Tick that changes made right now are stamped with.
+  pub fn change_tick(&self) -> Tick {
+    self.change_tick.load(Ordering::Acquire)
+  }
+
+  /// Starts a new tick and returns it; each system run gets its own.
+  pub fn increment_change_tick(&self) -> Tick {
+    self.change_tick.fetch_add(1, Ordering::AcqRel) + 1
+  }
+
+  /// Ends a frame for `query`: later `Added` / `Changed` filters only see changes made after
+  /// this call.
+  pub fn clear_trackers(&mut self) {
+    self.last_change_tick = self.increment_change_tick() - 1;
+  }
+
+  pub(crate) fn storage_lock<T: Component>(&self) -> Option<&StorageLock> {
+    self.components.get(&TypeId::of::<T>())
+  }
+
+  fn storage_mut<T: Component>(&mut self) -> &mut SparseSet<T> {
+    let storage = self
+      .components
+      .entry(TypeId::of::<T>())
+      .or_insert_with(|| RwLock::new(Box::new(SparseSet::<T>::default())))
+      .get_mut()
+      .unwrap_or_else(|e| e.into_inner());
+
+    downcast_mut::<T>(storage.as_mut())
+  }
+}
+
+pub(crate) fn downcast<T: Component>(storage: &dyn AnyStorage) -> &SparseSet<T> {
+  storage.as_any().downcast_ref().expect("storage of the wrong type")
+}
+
+pub(crate) fn downcast_mut<T: Component>(storage: &mut dyn AnyStorage) -> &mut SparseSet<T> {
+  storage.as_any_mut().downcast_mut().expect("storage of the wrong type")
+}
+
+/// Component borrowed from a `&World`, keeping its storage locked for reading.
+pub struct Ref<'w, T> {
+  _guard: RwLockReadGuard<'w, Box<dyn AnyStorage>>,
+  value: *const T,
+  _marker: PhantomData<&'w T>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    // El guard mantiene el almacenamiento bloqueado mientras viva la referencia.
+    unsafe { &*self.value }
+  }
+}
+
+/// Mutable component that stamps its change tick on write.
+pub struct Mut<'a, T> {
+  value: &'a mut T,
+  ticks: &'a mut ComponentTicks,
+  this_run: Tick,
+}
+
+impl<'a, T> Mut<'a, T> {
+  pub(crate) fn new(value: &'a mut T, ticks: &'a mut ComponentTicks, this_run: Tick) -> Self {
+    Self { value, ticks, this_run }
+  }
+
+  pub fn ticks(&self) -> ComponentTicks {
+    *self.ticks
+  }
+
+  /// Marks the component as changed without writing it.
+  pub fn set_changed(&mut self) {
+    self.ticks.changed = self.this_run;
+  }
+
+  /// Writes without marking the component as changed.
+  pub fn bypass_change_detection(&mut self) -> &mut T {
+    self.value
+  }
+}
+
+impl<T> Deref for Mut<'_, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    self.value
+  }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    self.ticks.changed = self.this_run;
+    self.value
+  }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mut<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.value.fmt(f)
+  }
+}
+
+pub struct Res<'w, R> {
+  guard: RwLockReadGuard<'w, Box<dyn Any + Send + Sync>>,
+  _marker: PhantomData<&'w R>,
+}
+
+impl<R: Resource> Deref for Res<'_, R> {
+  type Target = R;
+
+  fn deref(&self) -> &R {
+    self.guard.downcast_ref().expect("resource stored under the wrong type")
+  }
+}
+
+pub struct ResMut<'w, R> {
+  guard: RwLockWriteGuard<'w, Box<dyn Any + Send + Sync>>,
+  _marker: PhantomData<&'w mut R>,
+}
+
+impl<R: Resource> Deref for ResMut<'_, R> {
+  type Target = R;
+
+  fn deref(&self) -> &R {
+    self.guard.downcast_ref().expect("resource stored under the wrong type")
+  }
+}
+
+impl<R: Resource> DerefMut for ResMut<'_, R> {
+  fn deref_mut(&mut self) -> &mut R {
+    self.guard.downcast_mut().expect("resource stored under the wrong type")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, PartialEq)]
+  struct Position(i32);
+
+  impl Component for Position {}
+
+  #[test]
+  fn despawn_frees_the_index_for_a_new_generation() {
+    let mut world = World::new();
+    let a = world.spawn(Position(1));
+    let b = world.spawn(Position(2));
+
+    assert!(world.despawn(a));
+    assert!(!world.despawn(a));
+    assert!(!world.is_alive(a));
+    assert_eq!(world.len(), 1);
+
+    let c = world.spawn(());
+
+    assert_eq!(c.index(), a.index());
+    assert_eq!(c.generation(), a.generation() + 1);
+    assert_eq!(world.entity_at(a.index()), Some(c));
+    assert!(!world.has::<Position>(c));
+    assert!(world.get::<Position>(a).is_none());
+    assert!(!world.insert(a, Position(3)));
+    assert_eq!(world.query::<&Position>().get(a), None);
+    assert_eq!(world.iter_entities().collect::<Vec<_>>(), [c, b]);
+  }
+
+  #[test]
+  fn reused_index_doesnt_see_stale_components() {
+    let mut world = World::new();
+    let a = world.spawn(Position(1));
+    world.despawn(a);
+    let b = world.spawn(Position(2));
+
+    assert_eq!(b.index(), a.index());
+    assert_eq!(world.query::<&Position>().iter().collect::<Vec<_>>(), [&Position(2)]);
+  }
+
+  #[test]
+  fn insert_replaces_and_marks_as_changed() {
+    let mut world = World::new();
+    let a = world.spawn(Position(1));
+    world.clear_trackers();
+
+    assert_eq!(world.insert_one(a, Position(2)), Some(Position(1)));
+
+    let ticks = world.ticks::<Position>(a).unwrap();
+    assert!(!ticks.is_added(world.last_change_tick));
+    assert!(ticks.is_changed(world.last_change_tick));
+  }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::key::{Analog, Button, Modifiers};
+use crate::state::InputState;
+use crate::InputError;
+
+/// Buttons that trigger an action, written `Ctrl+Shift+KeyZ` in configs.
+///
+/// Modifiers must match exactly, so `KeyZ` does not fire while Ctrl is held; a leading `*`
+/// (`*KeyW`) accepts any extra modifiers. Several non-modifier buttons (`KeyG+KeyX`) form a
+/// chord: all of them held and one of them pressed this frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Binding {
+  pub modifiers: Modifiers,
+  pub buttons: Vec<Button>,
+  /// Extra modifiers held on top of `modifiers` don't block the binding.
+  pub any_modifiers: bool,
+}
+
+impl Binding {
+  pub fn new(buttons: impl Into<Vec<Button>>) -> Self {
+    Self {
+      modifiers: Modifiers::default(),
+      buttons: buttons.into(),
+      any_modifiers: false,
+    }
+  }
+
+  pub fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+    self.modifiers = modifiers;
+    self
+  }
+
+  pub fn with_any_modifiers(mut self) -> Self {
+    self.any_modifiers = true;
+    self
+  }
+
+  /// All buttons held with the right modifiers.
+  pub fn held(&self, state: &InputState) -> bool {
+    self.modifiers_match(state) && self.buttons.iter().all(|b| state.held(*b))
+  }
+
+  /// Went down this frame: one of its buttons pressed and the rest held. A button pressed and
+  /// released within the frame still counts.
+  pub fn pressed(&self, state: &InputState) -> bool {
+    self.modifiers_match(state)
+      && self.buttons.iter().any(|b| state.pressed(*b))
+      && self.buttons.iter().all(|b| state.held(*b) || state.pressed(*b))
+  }
+
+  /// One of its buttons went up this frame while the rest were still down. Modifiers are not
+  /// checked: letting go of Ctrl before Z still releases Ctrl+Z.
+  pub fn released(&self, state: &InputState) -> bool {
+    self.buttons.iter().any(|b| state.released(*b)) && self.buttons.iter().all(|b| state.held(*b) || state.released(*b))
+  }
+
+  fn modifiers_match(&self, state: &InputState) -> bool {
+    // Las teclas modificadoras que forman parte de la combinacion no cuentan como extra.
+    let mut required = self.modifiers;
+
+    for m in self.buttons.iter().filter_map(|b| Modifiers::of(*b)) {
+      required = required.union(m);
+    }
+
+    let held = state.modifiers();
+
+    if self.any_modifiers {
+      required.is_subset(&held)
+    } else {
+      held == required
+    }
+  }
+}
+
+impl fmt::Display for Binding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let Modifiers { ctrl, shift, alt, logo } = self.modifiers;
+    let mut parts = vec![];
+
+    for (held, name) in [(ctrl, "Ctrl"), (shift, "Shift"), (alt, "Alt"), (logo, "Super")] {
+      if held {
+        parts.push(name.to_string());
+      }
+    }
+
+    parts.extend(self.buttons.iter().map(|b| b.name()));
+
+    if self.any_modifiers {
+      write!(f, "*")?;
+    }
+
+    write!(f, "{}", parts.join("+"))
+  }
+}
+
+impl FromStr for Binding {
+  type Err = InputError;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    let (any_modifiers, rest) = match value.trim().strip_prefix('*') {
+      Some(rest) => (true, rest),
+      None => (false, value.trim()),
+    };
+
+    let mut binding = Binding::new(vec![]);
+    binding.any_modifiers = any_modifiers;
+
+    for part in rest.split('+').map(str::trim) {
+      match part {
+        "Ctrl" => binding.modifiers.ctrl = true,
+        "Shift" => binding.modifiers.shift = true,
+        "Alt" => binding.modifiers.alt = true,
+        "Super" => binding.modifiers.logo = true,
+        _ => binding
+          .buttons
+          .push(Button::from_name(part).ok_or_else(|| InputError::UnknownInput(part.to_string()))?),
+      }
+    }
+
+    if binding.buttons.is_empty() {
+      return Err(InputError::EmptyBinding(value.to_string()));
+    }
+
+    Ok(binding)
+  }
+}
+
+impl TryFrom<String> for Binding {
+  type Error = InputError;
+
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    value.parse()
+  }
+}
+
+impl From<Binding> for String {
+  fn from(binding: Binding) -> Self {
+    binding.to_string()
+  }
+}
+
+fn one() -> f32 {
+  1.0
+}
+
+/// Source of an axis value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding {
+  /// `scale` while `positive` is held, `-scale` while `negative` is, 0 with both or neither.
+  Buttons {
+    positive: Binding,
+    negative: Binding,
+    #[serde(default = "one")]
+    scale: f32,
+  },
+  /// Analog value times `scale`. Sticks and triggers below `dead_zone` read 0 and the rest of
+  /// the range is stretched back to `[0, 1]`; mouse and wheel deltas are only thresholded.
+  Analog {
+    source: Analog,
+    #[serde(default = "one")]
+    scale: f32,
+    #[serde(default)]
+    dead_zone: f32,
+  },
+}
+
+impl AxisBinding {
+  pub fn value(&self, state: &InputState) -> f32 {
+    match self {
+      AxisBinding::Buttons {
+        positive,
+        negative,
+        scale,
+      } => (positive.held(state) as i32 - negative.held(state) as i32) as f32 * scale,
+      AxisBinding::Analog {
+        source,
+        scale,
+        dead_zone,
+      } => {
+        let value = state.analog(*source);
+
+        if value.abs() <= *dead_zone {
+          0.0
+        } else if source.is_absolute() {
+          value.signum() * (value.abs() - dead_zone) / (1.0 - dead_zone).max(f32::EPSILON) * scale
+        } else {
+          value * scale
+        }
+      }
+    }
+  }
+}
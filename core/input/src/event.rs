@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::key::{Analog, Button};
+
+/// Platform independent input event, what backends feed into `Input` and what recordings store.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+  Button {
+    button: Button,
+    pressed: bool,
+  },
+  /// Raw mouse movement in pixels, accumulated into `Analog::MouseX` / `MouseY`.
+  MouseMotion {
+    dx: f32,
+    dy: f32,
+  },
+  /// Cursor position in physical pixels from the top left corner of the window.
+  CursorMoved {
+    x: f32,
+    y: f32,
+  },
+  CursorLeft,
+  /// Scroll in lines, accumulated into `Analog::Wheel`.
+  Wheel {
+    lines: f32,
+  },
+  /// Absolute value of a stick or trigger.
+  Axis {
+    analog: Analog,
+    value: f32,
+  },
+  /// The window lost focus: everything held is released.
+  FocusLost,
+}
+
+impl InputEvent {
+  pub fn button(button: Button, pressed: bool) -> Self {
+    InputEvent::Button { button, pressed }
+  }
+}
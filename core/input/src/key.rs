@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+macro_rules! keys {
+  ($($name:ident),* $(,)?) => {
+    /// Physical keyboard key, named after its position on a US layout like winit's `KeyCode`.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+    pub enum Key {
+      $($name),*
+    }
+
+    impl Key {
+      pub const ALL: &'static [Key] = &[$(Key::$name),*];
+
+      pub fn name(self) -> &'static str {
+        match self {
+          $(Key::$name => stringify!($name)),*
+        }
+      }
+
+      #[cfg(feature = "winit")]
+      pub fn from_winit(code: ::winit::keyboard::KeyCode) -> Option<Self> {
+        match code {
+          $(::winit::keyboard::KeyCode::$name => Some(Key::$name),)*
+          _ => None,
+        }
+      }
+    }
+  };
+}
+
+keys! {
+  KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS,
+  KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ, Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8,
+  Digit9, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Escape, Tab, Space, Enter, Backspace, Delete, Insert,
+  Home, End, PageUp, PageDown, ArrowUp, ArrowDown, ArrowLeft, ArrowRight, Minus, Equal, BracketLeft, BracketRight,
+  Comma, Period, Slash, Backslash, Semicolon, Quote, Backquote, ShiftLeft, ShiftRight, ControlLeft, ControlRight,
+  AltLeft, AltRight, SuperLeft, SuperRight, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7,
+  Numpad8, Numpad9, NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MouseButton {
+  Left,
+  Right,
+  Middle,
+  Back,
+  Forward,
+}
+
+/// Gamepad buttons with the positional names of the south / east / west / north face buttons.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+  South,
+  East,
+  West,
+  North,
+  LeftBumper,
+  RightBumper,
+  LeftStick,
+  RightStick,
+  Select,
+  Start,
+  DPadUp,
+  DPadDown,
+  DPadLeft,
+  DPadRight,
+}
+
+/// Analog values an axis can read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Analog {
+  /// Mouse movement this frame in pixels, right is positive.
+  MouseX,
+  /// Mouse movement this frame in pixels, down is positive.
+  MouseY,
+  /// Scroll lines this frame, away from the user is positive.
+  Wheel,
+  /// Stick positions in `[-1, 1]`, up and right are positive.
+  LeftStickX,
+  LeftStickY,
+  RightStickX,
+  RightStickY,
+  /// Trigger positions in `[0, 1]`.
+  LeftTrigger,
+  RightTrigger,
+}
+
+impl Analog {
+  pub const ALL: &'static [Analog] = &[
+    Analog::MouseX,
+    Analog::MouseY,
+    Analog::Wheel,
+    Analog::LeftStickX,
+    Analog::LeftStickY,
+    Analog::RightStickX,
+    Analog::RightStickY,
+    Analog::LeftTrigger,
+    Analog::RightTrigger,
+  ];
+
+  /// Values that persist between frames, unlike the per-frame mouse and wheel deltas.
+  pub fn is_absolute(self) -> bool {
+    !matches!(self, Analog::MouseX | Analog::MouseY | Analog::Wheel)
+  }
+}
+
+/// Anything with a pressed / released state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Button {
+  Key(Key),
+  Mouse(MouseButton),
+  Gamepad(GamepadButton),
+}
+
+impl Button {
+  /// Name used in binding strings: `KeyZ`, `MouseLeft`, `GamepadSouth`...
+  pub fn name(self) -> String {
+    match self {
+      Button::Key(key) => key.name().to_string(),
+      Button::Mouse(button) => format!("Mouse{:?}", button),
+      Button::Gamepad(button) => format!("Gamepad{:?}", button),
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    const MOUSE: [MouseButton; 5] = [
+      MouseButton::Left,
+      MouseButton::Right,
+      MouseButton::Middle,
+      MouseButton::Back,
+      MouseButton::Forward,
+    ];
+    const GAMEPAD: [GamepadButton; 14] = [
+      GamepadButton::South,
+      GamepadButton::East,
+      GamepadButton::West,
+      GamepadButton::North,
+      GamepadButton::LeftBumper,
+      GamepadButton::RightBumper,
+      GamepadButton::LeftStick,
+      GamepadButton::RightStick,
+      GamepadButton::Select,
+      GamepadButton::Start,
+      GamepadButton::DPadUp,
+      GamepadButton::DPadDown,
+      GamepadButton::DPadLeft,
+      GamepadButton::DPadRight,
+    ];
+
+    Key::ALL
+      .iter()
+      .map(|k| Button::Key(*k))
+      .chain(MOUSE.map(Button::Mouse))
+      .chain(GAMEPAD.map(Button::Gamepad))
+      .find(|b| b.name() == name)
+  }
+}
+
+/// Modifier keys, either side.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Modifiers {
+  pub ctrl: bool,
+  pub shift: bool,
+  pub alt: bool,
+  pub logo: bool,
+}
+
+impl Modifiers {
+  /// Which modifier `button` is, if any.
+  pub fn of(button: Button) -> Option<Modifiers> {
+    let mut modifiers = Modifiers::default();
+
+    match button {
+      Button::Key(Key::ControlLeft | Key::ControlRight) => modifiers.ctrl = true,
+      Button::Key(Key::ShiftLeft | Key::ShiftRight) => modifiers.shift = true,
+      Button::Key(Key::AltLeft | Key::AltRight) => modifiers.alt = true,
+      Button::Key(Key::SuperLeft | Key::SuperRight) => modifiers.logo = true,
+      _ => return None,
+    }
+
+    Some(modifiers)
+  }
+
+  pub fn union(self, other: Modifiers) -> Modifiers {
+    Modifiers {
+      ctrl: self.ctrl || other.ctrl,
+      shift: self.shift || other.shift,
+      alt: self.alt || other.alt,
+      logo: self.logo || other.logo,
+    }
+  }
+
+  /// Whether every modifier of `self` is also in `other`.
+  pub fn is_subset(&self, other: &Modifiers) -> bool {
+    (!self.ctrl || other.ctrl) && (!self.shift || other.shift) && (!self.alt || other.alt) && (!self.logo || other.logo)
+  }
+}
@@ -0,0 +1,162 @@
+//! Input mapping: raw keyboard, mouse and gamepad state per frame, named actions and axes bound
+//! to it through a RON config, and recordings of the event stream to replay.
+//!
+//! Only keyboard and mouse have a backend, the winit one in `platform`; gamepad buttons and
+//! sticks are read from `InputEvent`s fed by hand or from a recording.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+pub mod binding;
+pub mod event;
+pub mod key;
+pub mod map;
+#[cfg(feature = "winit")]
+pub mod platform;
+pub mod record;
+pub mod state;
+
+pub use binding::{AxisBinding, Binding};
+pub use event::InputEvent;
+pub use key::{Analog, Button, GamepadButton, Key, Modifiers, MouseButton};
+pub use map::InputMap;
+pub use record::Recording;
+pub use state::InputState;
+
+#[derive(Debug, Error)]
+pub enum InputError {
+  #[error("Unknown input `{0}`")]
+  UnknownInput(String),
+  #[error("Binding `{0}` has no button")]
+  EmptyBinding(String),
+  #[error("Invalid input config: {0}")]
+  Parse(#[from] ron::error::SpannedError),
+  #[error("Can't write input config: {0}")]
+  Serialize(#[from] ron::Error),
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+}
+
+/// Input of one window: feed it events, query actions and axes, then call `end_frame` once the
+/// frame has handled them.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+  map: InputMap,
+  state: InputState,
+  /// Actions held at the end of the last frame, to detect their release.
+  active: HashSet<String>,
+  /// Actions pressed from code this frame, see `trigger`.
+  triggered: HashSet<String>,
+  recording: Option<Recording>,
+}
+
+impl Input {
+  pub fn new(map: InputMap) -> Self {
+    Self {
+      map,
+      ..Default::default()
+    }
+  }
+
+  pub fn map(&self) -> &InputMap {
+    &self.map
+  }
+
+  pub fn map_mut(&mut self) -> &mut InputMap {
+    &mut self.map
+  }
+
+  pub fn state(&self) -> &InputState {
+    &self.state
+  }
+
+  pub fn handle(&mut self, event: &InputEvent) {
+    if let Some(recording) = self.recording.as_mut() {
+      recording.push(*event);
+    }
+
+    self.state.handle(event);
+  }
+
+  pub fn end_frame(&mut self) {
+    self.active = self
+      .map
+      .actions()
+      .filter(|action| self.held(action))
+      .map(str::to_string)
+      .collect();
+
+    self.state.end_frame();
+    self.triggered.clear();
+
+    if let Some(recording) = self.recording.as_mut() {
+      recording.end_frame();
+    }
+  }
+
+  /// Some binding of `action` started this frame, or it was triggered.
+  pub fn pressed(&self, action: &str) -> bool {
+    self.triggered.contains(action) || self.map.bindings(action).iter().any(|b| b.pressed(&self.state))
+  }
+
+  /// Presses `action` for this frame without any of its bindings, e.g. from a menu item. It is
+  /// never held, so it doesn't count as released either.
+  pub fn trigger(&mut self, action: impl Into<String>) {
+    self.triggered.insert(action.into());
+  }
+
+  pub fn held(&self, action: &str) -> bool {
+    self.map.bindings(action).iter().any(|b| b.held(&self.state))
+  }
+
+  /// `action` stopped being held this frame, including presses that began and ended within it.
+  pub fn released(&self, action: &str) -> bool {
+    !self.held(action)
+      && (self.active.contains(action)
+        || self
+          .map
+          .bindings(action)
+          .iter()
+          .any(|b| b.pressed(&self.state) && b.released(&self.state)))
+  }
+
+  /// Value of the strongest binding of `axis`, 0 if it doesn't exist.
+  pub fn axis(&self, axis: &str) -> f32 {
+    self
+      .map
+      .axis_bindings(axis)
+      .iter()
+      .map(|b| b.value(&self.state))
+      .fold(0.0, |strongest, value| {
+        if value.abs() > strongest.abs() {
+          value
+        } else {
+          strongest
+        }
+      })
+  }
+
+  pub fn cursor(&self) -> Option<[f32; 2]> {
+    self.state.cursor()
+  }
+
+  /// Binding of the button pressed this frame with the modifiers held, to capture a new binding
+  /// while the user rebinds an action. Modifier keys alone don't count.
+  pub fn captured_binding(&self) -> Option<Binding> {
+    let button = self.state.pressed_buttons().find(|b| Modifiers::of(*b).is_none())?;
+    Some(Binding::new(vec![button]).with_modifiers(self.state.modifiers()))
+  }
+
+  pub fn start_recording(&mut self) {
+    self.recording = Some(Recording::new());
+  }
+
+  pub fn stop_recording(&mut self) -> Option<Recording> {
+    self.recording.take()
+  }
+
+  pub fn is_recording(&self) -> bool {
+    self.recording.is_some()
+  }
+}
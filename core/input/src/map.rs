@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::binding::{AxisBinding, Binding};
+use crate::InputError;
+
+/// Named actions and axes with the inputs bound to them, loaded from and saved to RON:
+///
+/// ```ron
+/// (
+///   actions: { "undo": ["Ctrl+KeyZ"], "redo": ["Ctrl+KeyY", "Ctrl+Shift+KeyZ"] },
+///   axes: {
+///     "move_x": [
+///       Buttons(positive: "*KeyD", negative: "*KeyA"),
+///       Analog(source: LeftStickX, dead_zone: 0.2),
+///     ],
+///   },
+/// )
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+  #[serde(default)]
+  actions: BTreeMap<String, Vec<Binding>>,
+  #[serde(default)]
+  axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+impl InputMap {
+  pub fn from_ron(source: &str) -> Result<Self, InputError> {
+    Ok(ron::from_str(source)?)
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, InputError> {
+    Self::from_ron(&fs::read_to_string(path)?)
+  }
+
+  pub fn to_ron(&self) -> Result<String, InputError> {
+    Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), InputError> {
+    Ok(fs::write(path, self.to_ron()?)?)
+  }
+
+  /// Bindings of `action`, empty if it doesn't exist.
+  pub fn bindings(&self, action: &str) -> &[Binding] {
+    self.actions.get(action).map_or(&[], Vec::as_slice)
+  }
+
+  pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+    self.axes.get(axis).map_or(&[], Vec::as_slice)
+  }
+
+  pub fn actions(&self) -> impl Iterator<Item = &str> {
+    self.actions.keys().map(String::as_str)
+  }
+
+  pub fn axes(&self) -> impl Iterator<Item = &str> {
+    self.axes.keys().map(String::as_str)
+  }
+
+  /// Adds a binding to `action`, creating the action if needed.
+  pub fn bind(&mut self, action: impl Into<String>, binding: Binding) {
+    let bindings = self.actions.entry(action.into()).or_default();
+
+    if !bindings.contains(&binding) {
+      bindings.push(binding);
+    }
+  }
+
+  /// Replaces every binding of `action`.
+  pub fn rebind(&mut self, action: impl Into<String>, bindings: Vec<Binding>) {
+    self.actions.insert(action.into(), bindings);
+  }
+
+  pub fn bind_axis(&mut self, axis: impl Into<String>, binding: AxisBinding) {
+    self.axes.entry(axis.into()).or_default().push(binding);
+  }
+
+  pub fn rebind_axis(&mut self, axis: impl Into<String>, bindings: Vec<AxisBinding>) {
+    self.axes.insert(axis.into(), bindings);
+  }
+
+  /// Actions other than `action` that `binding` also triggers, to warn about when rebinding.
+  pub fn conflicts<'a>(&'a self, action: &'a str, binding: &'a Binding) -> impl Iterator<Item = &'a str> {
+    self
+      .actions
+      .iter()
+      .filter(move |(name, bindings)| name.as_str() != action && bindings.contains(binding))
+      .map(|(name, _)| name.as_str())
+  }
+
+  /// Takes the bindings of `other` over ours, action by action, e.g. user overrides over the
+  /// defaults.
+  pub fn merge(&mut self, other: InputMap) {
+    self.actions.extend(other.actions);
+    self.axes.extend(other.axes);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::key::{Analog, Button, Key, Modifiers};
+
+  const CONFIG: &str = r#"(
+    actions: {
+      "undo": ["Ctrl+KeyZ"],
+      "redo": ["Ctrl+KeyY", "Ctrl+Shift+KeyZ"],
+      "forward": ["*KeyW"],
+      "grab_x": ["KeyG+KeyX"],
+    },
+    axes: {
+      "move_x": [
+        Buttons(positive: "*KeyD", negative: "*KeyA"),
+        Analog(source: LeftStickX, dead_zone: 0.2),
+      ],
+    },
+  )"#;
+
+  fn ctrl() -> Modifiers {
+    Modifiers {
+      ctrl: true,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn parses_actions_and_axes() {
+    let map = InputMap::from_ron(CONFIG).unwrap();
+
+    assert_eq!(
+      map.bindings("undo"),
+      [Binding::new([Button::Key(Key::KeyZ)]).with_modifiers(ctrl())]
+    );
+    assert_eq!(map.bindings("redo").len(), 2);
+    assert_eq!(
+      map.bindings("forward"),
+      [Binding::new([Button::Key(Key::KeyW)]).with_any_modifiers()]
+    );
+    assert_eq!(
+      map.bindings("grab_x"),
+      [Binding::new([Button::Key(Key::KeyG), Button::Key(Key::KeyX)])]
+    );
+    assert!(map.bindings("missing").is_empty());
+
+    let axes = map.axis_bindings("move_x");
+    assert_eq!(axes.len(), 2);
+    assert_eq!(
+      axes[1],
+      AxisBinding::Analog {
+        source: Analog::LeftStickX,
+        scale: 1.0,
+        dead_zone: 0.2,
+      }
+    );
+  }
+
+  #[test]
+  fn round_trips_through_ron() {
+    let map = InputMap::from_ron(CONFIG).unwrap();
+    assert_eq!(InputMap::from_ron(&map.to_ron().unwrap()).unwrap(), map);
+  }
+
+  #[test]
+  fn bindings_print_as_they_parse() {
+    for text in ["Ctrl+Shift+KeyZ", "*KeyW", "KeyG+KeyX", "Alt+MouseLeft", "GamepadSouth"] {
+      assert_eq!(text.parse::<Binding>().unwrap().to_string(), text);
+    }
+  }
+
+  #[test]
+  fn rejects_unknown_inputs_and_empty_bindings() {
+    let unknown = InputMap::from_ron(r#"(actions: { "jump": ["KeyNope"] })"#);
+    assert!(matches!(unknown, Err(InputError::Parse(e)) if e.to_string().contains("KeyNope")));
+
+    assert!(matches!(
+      "Ctrl+Shift".parse::<Binding>(),
+      Err(InputError::EmptyBinding(_))
+    ));
+    assert!(matches!("Ctrl+Nope".parse::<Binding>(), Err(InputError::UnknownInput(name)) if name == "Nope"));
+  }
+
+  #[test]
+  fn merge_replaces_whole_actions() {
+    let mut map = InputMap::from_ron(CONFIG).unwrap();
+    map.merge(InputMap::from_ron(r#"(actions: { "redo": ["Ctrl+KeyR"] })"#).unwrap());
+
+    assert_eq!(
+      map.bindings("redo"),
+      [Binding::new([Button::Key(Key::KeyR)]).with_modifiers(ctrl())]
+    );
+    assert_eq!(map.bindings("undo").len(), 1);
+  }
+
+  #[test]
+  fn conflicts_list_other_actions_with_the_binding() {
+    let mut map = InputMap::from_ron(CONFIG).unwrap();
+    let binding = "Ctrl+KeyZ".parse::<Binding>().unwrap();
+    map.bind("also_undo", binding.clone());
+
+    assert_eq!(map.conflicts("undo", &binding).collect::<Vec<_>>(), ["also_undo"]);
+  }
+}
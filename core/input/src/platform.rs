@@ -0,0 +1,63 @@
+use winit::event::{DeviceEvent, ElementState, MouseScrollDelta, WindowEvent};
+use winit::keyboard::PhysicalKey;
+
+use crate::event::InputEvent;
+use crate::key::{Button, Key, MouseButton};
+
+/// Pixels of a precise (touchpad) scroll that count as one line.
+const PIXELS_PER_LINE: f64 = 40.0;
+
+/// Converts the input related window events, the rest return `None`.
+pub fn from_window_event(event: &WindowEvent) -> Option<InputEvent> {
+  let event = match event {
+    WindowEvent::KeyboardInput { event, .. } => {
+      let PhysicalKey::Code(code) = event.physical_key else {
+        return None;
+      };
+
+      InputEvent::button(
+        Button::Key(Key::from_winit(code)?),
+        event.state == ElementState::Pressed,
+      )
+    }
+    WindowEvent::MouseInput { state, button, .. } => {
+      let button = match button {
+        winit::event::MouseButton::Left => MouseButton::Left,
+        winit::event::MouseButton::Right => MouseButton::Right,
+        winit::event::MouseButton::Middle => MouseButton::Middle,
+        winit::event::MouseButton::Back => MouseButton::Back,
+        winit::event::MouseButton::Forward => MouseButton::Forward,
+        winit::event::MouseButton::Other(_) => return None,
+      };
+
+      InputEvent::button(Button::Mouse(button), *state == ElementState::Pressed)
+    }
+    WindowEvent::CursorMoved { position, .. } => InputEvent::CursorMoved {
+      x: position.x as f32,
+      y: position.y as f32,
+    },
+    WindowEvent::CursorLeft { .. } => InputEvent::CursorLeft,
+    WindowEvent::MouseWheel { delta, .. } => InputEvent::Wheel {
+      lines: match delta {
+        MouseScrollDelta::LineDelta(_, y) => *y,
+        MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+      },
+    },
+    WindowEvent::Focused(false) => InputEvent::FocusLost,
+    _ => return None,
+  };
+
+  Some(event)
+}
+
+/// Raw mouse motion, for cameras that lock the cursor; don't feed it together with
+/// `CursorMoved` or the movement counts twice.
+pub fn from_device_event(event: &DeviceEvent) -> Option<InputEvent> {
+  match event {
+    DeviceEvent::MouseMotion { delta: (dx, dy) } => Some(InputEvent::MouseMotion {
+      dx: *dx as f32,
+      dy: *dy as f32,
+    }),
+    _ => None,
+  }
+}
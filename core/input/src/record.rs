@@ -0,0 +1,271 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::InputEvent;
+use crate::{Input, InputError};
+
+/// Events fed into `Input`, frame by frame, so a session can be replayed deterministically.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+  frames: Vec<Vec<InputEvent>>,
+}
+
+impl Recording {
+  pub fn new() -> Self {
+    Self { frames: vec![vec![]] }
+  }
+
+  /// Adds `event` to the current frame.
+  pub fn push(&mut self, event: InputEvent) {
+    match self.frames.last_mut() {
+      Some(frame) => frame.push(event),
+      None => self.frames.push(vec![event]),
+    }
+  }
+
+  /// Starts a new frame.
+  pub fn end_frame(&mut self) {
+    self.frames.push(vec![]);
+  }
+
+  pub fn frames(&self) -> &[Vec<InputEvent>] {
+    &self.frames
+  }
+
+  /// Feeds every frame into `input`, calling `frame` between the events and the end of the frame,
+  /// where the actions of that frame can be queried.
+  pub fn replay(&self, input: &mut Input, mut frame: impl FnMut(&Input)) {
+    for events in &self.frames {
+      events.iter().for_each(|event| input.handle(event));
+      frame(input);
+      input.end_frame();
+    }
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, InputError> {
+    Ok(ron::from_str(&fs::read_to_string(path)?)?)
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), InputError> {
+    Ok(fs::write(path, ron::ser::to_string(self)?)?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::key::{Analog, Button, Key};
+  use crate::InputMap;
+
+  fn key(key: Key, pressed: bool) -> InputEvent {
+    InputEvent::button(Button::Key(key), pressed)
+  }
+
+  /// A recording with one frame per item of `frames`.
+  fn recording(frames: Vec<Vec<InputEvent>>) -> Recording {
+    let mut recording = Recording::new();
+
+    for (i, events) in frames.into_iter().enumerate() {
+      if i > 0 {
+        recording.end_frame();
+      }
+
+      events.into_iter().for_each(|e| recording.push(e));
+    }
+
+    recording
+  }
+
+  fn input(config: &str) -> Input {
+    Input::new(InputMap::from_ron(config).unwrap())
+  }
+
+  /// What `query` returns on each frame of `recording`.
+  fn replay<T>(config: &str, recording: &Recording, mut query: impl FnMut(&Input) -> T) -> Vec<T> {
+    let mut frames = vec![];
+    recording.replay(&mut input(config), |input| frames.push(query(input)));
+    frames
+  }
+
+  fn edges(action: &'static str) -> impl FnMut(&Input) -> (bool, bool, bool) {
+    move |input| (input.pressed(action), input.held(action), input.released(action))
+  }
+
+  fn assert_close(found: &[f32], expected: &[f32]) {
+    assert_eq!(found.len(), expected.len());
+
+    for (found, expected) in found.iter().zip(expected) {
+      assert!((found - expected).abs() < 1e-5, "{:?} != {:?}", found, expected);
+    }
+  }
+
+  #[test]
+  fn replays_pressed_held_and_released() {
+    let recording = recording(vec![
+      vec![key(Key::Space, true)],
+      // Repeticion del sistema.
+      vec![key(Key::Space, true)],
+      vec![],
+      vec![key(Key::Space, false)],
+      vec![],
+    ]);
+
+    let frames = replay(r#"(actions: { "jump": ["Space"] })"#, &recording, edges("jump"));
+
+    assert_eq!(
+      frames,
+      [
+        (true, true, false),
+        (false, true, false),
+        (false, true, false),
+        (false, false, true),
+        (false, false, false),
+      ]
+    );
+  }
+
+  #[test]
+  fn tap_within_a_frame_is_pressed_and_released() {
+    let recording = recording(vec![vec![key(Key::Space, true), key(Key::Space, false)], vec![]]);
+    let frames = replay(r#"(actions: { "jump": ["Space"] })"#, &recording, edges("jump"));
+
+    assert_eq!(frames, [(true, false, true), (false, false, false)]);
+  }
+
+  #[test]
+  fn replay_is_deterministic_after_saving() {
+    let recording = recording(vec![
+      vec![key(Key::KeyW, true)],
+      vec![InputEvent::MouseMotion { dx: 3.0, dy: -1.0 }],
+      vec![key(Key::KeyW, false)],
+    ]);
+    let saved = ron::from_str::<Recording>(&ron::ser::to_string(&recording).unwrap()).unwrap();
+    let config = r#"(actions: { "forward": ["*KeyW"] })"#;
+
+    assert_eq!(saved, recording);
+    assert_eq!(
+      replay(config, &saved, edges("forward")),
+      replay(config, &recording, edges("forward"))
+    );
+  }
+
+  #[test]
+  fn chords_need_every_button() {
+    let recording = recording(vec![
+      vec![key(Key::KeyG, true)],
+      vec![key(Key::KeyX, true)],
+      vec![key(Key::KeyG, false)],
+      vec![key(Key::KeyX, false), key(Key::KeyX, true)],
+    ]);
+
+    let frames = replay(r#"(actions: { "grab_x": ["KeyG+KeyX"] })"#, &recording, edges("grab_x"));
+
+    assert_eq!(
+      frames,
+      [
+        (false, false, false),
+        (true, true, false),
+        (false, false, true),
+        (false, false, false),
+      ]
+    );
+  }
+
+  #[test]
+  fn modifiers_match_exactly_unless_starred() {
+    let config = r#"(actions: {
+      "undo": ["Ctrl+KeyZ"],
+      "redo": ["Ctrl+Shift+KeyZ"],
+      "plain": ["KeyZ"],
+      "any": ["*KeyZ"],
+    })"#;
+    let recording = recording(vec![
+      vec![key(Key::ControlLeft, true), key(Key::KeyZ, true)],
+      vec![key(Key::KeyZ, false)],
+      vec![key(Key::ShiftRight, true), key(Key::KeyZ, true)],
+    ]);
+
+    let frames = replay(config, &recording, |input| {
+      ["undo", "redo", "plain", "any"].map(|action| input.pressed(action))
+    });
+
+    assert_eq!(
+      frames,
+      [
+        [true, false, false, true],
+        [false, false, false, false],
+        [false, true, false, true],
+      ]
+    );
+  }
+
+  #[test]
+  fn letting_go_of_the_modifier_first_releases_the_binding() {
+    let recording = recording(vec![
+      vec![key(Key::ControlLeft, true), key(Key::KeyZ, true)],
+      vec![key(Key::ControlLeft, false)],
+      vec![key(Key::KeyZ, false)],
+    ]);
+
+    let frames = replay(r#"(actions: { "undo": ["Ctrl+KeyZ"] })"#, &recording, edges("undo"));
+
+    assert_eq!(
+      frames,
+      [(true, true, false), (false, false, true), (false, false, false)]
+    );
+  }
+
+  #[test]
+  fn analog_axes_apply_dead_zones_and_keep_absolute_values() {
+    let config = r#"(axes: {
+      "move_x": [Analog(source: LeftStickX, dead_zone: 0.2)],
+      "look_x": [Analog(source: MouseX, scale: 0.5, dead_zone: 1.0)],
+    })"#;
+    let stick = |value| InputEvent::Axis {
+      analog: Analog::LeftStickX,
+      value,
+    };
+    let mouse = |dx| InputEvent::MouseMotion { dx, dy: 0.0 };
+    let recording = recording(vec![
+      vec![stick(0.1), mouse(0.5)],
+      vec![stick(0.6), mouse(4.0)],
+      vec![stick(-1.0)],
+      vec![],
+      vec![InputEvent::FocusLost],
+    ]);
+
+    let move_x = replay(config, &recording, |input| input.axis("move_x"));
+    let look_x = replay(config, &recording, |input| input.axis("look_x"));
+
+    // Los sticks conservan su valor entre frames, el raton solo cuenta en el suyo.
+    assert_close(&move_x, &[0.0, 0.5, -1.0, -1.0, 0.0]);
+    assert_close(&look_x, &[0.0, 2.0, 0.0, 0.0, 0.0]);
+  }
+
+  #[test]
+  fn button_axes_cancel_out() {
+    let config = r#"(axes: { "strafe": [Buttons(positive: "*KeyD", negative: "*KeyA", scale: 2.0)] })"#;
+    let recording = recording(vec![
+      vec![key(Key::KeyD, true)],
+      vec![key(Key::KeyA, true)],
+      vec![key(Key::KeyD, false)],
+    ]);
+
+    let frames = replay(config, &recording, |input| input.axis("strafe"));
+
+    assert_close(&frames, &[2.0, 0.0, -2.0]);
+  }
+
+  #[test]
+  fn focus_lost_releases_everything_held() {
+    let recording = recording(vec![vec![key(Key::KeyW, true)], vec![InputEvent::FocusLost], vec![]]);
+    let frames = replay(r#"(actions: { "forward": ["KeyW"] })"#, &recording, edges("forward"));
+
+    assert_eq!(
+      frames,
+      [(true, true, false), (false, false, true), (false, false, false)]
+    );
+  }
+}
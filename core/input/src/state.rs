@@ -0,0 +1,95 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::event::InputEvent;
+use crate::key::{Analog, Button, Modifiers};
+
+/// Raw device state: which buttons are held, which changed this frame and the analog values.
+#[derive(Clone, Debug, Default)]
+pub struct InputState {
+  held: HashSet<Button>,
+  pressed: HashSet<Button>,
+  released: HashSet<Button>,
+  analog: HashMap<Analog, f32>,
+  cursor: Option<[f32; 2]>,
+}
+
+impl InputState {
+  pub fn handle(&mut self, event: &InputEvent) {
+    match *event {
+      InputEvent::Button { button, pressed: true } => {
+        // Las repeticiones del sistema no cuentan como una nueva pulsacion.
+        if self.held.insert(button) {
+          self.pressed.insert(button);
+        }
+      }
+      InputEvent::Button { button, pressed: false } => {
+        if self.held.remove(&button) {
+          self.released.insert(button);
+        }
+      }
+      InputEvent::MouseMotion { dx, dy } => {
+        *self.analog.entry(Analog::MouseX).or_default() += dx;
+        *self.analog.entry(Analog::MouseY).or_default() += dy;
+      }
+      InputEvent::CursorMoved { x, y } => {
+        if let Some([previous_x, previous_y]) = self.cursor.replace([x, y]) {
+          *self.analog.entry(Analog::MouseX).or_default() += x - previous_x;
+          *self.analog.entry(Analog::MouseY).or_default() += y - previous_y;
+        }
+      }
+      InputEvent::CursorLeft => self.cursor = None,
+      InputEvent::Wheel { lines } => *self.analog.entry(Analog::Wheel).or_default() += lines,
+      InputEvent::Axis { analog, value } => {
+        self.analog.insert(analog, value);
+      }
+      InputEvent::FocusLost => {
+        self.released.extend(self.held.drain());
+        self.analog.retain(|analog, _| !analog.is_absolute());
+      }
+    }
+  }
+
+  /// Ends the frame: clears the pressed / released edges and the per-frame deltas.
+  pub fn end_frame(&mut self) {
+    self.pressed.clear();
+    self.released.clear();
+    self.analog.retain(|analog, _| analog.is_absolute());
+  }
+
+  pub fn held(&self, button: Button) -> bool {
+    self.held.contains(&button)
+  }
+
+  /// Went down this frame.
+  pub fn pressed(&self, button: Button) -> bool {
+    self.pressed.contains(&button)
+  }
+
+  /// Went up this frame.
+  pub fn released(&self, button: Button) -> bool {
+    self.released.contains(&button)
+  }
+
+  pub fn pressed_buttons(&self) -> impl Iterator<Item = Button> + '_ {
+    self.pressed.iter().copied()
+  }
+
+  pub fn analog(&self, analog: Analog) -> f32 {
+    self.analog.get(&analog).copied().unwrap_or(0.0)
+  }
+
+  pub fn cursor(&self) -> Option<[f32; 2]> {
+    self.cursor
+  }
+
+  /// Modifiers currently held, either side.
+  pub fn modifiers(&self) -> Modifiers {
+    let mut modifiers = Modifiers::default();
+
+    for m in self.held.iter().filter_map(|b| Modifiers::of(*b)) {
+      modifiers = modifiers.union(m);
+    }
+
+    modifiers
+  }
+}
@@ -0,0 +1,378 @@
+//! `#[derive(Reflect)]` for `sagitario-reflect`: implements `Reflect` and `FromValue` for structs
+//! and enums whose fields implement them.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::ParseStream;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, ExprLit, Fields, Ident, Index, Lit, LitStr, Meta, Token};
+
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+
+  let expanded = match &input.data {
+    Data::Struct(data) => reflect_struct(&input, &data.fields),
+    Data::Enum(data) => reflect_enum(&input, data.variants.iter().collect()),
+    Data::Union(_) => Err(syn::Error::new_spanned(&input.ident, "unions can't derive `Reflect`")),
+  };
+
+  expanded.unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// A field with what the derive needs of it.
+struct FieldData {
+  /// Identifier, or index in a tuple struct or variant.
+  name: String,
+  /// How a struct reaches it: `a` or `0`.
+  member: TokenStream2,
+  /// Binding in variant patterns.
+  binding: Ident,
+  info: TokenStream2,
+}
+
+fn field_data(fields: &Fields) -> syn::Result<Vec<FieldData>> {
+  let mut data = vec![];
+
+  for (i, field) in fields.iter().enumerate() {
+    let (name, member) = match &field.ident {
+      Some(ident) => (ident.to_string(), quote!(#ident)),
+      None => {
+        let index = Index::from(i);
+        (i.to_string(), quote!(#index))
+      }
+    };
+
+    let ty = &field.ty;
+    let type_name = quote!(#ty).to_string().replace(' ', "");
+    let Attributes { range, tooltip, hidden } = attributes(field)?;
+
+    let range = match range {
+      Some((min, max)) => quote!(::core::option::Option::Some((#min, #max))),
+      None => quote!(::core::option::Option::None),
+    };
+    let tooltip = match tooltip {
+      Some(tooltip) => quote!(::core::option::Option::Some(#tooltip)),
+      None => quote!(::core::option::Option::None),
+    };
+
+    data.push(FieldData {
+      binding: format_ident!("field_{}", i),
+      info: quote! {
+        ::sagitario_reflect::FieldInfo {
+          name: #name,
+          type_name: #type_name,
+          range: #range,
+          tooltip: #tooltip,
+          hidden: #hidden,
+        }
+      },
+      name,
+      member,
+    });
+  }
+
+  Ok(data)
+}
+
+/// What `#[reflect(...)]` says of a field.
+struct Attributes {
+  range: Option<(f64, f64)>,
+  tooltip: Option<String>,
+  hidden: bool,
+}
+
+/// `#[reflect(range(min, max), tooltip = "...", hidden)]`. Without a tooltip, the doc comment is
+/// used.
+fn attributes(field: &syn::Field) -> syn::Result<Attributes> {
+  let (mut range, mut tooltip, mut hidden) = (None, None, false);
+  let mut docs = vec![];
+
+  for attribute in &field.attrs {
+    if attribute.path().is_ident("doc") {
+      if let Meta::NameValue(meta) = &attribute.meta {
+        if let Expr::Lit(ExprLit { lit: Lit::Str(doc), .. }) = &meta.value {
+          docs.push(doc.value().trim().to_string());
+        }
+      }
+
+      continue;
+    }
+
+    if !attribute.path().is_ident("reflect") {
+      continue;
+    }
+
+    attribute.parse_nested_meta(|meta| {
+      if meta.path.is_ident("hidden") {
+        hidden = true;
+      } else if meta.path.is_ident("tooltip") {
+        tooltip = Some(meta.value()?.parse::<LitStr>()?.value());
+      } else if meta.path.is_ident("range") {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        let min = number(&content)?;
+        content.parse::<Token![,]>()?;
+        range = Some((min, number(&content)?));
+      } else {
+        return Err(meta.error("expected `range(min, max)`, `tooltip = \"...\"` or `hidden`"));
+      }
+
+      Ok(())
+    })?;
+  }
+
+  docs.retain(|d| !d.is_empty());
+
+  if tooltip.is_none() && !docs.is_empty() {
+    tooltip = Some(docs.join(" "));
+  }
+
+  Ok(Attributes { range, tooltip, hidden })
+}
+
+/// An integer or float literal, maybe negative.
+fn number(input: ParseStream) -> syn::Result<f64> {
+  let negative = input.parse::<Option<Token![-]>>()?.is_some();
+
+  let value = match input.parse::<Lit>()? {
+    Lit::Float(float) => float.base10_parse::<f64>()?,
+    Lit::Int(int) => int.base10_parse::<f64>()?,
+    lit => return Err(syn::Error::new_spanned(lit, "expected a number")),
+  };
+
+  Ok(if negative { -value } else { value })
+}
+
+/// `as_any` and `as_any_mut`.
+fn any_methods() -> TokenStream2 {
+  quote! {
+    fn as_any(&self) -> &dyn ::core::any::Any {
+      self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn ::core::any::Any {
+      self
+    }
+  }
+}
+
+/// Value of the fields, `Value::Struct` or `Value::Tuple`, reading them with `read`.
+fn fields_value(fields: &Fields, data: &[FieldData], read: impl Fn(&FieldData) -> TokenStream2) -> TokenStream2 {
+  let values = data.iter().map(|f| {
+    let read = read(f);
+    quote!(::sagitario_reflect::Reflect::to_value(#read))
+  });
+
+  match fields {
+    Fields::Named(_) => {
+      let names = data.iter().map(|f| &f.name);
+      quote!(::sagitario_reflect::Value::Struct(
+        vec![#((#names.to_string(), #values)),*]
+      ))
+    }
+    Fields::Unnamed(_) => quote!(::sagitario_reflect::Value::Tuple(vec![#(#values),*])),
+    Fields::Unit => quote!(::sagitario_reflect::Value::Unit),
+  }
+}
+
+/// The fields read from `value`, for a struct or variant literal: `{ a: .., b: .. }` or `(.., ..)`.
+fn fields_from_value(fields: &Fields, data: &[FieldData]) -> TokenStream2 {
+  let reads = data.iter().map(|f| {
+    let name = &f.name;
+    quote!(::sagitario_reflect::FromValue::from_value(value.field(#name))?)
+  });
+
+  match fields {
+    Fields::Named(_) => {
+      let members = data.iter().map(|f| &f.member);
+      quote!({ #(#members: #reads),* })
+    }
+    Fields::Unnamed(_) => quote!((#(#reads),*)),
+    Fields::Unit => quote!(),
+  }
+}
+
+fn reflect_struct(input: &DeriveInput, fields: &Fields) -> syn::Result<TokenStream2> {
+  let ident = &input.ident;
+  let type_name = ident.to_string();
+  let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+  let data = field_data(fields)?;
+
+  let infos = data.iter().map(|f| &f.info);
+  let names = data.iter().map(|f| &f.name).collect::<Vec<_>>();
+  let members = data.iter().map(|f| &f.member).collect::<Vec<_>>();
+  let to_value = fields_value(fields, &data, |f| {
+    let member = &f.member;
+    quote!(&self.#member)
+  });
+  let from_value = fields_from_value(fields, &data);
+  let any_methods = any_methods();
+
+  Ok(quote! {
+    impl #impl_generics ::sagitario_reflect::Reflect for #ident #type_generics #where_clause {
+      fn type_name(&self) -> &'static str {
+        #type_name
+      }
+
+      fn fields(&self) -> &'static [::sagitario_reflect::FieldInfo] {
+        const FIELDS: &[::sagitario_reflect::FieldInfo] = &[#(#infos),*];
+        FIELDS
+      }
+
+      fn field(&self, name: &str) -> ::core::option::Option<&dyn ::sagitario_reflect::Reflect> {
+        match name {
+          #(#names => ::core::option::Option::Some(&self.#members),)*
+          _ => ::core::option::Option::None,
+        }
+      }
+
+      fn field_mut(&mut self, name: &str) -> ::core::option::Option<&mut dyn ::sagitario_reflect::Reflect> {
+        match name {
+          #(#names => ::core::option::Option::Some(&mut self.#members),)*
+          _ => ::core::option::Option::None,
+        }
+      }
+
+      fn to_value(&self) -> ::sagitario_reflect::Value {
+        #to_value
+      }
+
+      fn set_value(&mut self, value: &::sagitario_reflect::Value) -> ::core::result::Result<(), ::sagitario_reflect::ReflectError> {
+        ::sagitario_reflect::set_fields(self, value)
+      }
+
+      #any_methods
+    }
+
+    impl #impl_generics ::sagitario_reflect::FromValue for #ident #type_generics #where_clause {
+      fn from_value(value: &::sagitario_reflect::Value) -> ::core::result::Result<Self, ::sagitario_reflect::ReflectError> {
+        ::sagitario_reflect::expect_fields(#type_name, value)?;
+        ::core::result::Result::Ok(Self #from_value)
+      }
+    }
+  })
+}
+
+fn reflect_enum(input: &DeriveInput, variants: Vec<&syn::Variant>) -> syn::Result<TokenStream2> {
+  let ident = &input.ident;
+  let type_name = ident.to_string();
+  let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+  let names = variants.iter().map(|v| v.ident.to_string()).collect::<Vec<_>>();
+  let (mut fields_arms, mut field_arms, mut value_arms, mut name_arms, mut from_arms) =
+    (vec![], vec![], vec![], vec![], vec![]);
+
+  for (variant, name) in variants.iter().zip(&names) {
+    let variant_ident = &variant.ident;
+    let data = field_data(&variant.fields)?;
+    let bindings = data.iter().map(|f| &f.binding).collect::<Vec<_>>();
+
+    // `Self::A`, `Self::A(field_0, ..)` o `Self::A { a: field_0, .. }`.
+    let pattern = match &variant.fields {
+      Fields::Named(_) => {
+        let members = data.iter().map(|f| &f.member);
+        quote!(Self::#variant_ident { #(#members: #bindings),* })
+      }
+      Fields::Unnamed(_) => quote!(Self::#variant_ident(#(#bindings),*)),
+      Fields::Unit => quote!(Self::#variant_ident),
+    };
+
+    let infos = data.iter().map(|f| &f.info);
+    fields_arms.push(quote! {
+      Self::#variant_ident { .. } => {
+        const FIELDS: &[::sagitario_reflect::FieldInfo] = &[#(#infos),*];
+        FIELDS
+      }
+    });
+
+    let field_names = data.iter().map(|f| &f.name);
+    field_arms.push(quote! {
+      #pattern => match name {
+        #(#field_names => ::core::option::Option::Some(#bindings),)*
+        _ => ::core::option::Option::None,
+      }
+    });
+
+    // Las variantes guardan sus campos por nombre, o por indice si son tuplas.
+    let field_names = data.iter().map(|f| &f.name);
+    let fields = quote!(vec![#((#field_names.to_string(), ::sagitario_reflect::Reflect::to_value(#bindings))),*]);
+    value_arms.push(quote!(#pattern => ::sagitario_reflect::Value::Variant(#name.to_string(), #fields)));
+    name_arms.push(quote!(Self::#variant_ident { .. } => #name));
+
+    let from_value = fields_from_value(&variant.fields, &data);
+    from_arms.push(
+      quote!(::core::option::Option::Some(#name) => ::core::result::Result::Ok(Self::#variant_ident #from_value)),
+    );
+  }
+
+  let any_methods = any_methods();
+
+  Ok(quote! {
+    impl #impl_generics ::sagitario_reflect::Reflect for #ident #type_generics #where_clause {
+      fn type_name(&self) -> &'static str {
+        #type_name
+      }
+
+      fn fields(&self) -> &'static [::sagitario_reflect::FieldInfo] {
+        match self {
+          #(#fields_arms)*
+        }
+      }
+
+      fn variants(&self) -> &'static [&'static str] {
+        &[#(#names),*]
+      }
+
+      #[allow(unused_variables)]
+      fn field(&self, name: &str) -> ::core::option::Option<&dyn ::sagitario_reflect::Reflect> {
+        match self {
+          #(#field_arms,)*
+        }
+      }
+
+      #[allow(unused_variables)]
+      fn field_mut(&mut self, name: &str) -> ::core::option::Option<&mut dyn ::sagitario_reflect::Reflect> {
+        match self {
+          #(#field_arms,)*
+        }
+      }
+
+      fn to_value(&self) -> ::sagitario_reflect::Value {
+        match self {
+          #(#value_arms,)*
+        }
+      }
+
+      /// Another variant is built from `value`; the same one only takes the fields `value` has.
+      fn set_value(&mut self, value: &::sagitario_reflect::Value) -> ::core::result::Result<(), ::sagitario_reflect::ReflectError> {
+        let current = match self {
+          #(#name_arms,)*
+        };
+
+        if value.variant() == ::core::option::Option::Some(current) {
+          return ::sagitario_reflect::set_fields(self, value);
+        }
+
+        *self = <Self as ::sagitario_reflect::FromValue>::from_value(value)?;
+        ::core::result::Result::Ok(())
+      }
+
+      #any_methods
+    }
+
+    impl #impl_generics ::sagitario_reflect::FromValue for #ident #type_generics #where_clause {
+      fn from_value(value: &::sagitario_reflect::Value) -> ::core::result::Result<Self, ::sagitario_reflect::ReflectError> {
+        match value.variant() {
+          #(#from_arms,)*
+          ::core::option::Option::Some(name) => ::core::result::Result::Err(
+            ::sagitario_reflect::ReflectError::UnknownVariant(name.to_string(), #type_name),
+          ),
+          ::core::option::Option::None => ::core::result::Result::Err(
+            ::sagitario_reflect::ReflectError::mismatch(#type_name, value),
+          ),
+        }
+      }
+    }
+  })
+}
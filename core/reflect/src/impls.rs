@@ -0,0 +1,297 @@
+use std::any::Any;
+use std::path::PathBuf;
+
+use crate::{FieldInfo, FromValue, Reflect, ReflectError, Value};
+
+/// `as_any` and `as_any_mut`, the same for every type.
+macro_rules! any_methods {
+  () => {
+    fn as_any(&self) -> &dyn Any {
+      self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+      self
+    }
+  };
+}
+
+pub(crate) use any_methods;
+
+/// A value without fields, replaced whole by `set_value`.
+macro_rules! reflect_value {
+  ($ty:ty, $name:expr, |$this:ident| $to_value:expr) => {
+    impl Reflect for $ty {
+      fn type_name(&self) -> &'static str {
+        $name
+      }
+
+      fn to_value(&self) -> Value {
+        let $this = self;
+        $to_value
+      }
+
+      fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+        *self = Self::from_value(value)?;
+        Ok(())
+      }
+
+      any_methods!();
+    }
+  };
+}
+
+macro_rules! reflect_int {
+  ($($ty:ty),*) => {$(
+    reflect_value!($ty, stringify!($ty), |this| Value::Int(*this as i64));
+
+    impl FromValue for $ty {
+      fn from_value(value: &Value) -> Result<Self, ReflectError> {
+        let int = match *value {
+          Value::Unit => 0,
+          Value::Int(int) => int,
+          Value::Float(float) if float.fract() == 0.0 => float as i64,
+          _ => return Err(ReflectError::mismatch(stringify!($ty), value)),
+        };
+
+        Self::try_from(int).map_err(|_| ReflectError::mismatch(stringify!($ty), value))
+      }
+    }
+  )*};
+}
+
+reflect_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! reflect_float {
+  ($($ty:ty),*) => {$(
+    reflect_value!($ty, stringify!($ty), |this| Value::Float(*this as f64));
+
+    impl FromValue for $ty {
+      fn from_value(value: &Value) -> Result<Self, ReflectError> {
+        match *value {
+          Value::Unit => Ok(0.0),
+          _ => value.as_f64().map(|f| f as $ty).ok_or_else(|| ReflectError::mismatch(stringify!($ty), value)),
+        }
+      }
+    }
+  )*};
+}
+
+reflect_float!(f32, f64);
+
+reflect_value!(bool, "bool", |this| Value::Bool(*this));
+
+impl FromValue for bool {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    match *value {
+      Value::Unit => Ok(false),
+      Value::Bool(bool) => Ok(bool),
+      _ => Err(ReflectError::mismatch("bool", value)),
+    }
+  }
+}
+
+reflect_value!(String, "String", |this| Value::Text(this.clone()));
+
+impl FromValue for String {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    match value {
+      Value::Unit => Ok(String::new()),
+      Value::Text(text) => Ok(text.clone()),
+      _ => Err(ReflectError::mismatch("String", value)),
+    }
+  }
+}
+
+reflect_value!(PathBuf, "PathBuf", |this| Value::Text(
+  this.to_string_lossy().into_owned()
+));
+
+impl FromValue for PathBuf {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    String::from_value(value).map(PathBuf::from)
+  }
+}
+
+/// Transparent: `Some` reads and writes as its value and `None` as `Value::Unit`, so a component
+/// in an `Option` is reached by the same paths as one that isn't.
+impl<T: Reflect + FromValue> Reflect for Option<T> {
+  fn type_name(&self) -> &'static str {
+    "Option"
+  }
+
+  fn fields(&self) -> &'static [FieldInfo] {
+    self.as_ref().map_or(&[], |v| v.fields())
+  }
+
+  fn variants(&self) -> &'static [&'static str] {
+    self.as_ref().map_or(&[], |v| v.variants())
+  }
+
+  fn field(&self, name: &str) -> Option<&dyn Reflect> {
+    self.as_ref()?.field(name)
+  }
+
+  fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+    self.as_mut()?.field_mut(name)
+  }
+
+  fn to_value(&self) -> Value {
+    self.as_ref().map_or(Value::Unit, |v| v.to_value())
+  }
+
+  fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+    match (self.as_mut(), some(value)) {
+      (_, None) => *self = None,
+      (Some(current), Some(value)) => current.set_value(value)?,
+      (None, Some(value)) => *self = Some(T::from_value(value)?),
+    }
+
+    Ok(())
+  }
+
+  any_methods!();
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    some(value).map(T::from_value).transpose()
+  }
+}
+
+/// What an `Option` holds: `None` for `()` and `None`, the inside of `Some(..)` as `ron` writes
+/// it, or the value itself.
+fn some(value: &Value) -> Option<&Value> {
+  match value {
+    Value::Unit => None,
+    Value::Variant(name, fields) if name == "None" && fields.is_empty() => None,
+    Value::Variant(name, fields) if name == "Some" && fields.len() == 1 => Some(&fields[0].1),
+    value => Some(value),
+  }
+}
+
+/// Items of a tuple or a list, checking there are `length` if given. `Unit` has none.
+fn items<'a>(type_name: &'static str, value: &'a Value, length: Option<usize>) -> Result<&'a [Value], ReflectError> {
+  let items = match value {
+    Value::Unit => &[],
+    value => value.items().ok_or_else(|| ReflectError::mismatch(type_name, value))?,
+  };
+
+  match length {
+    Some(length) if !items.is_empty() && items.len() != length => Err(ReflectError::mismatch(type_name, value)),
+    _ => Ok(items),
+  }
+}
+
+impl<T: Reflect + FromValue> Reflect for Vec<T> {
+  fn type_name(&self) -> &'static str {
+    "Vec"
+  }
+
+  fn field(&self, name: &str) -> Option<&dyn Reflect> {
+    Some(self.get(name.parse::<usize>().ok()?)?)
+  }
+
+  fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+    Some(self.get_mut(name.parse::<usize>().ok()?)?)
+  }
+
+  fn to_value(&self) -> Value {
+    Value::List(self.iter().map(|v| v.to_value()).collect())
+  }
+
+  fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+    *self = Self::from_value(value)?;
+    Ok(())
+  }
+
+  any_methods!();
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    items("Vec", value, None)?.iter().map(T::from_value).collect()
+  }
+}
+
+impl<T: Reflect + FromValue, const N: usize> Reflect for [T; N] {
+  fn type_name(&self) -> &'static str {
+    "array"
+  }
+
+  fn field(&self, name: &str) -> Option<&dyn Reflect> {
+    Some(self.get(name.parse::<usize>().ok()?)?)
+  }
+
+  fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+    Some(self.get_mut(name.parse::<usize>().ok()?)?)
+  }
+
+  fn to_value(&self) -> Value {
+    Value::Tuple(self.iter().map(|v| v.to_value()).collect())
+  }
+
+  fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+    *self = Self::from_value(value)?;
+    Ok(())
+  }
+
+  any_methods!();
+}
+
+impl<T: FromValue, const N: usize> FromValue for [T; N] {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    items("array", value, Some(N))?;
+    let items = (0..N).map(|i| T::from_value(value.field(&i.to_string())));
+    let items = items.collect::<Result<Vec<_>, _>>()?;
+
+    items.try_into().map_err(|_| ReflectError::mismatch("array", value))
+  }
+}
+
+macro_rules! reflect_tuple {
+  ($length:literal: $($ty:ident $index:tt),*) => {
+    impl<$($ty: Reflect + FromValue),*> Reflect for ($($ty,)*) {
+      fn type_name(&self) -> &'static str {
+        "tuple"
+      }
+
+      fn field(&self, name: &str) -> Option<&dyn Reflect> {
+        match name {
+          $(stringify!($index) => Some(&self.$index),)*
+          _ => None,
+        }
+      }
+
+      fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+        match name {
+          $(stringify!($index) => Some(&mut self.$index),)*
+          _ => None,
+        }
+      }
+
+      fn to_value(&self) -> Value {
+        Value::Tuple(vec![$(self.$index.to_value()),*])
+      }
+
+      fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+        *self = Self::from_value(value)?;
+        Ok(())
+      }
+
+      any_methods!();
+    }
+
+    impl<$($ty: FromValue),*> FromValue for ($($ty,)*) {
+      fn from_value(value: &Value) -> Result<Self, ReflectError> {
+        items("tuple", value, Some($length))?;
+        Ok(($($ty::from_value(value.field(stringify!($index)))?,)*))
+      }
+    }
+  };
+}
+
+reflect_tuple!(1: A 0);
+reflect_tuple!(2: A 0, B 1);
+reflect_tuple!(3: A 0, B 1, C 2);
+reflect_tuple!(4: A 0, B 1, C 2, D 3);
@@ -0,0 +1,365 @@
+//! Runtime reflection: the fields of engine types with their type names and editor attributes,
+//! read and written by path as dynamic `Value`s. The inspector, undo, prefab overrides and the
+//! scripting bridge handle any component through it instead of code of their own.
+
+use std::any::Any;
+
+use thiserror::Error;
+
+mod impls;
+#[cfg(feature = "cgmath")]
+mod math;
+pub mod value;
+
+pub use sagitario_reflect_derive::Reflect;
+pub use value::Value;
+
+// El derive nombra `::sagitario_reflect`, tambien en las pruebas del propio crate.
+#[cfg(test)]
+extern crate self as sagitario_reflect;
+
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum ReflectError {
+  #[error("No field named `{0}`")]
+  UnknownField(String),
+  #[error("`{0}` isn't a variant of {1}")]
+  UnknownVariant(String, &'static str),
+  #[error("Expected {expected}, found `{found}`")]
+  Mismatch { expected: &'static str, found: String },
+  #[error("Invalid value at {position}: {message}")]
+  Parse { position: usize, message: &'static str },
+}
+
+impl ReflectError {
+  /// `value` can't be read as `expected`.
+  pub fn mismatch(expected: &'static str, value: &Value) -> Self {
+    Self::Mismatch {
+      expected,
+      found: value.to_string(),
+    }
+  }
+}
+
+/// A field as `Reflect::fields` lists it. The attributes come from `#[reflect(...)]`, and the
+/// tooltip defaults to the field's doc comment.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FieldInfo {
+  pub name: &'static str,
+  /// As written in the type, e.g. `Vector3<f32>`.
+  pub type_name: &'static str,
+  /// `range(min, max)`: values the editor lets through, both included.
+  pub range: Option<(f64, f64)>,
+  /// `tooltip = "..."`
+  pub tooltip: Option<&'static str>,
+  /// `hidden`: reflected like the rest, but not shown in the inspector.
+  pub hidden: bool,
+}
+
+impl FieldInfo {
+  /// A field without attributes.
+  pub const fn new(name: &'static str, type_name: &'static str) -> Self {
+    Self {
+      name,
+      type_name,
+      range: None,
+      tooltip: None,
+      hidden: false,
+    }
+  }
+}
+
+/// A type whose fields can be listed, read and written at runtime. `#[derive(Reflect)]`
+/// implements it for structs and enums, along with `FromValue`:
+///
+/// ```ignore
+/// #[derive(Reflect)]
+/// struct Light {
+///   /// Candela.
+///   #[reflect(range(0.0, 1000.0))]
+///   intensity: f32,
+///   #[reflect(hidden)]
+///   id: u32,
+/// }
+/// ```
+///
+/// Fields are named by their identifier, and tuple fields and sequence items by their index.
+/// Paths join the names with dots, like `shadows.depth_bias` or `color.0`.
+pub trait Reflect: Any {
+  /// Name of the type, without its module path.
+  fn type_name(&self) -> &'static str;
+
+  /// Named fields of a struct, or of the current variant of an enum; empty for other types.
+  fn fields(&self) -> &'static [FieldInfo] {
+    &[]
+  }
+
+  /// Variants of an enum, empty for other types.
+  fn variants(&self) -> &'static [&'static str] {
+    &[]
+  }
+
+  fn field(&self, _name: &str) -> Option<&dyn Reflect> {
+    None
+  }
+
+  fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect> {
+    None
+  }
+
+  fn to_value(&self) -> Value;
+
+  /// Writes `value` in place. Structs take any of their fields and keep the rest.
+  fn set_value(&mut self, value: &Value) -> Result<(), ReflectError>;
+
+  fn as_any(&self) -> &dyn Any;
+
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+
+  /// The field at `path`, which can't be empty.
+  fn path(&self, path: &str) -> Result<&dyn Reflect, ReflectError> {
+    let unknown = || ReflectError::UnknownField(path.to_string());
+    let mut names = path.split('.');
+    let mut current = self.field(names.next().unwrap_or_default()).ok_or_else(unknown)?;
+
+    for name in names {
+      current = current.field(name).ok_or_else(unknown)?;
+    }
+
+    Ok(current)
+  }
+
+  fn path_mut(&mut self, path: &str) -> Result<&mut dyn Reflect, ReflectError> {
+    let unknown = || ReflectError::UnknownField(path.to_string());
+    let mut names = path.split('.');
+    let mut current = self.field_mut(names.next().unwrap_or_default()).ok_or_else(unknown)?;
+
+    for name in names {
+      current = current.field_mut(name).ok_or_else(unknown)?;
+    }
+
+    Ok(current)
+  }
+
+  fn get_path(&self, path: &str) -> Result<Value, ReflectError> {
+    Ok(self.path(path)?.to_value())
+  }
+
+  fn set_path(&mut self, path: &str, value: &Value) -> Result<(), ReflectError> {
+    self.path_mut(path)?.set_value(value)
+  }
+}
+
+impl dyn Reflect {
+  pub fn downcast_ref<T: Reflect>(&self) -> Option<&T> {
+    self.as_any().downcast_ref()
+  }
+
+  pub fn downcast_mut<T: Reflect>(&mut self) -> Option<&mut T> {
+    self.as_any_mut().downcast_mut()
+  }
+}
+
+/// Builds a value of the type from a `Value`. `Value::Unit` reads as the zero of the type (`0`,
+/// `false`, empty text or sequences, `None`), so fields missing from a struct value get it.
+pub trait FromValue: Sized {
+  fn from_value(value: &Value) -> Result<Self, ReflectError>;
+}
+
+/// Sets the fields of `target` that `value` has, by name or index. `set_value` of the derive.
+#[doc(hidden)]
+pub fn set_fields(target: &mut dyn Reflect, value: &Value) -> Result<(), ReflectError> {
+  let Some(entries) = value.entries() else {
+    return Err(ReflectError::mismatch(target.type_name(), value));
+  };
+
+  for (name, value) in entries {
+    match target.field_mut(&name) {
+      Some(field) => field.set_value(value)?,
+      None => return Err(ReflectError::UnknownField(name)),
+    }
+  }
+
+  Ok(())
+}
+
+/// Fails unless `value` can hold the fields of a struct. `from_value` of the derive.
+#[doc(hidden)]
+pub fn expect_fields(type_name: &'static str, value: &Value) -> Result<(), ReflectError> {
+  match value.entries() {
+    Some(_) => Ok(()),
+    None => Err(ReflectError::mismatch(type_name, value)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Clone, Debug, PartialEq, Reflect)]
+  enum Shape {
+    Point,
+    Spot { inner_angle: f32 },
+    Area(f32, f32),
+  }
+
+  #[derive(Clone, Debug, PartialEq, Reflect)]
+  struct Color(f32, f32, f32);
+
+  #[derive(Clone, Debug, PartialEq, Reflect)]
+  struct Lamp {
+    /// Light emitted,
+    /// in candela.
+    #[reflect(range(0.0, 1000.0))]
+    intensity: f32,
+    /// Not shown, the attribute wins.
+    #[reflect(tooltip = "Linear RGB", range(-1, 2))]
+    color: Color,
+    #[reflect(hidden)]
+    id: u32,
+    name: String,
+    shape: Shape,
+    tags: Vec<String>,
+    parent: Option<u32>,
+  }
+
+  fn lamp() -> Lamp {
+    Lamp {
+      intensity: 100.0,
+      color: Color(1.0, 0.5, 0.25),
+      id: 7,
+      name: "desk \"lamp\"".to_string(),
+      shape: Shape::Spot { inner_angle: 20.0 },
+      tags: vec!["a".to_string(), "b".to_string()],
+      parent: Some(3),
+    }
+  }
+
+  #[test]
+  fn fields_carry_their_attributes() {
+    let lamp = lamp();
+    let fields = lamp.fields();
+
+    assert_eq!(
+      fields.iter().map(|f| f.name).collect::<Vec<_>>(),
+      ["intensity", "color", "id", "name", "shape", "tags", "parent"]
+    );
+    assert_eq!(
+      fields[0],
+      FieldInfo {
+        range: Some((0.0, 1000.0)),
+        tooltip: Some("Light emitted, in candela."),
+        ..FieldInfo::new("intensity", "f32")
+      }
+    );
+    assert_eq!(fields[1].tooltip, Some("Linear RGB"));
+    assert_eq!(fields[1].range, Some((-1.0, 2.0)));
+    assert!(fields[2].hidden);
+    assert_eq!(fields[3], FieldInfo::new("name", "String"));
+    assert_eq!(fields[5].type_name, "Vec<String>");
+    assert_eq!(lamp.type_name(), "Lamp");
+  }
+
+  #[test]
+  fn enums_list_variants_and_their_fields() {
+    let shape = Shape::Spot { inner_angle: 20.0 };
+
+    assert_eq!(shape.variants(), ["Point", "Spot", "Area"]);
+    assert_eq!(shape.fields(), [FieldInfo::new("inner_angle", "f32")]);
+    assert!(Shape::Point.fields().is_empty());
+    assert_eq!(Shape::Area(1.0, 2.0).to_value().to_string(), "Area(1.0,2.0)");
+  }
+
+  #[test]
+  fn get_and_set_by_path() {
+    let mut lamp = lamp();
+
+    assert_eq!(lamp.get_path("color.0"), Ok(Value::Float(1.0)));
+    assert_eq!(lamp.get_path("shape.inner_angle"), Ok(Value::Float(20.0)));
+    assert_eq!(lamp.get_path("tags.1"), Ok(Value::Text("b".to_string())));
+    assert_eq!(lamp.get_path("parent"), Ok(Value::Int(3)));
+
+    lamp.set_path("color.2", &Value::Float(0.75)).unwrap();
+    lamp.set_path("intensity", &Value::Int(5)).unwrap();
+    lamp.set_path("shape.inner_angle", &Value::Float(30.0)).unwrap();
+    lamp.set_path("parent", &Value::Unit).unwrap();
+
+    assert_eq!(lamp.color, Color(1.0, 0.5, 0.75));
+    assert_eq!(lamp.intensity, 5.0);
+    assert_eq!(lamp.shape, Shape::Spot { inner_angle: 30.0 });
+    assert_eq!(lamp.parent, None);
+  }
+
+  #[test]
+  fn setting_another_variant_builds_it() {
+    let mut lamp = lamp();
+
+    lamp.set_path("shape", &Value::parse("Area(2.0,3.0)").unwrap()).unwrap();
+    assert_eq!(lamp.shape, Shape::Area(2.0, 3.0));
+
+    lamp.set_path("shape", &Value::parse("Point").unwrap()).unwrap();
+    assert_eq!(lamp.shape, Shape::Point);
+  }
+
+  #[test]
+  fn struct_values_only_set_the_fields_they_have() {
+    let mut lamp = lamp();
+    lamp
+      .set_value(&Value::parse("(intensity:5.0,color:(0.0,0.0,0.0))").unwrap())
+      .unwrap();
+
+    assert_eq!(
+      lamp,
+      Lamp {
+        intensity: 5.0,
+        color: Color(0.0, 0.0, 0.0),
+        ..self::lamp()
+      }
+    );
+  }
+
+  #[test]
+  fn round_trips_through_text() {
+    let lamp = lamp();
+    let text = lamp.to_value().to_string();
+
+    assert_eq!(Lamp::from_value(&Value::parse(&text).unwrap()), Ok(lamp));
+  }
+
+  #[test]
+  fn missing_fields_read_as_zero() {
+    let lamp = Lamp::from_value(&Value::parse("(shape:Point)").unwrap()).unwrap();
+
+    assert_eq!((lamp.intensity, lamp.id, lamp.parent), (0.0, 0, None));
+    assert!(lamp.name.is_empty() && lamp.tags.is_empty());
+  }
+
+  #[test]
+  fn rejects_unknown_fields_and_variants() {
+    let mut lamp = lamp();
+
+    assert_eq!(
+      lamp.get_path("color.3"),
+      Err(ReflectError::UnknownField("color.3".to_string()))
+    );
+    assert_eq!(
+      lamp.set_path("nope.x", &Value::Unit),
+      Err(ReflectError::UnknownField("nope.x".to_string()))
+    );
+    assert_eq!(
+      lamp.set_value(&Value::parse("(brightness:1.0)").unwrap()),
+      Err(ReflectError::UnknownField("brightness".to_string()))
+    );
+    assert_eq!(
+      lamp.set_path("shape", &Value::parse("Cube").unwrap()),
+      Err(ReflectError::UnknownVariant("Cube".to_string(), "Shape"))
+    );
+    assert_eq!(
+      lamp.set_path("name", &Value::Int(1)),
+      Err(ReflectError::Mismatch {
+        expected: "String",
+        found: "1".to_string(),
+      })
+    );
+    assert_eq!(lamp, self::lamp());
+  }
+}
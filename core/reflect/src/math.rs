@@ -0,0 +1,120 @@
+use std::any::Any;
+
+use cgmath::{Deg, Point2, Point3, Quaternion, Rad, Vector2, Vector3, Vector4};
+
+use crate::impls::any_methods;
+use crate::{expect_fields, set_fields, FieldInfo, FromValue, Reflect, ReflectError, Value};
+
+/// Structs with named fields, for each scalar type. Field types are written out since they're
+/// shown to the user.
+macro_rules! reflect_struct {
+  ($name:ident<$scalar:ty> { $($field:ident: $field_type:ty => $type_name:expr),* }) => {
+    impl Reflect for $name<$scalar> {
+      fn type_name(&self) -> &'static str {
+        stringify!($name)
+      }
+
+      fn fields(&self) -> &'static [FieldInfo] {
+        const FIELDS: &[FieldInfo] = &[$(FieldInfo::new(stringify!($field), $type_name)),*];
+        FIELDS
+      }
+
+      fn field(&self, name: &str) -> Option<&dyn Reflect> {
+        match name {
+          $(stringify!($field) => Some(&self.$field),)*
+          _ => None,
+        }
+      }
+
+      fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+        match name {
+          $(stringify!($field) => Some(&mut self.$field),)*
+          _ => None,
+        }
+      }
+
+      fn to_value(&self) -> Value {
+        Value::Struct(vec![$((stringify!($field).to_string(), self.$field.to_value())),*])
+      }
+
+      fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+        set_fields(self, value)
+      }
+
+      any_methods!();
+    }
+
+    impl FromValue for $name<$scalar> {
+      fn from_value(value: &Value) -> Result<Self, ReflectError> {
+        expect_fields(stringify!($name), value)?;
+
+        Ok(Self {
+          $($field: <$field_type>::from_value(value.field(stringify!($field)))?),*
+        })
+      }
+    }
+  };
+}
+
+/// Angles, a tuple struct with the amount in its unit: `(20.0)`, as serde writes them.
+macro_rules! reflect_angle {
+  ($name:ident<$scalar:ty>) => {
+    impl Reflect for $name<$scalar> {
+      fn type_name(&self) -> &'static str {
+        stringify!($name)
+      }
+
+      fn field(&self, name: &str) -> Option<&dyn Reflect> {
+        (name == "0").then_some(&self.0 as &dyn Reflect)
+      }
+
+      fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+        (name == "0").then_some(&mut self.0 as &mut dyn Reflect)
+      }
+
+      fn to_value(&self) -> Value {
+        Value::Tuple(vec![self.0.to_value()])
+      }
+
+      fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+        *self = Self::from_value(value)?;
+        Ok(())
+      }
+
+      any_methods!();
+    }
+
+    /// Also takes the bare amount.
+    impl FromValue for $name<$scalar> {
+      fn from_value(value: &Value) -> Result<Self, ReflectError> {
+        match value {
+          Value::Tuple(items) if items.len() == 1 => Ok(Self(<$scalar>::from_value(&items[0])?)),
+          value => Ok(Self(<$scalar>::from_value(value)?)),
+        }
+      }
+    }
+  };
+}
+
+macro_rules! reflect_math {
+  ($($scalar:ty => $name:literal),*) => {$(
+    reflect_struct!(Vector2<$scalar> { x: $scalar => $name, y: $scalar => $name });
+    reflect_struct!(Vector3<$scalar> { x: $scalar => $name, y: $scalar => $name, z: $scalar => $name });
+    reflect_struct!(Vector4<$scalar> {
+      x: $scalar => $name,
+      y: $scalar => $name,
+      z: $scalar => $name,
+      w: $scalar => $name
+    });
+    reflect_struct!(Point2<$scalar> { x: $scalar => $name, y: $scalar => $name });
+    reflect_struct!(Point3<$scalar> { x: $scalar => $name, y: $scalar => $name, z: $scalar => $name });
+    reflect_struct!(Quaternion<$scalar> {
+      v: Vector3<$scalar> => concat!("Vector3<", $name, ">"),
+      s: $scalar => $name
+    });
+    reflect_angle!(Deg<$scalar>);
+    reflect_angle!(Rad<$scalar>);
+  )*};
+}
+
+reflect_math!(f32 => "f32", f64 => "f64");
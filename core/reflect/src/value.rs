@@ -0,0 +1,536 @@
+use std::fmt::{self, Write};
+
+use crate::ReflectError;
+
+/// A reflected value, detached from its type. `Display` writes it as RON-like text and
+/// `Value::parse` reads it back, e.g. `(x:1.0,y:2.0)`, `Spot(inner_angle:(20.0))` or `[1,2]`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  /// `()`, also read from `None`.
+  Unit,
+  Bool(bool),
+  Int(i64),
+  Float(f64),
+  Text(String),
+  /// Arrays, tuples and tuple structs: `(a,b)`.
+  Tuple(Vec<Value>),
+  /// Sequences that can grow: `[a,b]`.
+  List(Vec<Value>),
+  /// Named fields in declaration order: `(x:1.0,y:2.0)`.
+  Struct(Vec<(String, Value)>),
+  /// An enum variant and its fields, by index for tuple variants: `Point`, `Some(1)` or
+  /// `Spot(inner_angle:(20.0))`.
+  Variant(String, Vec<(String, Value)>),
+}
+
+const UNIT: Value = Value::Unit;
+
+impl Value {
+  pub fn parse(source: &str) -> Result<Self, ReflectError> {
+    let mut parser = Parser { source, position: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    match parser.position == source.len() {
+      true => Ok(value),
+      false => Err(parser.error("expected the end")),
+    }
+  }
+
+  /// The field named `name`, or the item at that index; `Unit` if there's none.
+  pub fn field(&self, name: &str) -> &Value {
+    match self {
+      Self::Struct(fields) | Self::Variant(_, fields) => {
+        let found = fields.iter().find(|(n, _)| n == name);
+        found.map_or(&UNIT, |(_, value)| value)
+      }
+      Self::Tuple(items) | Self::List(items) => {
+        let item = name.parse::<usize>().ok().and_then(|i| items.get(i));
+        item.unwrap_or(&UNIT)
+      }
+      _ => &UNIT,
+    }
+  }
+
+  /// Fields by name and items by index; `None` for values without either.
+  pub fn entries(&self) -> Option<Vec<(String, &Value)>> {
+    match self {
+      Self::Unit => Some(vec![]),
+      Self::Struct(fields) | Self::Variant(_, fields) => Some(fields.iter().map(|(n, v)| (n.clone(), v)).collect()),
+      Self::Tuple(items) | Self::List(items) => {
+        Some(items.iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect())
+      }
+      _ => None,
+    }
+  }
+
+  /// Items of a tuple or a list.
+  pub fn items(&self) -> Option<&[Value]> {
+    match self {
+      Self::Tuple(items) | Self::List(items) => Some(items),
+      _ => None,
+    }
+  }
+
+  /// Name of a variant; a bare name can also be given as text.
+  pub fn variant(&self) -> Option<&str> {
+    match self {
+      Self::Variant(name, _) | Self::Text(name) => Some(name),
+      _ => None,
+    }
+  }
+
+  pub fn as_f64(&self) -> Option<f64> {
+    match *self {
+      Self::Int(int) => Some(int as f64),
+      Self::Float(float) => Some(float),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Unit => f.write_str("()"),
+      Self::Bool(bool) => write!(f, "{}", bool),
+      Self::Int(int) => write!(f, "{}", int),
+      // Los f32 se escriben con los digitos justos para volver a leerlos.
+      Self::Float(float) if *float as f32 as f64 == *float => write!(f, "{:?}", *float as f32),
+      Self::Float(float) => write!(f, "{:?}", float),
+      Self::Text(text) => write_text(f, text),
+      Self::Tuple(items) => write_items(f, "(", items, ")"),
+      Self::List(items) => write_items(f, "[", items, "]"),
+      Self::Struct(fields) => write_fields(f, fields),
+      Self::Variant(name, fields) if fields.is_empty() => f.write_str(name),
+      Self::Variant(name, fields) if fields.iter().all(|(n, _)| n.parse::<usize>().is_ok()) => {
+        f.write_str(name)?;
+        let items = fields.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>();
+        write_items(f, "(", &items, ")")
+      }
+      Self::Variant(name, fields) => {
+        f.write_str(name)?;
+        write_fields(f, fields)
+      }
+    }
+  }
+}
+
+fn write_text(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+  f.write_char('"')?;
+
+  for c in text.chars() {
+    match c {
+      '"' => f.write_str("\\\"")?,
+      '\\' => f.write_str("\\\\")?,
+      '\n' => f.write_str("\\n")?,
+      '\r' => f.write_str("\\r")?,
+      '\t' => f.write_str("\\t")?,
+      c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+      c => f.write_char(c)?,
+    }
+  }
+
+  f.write_char('"')
+}
+
+fn write_items(f: &mut fmt::Formatter<'_>, open: &str, items: &[Value], close: &str) -> fmt::Result {
+  f.write_str(open)?;
+
+  for (i, item) in items.iter().enumerate() {
+    if i > 0 {
+      f.write_char(',')?;
+    }
+
+    write!(f, "{}", item)?;
+  }
+
+  f.write_str(close)
+}
+
+fn write_fields(f: &mut fmt::Formatter<'_>, fields: &[(String, Value)]) -> fmt::Result {
+  f.write_char('(')?;
+
+  for (i, (name, value)) in fields.iter().enumerate() {
+    if i > 0 {
+      f.write_char(',')?;
+    }
+
+    write!(f, "{}:{}", name, value)?;
+  }
+
+  f.write_char(')')
+}
+
+/// Recursive descent over the text `Display` writes. Also reads what `ron` writes for the same
+/// types, so values saved by serde can be parsed too.
+struct Parser<'a> {
+  source: &'a str,
+  position: usize,
+}
+
+impl Parser<'_> {
+  fn error(&self, message: &'static str) -> ReflectError {
+    ReflectError::Parse {
+      position: self.position,
+      message,
+    }
+  }
+
+  fn rest(&self) -> &str {
+    &self.source[self.position..]
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.rest().chars().next()
+  }
+
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.position += rest.len() - rest.trim_start().len();
+  }
+
+  /// Skips whitespace and `c` if it comes next.
+  fn eat(&mut self, c: char) -> bool {
+    self.skip_whitespace();
+
+    if self.peek() == Some(c) {
+      self.position += c.len_utf8();
+      return true;
+    }
+
+    false
+  }
+
+  fn expect(&mut self, c: char, message: &'static str) -> Result<(), ReflectError> {
+    match self.eat(c) {
+      true => Ok(()),
+      false => Err(self.error(message)),
+    }
+  }
+
+  fn identifier(&mut self) -> &str {
+    let start = self.position;
+    let length = self.rest().find(|c: char| !c.is_alphanumeric() && c != '_');
+    self.position += length.unwrap_or(self.rest().len());
+    &self.source[start..self.position]
+  }
+
+  fn value(&mut self) -> Result<Value, ReflectError> {
+    self.skip_whitespace();
+
+    match self.peek() {
+      Some('(') => {
+        self.position += 1;
+        self.group()
+      }
+      Some('[') => {
+        self.position += 1;
+        Ok(Value::List(self.items(']')?))
+      }
+      Some('"') => self.text(),
+      Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => self.number(),
+      Some(c) if c.is_alphabetic() || c == '_' => {
+        let name = self.identifier().to_string();
+
+        match name.as_str() {
+          "true" => return Ok(Value::Bool(true)),
+          "false" => return Ok(Value::Bool(false)),
+          "inf" => return Ok(Value::Float(f64::INFINITY)),
+          "NaN" => return Ok(Value::Float(f64::NAN)),
+          _ => {}
+        }
+
+        if !self.eat('(') {
+          return Ok(Value::Variant(name, vec![]));
+        }
+
+        let fields = match self.group()? {
+          Value::Unit => vec![],
+          Value::Struct(fields) => fields,
+          Value::Tuple(items) => items.into_iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+          _ => unreachable!(),
+        };
+
+        Ok(Value::Variant(name, fields))
+      }
+      _ => Err(self.error("expected a value")),
+    }
+  }
+
+  /// After `(`: the unit, a struct or a tuple.
+  fn group(&mut self) -> Result<Value, ReflectError> {
+    if self.eat(')') {
+      return Ok(Value::Unit);
+    }
+
+    // `(nombre: ...` es un struct; cualquier otra cosa, una tupla.
+    let start = self.position;
+    let named = !self.identifier().is_empty() && self.eat(':');
+    self.position = start;
+
+    if !named {
+      return Ok(Value::Tuple(self.items(')')?));
+    }
+
+    let mut fields = vec![];
+
+    loop {
+      self.skip_whitespace();
+      let name = self.identifier().to_string();
+
+      if name.is_empty() {
+        return Err(self.error("expected a field name"));
+      }
+
+      self.expect(':', "expected `:`")?;
+      fields.push((name, self.value()?));
+
+      if !self.eat(',') {
+        self.expect(')', "expected `,` or `)`")?;
+        return Ok(Value::Struct(fields));
+      }
+
+      if self.eat(')') {
+        return Ok(Value::Struct(fields));
+      }
+    }
+  }
+
+  /// Values separated by commas up to `close`.
+  fn items(&mut self, close: char) -> Result<Vec<Value>, ReflectError> {
+    let mut items = vec![];
+
+    loop {
+      if self.eat(close) {
+        return Ok(items);
+      }
+
+      items.push(self.value()?);
+
+      if !self.eat(',') {
+        self.expect(close, "expected `,` or the end of the sequence")?;
+        return Ok(items);
+      }
+    }
+  }
+
+  fn number(&mut self) -> Result<Value, ReflectError> {
+    let start = self.position;
+    let mut previous = None;
+    let length = self.rest().find(|c: char| {
+      // Un signo solo va al principio o tras el exponente.
+      let sign = (c == '-' || c == '+') && matches!(previous, None | Some('e' | 'E'));
+      let part = c.is_ascii_alphanumeric() || c == '.' || c == '_' || sign;
+      previous = Some(c);
+      !part
+    });
+    self.position += length.unwrap_or(self.rest().len());
+
+    let token = self.source[start..self.position].replace('_', "");
+
+    if let Result::Ok(int) = token.parse::<i64>() {
+      return Ok(Value::Int(int));
+    }
+
+    match token.parse::<f64>() {
+      Result::Ok(float) => Ok(Value::Float(float)),
+      Err(_) => {
+        self.position = start;
+        Err(self.error("invalid number"))
+      }
+    }
+  }
+
+  fn text(&mut self) -> Result<Value, ReflectError> {
+    let mut text = String::new();
+    let mut chars = self.rest().char_indices().skip(1);
+    let start = self.position;
+
+    while let Some((i, c)) = chars.next() {
+      let escaped = match c {
+        '"' => {
+          self.position = start + i + 1;
+          return Ok(Value::Text(text));
+        }
+        '\\' => chars.next().map(|(_, c)| c),
+        c => {
+          text.push(c);
+          continue;
+        }
+      };
+
+      match escaped {
+        Some('n') => text.push('\n'),
+        Some('r') => text.push('\r'),
+        Some('t') => text.push('\t'),
+        Some('0') => text.push('\0'),
+        Some('u') => {
+          let rest = &self.source[start + i + 2..];
+          let hex = rest
+            .strip_prefix('{')
+            .and_then(|r| r.split_once('}'))
+            .map(|(hex, _)| hex);
+          let c = hex
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .and_then(char::from_u32);
+
+          match (c, hex) {
+            (Some(c), Some(hex)) => {
+              text.push(c);
+              // Salta `{hex}`.
+              (0..hex.len() + 2).for_each(|_| _ = chars.next());
+            }
+            _ => {
+              self.position = start + i;
+              return Err(self.error("invalid unicode escape"));
+            }
+          }
+        }
+        Some(c) => text.push(c),
+        None => break,
+      }
+    }
+
+    Err(self.error("unterminated text"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn round_trip(value: Value) {
+    let text = value.to_string();
+    assert_eq!(Value::parse(&text), Ok(value), "{}", text);
+  }
+
+  fn fields(fields: &[(&str, Value)]) -> Vec<(String, Value)> {
+    fields.iter().map(|(n, v)| (n.to_string(), v.clone())).collect()
+  }
+
+  #[test]
+  fn scalars_round_trip() {
+    for value in [
+      Value::Unit,
+      Value::Bool(true),
+      Value::Bool(false),
+      Value::Int(-3),
+      Value::Int(i64::MAX),
+      Value::Float(1.0),
+      Value::Float(-1.5),
+      Value::Float(0.1),
+      Value::Float(2.5e-8),
+      Value::Float(-6.02e23),
+      Value::Float(f64::INFINITY),
+      Value::Float(f64::NEG_INFINITY),
+    ] {
+      round_trip(value);
+    }
+
+    assert_eq!(Value::Float(1.0).to_string(), "1.0");
+    assert_eq!(Value::Float(f64::INFINITY).to_string(), "inf");
+    assert_eq!(Value::parse("1e-3"), Ok(Value::Float(0.001)));
+    assert_eq!(Value::parse("-2.5E-2"), Ok(Value::Float(-0.025)));
+    assert_eq!(Value::parse("1_000"), Ok(Value::Int(1000)));
+  }
+
+  #[test]
+  fn nan_round_trips() {
+    let text = Value::Float(f64::NAN).to_string();
+
+    assert_eq!(text, "NaN");
+    assert!(matches!(Value::parse(&text), Ok(Value::Float(f)) if f.is_nan()));
+  }
+
+  #[test]
+  fn f32_values_come_back_as_the_same_f32() {
+    for float in [0.1f32, 1.0 / 3.0, 1e-10, -7.25e12, f32::MIN_POSITIVE] {
+      let text = Value::Float(float as f64).to_string();
+      let parsed = Value::parse(&text).unwrap().as_f64().unwrap();
+
+      assert_eq!(parsed as f32, float, "{}", text);
+    }
+
+    // Los digitos justos del f32, no los del f64 que lo guarda.
+    assert_eq!(Value::Float(0.1f32 as f64).to_string(), "0.1");
+  }
+
+  #[test]
+  fn text_round_trips_with_escapes() {
+    let text = "quote \" backslash \\ lines \n\r tab \t nul \0 bell \u{7} accents ñü rocket 🚀";
+    round_trip(Value::Text(text.to_string()));
+
+    assert_eq!(Value::Text("a\u{1b}".to_string()).to_string(), r#""a\u{1b}""#);
+    assert_eq!(
+      Value::parse(r#""\u{1F680} \u{e9}""#),
+      Ok(Value::Text("🚀 é".to_string()))
+    );
+  }
+
+  #[test]
+  fn variants_round_trip() {
+    round_trip(Value::Variant("Point".to_string(), vec![]));
+    round_trip(Value::Variant("Some".to_string(), fields(&[("0", Value::Int(1))])));
+    round_trip(Value::Variant(
+      "Spot".to_string(),
+      fields(&[("inner_angle", Value::Tuple(vec![Value::Float(20.0)]))]),
+    ));
+
+    assert_eq!(
+      Value::Variant("Some".to_string(), fields(&[("0", Value::Int(1))])).to_string(),
+      "Some(1)"
+    );
+    assert_eq!(
+      Value::parse("Spot(inner_angle:(20.0))").unwrap().to_string(),
+      "Spot(inner_angle:(20.0))"
+    );
+  }
+
+  #[test]
+  fn nested_values_round_trip() {
+    round_trip(Value::Struct(fields(&[
+      ("name", Value::Text("lamp".to_string())),
+      (
+        "color",
+        Value::Tuple(vec![Value::Float(1.0), Value::Float(0.5), Value::Float(0.0)]),
+      ),
+      (
+        "tags",
+        Value::List(vec![Value::Text("a".to_string()), Value::Text("b".to_string())]),
+      ),
+      ("empty", Value::List(vec![])),
+      ("parent", Value::Unit),
+      ("shape", Value::Variant("Point".to_string(), vec![])),
+    ])));
+  }
+
+  #[test]
+  fn parses_ron_layout() {
+    let value = Value::parse("( x : 1 , y : [ 1, 2, ], z: (3, 4,), )").unwrap();
+
+    assert_eq!(
+      value,
+      Value::Struct(fields(&[
+        ("x", Value::Int(1)),
+        ("y", Value::List(vec![Value::Int(1), Value::Int(2)])),
+        ("z", Value::Tuple(vec![Value::Int(3), Value::Int(4)])),
+      ]))
+    );
+  }
+
+  #[test]
+  fn reports_where_parsing_fails() {
+    let error = |source: &str| match Value::parse(source) {
+      Err(ReflectError::Parse { position, message }) => (position, message),
+      other => panic!("{:?} parsed as {:?}", source, other),
+    };
+
+    assert_eq!(error("(x:1"), (4, "expected `,` or `)`"));
+    assert_eq!(error("1 2"), (2, "expected the end"));
+    assert_eq!(error(r#""abc"#), (0, "unterminated text"));
+    assert_eq!(error(r#""a\u{zz}""#).1, "invalid unicode escape");
+    assert_eq!(error("1.2.3").1, "invalid number");
+    assert_eq!(error("").1, "expected a value");
+  }
+}
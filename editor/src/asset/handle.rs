@@ -0,0 +1,232 @@
+use std::any::Any;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+
+use super::import::Asset;
+use super::loader::Loader;
+use super::meta::ImportSettings;
+use crate::scene::guid::Guid;
+
+/// Where the import of an asset is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadState {
+  /// Not imported yet, the handle holds the placeholder.
+  Loading,
+  Ready,
+  /// The first import failed, the handle keeps the placeholder.
+  Failed,
+}
+
+/// What the handles to one asset share.
+struct Slot<T> {
+  guid: Guid,
+  asset: RwLock<Arc<T>>,
+  /// Times the asset was imported, 0 before the first.
+  version: AtomicU64,
+  /// Imports queued into the slot; only the latest one may land.
+  generation: AtomicU64,
+  failed: AtomicBool,
+}
+
+/// A typed, reference-counted reference to an imported asset, from `AssetDatabase::load`. The
+/// asset stays loaded while a handle to it exists, and reimports swap its data in place, so
+/// holders see the new data through the same handle. It resolves to a placeholder while the
+/// file is imported in the background.
+pub struct Handle<T> {
+  slot: Arc<Slot<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+  fn clone(&self) -> Self {
+    Self {
+      slot: self.slot.clone(),
+    }
+  }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Handle")
+      .field("guid", &self.slot.guid)
+      .field("version", &self.slot.version)
+      .finish()
+  }
+}
+
+impl<T: Asset> Handle<T> {
+  pub(super) fn new(guid: Guid) -> Self {
+    Self {
+      slot: Arc::new(Slot {
+        guid,
+        asset: RwLock::new(Arc::new(T::placeholder())),
+        version: AtomicU64::new(0),
+        generation: AtomicU64::new(0),
+        failed: AtomicBool::new(false),
+      }),
+    }
+  }
+
+  pub fn guid(&self) -> Guid {
+    self.slot.guid
+  }
+
+  /// The asset as last imported, or the placeholder if it hasn't been yet.
+  pub fn get(&self) -> Arc<T> {
+    match self.slot.asset.read() {
+      Result::Ok(asset) => asset.clone(),
+      Err(_) => Arc::new(T::placeholder()),
+    }
+  }
+
+  pub fn state(&self) -> LoadState {
+    if self.version() > 0 {
+      LoadState::Ready
+    } else if self.slot.failed.load(Ordering::Acquire) {
+      LoadState::Failed
+    } else {
+      LoadState::Loading
+    }
+  }
+
+  /// Changes each time the asset is imported, so holders of derived data, like a GPU texture,
+  /// know to rebuild it.
+  pub fn version(&self) -> u64 {
+    self.slot.version.load(Ordering::Acquire)
+  }
+
+  /// Starts a new import of the asset and returns its generation, for `set` or `fail`.
+  pub(super) fn next_generation(&self) -> u64 {
+    self.slot.generation.fetch_add(1, Ordering::AcqRel) + 1
+  }
+
+  fn is_current(&self, generation: u64) -> bool {
+    self.slot.generation.load(Ordering::Acquire) == generation
+  }
+
+  /// Stores the result of the import `generation`; returns false and drops it if a newer one
+  /// was queued since.
+  pub(super) fn set(&self, asset: T, generation: u64) -> bool {
+    let Result::Ok(mut slot) = self.slot.asset.write() else {
+      return false;
+    };
+
+    // Bajo el bloqueo, para que un import viejo no pise a uno nuevo ya escrito.
+    if !self.is_current(generation) {
+      return false;
+    }
+
+    *slot = Arc::new(asset);
+    self.slot.version.fetch_add(1, Ordering::AcqRel);
+    true
+  }
+
+  /// Marks the import `generation` as failed, unless a newer one was queued since.
+  pub(super) fn fail(&self, generation: u64) -> bool {
+    if !self.is_current(generation) {
+      return false;
+    }
+
+    self.slot.failed.store(true, Ordering::Release);
+    true
+  }
+}
+
+/// Queues a file to be imported again into a slot of the type it was made for.
+type Reimport = fn(Arc<dyn Any + Send + Sync>, &mut Loader, PathBuf, ImportSettings);
+
+/// A loaded asset as the database keeps it: without its type, and without keeping it alive.
+pub(super) struct Loaded {
+  slot: Weak<dyn Any + Send + Sync>,
+  reimport: Reimport,
+}
+
+impl Loaded {
+  pub fn new<T: Asset>(handle: &Handle<T>) -> Self {
+    let slot: Arc<dyn Any + Send + Sync> = handle.slot.clone();
+
+    Self {
+      slot: Arc::downgrade(&slot),
+      reimport: reimport::<T>,
+    }
+  }
+
+  /// Whether a handle to the asset still exists.
+  pub fn is_alive(&self) -> bool {
+    self.slot.strong_count() > 0
+  }
+
+  /// A new handle to the asset, if it's still loaded as a `T`.
+  pub fn handle<T: Asset>(&self) -> Option<Handle<T>> {
+    let slot = self.slot.upgrade()?.downcast::<Slot<T>>().ok()?;
+    Some(Handle { slot })
+  }
+
+  /// Queues the asset to be imported again if it's still loaded; `false` if it isn't.
+  pub fn reimport(&self, loader: &mut Loader, path: PathBuf, settings: ImportSettings) -> bool {
+    let Some(slot) = self.slot.upgrade() else {
+      return false;
+    };
+
+    (self.reimport)(slot, loader, path, settings);
+    true
+  }
+}
+
+fn reimport<T: Asset>(slot: Arc<dyn Any + Send + Sync>, loader: &mut Loader, path: PathBuf, settings: ImportSettings) {
+  if let Result::Ok(slot) = slot.downcast::<Slot<T>>() {
+    loader.import(Handle { slot }, path, settings);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::asset::import::TextureAsset;
+
+  fn texture(width: u32) -> TextureAsset {
+    TextureAsset {
+      width,
+      height: 1,
+      levels: vec![],
+    }
+  }
+
+  #[test]
+  fn starts_as_the_placeholder() {
+    let handle = Handle::<TextureAsset>::new(Guid::new());
+
+    assert_eq!(handle.state(), LoadState::Loading);
+    assert_eq!(*handle.get(), TextureAsset::placeholder());
+    assert_eq!(handle.version(), 0);
+  }
+
+  #[test]
+  fn drops_results_of_stale_imports() {
+    let handle = Handle::<TextureAsset>::new(Guid::new());
+    let (older, newer) = (handle.next_generation(), handle.next_generation());
+
+    assert!(handle.set(texture(2), newer));
+    assert!(!handle.set(texture(1), older));
+    assert!(!handle.fail(older));
+
+    assert_eq!(handle.get().width, 2);
+    assert_eq!(handle.version(), 1);
+    assert_eq!(handle.state(), LoadState::Ready);
+  }
+
+  #[test]
+  fn stale_failures_dont_fail_the_handle() {
+    let handle = Handle::<TextureAsset>::new(Guid::new());
+    let older = handle.next_generation();
+    let newer = handle.next_generation();
+
+    assert!(!handle.fail(older));
+    assert_eq!(handle.state(), LoadState::Loading);
+
+    assert!(handle.fail(newer));
+    assert_eq!(handle.state(), LoadState::Failed);
+    assert_eq!(*handle.get(), TextureAsset::placeholder());
+  }
+}
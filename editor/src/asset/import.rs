@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cgmath::{vec2, vec3, InnerSpace, Vector3, Zero};
+use image::imageops::FilterType;
+use image::RgbaImage;
+use serde::Deserialize;
+
+use super::meta::{AssetKind, ImportSettings};
+use super::AssetError;
+use crate::scene::mesh::{Mesh, Vertex};
+use crate::scene::prop::Material;
+
+/// Side of the checkerboard textures resolve to while they load.
+const PLACEHOLDER_SIZE: u32 = 64;
+const PLACEHOLDER_CELL: u32 = 8;
+
+/// Data imported from an asset file, what a `Handle` points to. Imports run on the loader's
+/// threads, so they do all the decoding the GPU upload doesn't need.
+pub trait Asset: Sized + Send + Sync + 'static {
+  /// Files of this kind import into this type.
+  const KIND: AssetKind;
+
+  fn import(path: &Path, settings: &ImportSettings) -> Result<Self, AssetError>;
+
+  /// What a handle resolves to until the file is imported.
+  fn placeholder() -> Self;
+}
+
+/// Decoded RGBA8 pixels, top row first, with the whole mip chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureAsset {
+  pub width: u32,
+  pub height: u32,
+  /// From full size down to 1x1, each half the previous one.
+  pub levels: Vec<Vec<u8>>,
+}
+
+impl TextureAsset {
+  fn new(image: RgbaImage) -> Self {
+    let (width, height) = image.dimensions();
+    let mut levels = vec![];
+    let mut level = image;
+
+    loop {
+      let (w, h) = level.dimensions();
+      let next =
+        (w > 1 || h > 1).then(|| image::imageops::resize(&level, (w / 2).max(1), (h / 2).max(1), FilterType::Triangle));
+      levels.push(level.into_raw());
+
+      match next {
+        Some(next) => level = next,
+        None => break,
+      }
+    }
+
+    Self { width, height, levels }
+  }
+}
+
+impl Asset for TextureAsset {
+  const KIND: AssetKind = AssetKind::Texture;
+
+  fn import(path: &Path, settings: &ImportSettings) -> Result<Self, AssetError> {
+    let mut image = image::open(path)?;
+
+    if let ImportSettings::Texture(settings) = settings {
+      if let Some(max_size) = settings.max_size.filter(|&m| image.width().max(image.height()) > m) {
+        image = image.resize(max_size, max_size, FilterType::Triangle);
+      }
+
+      if settings.flip_vertically {
+        image = image.flipv();
+      }
+    }
+
+    Ok(Self::new(image.into_rgba8()))
+  }
+
+  /// Grey checkerboard.
+  fn placeholder() -> Self {
+    Self::new(RgbaImage::from_fn(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, |x, y| {
+      match (x / PLACEHOLDER_CELL + y / PLACEHOLDER_CELL) % 2 {
+        0 => image::Rgba([200, 200, 200, 255]),
+        _ => image::Rgba([120, 120, 120, 255]),
+      }
+    }))
+  }
+}
+
+/// The triangles of an `.obj` file, all its objects in one mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshAsset {
+  pub vertices: Vec<Vertex>,
+  pub indices: Vec<u32>,
+}
+
+impl Asset for MeshAsset {
+  const KIND: AssetKind = AssetKind::Mesh;
+
+  fn import(path: &Path, settings: &ImportSettings) -> Result<Self, AssetError> {
+    let scale = match settings {
+      ImportSettings::Mesh(settings) => settings.scale,
+      _ => 1.0,
+    };
+
+    let (models, _) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    let mut asset = Self {
+      vertices: vec![],
+      indices: vec![],
+    };
+
+    for model in models {
+      let mesh = model.mesh;
+      let base = asset.vertices.len() as u32;
+
+      for i in 0..mesh.positions.len() / 3 {
+        let position = vec3(
+          mesh.positions[i * 3],
+          mesh.positions[i * 3 + 1],
+          mesh.positions[i * 3 + 2],
+        ) * scale;
+        let normal = match mesh.normals.get(i * 3..i * 3 + 3) {
+          Some(n) => vec3(n[0], n[1], n[2]),
+          None => Vector3::zero(),
+        };
+        // Las UV de OBJ empiezan abajo, las de las texturas arriba.
+        let uv = match mesh.texcoords.get(i * 2..i * 2 + 2) {
+          Some(t) => vec2(t[0], 1.0 - t[1]),
+          None => vec2(0.0, 0.0),
+        };
+
+        asset.vertices.push(Vertex::new(position, normal, uv));
+      }
+
+      let indices = mesh.indices.iter().map(|i| base + i);
+      asset.indices.extend(indices);
+
+      if mesh.normals.is_empty() {
+        smooth_normals(&mut asset.vertices[base as usize..], &mesh.indices);
+      }
+    }
+
+    Ok(asset)
+  }
+
+  /// Unit cube.
+  fn placeholder() -> Self {
+    let cube = Mesh::cube();
+
+    Self {
+      vertices: cube.vertices,
+      indices: cube.indices,
+    }
+  }
+}
+
+/// Normals averaged from the faces around each vertex, for files without them.
+fn smooth_normals(vertices: &mut [Vertex], indices: &[u32]) {
+  for triangle in indices.chunks_exact(3) {
+    let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+    let normal = (vertices[b].position - vertices[a].position).cross(vertices[c].position - vertices[a].position);
+
+    for i in [a, b, c] {
+      vertices[i].normal += normal;
+    }
+  }
+
+  for vertex in vertices {
+    if vertex.normal.magnitude2() > 0.0 {
+      vertex.normal = vertex.normal.normalize();
+    }
+  }
+}
+
+/// What a `.material` file holds: the factors and textures of a scene material and the shader
+/// it's drawn with. Paths are relative to the file.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MaterialAsset {
+  pub shader: Option<PathBuf>,
+  pub material: Material,
+}
+
+impl MaterialAsset {
+  /// The files the material uses, resolved next to `path`.
+  pub fn dependencies(&self, path: &Path) -> Vec<PathBuf> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let textures = &self.material.textures;
+    let files = [
+      &self.shader,
+      &textures.albedo,
+      &textures.normal,
+      &textures.metallic_roughness,
+      &textures.occlusion,
+      &textures.emissive,
+    ];
+
+    files.into_iter().flatten().map(|file| directory.join(file)).collect()
+  }
+}
+
+impl Asset for MaterialAsset {
+  const KIND: AssetKind = AssetKind::Material;
+
+  fn import(path: &Path, _settings: &ImportSettings) -> Result<Self, AssetError> {
+    Ok(ron::from_str(&fs::read_to_string(path)?)?)
+  }
+
+  fn placeholder() -> Self {
+    Self::default()
+  }
+}
@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use log::{info, warn};
+
+use super::handle::Handle;
+use super::import::Asset;
+use super::meta::ImportSettings;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Imports queued and finished since the loader was last idle, for a status bar.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+  pub queued: usize,
+  /// Including the failed ones.
+  pub finished: usize,
+  pub failed: usize,
+}
+
+impl LoadProgress {
+  pub fn is_loading(&self) -> bool {
+    self.finished < self.queued
+  }
+
+  /// Share of the queued imports finished, 1 when idle.
+  pub fn fraction(&self) -> f32 {
+    match self.queued {
+      0 => 1.0,
+      queued => self.finished as f32 / queued as f32,
+    }
+  }
+}
+
+/// Worker threads that read and decode asset files, started with the first import. Each import
+/// fills its handle when it's done; until then the handle holds the placeholder of its type.
+#[derive(Default)]
+pub struct Loader {
+  workers: Option<Workers>,
+  progress: Arc<Mutex<LoadProgress>>,
+}
+
+struct Workers {
+  sender: Sender<Job>,
+  threads: Vec<JoinHandle<()>>,
+}
+
+impl Loader {
+  /// Imports count as finished once their handle holds the result, so when the loader is idle
+  /// every handle is up to date.
+  pub fn progress(&self) -> LoadProgress {
+    self.progress.lock().map(|p| *p).unwrap_or_default()
+  }
+
+  /// Imports the file at `path` into `handle` on a worker. A failed first import marks the
+  /// handle as failed; a failed reimport keeps the previous import. If the handle is queued
+  /// again before this import finishes, its result is dropped.
+  pub fn import<T: Asset>(&mut self, handle: Handle<T>, path: PathBuf, settings: ImportSettings) {
+    if let Result::Ok(mut progress) = self.progress.lock() {
+      // Una tanda nueva empieza la cuenta de cero.
+      if !progress.is_loading() {
+        *progress = LoadProgress::default();
+      }
+
+      progress.queued += 1;
+    }
+
+    let progress = self.progress.clone();
+    let generation = handle.next_generation();
+    let job = Box::new(move || {
+      let result = T::import(&path, &settings);
+
+      if let Err(error) = &result {
+        warn!("Can't import {}: {}", path.display(), error);
+      }
+
+      // Un import encolado despues ya tiene la ultima palabra.
+      let failed = result.is_err();
+      let current = match result {
+        Result::Ok(asset) => handle.set(asset, generation),
+        Err(_) => handle.fail(generation),
+      };
+
+      if !current {
+        info!("[+] assets -> dropped a stale import of {}", path.display());
+      }
+
+      // Se cuenta despues de llenar el handle, ver `progress`.
+      if let Result::Ok(mut progress) = progress.lock() {
+        progress.finished += 1;
+        progress.failed += usize::from(failed);
+      }
+    });
+
+    let workers = self.workers.get_or_insert_with(Workers::start);
+
+    if let Err(error) = workers.sender.send(job) {
+      warn!("Can't queue an asset import: {}", error);
+    }
+  }
+}
+
+impl Workers {
+  fn start() -> Self {
+    let count = thread::available_parallelism().map_or(2, |n| n.get().saturating_sub(1).max(1));
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let threads = (0..count)
+      .filter_map(|i| {
+        let receiver = receiver.clone();
+        let spawned = thread::Builder::new()
+          .name(format!("asset-loader-{}", i))
+          .spawn(move || work(&receiver));
+
+        spawned
+          .inspect_err(|e| warn!("Can't start an asset loader: {}", e))
+          .ok()
+      })
+      .collect::<Vec<_>>();
+
+    info!("[+] assets -> {} loader threads", threads.len());
+
+    Self { sender, threads }
+  }
+}
+
+impl Drop for Workers {
+  fn drop(&mut self) {
+    // Sin emisor, cada hilo sale al acabar la cola.
+    let (sender, _) = mpsc::channel();
+    drop(std::mem::replace(&mut self.sender, sender));
+
+    for thread in self.threads.drain(..) {
+      _ = thread.join();
+    }
+  }
+}
+
+fn work(receiver: &Mutex<Receiver<Job>>) {
+  loop {
+    let job = match receiver.lock() {
+      Result::Ok(receiver) => receiver.recv(),
+      Err(_) => return,
+    };
+
+    match job {
+      Result::Ok(job) => job(),
+      Err(_) => return,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use std::path::Path;
+  use std::time::{Duration, Instant};
+
+  use super::*;
+  use crate::asset::handle::LoadState;
+  use crate::asset::import::TextureAsset;
+  use crate::asset::meta::AssetKind;
+  use crate::asset::tests::{project, texture};
+  use crate::scene::guid::Guid;
+
+  fn import(loader: &mut Loader, handle: &Handle<TextureAsset>, path: &Path) {
+    loader.import(
+      handle.clone(),
+      path.to_path_buf(),
+      AssetKind::Texture.default_settings(),
+    );
+  }
+
+  /// Waits for the queued imports to finish.
+  fn wait(loader: &Loader) -> LoadProgress {
+    let start = Instant::now();
+
+    while loader.progress().is_loading() {
+      assert!(start.elapsed() < Duration::from_secs(10), "imports never finished");
+      thread::sleep(Duration::from_millis(1));
+    }
+
+    loader.progress()
+  }
+
+  #[test]
+  fn progress_counts_finished_imports() {
+    let idle = LoadProgress::default();
+    let halfway = LoadProgress {
+      queued: 4,
+      finished: 2,
+      failed: 1,
+    };
+
+    assert!(!idle.is_loading());
+    assert_eq!(idle.fraction(), 1.0);
+    assert!(halfway.is_loading());
+    assert_eq!(halfway.fraction(), 0.5);
+  }
+
+  #[test]
+  fn handles_go_from_placeholder_to_ready() {
+    let root = project("loader-ready");
+    let path = root.join("wide.png");
+    texture(&path, 8, 4);
+
+    let mut loader = Loader::default();
+    let handle = Handle::<TextureAsset>::new(Guid::new());
+    assert_eq!(*handle.get(), TextureAsset::placeholder());
+
+    import(&mut loader, &handle, &path);
+
+    assert_eq!(
+      wait(&loader),
+      LoadProgress {
+        queued: 1,
+        finished: 1,
+        failed: 0,
+      }
+    );
+    assert_eq!(handle.state(), LoadState::Ready);
+    assert_eq!((handle.get().width, handle.get().height), (8, 4));
+    assert_eq!(handle.get().levels.len(), 4);
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn failed_imports_keep_the_placeholder() {
+    let root = project("loader-failed");
+    let mut loader = Loader::default();
+    let handle = Handle::<TextureAsset>::new(Guid::new());
+
+    import(&mut loader, &handle, &root.join("missing.png"));
+
+    assert_eq!(wait(&loader).failed, 1);
+    assert_eq!(handle.state(), LoadState::Failed);
+    assert_eq!(*handle.get(), TextureAsset::placeholder());
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn the_last_queued_import_wins() {
+    let root = project("loader-stale");
+    let (big, small) = (root.join("big.png"), root.join("small.png"));
+    texture(&big, 256, 256);
+    texture(&small, 2, 2);
+
+    let mut loader = Loader::default();
+    let handle = Handle::<TextureAsset>::new(Guid::new());
+
+    // El grande tarda mas: sin generaciones acabaria pisando al pequeño.
+    import(&mut loader, &handle, &big);
+    import(&mut loader, &handle, &small);
+
+    assert_eq!(wait(&loader).finished, 2);
+    assert_eq!(handle.get().width, 2);
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn a_new_batch_restarts_the_count() {
+    let root = project("loader-batches");
+    let path = root.join("a.png");
+    texture(&path, 2, 2);
+
+    let mut loader = Loader::default();
+    import(&mut loader, &Handle::new(Guid::new()), &path);
+    assert_eq!(wait(&loader).queued, 1);
+
+    // Con el cargador parado, el siguiente import no suma al lote anterior.
+    import(&mut loader, &Handle::new(Guid::new()), &path);
+    assert_eq!(
+      wait(&loader),
+      LoadProgress {
+        queued: 1,
+        finished: 1,
+        failed: 0,
+      }
+    );
+
+    fs::remove_dir_all(root).unwrap();
+  }
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::AssetError;
+use crate::scene::guid::Guid;
+
+/// Version `AssetMeta::save` writes.
+pub const META_VERSION: u32 = 1;
+
+/// Extension added to an asset's file name for its sidecar, `rock.png.meta`.
+pub const META_EXTENSION: &str = "meta";
+
+/// What the database knows how to import, by file extension.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AssetKind {
+  Texture,
+  Mesh,
+  Material,
+  Shader,
+  Scene,
+}
+
+impl AssetKind {
+  /// `None` for files that aren't assets, including the sidecars.
+  pub fn of(path: &Path) -> Option<Self> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+    match extension.as_str() {
+      "png" | "jpg" | "jpeg" | "bmp" | "tga" | "gif" | "hdr" | "exr" => Some(Self::Texture),
+      "obj" => Some(Self::Mesh),
+      "material" => Some(Self::Material),
+      "spv" | "vert" | "frag" | "comp" => Some(Self::Shader),
+      "scene" => Some(Self::Scene),
+      _ => None,
+    }
+  }
+
+  pub fn default_settings(self) -> ImportSettings {
+    match self {
+      Self::Texture => ImportSettings::Texture(TextureSettings::default()),
+      Self::Mesh => ImportSettings::Mesh(MeshSettings::default()),
+      _ => ImportSettings::None,
+    }
+  }
+}
+
+/// How an asset is imported, kept in its sidecar so changing them reimports it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ImportSettings {
+  None,
+  Texture(TextureSettings),
+  Mesh(MeshSettings),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureSettings {
+  /// Largest side kept, larger images are scaled down on import.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_size: Option<u32>,
+  /// For images stored bottom row first.
+  pub flip_vertically: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshSettings {
+  /// Applied to the positions, for files in other units.
+  pub scale: f32,
+}
+
+impl Default for MeshSettings {
+  fn default() -> Self {
+    Self { scale: 1.0 }
+  }
+}
+
+/// Just the version, read before the rest in case the sidecar is too new to parse.
+#[derive(Deserialize)]
+struct Header {
+  version: u32,
+}
+
+/// The `.meta` sidecar next to each asset. Its GUID is what scenes and other assets reference,
+/// so moving or renaming the file along with its sidecar keeps them pointing at it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssetMeta {
+  pub version: u32,
+  pub guid: Guid,
+  pub settings: ImportSettings,
+}
+
+impl AssetMeta {
+  pub fn new(kind: AssetKind) -> Self {
+    Self {
+      version: META_VERSION,
+      guid: Guid::new(),
+      settings: kind.default_settings(),
+    }
+  }
+
+  pub fn load(path: &Path) -> Result<Self, AssetError> {
+    let text = fs::read_to_string(path)?;
+    let header = ron::from_str::<Header>(&text)?;
+
+    if header.version > META_VERSION {
+      return Err(AssetError::UnsupportedVersion {
+        found: header.version,
+        supported: META_VERSION,
+      });
+    }
+
+    Ok(ron::from_str(&text)?)
+  }
+
+  pub fn save(&self, path: &Path) -> Result<(), AssetError> {
+    let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+    Ok(fs::write(path, text)?)
+  }
+}
+
+/// Where the sidecar of the asset at `path` is.
+pub fn meta_path(path: &Path) -> PathBuf {
+  let mut name = path.file_name().unwrap_or_default().to_os_string();
+  name.push(".");
+  name.push(META_EXTENSION);
+  path.with_file_name(name)
+}
@@ -0,0 +1,508 @@
+pub mod handle;
+pub mod import;
+pub mod loader;
+pub mod meta;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::scene::guid::Guid;
+use handle::{Handle, Loaded};
+use import::{Asset, MaterialAsset};
+use loader::{LoadProgress, Loader};
+use meta::{meta_path, AssetKind, AssetMeta, ImportSettings};
+
+#[derive(Debug, Error)]
+pub enum AssetError {
+  #[error("{0} isn't a kind of asset the database imports")]
+  UnknownKind(PathBuf),
+  #[error("{path} is a {found:?} asset, not a {expected:?}")]
+  WrongKind {
+    path: PathBuf,
+    expected: AssetKind,
+    found: AssetKind,
+  },
+  #[error("asset sidecar version {found} is newer than the supported {supported}")]
+  UnsupportedVersion { found: u32, supported: u32 },
+  #[error(transparent)]
+  Image(#[from] image::ImageError),
+  #[error(transparent)]
+  Obj(#[from] tobj::LoadError),
+  #[error(transparent)]
+  Parse(#[from] ron::error::SpannedError),
+  #[error(transparent)]
+  Serialize(#[from] ron::Error),
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+}
+
+/// A file the database knows.
+#[derive(Clone, Debug)]
+struct AssetRecord {
+  guid: Guid,
+  kind: AssetKind,
+  settings: ImportSettings,
+  /// Whether `guid` and `settings` come from a sidecar. Files outside the project, or whose
+  /// sidecar can't be read, get a GUID for the session only.
+  sidecar: bool,
+  /// Latest change to the file or its sidecar, to tell when to reimport it.
+  modified: Option<SystemTime>,
+  /// Other files it's imported from, like a material's textures and shader.
+  dependencies: Vec<PathBuf>,
+}
+
+/// The assets of a project directory, by canonical path. Each one gets a stable GUID and its
+/// import settings from a `.meta` sidecar, written next to it the first time it's seen.
+///
+/// `refresh` finds the files that changed and reimports them along with everything that
+/// depends on them, updating the loaded assets in place for their `Handle`s. Assets are only
+/// loaded while a handle to them exists, and they're imported on the loader's threads.
+#[derive(Default)]
+pub struct AssetDatabase {
+  /// `None` without a project: assets can still be loaded, but nothing is scanned.
+  root: Option<PathBuf>,
+  records: HashMap<PathBuf, AssetRecord>,
+  loaded: HashMap<Guid, Loaded>,
+  loader: Loader,
+}
+
+impl AssetDatabase {
+  /// The database of the project at `root`, with every asset in it found.
+  pub fn open(root: impl Into<PathBuf>) -> Self {
+    let root = root.into();
+    let mut database = Self {
+      root: Some(fs::canonicalize(&root).unwrap_or(root)),
+      ..Default::default()
+    };
+
+    database.scan();
+
+    if let Some(root) = &database.root {
+      info!("[+] assets -> {} assets in {}", database.records.len(), root.display());
+    }
+
+    database
+  }
+
+  /// Scans the project for new, changed and removed files and reimports what they affect.
+  /// Returns the assets reimported.
+  pub fn refresh(&mut self) -> Vec<PathBuf> {
+    self.loaded.retain(|_, loaded| loaded.is_alive());
+
+    let mut queue = self.scan();
+    queue.extend(
+      self
+        .records
+        .iter()
+        .filter(|(path, record)| modified(path) != record.modified)
+        .map(|(path, _)| path.clone()),
+    );
+
+    // Lo que depende de un asset cambiado se reimporta detras de el.
+    let mut reimported = vec![];
+    let mut visited = HashSet::new();
+
+    while let Some(path) = queue.pop() {
+      if !visited.insert(path.clone()) {
+        continue;
+      }
+
+      queue.extend(self.dependents(&path));
+
+      if self.records.contains_key(&path) {
+        self.reimport(&path);
+        reimported.push(path);
+      }
+    }
+
+    reimported
+  }
+
+  /// A handle to the asset at `path`, queued to be imported if it isn't loaded yet. Files
+  /// outside the project are added to the database without a sidecar.
+  pub fn load<T: Asset>(&mut self, path: &Path) -> Result<Handle<T>, AssetError> {
+    let path = fs::canonicalize(path)?;
+
+    if !self.records.contains_key(&path) {
+      let kind = AssetKind::of(&path).ok_or_else(|| AssetError::UnknownKind(path.clone()))?;
+      self.add(path.clone(), kind)?;
+    }
+
+    let record = &self.records[&path];
+
+    if record.kind != T::KIND {
+      return Err(AssetError::WrongKind {
+        path,
+        expected: T::KIND,
+        found: record.kind,
+      });
+    }
+
+    if let Some(handle) = self.loaded.get(&record.guid).and_then(Loaded::handle) {
+      return Ok(handle);
+    }
+
+    let handle = Handle::new(record.guid);
+    self.loaded.insert(record.guid, Loaded::new(&handle));
+    let settings = record.settings.clone();
+    self.loader.import(handle.clone(), path, settings);
+
+    Ok(handle)
+  }
+
+  /// How the imports queued since the loader was last idle are going.
+  pub fn progress(&self) -> LoadProgress {
+    self.loader.progress()
+  }
+
+  fn in_project(&self, path: &Path) -> bool {
+    self.root.as_ref().is_some_and(|root| path.starts_with(root))
+  }
+
+  /// Forgets the removed files of the project and adds the new ones; returns both.
+  fn scan(&mut self) -> Vec<PathBuf> {
+    let Some(root) = self.root.clone() else {
+      return vec![];
+    };
+
+    let mut found = vec![];
+    find_assets(&root, &mut found);
+    // Ordenados, para que entre dos ficheros con el mismo GUID siempre gane el mismo.
+    found.sort();
+
+    let mut changed = vec![];
+    let present = found.iter().collect::<HashSet<_>>();
+    let removed = self
+      .records
+      .keys()
+      .filter(|path| self.in_project(path) && !present.contains(path))
+      .cloned()
+      .collect::<Vec<_>>();
+
+    // Primero se olvidan los borrados: un fichero movido con su sidecar conserva el GUID.
+    for path in removed {
+      info!("[+] assets -> removed {}", path.display());
+      self.records.remove(&path);
+      changed.push(path);
+    }
+
+    for path in found {
+      let Some(kind) = AssetKind::of(&path).filter(|_| !self.records.contains_key(&path)) else {
+        continue;
+      };
+
+      match self.add(path.clone(), kind) {
+        Result::Ok(()) => changed.push(path),
+        Err(error) => warn!("Can't add asset {}: {}", path.display(), error),
+      }
+    }
+
+    changed
+  }
+
+  /// Records the file at `path`, reading its sidecar or writing a new one.
+  fn add(&mut self, path: PathBuf, kind: AssetKind) -> Result<(), AssetError> {
+    let sidecar = meta_path(&path);
+    let in_project = self.in_project(&path);
+
+    let (mut meta, has_sidecar) = match (in_project, sidecar.exists()) {
+      (false, _) => (AssetMeta::new(kind), false),
+      (true, true) => match AssetMeta::load(&sidecar) {
+        Result::Ok(meta) => (meta, true),
+        Err(error) => {
+          warn!(
+            "Can't read {}, the asset gets a GUID for this session: {}",
+            sidecar.display(),
+            error
+          );
+          (AssetMeta::new(kind), false)
+        }
+      },
+      (true, false) => {
+        let meta = AssetMeta::new(kind);
+        meta.save(&sidecar)?;
+        info!("[+] assets -> new {} {}", meta.guid, path.display());
+        (meta, true)
+      }
+    };
+
+    // Un fichero copiado con su sidecar repite el GUID del original.
+    if has_sidecar && self.records.values().any(|r| r.guid == meta.guid) {
+      warn!("{} has the GUID of another asset, giving it a new one", path.display());
+      meta.guid = Guid::new();
+      meta.save(&sidecar)?;
+    }
+
+    // El fichero cambio de tipo con el mismo sidecar.
+    if std::mem::discriminant(&meta.settings) != std::mem::discriminant(&kind.default_settings()) {
+      meta.settings = kind.default_settings();
+    }
+
+    let record = AssetRecord {
+      guid: meta.guid,
+      kind,
+      settings: meta.settings,
+      sidecar: has_sidecar,
+      modified: modified(&path),
+      dependencies: dependencies(kind, &path),
+    };
+
+    self.records.insert(path, record);
+    Ok(())
+  }
+
+  /// Assets imported from the file at `path`.
+  fn dependents(&self, path: &Path) -> Vec<PathBuf> {
+    let records = self.records.iter();
+    let dependents = records.filter(|(_, record)| record.dependencies.iter().any(|d| d == path));
+    dependents.map(|(path, _)| path.clone()).collect()
+  }
+
+  /// Reads the settings and dependencies of the asset at `path` again, and imports it again if
+  /// it's loaded.
+  fn reimport(&mut self, path: &Path) {
+    let Some(record) = self.records.get_mut(path) else {
+      return;
+    };
+
+    if record.sidecar {
+      match AssetMeta::load(&meta_path(path)) {
+        Result::Ok(meta) => record.settings = meta.settings,
+        Err(error) => warn!("Can't read the sidecar of {}: {}", path.display(), error),
+      }
+    }
+
+    record.modified = modified(path);
+    record.dependencies = dependencies(record.kind, path);
+
+    let Some(loaded) = self.loaded.get(&record.guid) else {
+      return;
+    };
+
+    if loaded.reimport(&mut self.loader, path.to_path_buf(), record.settings.clone()) {
+      info!("[+] assets -> reimporting {}", path.display());
+    } else {
+      self.loaded.remove(&record.guid);
+    }
+  }
+}
+
+/// Asset files under `directory`, skipping hidden ones.
+fn find_assets(directory: &Path, found: &mut Vec<PathBuf>) {
+  let Result::Ok(entries) = fs::read_dir(directory) else {
+    return;
+  };
+
+  for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+    if path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')) {
+      continue;
+    }
+
+    if path.is_dir() {
+      find_assets(&path, found);
+    } else if AssetKind::of(&path).is_some() {
+      found.push(path);
+    }
+  }
+}
+
+/// When the file at `path` or its sidecar last changed.
+fn modified(path: &Path) -> Option<SystemTime> {
+  let time = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+  time(path).max(time(&meta_path(path)))
+}
+
+/// The files an asset of `kind` at `path` is imported from, canonical when they exist.
+fn dependencies(kind: AssetKind, path: &Path) -> Vec<PathBuf> {
+  let files = match kind {
+    AssetKind::Material => match MaterialAsset::import(path, &ImportSettings::None) {
+      Result::Ok(material) => material.dependencies(path),
+      Err(error) => {
+        warn!("Can't read the dependencies of {}: {}", path.display(), error);
+        vec![]
+      }
+    },
+    _ => vec![],
+  };
+
+  files
+    .into_iter()
+    .map(|file| fs::canonicalize(&file).unwrap_or(file))
+    .collect()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+  use std::time::{Duration, Instant};
+
+  use super::*;
+  use crate::asset::handle::LoadState;
+  use crate::asset::import::{MeshAsset, TextureAsset};
+  use crate::asset::meta::TextureSettings;
+
+  /// An empty directory for the test `name`, removed first if a previous run left it.
+  pub(crate) fn project(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("sagitario-assets-{}-{}", name, std::process::id()));
+    _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    fs::canonicalize(root).unwrap()
+  }
+
+  /// A red PNG of `width` by `height` at `path`.
+  pub(crate) fn texture(path: &Path, width: u32, height: u32) {
+    image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]))
+      .save(path)
+      .unwrap();
+  }
+
+  fn guid(database: &AssetDatabase, path: &Path) -> Guid {
+    database.records[path].guid
+  }
+
+  /// Moves the modification time of `path` forward, so `refresh` sees it changed even on file
+  /// systems with coarse timestamps.
+  fn touch(path: &Path) {
+    let file = fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+  }
+
+  fn wait_until(done: impl Fn() -> bool) {
+    let start = Instant::now();
+
+    while !done() {
+      assert!(start.elapsed() < Duration::from_secs(10), "imports never finished");
+      std::thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  #[test]
+  fn files_moved_with_their_sidecar_keep_the_guid() {
+    let root = project("rename");
+    let (old, new) = (root.join("old.png"), root.join("new.png"));
+    texture(&old, 4, 4);
+
+    let mut database = AssetDatabase::open(&root);
+    let before = guid(&database, &old);
+
+    fs::rename(&old, &new).unwrap();
+    fs::rename(meta_path(&old), meta_path(&new)).unwrap();
+    database.refresh();
+
+    assert_eq!(database.records.len(), 1);
+    assert_eq!(guid(&database, &new), before);
+    assert_eq!(AssetMeta::load(&meta_path(&new)).unwrap().guid, before);
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn files_copied_with_their_sidecar_get_a_new_guid() {
+    let root = project("copy");
+    let (original, copy) = (root.join("a.png"), root.join("b.png"));
+    texture(&original, 4, 4);
+
+    let mut database = AssetDatabase::open(&root);
+    let before = guid(&database, &original);
+
+    fs::copy(&original, &copy).unwrap();
+    fs::copy(meta_path(&original), meta_path(&copy)).unwrap();
+    database.refresh();
+
+    assert_eq!(guid(&database, &original), before);
+    assert_ne!(guid(&database, &copy), before);
+    assert_eq!(AssetMeta::load(&meta_path(&copy)).unwrap().guid, guid(&database, &copy));
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn removed_files_are_forgotten() {
+    let root = project("remove");
+    let path = root.join("a.png");
+    texture(&path, 4, 4);
+
+    let mut database = AssetDatabase::open(&root);
+    fs::remove_file(&path).unwrap();
+    database.refresh();
+
+    assert!(database.records.is_empty());
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn dependents_are_reimported_with_their_dependencies() {
+    let root = project("dependents");
+    let (albedo, material) = (root.join("albedo.png"), root.join("rock.material"));
+    texture(&albedo, 4, 4);
+    fs::write(&material, r#"(material: (textures: (albedo: Some("albedo.png"))))"#).unwrap();
+
+    let mut database = AssetDatabase::open(&root);
+    assert_eq!(database.records[&material].dependencies, std::slice::from_ref(&albedo));
+    assert!(database.refresh().is_empty());
+
+    texture(&albedo, 8, 8);
+    touch(&albedo);
+    let mut reimported = database.refresh();
+    reimported.sort();
+
+    assert_eq!(reimported, [albedo, material]);
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn changed_import_settings_reimport_the_asset() {
+    let root = project("settings");
+    let path = root.join("a.png");
+    texture(&path, 8, 8);
+
+    let mut database = AssetDatabase::open(&root);
+    let handle = database.load::<TextureAsset>(&path).unwrap();
+    wait_until(|| handle.state() == LoadState::Ready);
+    assert_eq!(handle.get().width, 8);
+
+    let mut meta = AssetMeta::load(&meta_path(&path)).unwrap();
+    meta.settings = ImportSettings::Texture(TextureSettings {
+      max_size: Some(2),
+      ..Default::default()
+    });
+    meta.save(&meta_path(&path)).unwrap();
+    touch(&meta_path(&path));
+
+    assert_eq!(database.refresh(), std::slice::from_ref(&path));
+    assert_eq!(database.records[&path].settings, meta.settings);
+
+    wait_until(|| handle.version() > 1);
+    assert_eq!((handle.get().width, handle.get().height), (2, 2));
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn load_rejects_the_wrong_kind() {
+    let root = project("kind");
+    let path = root.join("a.png");
+    texture(&path, 4, 4);
+
+    let mut database = AssetDatabase::open(&root);
+
+    assert!(matches!(
+      database.load::<MeshAsset>(&path),
+      Err(AssetError::WrongKind {
+        expected: AssetKind::Mesh,
+        found: AssetKind::Texture,
+        ..
+      })
+    ));
+    assert!(database.loaded.is_empty());
+    assert!(database.load::<TextureAsset>(&path).is_ok());
+
+    fs::remove_dir_all(root).unwrap();
+  }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Ok, Result};
+
+use crate::tools::history::DEFAULT_HISTORY_LIMIT;
+use crate::vulkan::view_mode::ViewMode;
+
+const USAGE: &str =
+  "usage: sagitario-editor [--headless] [--frames N] [--capture PATH] [--view-mode MODE] [--size WxH] [--lut PATH] \
+   [--environment PATH] [--input PATH] [--scene PATH] [--project DIR] [--history-limit N]";
+
+/// Command line options. `--headless` renders `frames` frames in a hidden window, saves the last
+/// one to `capture` and exits, which is what regression tests run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CliOptions {
+  pub headless: bool,
+  pub frames: u32,
+  pub capture: Option<PathBuf>,
+  pub view_mode: ViewMode,
+  /// Window size in physical pixels.
+  pub size: Option<(u32, u32)>,
+  /// Color grading LUT, enables grading when set.
+  pub lut: Option<PathBuf>,
+  /// Equirectangular `.hdr` / `.exr` used as skybox and image based lighting.
+  pub environment: Option<PathBuf>,
+  /// RON input map whose actions and axes override the default bindings.
+  pub input: Option<PathBuf>,
+  /// `.scene` file opened at startup if it exists, and where the scene is saved.
+  pub scene: Option<PathBuf>,
+  /// Directory the asset database scans, writing a `.meta` next to each asset.
+  pub project: Option<PathBuf>,
+  /// Most edits kept for undo.
+  pub history_limit: usize,
+}
+
+impl Default for CliOptions {
+  fn default() -> Self {
+    Self {
+      headless: false,
+      frames: 3,
+      capture: None,
+      view_mode: ViewMode::default(),
+      size: None,
+      lut: None,
+      environment: None,
+      input: None,
+      scene: None,
+      project: None,
+      history_limit: DEFAULT_HISTORY_LIMIT,
+    }
+  }
+}
+
+impl CliOptions {
+  pub fn from_env() -> Result<Self> {
+    Self::parse(std::env::args().skip(1))
+  }
+
+  pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+    let mut options = Self::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+      let mut value = || args.next().ok_or_else(|| anyhow!("`{}` needs a value. {}", arg, USAGE));
+
+      match arg.as_str() {
+        "--headless" => options.headless = true,
+        "--frames" => options.frames = value()?.parse().context("`--frames` must be a number")?,
+        "--capture" => options.capture = Some(PathBuf::from(value()?)),
+        "--view-mode" => options.view_mode = value()?.parse()?,
+        "--size" => options.size = Some(parse_size(&value()?)?),
+        "--lut" => options.lut = Some(PathBuf::from(value()?)),
+        "--environment" => options.environment = Some(PathBuf::from(value()?)),
+        "--input" => options.input = Some(PathBuf::from(value()?)),
+        "--scene" => options.scene = Some(PathBuf::from(value()?)),
+        "--project" => options.project = Some(PathBuf::from(value()?)),
+        "--history-limit" => {
+          options.history_limit = value()?.parse().context("`--history-limit` must be a number")?;
+        }
+        _ => return Err(anyhow!("Unknown argument `{}`. {}", arg, USAGE)),
+      }
+    }
+
+    options.frames = options.frames.max(1);
+
+    Ok(options)
+  }
+
+  /// `project`, or the folder of `scene` when it isn't given.
+  pub fn project_dir(&self) -> Option<PathBuf> {
+    let scene_dir = self.scene.as_ref().and_then(|path| path.parent());
+    let scene_dir = scene_dir.filter(|dir| !dir.as_os_str().is_empty());
+
+    self.project.clone().or_else(|| scene_dir.map(|dir| dir.to_path_buf()))
+  }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32)> {
+  let (width, height) = value
+    .split_once('x')
+    .ok_or_else(|| anyhow!("`--size` must look like 1280x720"))?;
+
+  Ok((width.parse()?, height.parse()?))
+}
@@ -1,15 +1,62 @@
-use anyhow::{Ok, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Ok, Result};
+use cgmath::{point3, vec3};
 use image::GenericImageView;
+use log::{info, warn};
+use sagitario_ecs::Entity;
+use sagitario_input::{platform, Input, InputMap};
+use sagitario_reflect::{Reflect, Value};
 use winit::application::ApplicationHandler;
-use winit::dpi::LogicalSize;
+use winit::dpi::{LogicalSize, PhysicalSize};
 use winit::event::WindowEvent;
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::{Icon, Theme, Window, WindowId};
 
+mod asset;
+mod cli;
+mod scene;
+mod tools;
+mod ui;
 mod vulkan;
+use asset::handle::{Handle, LoadState};
+use asset::import::MeshAsset;
+use asset::loader::LoadProgress;
+use asset::AssetDatabase;
+use cli::CliOptions;
+use scene::{
+  camera::{Projection, Ray},
+  emitter::ParticleEmitter,
+  environment::Background,
+  file::{reflected_entry, set_component_field, SceneError, SceneFile, DEFAULT_SCENE_PATH},
+  guid::Guid,
+  light::{Light, LightKind},
+  mesh::MeshSource,
+  prefab::{apply_to_prefab, create_prefab, instance_root, instantiate, overrides, revert, PrefabInstance, PrefabPart},
+  snapshot::Subtree,
+  transform::{ancestors, parent_of, set_parent, Transform},
+  Scene,
+};
+use tools::camera::{CameraController, CameraDrag};
+use tools::gizmo::{Gizmo, GizmoMode};
+use tools::history::{DespawnEntities, History, RestoreScene, SetComponent, SetField, SetParents, SpawnEntities};
+use ui::dock::user_layout_path;
+use ui::panels::{PanelOutput, Panels, SceneEdit, SceneStats};
+use ui::EditorUi;
+use vulkan::capture::{default_screenshot_path, default_sequence_dir, DEFAULT_SEQUENCE_FRAMES};
+use vulkan::post::PostSettings;
+use vulkan::present::{FrameLimiter, PresentConfig};
+use vulkan::shadows::spot_matrix;
 use vulkan::VulkanApp;
 // use vulkan::create_vk_instance;
 
+/// How often the project is scanned for changed assets.
+const ASSET_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the status bar is redrawn while assets load without continuous redraws.
+const LOADING_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
 fn load_icon() -> Result<Icon, Box<dyn std::error::Error>> {
   let icon_path = include_bytes!("./assets/icon.png");
   let image = image::load_from_memory(icon_path)?;
@@ -24,36 +71,1198 @@ fn load_icon() -> Result<Icon, Box<dyn std::error::Error>> {
 struct App {
   window: Option<Window>,
   vk_app: Option<VulkanApp>,
+  /// Editor overlay, not created in headless runs.
+  ui: Option<EditorUi>,
+  panels: Panels,
+  present_config: PresentConfig,
+  frame_limiter: FrameLimiter,
+  minimized: bool,
+  input: Input,
+  scene: Scene,
+  assets: AssetDatabase,
+  /// Time of the last scan for changed assets.
+  assets_refreshed: Option<Instant>,
+  /// Progress last shown, to redraw when it moves.
+  asset_progress: LoadProgress,
+  /// Handles of the meshes loaded from files, `None` for the files that can't be loaded.
+  mesh_assets: HashMap<PathBuf, Option<Handle<MeshAsset>>>,
+  options: CliOptions,
+  /// Frames rendered so far, only counted in headless runs.
+  frames_rendered: u32,
+  /// Light whose shadow settings the shadow keys edit.
+  selected_light: usize,
+  /// Draws the world bounds of every prop.
+  show_bounds: bool,
+  /// A click is waiting for its object id readback.
+  awaiting_pick: bool,
+  gizmo: Gizmo,
+  history: History,
+  /// Whether the title shows the scene as unsaved.
+  title_dirty: bool,
+  /// A close was refused because of unsaved changes; the next one discards them.
+  close_requested: bool,
+  camera_controller: CameraController,
+  /// Time of the last camera update, to move the fly camera at a fixed speed.
+  last_update: Option<Instant>,
+  post_settings: PostSettings,
+}
+
+impl App {
+  fn apply_present_config(&mut self, event_loop: &ActiveEventLoop, config: PresentConfig) {
+    info!(
+      "[+] present config -> vsync: {:?}, fps cap: {:?}, redraw on change: {}, hdr: {:?}",
+      config.vsync, config.target_fps, config.redraw_on_change, config.hdr.mode
+    );
+
+    self.present_config = config;
+    event_loop.set_control_flow(control_flow(&config));
+
+    if let Some(vk_app) = self.vk_app.as_mut() {
+      vk_app.set_present_config(config);
+    }
+
+    self.request_redraw();
+  }
+
+  /// Reimports the assets whose files changed, at most once per `ASSET_REFRESH_INTERVAL`.
+  fn refresh_assets(&mut self) {
+    if self
+      .assets_refreshed
+      .is_some_and(|t| t.elapsed() < ASSET_REFRESH_INTERVAL)
+    {
+      return;
+    }
+
+    self.assets_refreshed = Some(Instant::now());
+
+    if !self.assets.refresh().is_empty() {
+      self.request_redraw();
+    }
+  }
+
+  /// Swaps the placeholder cube of each mesh loaded from a file for the file's vertices once
+  /// they're imported, and again when it's reimported.
+  fn sync_meshes(&mut self) {
+    for mesh in &mut self.scene.meshes {
+      let MeshSource::File(path) = &mesh.source else {
+        continue;
+      };
+
+      let handle = self.mesh_assets.entry(path.clone()).or_insert_with(|| {
+        let handle = self.assets.load::<MeshAsset>(path);
+        handle
+          .inspect_err(|e| warn!("Can't load mesh {}: {}", path.display(), e))
+          .ok()
+      });
+
+      let Some(handle) = handle.as_ref().filter(|h| h.state() == LoadState::Ready) else {
+        continue;
+      };
+
+      if handle.version() != mesh.revision {
+        let asset = handle.get();
+        mesh.vertices = asset.vertices.clone();
+        mesh.indices = asset.indices.clone();
+        mesh.revision = handle.version();
+      }
+    }
+  }
+
+  fn request_redraw(&self) {
+    if let Some(window) = self.window.as_ref() {
+      window.request_redraw();
+    }
+  }
+
+  fn shutdown(&mut self, event_loop: &ActiveEventLoop) {
+    event_loop.exit();
+
+    if self.ui.is_some() {
+      self.panels.save_layout();
+    }
+
+    if let Some(mut vk_app) = self.vk_app.take() {
+      unsafe { vk_app.destroy() };
+    }
+  }
+
+  /// Renders the next headless frame, capturing the last one, and exits once all are done.
+  fn render_headless(&mut self, event_loop: &ActiveEventLoop) {
+    self.scene.update();
+    self.sync_meshes();
+    self.draw_helpers();
+
+    let (Some(window), Some(vk_app)) = (self.window.as_ref(), self.vk_app.as_mut()) else {
+      return;
+    };
+
+    if self.frames_rendered + 1 == self.options.frames {
+      if let Some(path) = self.options.capture.clone() {
+        vk_app.capture_screenshot(path);
+      }
+    }
+
+    unsafe { vk_app.render(window, &self.scene, &mut self.assets) }.unwrap();
+    self.apply_pick();
+
+    // Los frames con assets a medio cargar no cuentan, la captura sale con todo cargado.
+    if !self.assets.progress().is_loading() {
+      self.frames_rendered += 1;
+    }
+
+    if self.frames_rendered >= self.options.frames {
+      info!("[+] headless -> rendered {} frames", self.frames_rendered);
+      self.shutdown(event_loop);
+    }
+  }
+
+  /// Queues the editor helpers of the next frame: the ground grid, the transform gizmo and the
+  /// selected light.
+  fn draw_helpers(&mut self) {
+    let selected_light = self.selected_light();
+    let Some(vk_app) = self.vk_app.as_mut() else {
+      return;
+    };
+
+    let debug = vk_app.debug_draw();
+    self.scene.grid.draw(debug, &self.scene.camera);
+    self.gizmo.draw(debug, &self.scene);
+
+    if self.show_bounds {
+      for (min, max) in self.scene.props().into_iter().filter_map(|e| self.scene.prop_bounds(e)) {
+        debug.aabb(min, max, [0.95, 0.85, 0.2, 0.8]);
+      }
+    }
+
+    let Some(light) = selected_light.and_then(|e| self.scene.world.get::<Light>(e)) else {
+      return;
+    };
+
+    let color = [light.color[0], light.color[1], light.color[2], 1.0];
+    let anchor = match light.kind {
+      LightKind::Directional => self.scene.camera.target + vec3(0.0, 3.0, 0.0),
+      _ => light.position,
+    };
+
+    match light.kind {
+      LightKind::Point => debug.sphere(anchor, light.range, color),
+      LightKind::Spot { outer_angle, .. } => debug.frustum(spot_matrix(&light, outer_angle).0, color),
+      LightKind::Directional => debug.arrow(anchor, anchor + light.direction * 2.0, color),
+    }
+
+    // Marca en el suelo bajo la luz.
+    let ground = point3(anchor.x, self.scene.grid.height, anchor.z);
+    debug.grid(ground, 4, 0.25, [color[0], color[1], color[2], 0.5]);
+
+    debug.depth_test = false;
+    debug.text3d(anchor + vec3(0.0, 0.4, 0.0), &light.name, 0.25, color);
+    debug.depth_test = true;
+  }
+
+  /// Lays out the editor UI of the next frame, sizes the viewport texture to its tab, triggers
+  /// the actions picked in its menus and applies the edits made in its panels.
+  fn draw_ui(&mut self) {
+    let (Some(ui), Some(window), Some(vk_app)) = (self.ui.as_mut(), self.window.as_ref(), self.vk_app.as_mut()) else {
+      return;
+    };
+
+    let scene = &self.scene;
+    let stats = SceneStats {
+      entities: scene.world.len(),
+      props: scene.props().len(),
+      lights: scene.lights().len(),
+      prefabs: scene.prefabs.len(),
+      selected: scene.selection.len(),
+      undo: self.history.undo_name().map(str::to_string),
+      redo: self.history.redo_name().map(str::to_string),
+      dirty: self.history.is_dirty(),
+      grid: scene.grid.enabled,
+      bounds: self.show_bounds,
+      loading: self.asset_progress,
+    };
+
+    let mut output = PanelOutput::default();
+    vk_app.set_ui(ui.run(window, |context| {
+      output = self.panels.show(context, &self.input, scene, &stats);
+    }));
+
+    // Con la pestaña oculta se queda el ultimo tamaño.
+    ui.set_viewport(output.viewport);
+
+    if let Some([_, _, width, height]) = ui.viewport() {
+      vk_app.set_viewport(Some([width.round() as u32, height.round() as u32]));
+    }
+
+    // Se procesan en `about_to_wait`, como las teclas.
+    for action in output.actions {
+      self.input.trigger(action);
+    }
+
+    if !output.edits.is_empty() {
+      self.apply_edits(output.edits);
+      self.request_redraw();
+    }
+  }
+
+  /// Applies the changes asked for from the outliner and the inspector.
+  fn apply_edits(&mut self, edits: Vec<SceneEdit>) {
+    for edit in edits {
+      match edit {
+        SceneEdit::Select { entity, additive } => {
+          match additive {
+            true => self.scene.toggle_selected(entity),
+            false => self.scene.select(Some(entity)),
+          }
+
+          info!("[+] selection -> {:?}", self.scene.selection);
+        }
+        SceneEdit::SetParent { entity, parent } => {
+          let name = if parent.is_some() { "Parent" } else { "Clear Parent" };
+          self.set_parents(name, vec![entity], parent);
+        }
+        SceneEdit::SetField { entity, field, value } => self.set_field(entity, field, value),
+      }
+    }
+  }
+
+  /// Sets one component field from the inspector, recorded in the undo history.
+  fn set_field(&mut self, entity: Entity, field: String, value: Value) {
+    let (Some(guid), Result::Ok(entry)) = (
+      self.scene.world.get::<Guid>(entity).map(|g| *g),
+      reflected_entry(&self.scene, entity),
+    ) else {
+      return;
+    };
+
+    let Some(before) = entry.get_path(&field).ok().filter(|before| *before != value) else {
+      return;
+    };
+
+    if let Err(error) = set_component_field(&mut self.scene, entity, &field, &value) {
+      return info!("[INFO]: inspector -> {}", error);
+    }
+
+    info!("[+] history -> {} = {}", field, value);
+    self.history.push(Box::new(SetField {
+      entity: guid,
+      field,
+      before,
+      after: value,
+    }));
+  }
+
+  /// Viewport the scene is rendered to, in physical pixels: x, y, width and height. The UI's
+  /// viewport tab, or the whole window without the UI.
+  fn viewport_rect(&self) -> Option<[f32; 4]> {
+    match self.ui.as_ref() {
+      Some(ui) => ui.viewport(),
+      None => {
+        let size = self.window.as_ref()?.inner_size();
+        Some([0.0, 0.0, size.width as f32, size.height as f32])
+      }
+    }
+  }
+
+  /// Cursor relative to the viewport, `None` while it's outside.
+  fn viewport_cursor(&self) -> Option<[f32; 2]> {
+    let [cursor_x, cursor_y] = self.input.cursor()?;
+    let [x, y, width, height] = self.viewport_rect()?;
+    let cursor = [cursor_x - x, cursor_y - y];
+
+    let inside = (0.0..width).contains(&cursor[0]) && (0.0..height).contains(&cursor[1]);
+    inside.then_some(cursor)
+  }
+
+  /// Cursor ray through the viewport, `None` while the cursor is outside it.
+  fn cursor_ray(&self) -> Option<(Ray, [f32; 2])> {
+    let cursor = self.viewport_cursor()?;
+    let [_, _, width, height] = self.viewport_rect()?;
+    let ray = self.scene.camera.ray(cursor, [width, height]);
+
+    Some((ray, cursor))
+  }
+
+  /// Runs the actions of the input received since the last call, once per event loop iteration.
+  fn process_input(&mut self, event_loop: &ActiveEventLoop) {
+    self.handle_mouse_presses();
+    self.handle_cursor_moved();
+    self.handle_mouse_releases();
+    self.handle_scroll();
+
+    let input = &self.input;
+    let movement = [input.axis("fly_forward"), input.axis("fly_right"), input.axis("fly_up")];
+    self.camera_controller.set_movement(movement);
+
+    if self.camera_controller.is_animating() {
+      self.request_redraw();
+    }
+
+    if self.handle_camera_actions()
+      | self.handle_gizmo_actions()
+      | self.handle_hierarchy_actions()
+      | self.handle_prefab_actions()
+      | self.handle_delete_actions()
+      | self.handle_scene_actions()
+      | self.handle_shadow_actions()
+      | self.handle_post_actions()
+      | self.handle_environment_actions()
+      | self.handle_render_actions()
+    {
+      self.request_redraw();
+    }
+
+    self.update_title();
+    self.handle_present_actions(event_loop);
+  }
+
+  /// Alt + left mouse orbits, right mouse flies the 3D camera and pans the 2D one and middle
+  /// mouse pans both. Otherwise, clicking a gizmo handle starts dragging it, clicking a prop
+  /// selects it, shift-click adds or removes it and clicking the background clears the selection.
+  fn handle_mouse_presses(&mut self) {
+    let perspective = self.scene.camera.projection == Projection::Perspective;
+
+    if self.input.pressed("camera_orbit") {
+      if perspective {
+        self.camera_controller.begin_drag(CameraDrag::Orbit);
+      }
+
+      return;
+    }
+
+    for (action, drag) in [
+      (
+        "camera_fly",
+        if perspective { CameraDrag::Fly } else { CameraDrag::Pan },
+      ),
+      ("camera_pan", CameraDrag::Pan),
+    ] {
+      if self.input.pressed(action) {
+        self.camera_controller.begin_drag(drag);
+        self.request_redraw();
+      }
+    }
+
+    if !self.input.pressed("select") {
+      return;
+    }
+
+    if let Some((ray, cursor)) = self.cursor_ray() {
+      if self.gizmo.begin_drag(&self.scene, ray, cursor) {
+        return self.request_redraw();
+      }
+    }
+
+    let (Some([x, y]), Some(vk_app)) = (self.viewport_cursor(), self.vk_app.as_mut()) else {
+      return;
+    };
+
+    vk_app.request_pick(x as u32, y as u32, self.input.held("multi_select"));
+    self.awaiting_pick = true;
+    self.request_redraw();
+  }
+
+  fn handle_cursor_moved(&mut self) {
+    let delta = [self.input.axis("pointer_x"), self.input.axis("pointer_y")];
+
+    if delta == [0.0, 0.0] {
+      return;
+    }
+
+    if let (Some(_), Some([_, _, width, height])) = (self.camera_controller.drag(), self.viewport_rect()) {
+      let viewport = [width, height];
+      self
+        .camera_controller
+        .cursor_moved(&mut self.scene.camera, delta, viewport);
+      return self.request_redraw();
+    }
+
+    let Some((ray, cursor)) = self.cursor_ray() else {
+      return;
+    };
+
+    if self.gizmo.is_dragging() {
+      let snap = self.gizmo.snapping.enabled != self.input.held("invert_snapping");
+      self.gizmo.drag(&mut self.scene, ray, cursor, snap);
+      self.request_redraw();
+    } else if self.gizmo.hover(&self.scene, ray) {
+      self.request_redraw();
+    }
+  }
+
+  /// Ends camera drags and records a finished gizmo drag in the undo history.
+  fn handle_mouse_releases(&mut self) {
+    let perspective = self.scene.camera.projection == Projection::Perspective;
+
+    for (action, drag) in [
+      ("camera_orbit", CameraDrag::Orbit),
+      (
+        "camera_fly",
+        if perspective { CameraDrag::Fly } else { CameraDrag::Pan },
+      ),
+      ("camera_pan", CameraDrag::Pan),
+    ] {
+      if self.input.released(action) {
+        self.camera_controller.end_drag(drag);
+        self.request_redraw();
+      }
+    }
+
+    if !self.input.released("select") || !self.gizmo.is_dragging() {
+      return;
+    }
+
+    if let Some(command) = self.gizmo.end_drag(&self.scene) {
+      info!("[+] history -> {} {} props", command.name, command.changes.len());
+      self.history.push(Box::new(command));
+    }
+
+    self.request_redraw();
+  }
+
+  fn handle_scroll(&mut self) {
+    let lines = self.input.axis("zoom");
+
+    if lines == 0.0 {
+      return;
+    }
+
+    // Punto del plano de la camara bajo el cursor, para el zoom 2D.
+    let cursor = self.cursor_ray().map(|(ray, _)| ray.origin);
+    let camera = &mut self.scene.camera;
+    self
+      .camera_controller
+      .scroll(camera, &mut self.scene.view, lines, cursor);
+    self.request_redraw();
+  }
+
+  /// Moves the fly camera with the time since the last frame; `camera_fast` and `camera_slow`
+  /// (Shift / Ctrl) change its speed.
+  fn update_camera(&mut self) {
+    let now = Instant::now();
+    let delta_time = self
+      .last_update
+      .replace(now)
+      .map_or(0.0, |last| (now - last).as_secs_f32().min(0.1));
+
+    let speed_scale = match (self.input.held("camera_fast"), self.input.held("camera_slow")) {
+      (true, _) => 3.0,
+      (_, true) => 0.25,
+      _ => 1.0,
+    };
+
+    self
+      .camera_controller
+      .update(&mut self.scene.camera, &self.scene.view, speed_scale, delta_time);
+  }
+
+  /// Frames the selection and switches between the 3D and the 2D view.
+  fn handle_camera_actions(&mut self) -> bool {
+    let mut changed = false;
+
+    if self.input.pressed("frame_selected") {
+      if let Some((min, max)) = self.scene.selection_bounds() {
+        self.camera_controller.frame(&self.scene.camera, min, max);
+        changed = true;
+      }
+    }
+
+    if self.input.pressed("toggle_2d") {
+      self
+        .camera_controller
+        .toggle_2d(&self.scene.camera, &mut self.scene.view);
+      changed = true;
+    }
+
+    changed
+  }
+
+  /// Gizmo mode, space, pivot and snapping, plus cancelling a drag and undo / redo. The mode keys
+  /// are ignored while dragging, and while flying, where they move the camera.
+  fn handle_gizmo_actions(&mut self) -> bool {
+    let input = &self.input;
+    let gizmo = &mut self.gizmo;
+    let undo = input.pressed("undo");
+
+    if undo || input.pressed("redo") {
+      gizmo.cancel_drag(&mut self.scene);
+
+      let name = if undo {
+        self.history.undo(&mut self.scene)
+      } else {
+        self.history.redo(&mut self.scene)
+      };
+
+      let verb = if undo { "undo" } else { "redo" };
+
+      match name {
+        Some(name) => info!("[+] history -> {} {}", verb, name),
+        None => info!("[INFO]: history -> nothing to {}", verb),
+      }
+
+      return true;
+    }
+
+    if input.pressed("cancel") {
+      gizmo.cancel_drag(&mut self.scene);
+    } else if gizmo.is_dragging()
+      || self.camera_controller.drag() == Some(CameraDrag::Fly)
+      || !apply_actions(
+        input,
+        gizmo,
+        &[
+          ("gizmo_translate", |g| g.mode = GizmoMode::Translate),
+          ("gizmo_rotate", |g| g.mode = GizmoMode::Rotate),
+          ("gizmo_scale", |g| g.mode = GizmoMode::Scale),
+          ("gizmo_space", |g| g.space = g.space.next()),
+          ("gizmo_pivot", |g| g.pivot = g.pivot.next()),
+          ("toggle_snapping", |g| g.snapping.enabled = !g.snapping.enabled),
+        ],
+      )
+    {
+      return false;
+    }
+
+    info!(
+      "[INFO]: gizmo -> {:?}, {:?} space, {:?} pivot, snapping {}",
+      gizmo.mode, gizmo.space, gizmo.pivot, gizmo.snapping.enabled
+    );
+    true
+  }
+
+  /// Updates the selection once the id under the clicked pixel has been read back.
+  fn apply_pick(&mut self) {
+    let Some(pick) = self.vk_app.as_mut().and_then(|vk_app| vk_app.take_pick()) else {
+      // La lectura llega uno o dos frames despues: seguir dibujando hasta tenerla.
+      if self.awaiting_pick {
+        self.request_redraw();
+      }
+
+      return;
+    };
+
+    self.awaiting_pick = false;
+
+    let entity = pick.prop.and_then(|index| self.scene.world.entity_at(index));
+
+    match (entity, pick.additive) {
+      (Some(entity), true) => self.scene.toggle_selected(entity),
+      (entity, false) => self.scene.select(entity),
+      (None, true) => return,
+    }
+
+    info!("[+] selection -> {:?}", self.scene.selection);
+    self.request_redraw();
+  }
+
+  /// Attaches the selected props to the primary selection, or detaches them from their parents,
+  /// keeping their world transforms. Recorded in the undo history.
+  fn handle_hierarchy_actions(&mut self) -> bool {
+    let (name, parent) = if self.input.pressed("parent_to_primary") {
+      let Some(&primary) = self.scene.selection.last() else {
+        return false;
+      };
+
+      ("Parent", Some(primary))
+    } else if self.input.pressed("clear_parent") {
+      ("Clear Parent", None)
+    } else {
+      return false;
+    };
+
+    if self.gizmo.is_dragging() {
+      return false;
+    }
+
+    let selection = self.scene.selection.clone();
+    self.set_parents(name, selection, parent)
+  }
+
+  /// Parents `entities` to `parent`, or detaches them, keeping their world transforms. Recorded
+  /// in the undo history as `name`.
+  fn set_parents(&mut self, name: &'static str, entities: Vec<Entity>, parent: Option<Entity>) -> bool {
+    let world = &mut self.scene.world;
+    let guid = |world: &sagitario_ecs::World, e| world.get::<Guid>(e).map(|g| *g);
+    let mut changes = vec![];
+
+    for entity in entities.into_iter().filter(|&e| Some(e) != parent) {
+      let before = parent_of(world, entity);
+
+      if let Err(error) = set_parent(world, entity, parent) {
+        info!("[INFO]: hierarchy -> {}", error);
+      } else if let (true, Some(entity)) = (before != parent, guid(world, entity)) {
+        changes.push((
+          entity,
+          before.and_then(|p| guid(world, p)),
+          parent.and_then(|p| guid(world, p)),
+        ));
+      }
+    }
+
+    if changes.is_empty() {
+      return false;
+    }
+
+    info!("[+] hierarchy -> {} {} props", name, changes.len());
+    self.history.push(Box::new(SetParents { name, changes }));
+    true
+  }
+
+  /// Prefabs from the primary selection: makes one of its subtree, adds another instance of its
+  /// prefab next to it, or applies its overrides to the prefab or reverts them.
+  fn handle_prefab_actions(&mut self) -> bool {
+    let actions = ["make_prefab", "instantiate_prefab", "apply_prefab", "revert_prefab"];
+    let action = actions.into_iter().find(|&a| self.input.pressed(a));
+
+    let (Some(action), Some(&primary)) = (action, self.scene.selection.last()) else {
+      return false;
+    };
+
+    if self.gizmo.is_dragging() {
+      return false;
+    }
+
+    let scene = &mut self.scene;
+    let root = instance_root(scene, primary).ok_or(SceneError::NotAnInstance(primary));
+    let before = SceneFile::from_scene(scene);
+
+    let result = match action {
+      "make_prefab" => {
+        let name = format!("Prefab {}", scene.prefabs.len() + 1);
+        create_prefab(scene, primary, name.clone()).map(|_| ("Make Prefab", format!("created {}", name)))
+      }
+      "instantiate_prefab" => root
+        .and_then(|root| {
+          let prefab = scene.world.get::<PrefabInstance>(root).map(|i| i.prefab);
+          let prefab = prefab.ok_or(SceneError::NotAnInstance(root))?;
+          let overrides = overrides(scene, root)?;
+          instantiate(scene, prefab, &overrides)
+        })
+        .map(|instance| {
+          // Junto a la original para que no queden superpuestas.
+          if let Some(mut transform) = scene.world.get_mut::<Transform>(instance) {
+            transform.translation.x += 2.0;
+          }
+
+          scene.select(Some(instance));
+          ("Instantiate", "instantiated".to_string())
+        }),
+      "apply_prefab" => root
+        .and_then(|root| apply_to_prefab(scene, root))
+        .map(|count| ("Apply Prefab", format!("applied {} overrides", count))),
+      _ => root
+        .and_then(|root| revert(scene, root))
+        .map(|()| ("Revert Prefab", "reverted".to_string())),
+    };
+
+    let (name, message) = match result {
+      Result::Ok(result) => result,
+      Err(error) => {
+        info!("[INFO]: prefab -> {}", error);
+        return false;
+      }
+    };
+
+    // Una instancia nueva es solo su subarbol; el resto toca todas las instancias del prefab.
+    if action == "instantiate_prefab" {
+      let subtrees = scene
+        .selection
+        .iter()
+        .filter_map(|&e| Subtree::capture(scene, e))
+        .collect();
+      self.history.push(Box::new(SpawnEntities { name, subtrees }));
+    } else {
+      let after = SceneFile::from_scene(scene);
+      self.history.push(Box::new(RestoreScene { name, before, after }));
+    }
+
+    info!("[+] prefab -> {}", message);
+    true
+  }
+
+  /// Deletes the selected entities with everything below them. Parts of a prefab instance other
+  /// than its root can't be deleted, the prefab would bring them back on load.
+  fn handle_delete_actions(&mut self) -> bool {
+    if !self.input.pressed("delete_selected") || self.gizmo.is_dragging() {
+      return false;
+    }
+
+    let scene = &mut self.scene;
+    let mut subtrees = vec![];
+
+    for &entity in &scene.selection {
+      if ancestors(&scene.world, entity).any(|a| scene.is_selected(a)) {
+        continue;
+      }
+
+      if scene.world.has::<PrefabPart>(entity) && !scene.world.has::<PrefabInstance>(entity) {
+        info!("[INFO]: delete -> {}", SceneError::PartOfInstance(entity));
+        continue;
+      }
+
+      subtrees.extend(Subtree::capture(scene, entity));
+    }
+
+    if subtrees.is_empty() {
+      return false;
+    }
+
+    for subtree in &subtrees {
+      subtree.despawn(scene);
+    }
+
+    info!("[+] history -> Delete {} entities", subtrees.len());
+    self.history.push(Box::new(DespawnEntities {
+      name: "Delete",
+      subtrees,
+    }));
+    true
+  }
+
+  /// Writes the scene to `--scene`, or to `DEFAULT_SCENE_PATH` without one.
+  fn save_scene(&mut self) {
+    let path = self.options.scene.clone().unwrap_or_else(|| DEFAULT_SCENE_PATH.into());
+
+    match self.scene.save(&path) {
+      Result::Ok(()) => {
+        info!("[+] scene -> saved {}", path.display());
+        self.history.mark_saved();
+      }
+      Err(error) => warn!("Can't save the scene to {}: {}", path.display(), error),
+    }
+  }
+
+  /// Marks the window title while the scene has unsaved changes.
+  fn update_title(&mut self) {
+    let dirty = self.history.is_dirty();
+
+    if dirty == self.title_dirty {
+      return;
+    }
+
+    self.title_dirty = dirty;
+    self.close_requested = false;
+
+    if let Some(window) = self.window.as_ref() {
+      window.set_title(if dirty {
+        "Sagitario Engine *"
+      } else {
+        "Sagitario Engine"
+      });
+    }
+  }
+
+  /// Particles, new lights, the grid and the prop bounds.
+  fn handle_scene_actions(&mut self) -> bool {
+    let mut changed = false;
+
+    if self.input.pressed("save_scene") {
+      self.save_scene();
+    }
+
+    let input = &self.input;
+
+    if input.pressed("toggle_particles") {
+      let mut query = self.scene.world.query::<(Entity, &Guid, &ParticleEmitter)>();
+      let emitters = query.iter().map(|(e, g, p)| (e, *g, p.clone())).collect::<Vec<_>>();
+      drop(query);
+      self.history.begin("Toggle Particles");
+
+      for (emitter, entity, before) in emitters {
+        let after = ParticleEmitter {
+          enabled: !before.enabled,
+          ..before.clone()
+        };
+
+        self.scene.world.insert(emitter, after.clone());
+        self.history.push(Box::new(SetComponent {
+          name: "Toggle Particles",
+          entity,
+          before,
+          after,
+        }));
+      }
+
+      self.history.commit();
+      changed = true;
+    }
+
+    if input.pressed("add_light") {
+      let light = self.scene.spawn(Light {
+        position: self.scene.camera.target + vec3(0.0, 1.0, 0.0),
+        ..Default::default()
+      });
+      let subtrees = Subtree::capture(&self.scene, light).into_iter().collect();
+      self.history.push(Box::new(SpawnEntities {
+        name: "Add Light",
+        subtrees,
+      }));
+      changed = true;
+    }
+
+    if input.pressed("toggle_grid") {
+      self.scene.grid.enabled = !self.scene.grid.enabled;
+      changed = true;
+    }
+
+    if input.pressed("toggle_bounds") {
+      self.show_bounds = !self.show_bounds;
+      changed = true;
+    }
+
+    changed
+  }
+
+  /// Entity of the `selected_light`-th light.
+  fn selected_light(&self) -> Option<Entity> {
+    self.scene.lights().get(self.selected_light).copied()
+  }
+
+  fn log_selected_light(&self) {
+    if let Some(light) = self.selected_light().and_then(|e| self.scene.world.get::<Light>(e)) {
+      info!(
+        "[+] light {} `{}` -> shadows: {}, resolution: {}, depth bias: {}, slope bias: {}, normal bias: {}, pcf radius: {}",
+        self.selected_light,
+        light.name,
+        light.shadows.enabled,
+        light.shadows.resolution,
+        light.shadows.depth_bias,
+        light.shadows.slope_bias,
+        light.shadows.normal_bias,
+        light.shadows.pcf_radius
+      );
+    }
+  }
+
+  /// Selects a light and edits its shadows: toggle, depth bias and PCF radius.
+  fn handle_shadow_actions(&mut self) -> bool {
+    let input = &self.input;
+    let count = self.scene.lights().len().max(1);
+    let mut changed = false;
+
+    if input.pressed("previous_light") {
+      self.selected_light = (self.selected_light + count - 1) % count;
+      changed = true;
+    }
+
+    if input.pressed("next_light") {
+      self.selected_light = (self.selected_light + 1) % count;
+      changed = true;
+    }
+
+    let selected = self.selected_light();
+    let entity = selected.and_then(|e| self.scene.world.get::<Guid>(e).map(|g| *g));
+
+    if let (Some(light), Some(entity)) = (selected, entity) {
+      let before = self.scene.world.get::<Light>(light).unwrap().clone();
+      let mut after = before.clone();
+      let edited = apply_actions(
+        input,
+        &mut after.shadows,
+        &[
+          ("toggle_shadows", |s| s.enabled = !s.enabled),
+          ("shadow_bias_up", |s| s.depth_bias += 0.25),
+          ("shadow_bias_down", |s| s.depth_bias = (s.depth_bias - 0.25).max(0.0)),
+          ("next_pcf_radius", |s| s.pcf_radius = (s.pcf_radius + 1) % 4),
+        ],
+      );
+
+      if edited {
+        self.scene.world.insert(light, after.clone());
+        self.history.push(Box::new(SetComponent {
+          name: "Shadows",
+          entity,
+          before,
+          after,
+        }));
+      }
+
+      changed |= edited;
+    }
+
+    if changed {
+      self.log_selected_light();
+    }
+
+    changed
+  }
+
+  /// Bloom, FXAA, vignette and color grading toggles, the tonemapper, the exposure and the bloom
+  /// intensity.
+  fn handle_post_actions(&mut self) -> bool {
+    let changed = apply_actions(
+      &self.input,
+      &mut self.post_settings,
+      &[
+        ("toggle_bloom", |p| p.bloom.enabled = !p.bloom.enabled),
+        ("toggle_fxaa", |p| p.fxaa.enabled = !p.fxaa.enabled),
+        ("toggle_vignette", |p| p.vignette.enabled = !p.vignette.enabled),
+        ("toggle_grading", |p| p.grade.enabled = !p.grade.enabled),
+        ("next_tonemapper", |p| p.tonemapper = p.tonemapper.next()),
+        ("exposure_down", |p| p.exposure_ev -= 0.5),
+        ("exposure_up", |p| p.exposure_ev += 0.5),
+        ("bloom_down", |p| {
+          p.bloom.intensity = (p.bloom.intensity - 0.02).max(0.0)
+        }),
+        ("bloom_up", |p| p.bloom.intensity += 0.02),
+      ],
+    );
+
+    if let (true, Some(vk_app)) = (changed, self.vk_app.as_mut()) {
+      vk_app.set_post_settings(self.post_settings.clone());
+    }
+
+    changed
+  }
+
+  /// Cycles gradient, solid and HDRI backgrounds and rotates the environment.
+  fn handle_environment_actions(&mut self) -> bool {
+    let input = &self.input;
+    let environment = &mut self.scene.environment;
+
+    let mut changed = apply_actions(
+      input,
+      environment,
+      &[
+        ("rotate_environment", |e| {
+          e.rotation = (e.rotation + 15.0).rem_euclid(360.0)
+        }),
+        ("rotate_environment_back", |e| {
+          e.rotation = (e.rotation - 15.0).rem_euclid(360.0)
+        }),
+      ],
+    );
+
+    if input.pressed("next_background") {
+      environment.background = match (&environment.background, &self.options.environment) {
+        (Background::Gradient { .. }, _) => Background::Solid([0.05, 0.05, 0.06]),
+        (Background::Solid(_), Some(path)) => Background::Hdri(path.clone()),
+        _ => Background::default(),
+      };
+      changed = true;
+    }
+
+    if changed {
+      info!(
+        "[INFO]: environment -> {:?}, rotation {}",
+        environment.background, environment.rotation
+      );
+    }
+
+    changed
+  }
+
+  /// View modes, GPU culling, screenshots and frame sequences.
+  fn handle_render_actions(&mut self) -> bool {
+    let input = &self.input;
+
+    let Some(vk_app) = self.vk_app.as_mut() else {
+      return false;
+    };
+
+    let mut changed = false;
+
+    if input.pressed("next_view_mode") {
+      vk_app.set_view_mode(vk_app.view_mode().next());
+      changed = true;
+    }
+
+    if input.pressed("toggle_gpu_culling") {
+      vk_app.set_gpu_culling(!vk_app.gpu_culling());
+      changed = true;
+    }
+
+    if input.pressed("screenshot") {
+      vk_app.capture_screenshot(default_screenshot_path());
+      changed = true;
+    }
+
+    if input.pressed("record_sequence") {
+      vk_app.record_frames(default_sequence_dir(), DEFAULT_SEQUENCE_FRAMES);
+      changed = true;
+    }
+
+    changed
+  }
+
+  /// Vsync, FPS cap, redraw on change and HDR output.
+  fn handle_present_actions(&mut self, event_loop: &ActiveEventLoop) {
+    let mut config = self.present_config;
+
+    if apply_actions(
+      &self.input,
+      &mut config,
+      &[
+        ("next_vsync", |c| c.vsync = c.vsync.next()),
+        ("next_fps_cap", |c| c.target_fps = c.next_fps_cap()),
+        ("toggle_redraw_on_change", |c| c.redraw_on_change = !c.redraw_on_change),
+        ("next_hdr_mode", |c| c.hdr.mode = c.hdr.mode.next()),
+      ],
+    ) {
+      self.apply_present_config(event_loop, config);
+    }
+  }
+}
+
+/// Input action names and what each one does to its target.
+type Actions<'a, T> = &'a [(&'a str, fn(&mut T))];
+
+/// Applies to `target` each action of `actions` pressed this frame; returns whether any was.
+fn apply_actions<T>(input: &Input, target: &mut T, actions: Actions<T>) -> bool {
+  let mut applied = false;
+
+  for (action, apply) in actions {
+    if input.pressed(action) {
+      apply(target);
+      applied = true;
+    }
+  }
+
+  applied
+}
+
+fn control_flow(config: &PresentConfig) -> ControlFlow {
+  if config.redraw_on_change {
+    ControlFlow::Wait
+  } else {
+    ControlFlow::Poll
+  }
 }
 
 impl ApplicationHandler for App {
   fn resumed(&mut self, event_loop: &ActiveEventLoop) {
     let icon = load_icon().unwrap();
 
-    let custom_window = Window::default_attributes()
+    let mut custom_window = Window::default_attributes()
       .with_theme(Some(Theme::Dark))
       .with_title("Sagitario Engine")
       .with_inner_size(LogicalSize::new(800, 600))
       .with_window_icon(Some(icon))
-      .with_active(true);
+      .with_visible(!self.options.headless)
+      .with_active(!self.options.headless);
+
+    if let Some((width, height)) = self.options.size {
+      custom_window = custom_window.with_inner_size(PhysicalSize::new(width, height));
+    }
 
     self.window = Some(event_loop.create_window(custom_window).unwrap());
-    self.vk_app = Some(unsafe { VulkanApp::create(self.window.as_ref().unwrap()) }.unwrap());
+
+    let mut vk_app = unsafe { VulkanApp::create(self.window.as_ref().unwrap(), self.present_config) }.unwrap();
+    vk_app.set_view_mode(self.options.view_mode);
+    vk_app.set_post_settings(self.post_settings.clone());
+
+    if !self.options.headless {
+      self.ui = Some(EditorUi::new(self.window.as_ref().unwrap(), vk_app.max_texture_side()));
+
+      // El navegador de assets empieza en la carpeta del proyecto.
+      let assets = self
+        .options
+        .project_dir()
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+      self.panels = Panels::new(user_layout_path(), assets);
+    }
+
+    self.vk_app = Some(vk_app);
+
+    if self.options.headless {
+      event_loop.set_control_flow(ControlFlow::Poll);
+    } else {
+      event_loop.set_control_flow(control_flow(&self.present_config));
+    }
+  }
+
+  fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+    self.process_input(event_loop);
+    self.input.end_frame();
+
+    // Una ventana oculta no siempre recibe RedrawRequested.
+    if self.options.headless {
+      return self.render_headless(event_loop);
+    }
+
+    self.refresh_assets();
+
+    // Lo que termina de cargar se ve en la barra de estado y en el viewport.
+    let progress = self.assets.progress();
+
+    if progress != self.asset_progress {
+      self.asset_progress = progress;
+      self.request_redraw();
+    }
+
+    // Sin redibujos continuos hay que despertar para buscar cambios en los assets.
+    if self.present_config.redraw_on_change {
+      let next = match progress.is_loading() {
+        true => Instant::now() + LOADING_REDRAW_INTERVAL,
+        false => self.assets_refreshed.unwrap_or_else(Instant::now) + ASSET_REFRESH_INTERVAL,
+      };
+      event_loop.set_control_flow(ControlFlow::WaitUntil(next));
+    }
+
+    let capturing = self.vk_app.as_ref().is_some_and(|a| a.is_capturing());
+
+    if !self.present_config.redraw_on_change || capturing {
+      self.request_redraw();
+    }
   }
 
   fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
+    // Lo que cae sobre la UI no llega al viewport.
+    if let Some(ui) = self.ui.as_mut() {
+      let response = ui.handle(&event);
+
+      if response.repaint {
+        self.request_redraw();
+      }
+
+      if response.consumed {
+        return;
+      }
+    }
+
+    // La entrada se acumula aqui y las acciones se procesan en `about_to_wait`.
+    if let Some(event) = platform::from_window_event(&event) {
+      return self.input.handle(&event);
+    }
+
     match event {
       WindowEvent::CloseRequested => {
+        // El primer cierre con cambios sin guardar solo avisa.
+        if self.history.is_dirty() && !self.close_requested {
+          warn!("The scene has unsaved changes: save it with Ctrl+S or close again to discard them");
+          self.close_requested = true;
+          return;
+        }
+
         print!("The close button was pressed, stopping");
-        event_loop.exit();
-        unsafe {
-          self.vk_app.as_mut().unwrap().destroy();
+        self.shutdown(event_loop);
+      }
+      WindowEvent::Resized(size) => {
+        self.minimized = size.width == 0 || size.height == 0;
+
+        if let Some(vk_app) = self.vk_app.as_mut() {
+          vk_app.resized = true;
         }
+
+        self.request_redraw();
       }
-      WindowEvent::RedrawRequested => {
-        self.window.as_ref().unwrap().request_redraw();
+      WindowEvent::RedrawRequested if !self.minimized && !event_loop.exiting() && !self.options.headless => {
+        self.frame_limiter.wait(self.present_config.target_fps);
+
+        self.update_camera();
+        self.scene.update();
+        self.sync_meshes();
+        self.draw_helpers();
+        self.draw_ui();
+
+        let window = self.window.as_ref().unwrap();
+        let vk_app = self.vk_app.as_mut().unwrap();
+        unsafe { vk_app.render(window, &self.scene, &mut self.assets) }.unwrap();
+        self.apply_pick();
 
-        unsafe { self.vk_app.as_mut().unwrap().render(self.window.as_ref().unwrap()) }.unwrap()
+        if self.camera_controller.is_animating() || self.ui.as_ref().is_some_and(|ui| ui.needs_repaint()) {
+          self.request_redraw();
+        }
       }
       _ => (),
     }
@@ -61,16 +1270,46 @@ impl ApplicationHandler for App {
 }
 
 fn main() -> Result<()> {
-  pretty_env_logger::init();
+  ui::console::init_logger();
 
   // win
 
   let event_loop = EventLoop::new().unwrap();
-  event_loop.set_control_flow(ControlFlow::Poll);
 
-  event_loop.set_control_flow(ControlFlow::Wait);
+  let options = CliOptions::from_env()?;
+  let mut post_settings = PostSettings::default();
+
+  if let Some(lut) = options.lut.clone() {
+    post_settings.grade.enabled = true;
+    post_settings.grade.lut = Some(lut);
+  }
+
+  let mut scene = match options.scene.as_ref().filter(|path| path.exists()) {
+    Some(path) => Scene::load(path).with_context(|| format!("Can't load scene {}", path.display()))?,
+    None => Scene::default(),
+  };
+
+  if let Some(path) = options.environment.clone() {
+    scene.environment.background = Background::Hdri(path);
+  }
+
+  let mut input_map = InputMap::from_ron(include_str!("./assets/input.ron"))?;
+
+  if let Some(path) = options.input.as_ref() {
+    input_map.merge(InputMap::load(path).with_context(|| format!("Can't load input config {}", path.display()))?);
+  }
+
+  let assets = options.project_dir().map(AssetDatabase::open).unwrap_or_default();
 
-  let mut app = App::default();
+  let mut app = App {
+    assets,
+    options,
+    post_settings,
+    scene,
+    input: Input::new(input_map),
+    ..Default::default()
+  };
+  app.history.limit = app.options.history_limit;
   event_loop
     .run_app(&mut app)
     .expect("Error while running Sagitario Engine");
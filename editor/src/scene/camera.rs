@@ -0,0 +1,143 @@
+use cgmath::{
+  ortho, perspective, point3, vec3, vec4, Angle, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix,
+  Vector3,
+};
+use serde::{Deserialize, Serialize};
+
+/// Corrige el clip space de OpenGL (que asume cgmath) al de Vulkan: Y invertida y Z en [0, 1].
+#[rustfmt::skip]
+pub const VULKAN_CLIP_CORRECTION: Matrix4<f32> = Matrix4::new(
+  1.0,  0.0, 0.0, 0.0,
+  0.0, -1.0, 0.0, 0.0,
+  0.0,  0.0, 0.5, 0.0,
+  0.0,  0.0, 0.5, 1.0,
+);
+
+/// Half line from `origin` along the unit vector `direction`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+  pub origin: Point3<f32>,
+  pub direction: Vector3<f32>,
+}
+
+impl Ray {
+  pub fn at(&self, t: f32) -> Point3<f32> {
+    self.origin + self.direction * t
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+  /// Uses `Camera::fov_y`.
+  Perspective,
+  /// 2D view, `height` world units from the bottom to the top of the viewport.
+  Orthographic { height: f32 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+  pub position: Point3<f32>,
+  pub target: Point3<f32>,
+  pub up: Vector3<f32>,
+  pub fov_y: Deg<f32>,
+  pub near: f32,
+  pub far: f32,
+  pub projection: Projection,
+}
+
+impl Default for Camera {
+  fn default() -> Self {
+    Self {
+      position: point3(9.0, 6.0, 9.0),
+      target: point3(0.0, 0.5, 0.0),
+      up: vec3(0.0, 1.0, 0.0),
+      fov_y: Deg(60.0),
+      near: 0.1,
+      far: 500.0,
+      projection: Projection::Perspective,
+    }
+  }
+}
+
+impl Camera {
+  pub fn view(&self) -> Matrix4<f32> {
+    Matrix4::look_at_rh(self.position, self.target, self.up)
+  }
+
+  pub fn projection(&self, aspect: f32) -> Matrix4<f32> {
+    self.projection_range(aspect, self.near, self.far)
+  }
+
+  /// Projection of the view depths between `near` and `far` only, e.g. for a shadow cascade.
+  pub fn projection_range(&self, aspect: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let projection = match self.projection {
+      Projection::Perspective => perspective(self.fov_y, aspect, near, far),
+      Projection::Orthographic { height } => {
+        let (half_width, half_height) = (height * aspect * 0.5, height * 0.5);
+        ortho(-half_width, half_width, -half_height, half_height, near, far)
+      }
+    };
+
+    VULKAN_CLIP_CORRECTION * projection
+  }
+
+  pub fn forward(&self) -> Vector3<f32> {
+    (self.target - self.position).normalize()
+  }
+
+  /// Height in world units the viewport covers at `point`.
+  pub fn view_height_at(&self, point: Point3<f32>) -> f32 {
+    match self.projection {
+      Projection::Perspective => {
+        let depth = (point - self.position).dot(self.forward()).max(self.near);
+        2.0 * depth * (self.fov_y / 2.0).tan()
+      }
+      Projection::Orthographic { height } => height,
+    }
+  }
+
+  /// Ray from the near plane through the pixel `cursor` of a `viewport` sized image, both in
+  /// physical pixels from the top left corner.
+  pub fn ray(&self, cursor: [f32; 2], viewport: [f32; 2]) -> Ray {
+    let aspect = viewport[0] / viewport[1].max(1.0);
+    let inverse = (self.projection(aspect) * self.view())
+      .invert()
+      .unwrap_or(Matrix4::identity());
+
+    // La correccion de clip de Vulkan ya invierte la Y: la fila 0 de pixeles es y = -1.
+    let x = cursor[0] / viewport[0] * 2.0 - 1.0;
+    let y = cursor[1] / viewport[1] * 2.0 - 1.0;
+    let unproject = |depth: f32| {
+      let world = inverse * vec4(x, y, depth, 1.0);
+      Point3::from_vec(world.truncate() / world.w)
+    };
+
+    let near = unproject(0.0);
+    let far = unproject(1.0);
+
+    Ray {
+      origin: near,
+      direction: (far - near).normalize(),
+    }
+  }
+}
+
+/// Editor view state saved with a scene, besides its active camera.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneView {
+  /// Fly camera speed in world units per second.
+  pub fly_speed: f32,
+  /// Camera of the inactive view, the 3D one while the 2D view is active and the other way around.
+  pub stashed: Option<Camera>,
+}
+
+impl Default for SceneView {
+  fn default() -> Self {
+    Self {
+      fly_speed: 8.0,
+      stashed: None,
+    }
+  }
+}
@@ -0,0 +1,323 @@
+use std::f32::consts::TAU;
+
+use cgmath::{point3, vec3, vec4, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+
+use super::camera::Camera;
+
+/// Linear RGBA, alpha blended over the scene.
+pub type Color = [f32; 4];
+
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DebugVertex {
+  pub position: [f32; 3],
+  pub color: Color,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DebugText {
+  position: Point3<f32>,
+  text: String,
+  size: f32,
+  color: Color,
+  depth_test: bool,
+}
+
+/// Immediate-mode helper geometry. Any system can add lines while building a frame; the renderer
+/// draws them as a line list and clears everything once the frame is submitted.
+#[derive(Clone, Debug)]
+pub struct DebugDraw {
+  /// Whether the next primitives are hidden behind scene geometry or drawn on top of it.
+  pub depth_test: bool,
+  depth_tested: Vec<DebugVertex>,
+  overlay: Vec<DebugVertex>,
+  texts: Vec<DebugText>,
+}
+
+impl Default for DebugDraw {
+  fn default() -> Self {
+    Self {
+      depth_test: true,
+      depth_tested: vec![],
+      overlay: vec![],
+      texts: vec![],
+    }
+  }
+}
+
+impl DebugDraw {
+  pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: Color) {
+    self.line_gradient(a, b, color, color);
+  }
+
+  /// Line whose color goes from `color_a` at `a` to `color_b` at `b`.
+  pub fn line_gradient(&mut self, a: Point3<f32>, b: Point3<f32>, color_a: Color, color_b: Color) {
+    let lines = if self.depth_test {
+      &mut self.depth_tested
+    } else {
+      &mut self.overlay
+    };
+
+    lines.push(DebugVertex {
+      position: a.into(),
+      color: color_a,
+    });
+    lines.push(DebugVertex {
+      position: b.into(),
+      color: color_b,
+    });
+  }
+
+  /// Axis aligned box between `min` and `max`.
+  pub fn aabb(&mut self, min: Point3<f32>, max: Point3<f32>, color: Color) {
+    let corner = |i: usize| {
+      point3(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z },
+      )
+    };
+
+    self.box_edges(corner, color);
+  }
+
+  /// Three great circles, one per axis plane.
+  pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: Color) {
+    self.circle(center, vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), radius, color);
+    self.circle(center, vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0), radius, color);
+    self.circle(center, vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0), radius, color);
+  }
+
+  /// Circle spanned by the unit vectors `u` and `v`.
+  pub fn circle(&mut self, center: Point3<f32>, u: Vector3<f32>, v: Vector3<f32>, radius: f32, color: Color) {
+    let point = |i: usize| {
+      let angle = i as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+      center + (u * angle.cos() + v * angle.sin()) * radius
+    };
+
+    for i in 0..CIRCLE_SEGMENTS {
+      self.line(point(i), point(i + 1), color);
+    }
+  }
+
+  /// Edges of the volume a view-projection matrix sees, with Vulkan depth range `[0, 1]`.
+  pub fn frustum(&mut self, view_proj: Matrix4<f32>, color: Color) {
+    let Some(inverse) = view_proj.invert() else {
+      return;
+    };
+
+    let corner = |i: usize| {
+      let ndc = vec4(
+        if i & 1 == 0 { -1.0 } else { 1.0 },
+        if i & 2 == 0 { -1.0 } else { 1.0 },
+        if i & 4 == 0 { 0.0 } else { 1.0 },
+        1.0,
+      );
+      let world = inverse * ndc;
+      Point3::from_vec(world.truncate() / world.w)
+    };
+
+    self.box_edges(corner, color);
+  }
+
+  /// Line from `from` to `to` with a four-sided head at `to`.
+  pub fn arrow(&mut self, from: Point3<f32>, to: Point3<f32>, color: Color) {
+    self.line(from, to, color);
+
+    let direction = to - from;
+    let length = direction.magnitude();
+
+    if length <= f32::EPSILON {
+      return;
+    }
+
+    let forward = direction / length;
+    let (side, up) = perpendicular_basis(forward);
+    let head = length.min(1.0) * 0.2;
+    let base = to - forward * head;
+
+    for offset in [side, -side, up, -up] {
+      self.line(to, base + offset * head * 0.4, color);
+    }
+  }
+
+  /// Square grid on the XZ plane through `center`, `cells` cells per side.
+  pub fn grid(&mut self, center: Point3<f32>, cells: u32, spacing: f32, color: Color) {
+    let half = cells as f32 * spacing * 0.5;
+
+    for i in 0..=cells {
+      let offset = i as f32 * spacing - half;
+      self.line(
+        center + vec3(offset, 0.0, -half),
+        center + vec3(offset, 0.0, half),
+        color,
+      );
+      self.line(
+        center + vec3(-half, 0.0, offset),
+        center + vec3(half, 0.0, offset),
+        color,
+      );
+    }
+  }
+
+  /// Line text facing the camera, `size` is the height of a glyph in world units. Only ASCII
+  /// letters, digits and a few symbols have glyphs.
+  pub fn text3d(&mut self, position: Point3<f32>, text: &str, size: f32, color: Color) {
+    self.texts.push(DebugText {
+      position,
+      text: text.to_string(),
+      size,
+      color,
+      depth_test: self.depth_test,
+    });
+  }
+
+  /// Turns the queued text into lines; text is billboarded, so it needs the camera of the frame.
+  pub fn resolve_text(&mut self, camera: &Camera) {
+    let forward = (camera.target - camera.position).normalize();
+    let right = forward.cross(camera.up).normalize();
+    let up = right.cross(forward);
+    let depth_test = self.depth_test;
+
+    for text in std::mem::take(&mut self.texts) {
+      self.depth_test = text.depth_test;
+
+      let width = text.size * 0.5;
+      let advance = text.size * 0.75;
+      let point = |origin: Point3<f32>, (x, y): (u8, u8)| {
+        origin + right * (x as f32 * 0.5 * width) + up * (y as f32 * 0.5 * text.size)
+      };
+
+      for (i, c) in text.text.chars().enumerate() {
+        let origin = text.position + right * (i as f32 * advance);
+        let mask = glyph(c);
+
+        for (bit, (a, b)) in SEGMENTS.iter().enumerate() {
+          if mask & (1 << bit) != 0 {
+            self.line(point(origin, *a), point(origin, *b), text.color);
+          }
+        }
+      }
+    }
+
+    self.depth_test = depth_test;
+  }
+
+  /// Depth-tested and overlay vertices, two per line.
+  pub fn vertices(&self) -> (&[DebugVertex], &[DebugVertex]) {
+    (&self.depth_tested, &self.overlay)
+  }
+
+  pub fn clear(&mut self) {
+    self.depth_tested.clear();
+    self.overlay.clear();
+    self.texts.clear();
+  }
+
+  fn box_edges(&mut self, corner: impl Fn(usize) -> Point3<f32>, color: Color) {
+    // Las aristas unen esquinas que difieren en un solo bit.
+    for i in 0..8 {
+      for bit in [1, 2, 4] {
+        if i & bit == 0 {
+          self.line(corner(i), corner(i | bit), color);
+        }
+      }
+    }
+  }
+}
+
+fn perpendicular_basis(forward: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+  let reference = if forward.y.abs() < 0.99 {
+    vec3(0.0, 1.0, 0.0)
+  } else {
+    vec3(1.0, 0.0, 0.0)
+  };
+  let side = forward.cross(reference).normalize();
+
+  (side, side.cross(forward))
+}
+
+/// 16-segment display on a 3x3 grid of points, `(x, y)` with y up.
+const SEGMENTS: [((u8, u8), (u8, u8)); 16] = [
+  ((0, 2), (1, 2)),
+  ((1, 2), (2, 2)),
+  ((2, 2), (2, 1)),
+  ((2, 1), (2, 0)),
+  ((2, 0), (1, 0)),
+  ((1, 0), (0, 0)),
+  ((0, 0), (0, 1)),
+  ((0, 1), (0, 2)),
+  ((0, 1), (1, 1)),
+  ((1, 1), (2, 1)),
+  ((0, 2), (1, 1)),
+  ((1, 2), (1, 1)),
+  ((2, 2), (1, 1)),
+  ((0, 0), (1, 1)),
+  ((1, 0), (1, 1)),
+  ((2, 0), (1, 1)),
+];
+
+const TOP: u16 = 0b11;
+const RIGHT: u16 = 0b11 << 2;
+const BOTTOM: u16 = 0b11 << 4;
+const LEFT: u16 = 0b11 << 6;
+const MIDDLE: u16 = 0b11 << 8;
+const fn seg(i: u16) -> u16 {
+  1 << i
+}
+
+fn glyph(c: char) -> u16 {
+  match c.to_ascii_uppercase() {
+    '0' => TOP | RIGHT | BOTTOM | LEFT | seg(12) | seg(13),
+    '1' => RIGHT | seg(12),
+    '2' => TOP | seg(2) | MIDDLE | seg(6) | BOTTOM,
+    '3' => TOP | RIGHT | BOTTOM | seg(9),
+    '4' => seg(7) | MIDDLE | RIGHT,
+    '5' => TOP | seg(7) | MIDDLE | seg(3) | BOTTOM,
+    '6' => TOP | LEFT | BOTTOM | seg(3) | MIDDLE,
+    '7' => TOP | RIGHT,
+    '8' => TOP | RIGHT | BOTTOM | LEFT | MIDDLE,
+    '9' => TOP | RIGHT | BOTTOM | seg(7) | MIDDLE,
+    'A' => TOP | RIGHT | LEFT | MIDDLE,
+    'B' => TOP | RIGHT | BOTTOM | seg(11) | seg(14) | seg(9),
+    'C' => TOP | LEFT | BOTTOM,
+    'D' => TOP | RIGHT | BOTTOM | seg(11) | seg(14),
+    'E' => TOP | LEFT | BOTTOM | seg(8),
+    'F' => TOP | LEFT | seg(8),
+    'G' => TOP | LEFT | BOTTOM | seg(3) | seg(9),
+    'H' => LEFT | RIGHT | MIDDLE,
+    'I' => TOP | BOTTOM | seg(11) | seg(14),
+    'J' => RIGHT | BOTTOM | seg(6),
+    'K' => LEFT | seg(8) | seg(12) | seg(15),
+    'L' => LEFT | BOTTOM,
+    'M' => LEFT | RIGHT | seg(10) | seg(12),
+    'N' => LEFT | RIGHT | seg(10) | seg(15),
+    'O' => TOP | RIGHT | BOTTOM | LEFT,
+    'P' => TOP | LEFT | seg(2) | MIDDLE,
+    'Q' => TOP | RIGHT | BOTTOM | LEFT | seg(15),
+    'R' => TOP | LEFT | seg(2) | MIDDLE | seg(15),
+    'S' => TOP | seg(7) | MIDDLE | seg(3) | BOTTOM,
+    'T' => TOP | seg(11) | seg(14),
+    'U' => LEFT | RIGHT | BOTTOM,
+    'V' => LEFT | seg(13) | seg(12),
+    'W' => LEFT | RIGHT | seg(13) | seg(15),
+    'X' => seg(10) | seg(12) | seg(13) | seg(15),
+    'Y' => seg(10) | seg(12) | seg(14),
+    'Z' => TOP | seg(12) | seg(13) | BOTTOM,
+    '-' => MIDDLE,
+    '+' => MIDDLE | seg(11) | seg(14),
+    '=' => MIDDLE | BOTTOM,
+    '_' => BOTTOM,
+    '.' => seg(5),
+    ',' => seg(13),
+    ':' => seg(11) | seg(5),
+    '/' => seg(12) | seg(13),
+    '(' | '<' => seg(12) | seg(15),
+    ')' | '>' => seg(10) | seg(13),
+    '|' => seg(11) | seg(14),
+    '*' => MIDDLE | seg(10) | seg(11) | seg(12) | seg(13) | seg(14) | seg(15),
+    _ => 0,
+  }
+}
@@ -0,0 +1,81 @@
+use cgmath::{point3, vec3, Point3, Vector3};
+use sagitario_ecs::Component;
+use sagitario_reflect::Reflect;
+use serde::{Deserialize, Serialize};
+
+/// Color keys over a particle's normalized age (`0.0` = spawn, `1.0` = death).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct ColorGradient {
+  pub keys: Vec<(f32, [f32; 4])>,
+}
+
+impl ColorGradient {
+  pub fn sample(&self, t: f32) -> [f32; 4] {
+    let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+      return [1.0; 4];
+    };
+
+    if t <= first.0 {
+      return first.1;
+    }
+
+    for pair in self.keys.windows(2) {
+      let ((t0, c0), (t1, c1)) = (pair[0], pair[1]);
+
+      if t <= t1 {
+        let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 0.0 };
+        return [0, 1, 2, 3].map(|i| c0[i] + (c1[i] - c0[i]) * f);
+      }
+    }
+
+    last.1
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[serde(default)]
+pub struct ParticleEmitter {
+  pub name: String,
+  pub enabled: bool,
+  pub position: Point3<f32>,
+  /// Particles spawned per second.
+  #[reflect(range(0.0, 10000.0))]
+  pub spawn_rate: f32,
+  #[reflect(range(0.0, 100.0))]
+  pub spawn_radius: f32,
+  /// Lifetime range in seconds, each particle picks a random value in between.
+  pub lifetime: (f32, f32),
+  pub velocity: Vector3<f32>,
+  /// Random extra speed added in every direction.
+  pub velocity_spread: f32,
+  pub gravity: Vector3<f32>,
+  /// Billboard size at spawn and at death.
+  pub size: (f32, f32),
+  pub color_over_life: ColorGradient,
+}
+
+impl Default for ParticleEmitter {
+  fn default() -> Self {
+    Self {
+      name: "Fountain".to_string(),
+      enabled: true,
+      position: point3(0.0, 0.0, 0.0),
+      spawn_rate: 400.0,
+      spawn_radius: 0.1,
+      lifetime: (1.5, 2.5),
+      velocity: vec3(0.0, 4.0, 0.0),
+      velocity_spread: 1.0,
+      gravity: vec3(0.0, -4.0, 0.0),
+      size: (0.08, 0.02),
+      color_over_life: ColorGradient {
+        keys: vec![
+          (0.0, [1.0, 0.9, 0.4, 1.0]),
+          (0.5, [1.0, 0.4, 0.1, 0.8]),
+          (1.0, [0.3, 0.1, 0.1, 0.0]),
+        ],
+      },
+    }
+  }
+}
+
+impl Component for ParticleEmitter {}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+/// What's drawn behind the scene; it's also the source of the image based lighting.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Background {
+  /// Linear color.
+  Solid([f32; 3]),
+  /// Sky gradient from the horizon up to the zenith and down to the ground, linear colors.
+  Gradient {
+    zenith: [f32; 3],
+    horizon: [f32; 3],
+    ground: [f32; 3],
+  },
+  /// Equirectangular `.hdr` or `.exr` image. Falls back to the default gradient if it can't be loaded.
+  Hdri(PathBuf),
+}
+
+impl Default for Background {
+  fn default() -> Self {
+    Self::Gradient {
+      zenith: [0.18, 0.32, 0.62],
+      horizon: [0.75, 0.8, 0.85],
+      ground: [0.22, 0.2, 0.18],
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Environment {
+  pub background: Background,
+  /// Scale of the image based lighting.
+  pub intensity: f32,
+  /// Scale of the skybox, independent of the lighting.
+  pub sky_intensity: f32,
+  /// Mip of the environment cubemap the skybox shows, higher values blur it.
+  pub sky_blur: f32,
+  /// Rotation of the environment around the vertical axis, in degrees.
+  pub rotation: f32,
+}
+
+impl Default for Environment {
+  fn default() -> Self {
+    Self {
+      background: Background::default(),
+      intensity: 1.0,
+      sky_intensity: 1.0,
+      sky_blur: 0.0,
+      rotation: 0.0,
+    }
+  }
+}
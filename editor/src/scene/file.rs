@@ -0,0 +1,565 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use sagitario_ecs::{Component, Entity, World};
+use sagitario_reflect::{Reflect, ReflectError, Value};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::camera::{Camera, SceneView};
+use super::emitter::ParticleEmitter;
+use super::guid::Guid;
+use super::light::Light;
+use super::mesh::MeshSource;
+use super::prefab::{instance_entries, saved_entry, tag_instance, Prefab, PrefabRef};
+use super::prop::{Material, MaterialId, MeshId, Prop};
+use super::transform::{set_parent_local, Children, GlobalTransform, HierarchyError, Transform};
+use super::Scene;
+
+/// Version `SceneFile::to_ron` writes.
+pub const SCENE_VERSION: u32 = 1;
+
+/// Where the editor saves a scene that wasn't opened from a file.
+pub const DEFAULT_SCENE_PATH: &str = "untitled.scene";
+
+/// `MIGRATIONS[i]` upgrades a file of version `i + 1` to `i + 2`. They run on the parsed file, so
+/// fields added since then already hold their serde defaults and only need fixing up.
+const MIGRATIONS: &[fn(&mut SceneFile)] = &[];
+
+#[derive(Debug, Error)]
+pub enum SceneError {
+  #[error("scene file version {found} is newer than the supported {supported}")]
+  UnsupportedVersion { found: u32, supported: u32 },
+  #[error("{0} is used more than once")]
+  DuplicateGuid(Guid),
+  #[error("{0} is referenced but not in the scene file")]
+  MissingReference(Guid),
+  #[error("prefab {0} doesn't exist")]
+  UnknownPrefab(Guid),
+  #[error("prefab {0} contains itself")]
+  PrefabCycle(Guid),
+  #[error("entity {0} isn't the root of a prefab instance")]
+  NotAnInstance(Entity),
+  #[error("entity {0} is part of a prefab instance")]
+  PartOfInstance(Entity),
+  #[error("entity {0} has no GUID")]
+  Unsaved(Entity),
+  #[error(transparent)]
+  Hierarchy(#[from] HierarchyError),
+  #[error(transparent)]
+  Reflect(#[from] ReflectError),
+  #[error(transparent)]
+  Parse(#[from] ron::error::SpannedError),
+  #[error(transparent)]
+  Serialize(#[from] ron::Error),
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+}
+
+/// Just the version, read before the rest in case the file is too new to parse.
+#[derive(Deserialize)]
+struct Header {
+  version: u32,
+}
+
+/// What a `.scene` file holds: the assets, the prefabs, every entity with a `Guid`, its
+/// components and children, and the editor camera. Everything is keyed by GUID and entities are sorted by it, so saving the same
+/// scene twice gives the same text and edits stay local in a diff.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SceneFile {
+  pub version: u32,
+  #[serde(default)]
+  pub meshes: Vec<MeshEntry>,
+  #[serde(default)]
+  pub materials: Vec<Material>,
+  #[serde(default)]
+  pub prefabs: Vec<Prefab>,
+  #[serde(default)]
+  pub entities: Vec<EntityEntry>,
+  #[serde(default)]
+  pub camera: Camera,
+  #[serde(default)]
+  pub view: SceneView,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MeshEntry {
+  pub guid: Guid,
+  pub name: String,
+  pub source: MeshSource,
+}
+
+/// `Prop` with its mesh and material referenced by GUID instead of index.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct PropEntry {
+  pub mesh: Guid,
+  pub material: Guid,
+}
+
+/// One entity and the components it has. `GlobalTransform` and `Parent` aren't stored, they
+/// come back from `transform` and `children`. An entry with `prefab` is the root of a prefab
+/// instance: its components come from the prefab and the entry only adds its own children.
+///
+/// Reflected, its visible fields are the components, so `light.intensity` names a field of
+/// one of them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct EntityEntry {
+  #[reflect(hidden)]
+  pub guid: Guid,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  #[reflect(hidden)]
+  pub children: Vec<Guid>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[reflect(hidden)]
+  pub prefab: Option<PrefabRef>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub transform: Option<Transform>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub prop: Option<PropEntry>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub light: Option<Light>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub emitter: Option<ParticleEmitter>,
+}
+
+impl SceneFile {
+  pub fn from_scene(scene: &Scene) -> Self {
+    let entities = scene
+      .world
+      .query::<(Entity, &Guid)>()
+      .iter()
+      .map(|(e, _)| e)
+      .collect::<Vec<_>>();
+    let mut entities = entities
+      .into_iter()
+      .filter_map(|entity| saved_entry(scene, entity))
+      .collect::<Vec<_>>();
+
+    entities.sort_by_key(|e| e.guid);
+
+    Self {
+      version: SCENE_VERSION,
+      meshes: scene
+        .meshes
+        .iter()
+        .map(|m| MeshEntry {
+          guid: m.guid,
+          name: m.name.clone(),
+          source: m.source.clone(),
+        })
+        .collect(),
+      materials: scene.materials.clone(),
+      prefabs: scene.prefabs.values().cloned().collect(),
+      entities,
+      camera: scene.camera,
+      view: scene.view,
+    }
+  }
+
+  /// Parses a file of any version up to `SCENE_VERSION` and migrates it to the current one.
+  pub fn from_ron(source: &str) -> Result<Self, SceneError> {
+    let Header { version } = ron::from_str(source)?;
+
+    if version > SCENE_VERSION {
+      return Err(SceneError::UnsupportedVersion {
+        found: version,
+        supported: SCENE_VERSION,
+      });
+    }
+
+    let mut file: Self = ron::from_str(source)?;
+
+    for migrate in &MIGRATIONS[version.max(1) as usize - 1..] {
+      migrate(&mut file);
+    }
+
+    file.version = SCENE_VERSION;
+    Ok(file)
+  }
+
+  pub fn to_ron(&self) -> Result<String, SceneError> {
+    Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+  }
+
+  /// Builds the scene: rebuilt meshes, the materials, the prefabs, the camera and one entity per
+  /// entry with its hierarchy. Prefab instances are expanded into their parts. World transforms are filled
+  /// in by the first `Scene::update`.
+  pub fn into_scene(self) -> Result<Scene, SceneError> {
+    let prefabs = self
+      .prefabs
+      .into_iter()
+      .map(|p| (p.guid, p))
+      .collect::<BTreeMap<_, _>>();
+    let mut entries = vec![];
+    let mut instances = vec![];
+
+    for entry in self.entities {
+      let Some(instance) = entry.prefab else {
+        entries.push(entry);
+        continue;
+      };
+
+      let mut parts = instance_entries(&prefabs, entry.guid, &instance)?;
+      let guids = parts.iter().map(|p| p.guid).collect::<Vec<_>>();
+
+      if let Some(root) = parts.iter_mut().find(|p| p.guid == entry.guid) {
+        root.children.extend(entry.children);
+      }
+
+      entries.extend(parts);
+      instances.push((entry.guid, instance.prefab, guids));
+    }
+
+    let mut guids = BTreeSet::new();
+    let all = self.meshes.iter().map(|m| m.guid);
+    let all = all.chain(self.materials.iter().map(|m| m.guid));
+    let all = all.chain(prefabs.keys().copied());
+
+    if let Some(guid) = all.chain(entries.iter().map(|e| e.guid)).find(|&g| !guids.insert(g)) {
+      return Err(SceneError::DuplicateGuid(guid));
+    }
+
+    let meshes = self
+      .meshes
+      .iter()
+      .map(|entry| {
+        let mut mesh = entry.source.build();
+        mesh.guid = entry.guid;
+        mesh.name = entry.name.clone();
+        mesh
+      })
+      .collect::<Vec<_>>();
+
+    let mut scene = Scene::new(meshes, self.materials);
+    scene.prefabs = prefabs;
+    scene.camera = self.camera;
+    scene.view = self.view;
+    let entities = write_entries(&mut scene, &entries, HashMap::new())?;
+
+    for (root, prefab, parts) in instances {
+      let parts = parts.iter().map(|guid| entities[guid]);
+      tag_instance(&mut scene.world, root, prefab, parts);
+    }
+
+    Ok(scene)
+  }
+}
+
+/// `entity`'s components as `SceneFile` stores them, with its children by GUID; `None` for
+/// entities without a `Guid`.
+pub(super) fn entity_entry(scene: &Scene, entity: Entity) -> Option<EntityEntry> {
+  let world = &scene.world;
+  let guid_of = |entity| world.get::<Guid>(entity).map(|g| *g);
+
+  Some(EntityEntry {
+    guid: guid_of(entity)?,
+    children: world
+      .get::<Children>(entity)
+      .map(|c| c.iter().filter_map(guid_of).collect())
+      .unwrap_or_default(),
+    prefab: None,
+    transform: world.get::<Transform>(entity).map(|t| *t),
+    prop: world.get::<Prop>(entity).and_then(|p| {
+      Some(PropEntry {
+        mesh: scene.meshes.get(p.mesh.0)?.guid,
+        material: scene.materials.get(p.material.0)?.guid,
+      })
+    }),
+    light: world.get::<Light>(entity).map(|l| l.clone()),
+    emitter: world.get::<ParticleEmitter>(entity).map(|e| e.clone()),
+  })
+}
+
+/// Writes `entries` into the world, reusing the entities in `existing` by GUID and spawning the
+/// rest, then attaches every entry's children. Only components that differ are written, so
+/// unchanged ones aren't marked changed. Returns the entity of every entry by GUID.
+pub(super) fn write_entries(
+  scene: &mut Scene,
+  entries: &[EntityEntry],
+  mut existing: HashMap<Guid, Entity>,
+) -> Result<HashMap<Guid, Entity>, SceneError> {
+  for entry in entries {
+    let prop = match entry.prop {
+      Some(prop) => Some(Prop {
+        mesh: MeshId(position(scene.meshes.iter().map(|m| m.guid), prop.mesh)?),
+        material: MaterialId(position(scene.materials.iter().map(|m| m.guid), prop.material)?),
+      }),
+      None => None,
+    };
+
+    let world = &mut scene.world;
+    let entity = *existing.entry(entry.guid).or_insert_with(|| world.spawn(entry.guid));
+
+    if entry.transform.is_some() && !world.has::<GlobalTransform>(entity) {
+      world.insert(entity, GlobalTransform::default());
+    } else if entry.transform.is_none() {
+      world.remove::<GlobalTransform>(entity);
+    }
+
+    write(world, entity, entry.transform);
+    write(world, entity, prop);
+    write(world, entity, entry.light.clone());
+    write(world, entity, entry.emitter.clone());
+  }
+
+  for entry in entries {
+    for guid in &entry.children {
+      let child = lookup(&existing, *guid)?;
+      set_parent_local(&mut scene.world, child, Some(existing[&entry.guid]))?;
+    }
+  }
+
+  Ok(existing)
+}
+
+/// `entity`'s components as the scene file saves them, to read through `Reflect`.
+pub fn reflected_entry(scene: &Scene, entity: Entity) -> Result<EntityEntry, SceneError> {
+  entity_entry(scene, entity).ok_or(SceneError::Unsaved(entity))
+}
+
+/// Writes `value` into the component field of `entity` at `path`, e.g. `light.color.0`.
+pub fn set_component_field(scene: &mut Scene, entity: Entity, path: &str, value: &Value) -> Result<(), SceneError> {
+  let mut entry = reflected_entry(scene, entity)?;
+  entry.set_path(path, value)?;
+
+  // Los hijos ya cuelgan de la entidad.
+  entry.children.clear();
+  let guid = entry.guid;
+  write_entries(scene, &[entry], HashMap::from([(guid, entity)]))?;
+
+  Ok(())
+}
+
+fn write<T: Component + PartialEq>(world: &mut World, entity: Entity, component: Option<T>) {
+  match component {
+    Some(component) if world.get::<T>(entity).is_some_and(|c| *c == component) => {}
+    Some(component) => _ = world.insert(entity, component),
+    None => _ = world.remove::<T>(entity),
+  }
+}
+
+fn position(mut guids: impl Iterator<Item = Guid>, guid: Guid) -> Result<usize, SceneError> {
+  guids.position(|g| g == guid).ok_or(SceneError::MissingReference(guid))
+}
+
+fn lookup<T: Copy>(ids: &HashMap<Guid, T>, guid: Guid) -> Result<T, SceneError> {
+  ids.get(&guid).copied().ok_or(SceneError::MissingReference(guid))
+}
+
+impl Scene {
+  pub fn from_ron(source: &str) -> Result<Self, SceneError> {
+    SceneFile::from_ron(source)?.into_scene()
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+    Self::from_ron(&fs::read_to_string(path)?)
+  }
+
+  pub fn to_ron(&self) -> Result<String, SceneError> {
+    SceneFile::from_scene(self).to_ron()
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+    Ok(fs::write(path, self.to_ron()?)?)
+  }
+
+  /// Swaps what the file stores (assets, prefabs and entities) for `file`'s, keeping the camera,
+  /// environment and grid. The selection follows its entities by GUID.
+  pub fn restore(&mut self, file: &SceneFile) -> Result<(), SceneError> {
+    let selection = self
+      .selection
+      .iter()
+      .filter_map(|&e| self.world.get::<Guid>(e).map(|g| *g));
+    let selection = selection.collect::<Vec<_>>();
+    let loaded = file.clone().into_scene()?;
+
+    self.meshes = loaded.meshes;
+    self.materials = loaded.materials;
+    self.prefabs = loaded.prefabs;
+    self.world = loaded.world;
+    self.schedule = loaded.schedule;
+    self.selection = selection.into_iter().filter_map(|g| self.entity(g)).collect();
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+  use cgmath::{point3, vec3, Deg, Quaternion, Rotation3};
+
+  use super::*;
+  use crate::scene::camera::Projection;
+  use crate::scene::light::LightKind;
+  use crate::scene::transform::{parent_of, set_parent};
+
+  fn children_of(scene: &Scene, entity: Entity) -> Vec<Guid> {
+    let children = scene
+      .world
+      .get::<Children>(entity)
+      .map(|c| c.iter().collect::<Vec<_>>());
+    let guid = |e| *scene.world.get::<Guid>(e).unwrap();
+    children.unwrap_or_default().into_iter().map(guid).collect()
+  }
+
+  pub(crate) fn guid_of(scene: &Scene, entity: Entity) -> Guid {
+    *scene.world.get::<Guid>(entity).unwrap()
+  }
+
+  pub(crate) fn find(scene: &Scene, guid: Guid) -> Entity {
+    let mut query = scene.world.query::<(Entity, &Guid)>();
+    let found = query.iter().find(|(_, g)| **g == guid).map(|(e, _)| e);
+    found.unwrap()
+  }
+
+  /// A root with three children in a non-sorted order and a grandchild, with awkward floats.
+  pub(crate) fn hierarchy() -> (Scene, [Guid; 5]) {
+    let mut scene = Scene::default();
+    let transform = |x: f32| Transform {
+      translation: vec3(x, 0.1 + x / 3.0, -x * 7.3),
+      rotation: Quaternion::from_angle_y(Deg(x * 13.7)),
+      scale: vec3(1.0, 1.0 / 3.0, 2.5),
+    };
+
+    let root = scene.spawn((transform(1.0), GlobalTransform::default()));
+    let children = [2.0, 3.0, 4.0].map(|x| scene.spawn((transform(x), GlobalTransform::default())));
+    let grandchild = scene.spawn((transform(5.0), GlobalTransform::default(), Light::default()));
+
+    for child in [children[2], children[0], children[1]] {
+      set_parent(&mut scene.world, child, Some(root)).unwrap();
+    }
+
+    set_parent(&mut scene.world, grandchild, Some(children[1])).unwrap();
+    scene.update();
+
+    let guids = [root, children[0], children[1], children[2], grandchild].map(|e| *scene.world.get::<Guid>(e).unwrap());
+    (scene, guids)
+  }
+
+  #[test]
+  fn default_scene_round_trips() {
+    let scene = Scene::default();
+    let text = scene.to_ron().unwrap();
+    let loaded = Scene::from_ron(&text).unwrap();
+
+    assert_eq!(SceneFile::from_scene(&loaded), SceneFile::from_scene(&scene));
+    assert_eq!(loaded.to_ron().unwrap(), text);
+    assert_eq!(loaded.meshes, scene.meshes);
+    assert_eq!(loaded.world.len(), scene.world.len());
+  }
+
+  #[test]
+  fn hierarchy_round_trips() {
+    let (mut scene, [root, a, b, c, grandchild]) = hierarchy();
+    let mut loaded = Scene::from_ron(&scene.to_ron().unwrap()).unwrap();
+    loaded.update();
+
+    assert_eq!(children_of(&loaded, find(&loaded, root)), vec![c, a, b]);
+    assert_eq!(children_of(&loaded, find(&loaded, b)), vec![grandchild]);
+
+    for guid in [root, a, b, c, grandchild] {
+      let (before, after) = (find(&scene, guid), find(&loaded, guid));
+      let parent = |scene: &Scene, e| parent_of(&scene.world, e).map(|p| *scene.world.get::<Guid>(p).unwrap());
+
+      assert_eq!(
+        *loaded.world.get::<Transform>(after).unwrap(),
+        *scene.world.get::<Transform>(before).unwrap()
+      );
+      assert_eq!(
+        loaded.world.get::<GlobalTransform>(after).unwrap().0,
+        scene.world.get::<GlobalTransform>(before).unwrap().0
+      );
+      assert_eq!(parent(&loaded, after), parent(&scene, before));
+    }
+
+    let light = find(&loaded, grandchild);
+    assert_eq!(loaded.world.get::<Light>(light).unwrap().kind, LightKind::Point);
+
+    scene.update();
+    assert_eq!(loaded.to_ron().unwrap(), scene.to_ron().unwrap());
+  }
+
+  #[test]
+  fn camera_and_view_round_trip() {
+    let scene = Scene {
+      camera: Camera {
+        position: point3(1.5, 2.0 / 3.0, -4.0),
+        target: point3(1.5, 2.0 / 3.0, 0.0),
+        projection: Projection::Orthographic { height: 12.5 },
+        ..Camera::default()
+      },
+      view: SceneView {
+        fly_speed: 3.25,
+        stashed: Some(Camera::default()),
+      },
+      ..Scene::default()
+    };
+
+    let loaded = Scene::from_ron(&scene.to_ron().unwrap()).unwrap();
+
+    assert_eq!(loaded.camera, scene.camera);
+    assert_eq!(loaded.view, scene.view);
+  }
+
+  #[test]
+  fn output_is_sorted_by_guid() {
+    let (scene, _) = hierarchy();
+    let file = SceneFile::from_scene(&scene);
+
+    assert!(file.entities.windows(2).all(|w| w[0].guid < w[1].guid));
+  }
+
+  #[test]
+  fn rejects_newer_versions() {
+    let text = format!("(version: {}, future_field: 1)", SCENE_VERSION + 1);
+
+    assert!(matches!(
+      SceneFile::from_ron(&text),
+      Err(SceneError::UnsupportedVersion { found, .. }) if found == SCENE_VERSION + 1
+    ));
+  }
+
+  #[test]
+  fn missing_fields_take_defaults() {
+    let file = SceneFile::from_ron("(version: 1)").unwrap();
+
+    assert!(file.meshes.is_empty() && file.materials.is_empty() && file.entities.is_empty());
+    assert_eq!((file.camera, file.view), (Camera::default(), SceneView::default()));
+  }
+
+  #[test]
+  fn rejects_broken_references() {
+    let scene = Scene::default();
+    let mut file = SceneFile::from_scene(&scene);
+    let missing = Guid::new();
+    file
+      .entities
+      .iter_mut()
+      .find(|e| e.prop.is_some())
+      .unwrap()
+      .prop
+      .as_mut()
+      .unwrap()
+      .mesh = missing;
+
+    assert!(matches!(file.into_scene(), Err(SceneError::MissingReference(g)) if g == missing));
+  }
+
+  #[test]
+  fn rejects_cycles() {
+    let (scene, [root, a, ..]) = hierarchy();
+    let mut file = SceneFile::from_scene(&scene);
+    file
+      .entities
+      .iter_mut()
+      .find(|e| e.guid == a)
+      .unwrap()
+      .children
+      .push(root);
+
+    assert!(matches!(
+      file.into_scene(),
+      Err(SceneError::Hierarchy(HierarchyError::Cycle { .. }))
+    ));
+  }
+}
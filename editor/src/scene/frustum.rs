@@ -0,0 +1,26 @@
+use cgmath::{vec4, InnerSpace, Matrix4, Point3, Vector4};
+
+/// View frustum planes `(normal, distance)` pointing inwards, extracted from a view-projection
+/// matrix with Vulkan depth range `[0, 1]`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+  pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+  pub fn from_matrix(m: Matrix4<f32>) -> Self {
+    let row = |i: usize| vec4(m.x[i], m.y[i], m.z[i], m.w[i]);
+    let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+    let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|p| p / p.truncate().magnitude());
+
+    Self { planes }
+  }
+
+  pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+    self
+      .planes
+      .iter()
+      .all(|p| p.x * center.x + p.y * center.y + p.z * center.z + p.w >= -radius)
+  }
+}
@@ -0,0 +1,89 @@
+use cgmath::{point3, Point3};
+
+use super::camera::Camera;
+use super::debug_draw::{Color, DebugDraw};
+
+const MINOR_COLOR: Color = [0.45, 0.45, 0.48, 0.35];
+const MAJOR_COLOR: Color = [0.6, 0.6, 0.65, 0.6];
+const X_AXIS_COLOR: Color = [0.9, 0.2, 0.2, 0.9];
+const Z_AXIS_COLOR: Color = [0.2, 0.35, 0.95, 0.9];
+
+/// Editor ground grid on the XZ plane. It follows the camera and fades out with the distance, so
+/// it reads as infinite.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GroundGrid {
+  pub enabled: bool,
+  /// Size of a minor cell in world units.
+  pub spacing: f32,
+  /// Minor cells per major line.
+  pub major_every: u32,
+  /// Distance from the camera at which the grid has faded out.
+  pub radius: f32,
+  /// Height of the plane, slightly above zero so it doesn't fight with the ground.
+  pub height: f32,
+}
+
+impl Default for GroundGrid {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      spacing: 1.0,
+      major_every: 10,
+      radius: 60.0,
+      height: 0.01,
+    }
+  }
+}
+
+impl GroundGrid {
+  pub fn draw(&self, debug: &mut DebugDraw, camera: &Camera) {
+    if !self.enabled || self.spacing <= 0.0 {
+      return;
+    }
+
+    let major = self.spacing * self.major_every.max(1) as f32;
+    // Centrado en un multiplo de las lineas mayores para que no "resbale" al mover la camara.
+    let center = [camera.position.x, camera.position.z].map(|v| (v / major).round() * major);
+    let focus = [camera.position.x, camera.position.z];
+    let lines = (self.radius / self.spacing).ceil() as i32;
+    let depth_test = debug.depth_test;
+    debug.depth_test = true;
+
+    for axis in 0..2 {
+      for i in -lines..=lines {
+        let offset = center[axis] + i as f32 * self.spacing;
+        let index = (offset / self.spacing).round() as i64;
+
+        let color = match (axis, index) {
+          (0, 0) => Z_AXIS_COLOR,
+          (1, 0) => X_AXIS_COLOR,
+          _ if index % self.major_every.max(1) as i64 == 0 => MAJOR_COLOR,
+          _ => MINOR_COLOR,
+        };
+
+        // Se parte en tramos de una celda mayor para que el degradado siga a la distancia.
+        let segments = (2.0 * self.radius / major).ceil() as i32;
+        let along = |t: i32| center[1 - axis] - self.radius + t as f32 * major;
+        let point = |s: f32| -> Point3<f32> {
+          match axis {
+            0 => point3(offset, self.height, s),
+            _ => point3(s, self.height, offset),
+          }
+        };
+
+        for t in 0..segments {
+          let (a, b) = (point(along(t)), point(along(t + 1)));
+          debug.line_gradient(a, b, self.fade(color, focus, a), self.fade(color, focus, b));
+        }
+      }
+    }
+
+    debug.depth_test = depth_test;
+  }
+
+  fn fade(&self, color: Color, focus: [f32; 2], point: Point3<f32>) -> Color {
+    let distance = (point.x - focus[0]).hypot(point.z - focus[1]);
+    let fade = (1.0 - distance / self.radius).clamp(0.0, 1.0);
+    [color[0], color[1], color[2], color[3] * fade * fade]
+  }
+}
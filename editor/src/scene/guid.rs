@@ -0,0 +1,76 @@
+use std::fmt;
+
+use sagitario_ecs::Component;
+use sagitario_reflect::{FromValue, Reflect, ReflectError, Value};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Identity of an entity or asset that survives saving and loading, unlike `Entity` handles
+/// and `MeshId` / `MaterialId` indices.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Guid(Uuid);
+
+impl Guid {
+  /// A new random GUID.
+  pub fn new() -> Self {
+    Self(Uuid::new_v4())
+  }
+
+  /// `self` with `key` applied, by XOR. Keys compose and applying one twice gives `self` back,
+  /// so prefab instances can derive their entity GUIDs from the prefab's and map them back.
+  pub fn mix(self, key: Guid) -> Self {
+    Self(Uuid::from_u128(self.0.as_u128() ^ key.0.as_u128()))
+  }
+}
+
+impl Default for Guid {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl fmt::Display for Guid {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.0.fmt(f)
+  }
+}
+
+/// Reflected as its text, like the scene file writes it.
+impl Reflect for Guid {
+  fn type_name(&self) -> &'static str {
+    "Guid"
+  }
+
+  fn to_value(&self) -> Value {
+    Value::Text(self.to_string())
+  }
+
+  fn set_value(&mut self, value: &Value) -> Result<(), ReflectError> {
+    *self = Self::from_value(value)?;
+    Ok(())
+  }
+
+  fn as_any(&self) -> &dyn std::any::Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+    self
+  }
+}
+
+impl FromValue for Guid {
+  fn from_value(value: &Value) -> Result<Self, ReflectError> {
+    match value {
+      Value::Text(text) => text
+        .parse()
+        .map(Self)
+        .map_err(|_| ReflectError::mismatch("Guid", value)),
+      _ => Err(ReflectError::mismatch("Guid", value)),
+    }
+  }
+}
+
+/// Entities with a `Guid` are part of the scene file; the rest are editor-only.
+impl Component for Guid {}
@@ -0,0 +1,132 @@
+use cgmath::{point3, vec3, Deg, InnerSpace, Point3, Vector3};
+use sagitario_ecs::Component;
+use sagitario_reflect::Reflect;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+pub enum LightKind {
+  /// Infinitely far away, only `direction` matters.
+  Directional,
+  Point,
+  /// Full intensity inside `inner_angle`, fading out up to `outer_angle` (half angles).
+  Spot {
+    inner_angle: Deg<f32>,
+    outer_angle: Deg<f32>,
+  },
+}
+
+/// Per-light shadow map settings.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[serde(default)]
+pub struct ShadowSettings {
+  pub enabled: bool,
+  /// Size in texels of each cascade, cube face or spot map inside the shadow atlas.
+  #[reflect(range(64, 8192))]
+  pub resolution: u32,
+  /// Constant depth bias, in depth buffer units.
+  pub depth_bias: f32,
+  /// Depth bias scaled by the polygon slope.
+  pub slope_bias: f32,
+  /// World space offset of the receiver along its normal.
+  pub normal_bias: f32,
+  /// PCF kernel radius in texels, `0` is a single hardware filtered tap.
+  #[reflect(range(0, 4))]
+  pub pcf_radius: u32,
+  /// Directional lights only: number of cascades, up to `MAX_CASCADES`.
+  #[reflect(range(1, 4))]
+  pub cascades: u32,
+  /// Directional lights only: blend between uniform (0) and logarithmic (1) cascade splits.
+  #[reflect(range(0.0, 1.0))]
+  pub split_lambda: f32,
+  /// Directional lights only: distance from the camera covered by the cascades.
+  pub max_distance: f32,
+}
+
+pub const MAX_CASCADES: u32 = 4;
+
+impl Default for ShadowSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      resolution: 1024,
+      depth_bias: 1.25,
+      slope_bias: 1.75,
+      normal_bias: 0.02,
+      pcf_radius: 1,
+      cascades: MAX_CASCADES,
+      split_lambda: 0.75,
+      max_distance: 60.0,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[serde(default)]
+pub struct Light {
+  pub name: String,
+  pub enabled: bool,
+  pub kind: LightKind,
+  pub position: Point3<f32>,
+  /// Direction the light travels, for directional and spot lights.
+  pub direction: Vector3<f32>,
+  /// Linear color.
+  #[reflect(range(0.0, 1.0))]
+  pub color: [f32; 3],
+  /// Lux for directional lights, candela for point and spot lights.
+  #[reflect(range(0.0, 100000.0))]
+  pub intensity: f32,
+  /// Distance where point and spot lights fade to zero; also their culling radius.
+  #[reflect(range(0.0, 1000.0))]
+  pub range: f32,
+  pub shadows: ShadowSettings,
+}
+
+impl Default for Light {
+  fn default() -> Self {
+    Self::point(point3(0.0, 2.0, 0.0), [1.0; 3], 10.0, 8.0)
+  }
+}
+
+impl Light {
+  pub fn directional(direction: Vector3<f32>, color: [f32; 3], intensity: f32) -> Self {
+    Self {
+      name: "Directional Light".to_string(),
+      enabled: true,
+      kind: LightKind::Directional,
+      position: point3(0.0, 10.0, 0.0),
+      direction: direction.normalize(),
+      color,
+      intensity,
+      range: 0.0,
+      shadows: ShadowSettings::default(),
+    }
+  }
+
+  pub fn point(position: Point3<f32>, color: [f32; 3], intensity: f32, range: f32) -> Self {
+    Self {
+      name: "Point Light".to_string(),
+      enabled: true,
+      kind: LightKind::Point,
+      position,
+      direction: vec3(0.0, -1.0, 0.0),
+      color,
+      intensity,
+      range,
+      shadows: ShadowSettings::default(),
+    }
+  }
+
+  pub fn spot(position: Point3<f32>, direction: Vector3<f32>, color: [f32; 3], intensity: f32, range: f32) -> Self {
+    Self {
+      name: "Spot Light".to_string(),
+      kind: LightKind::Spot {
+        inner_angle: Deg(20.0),
+        outer_angle: Deg(30.0),
+      },
+      direction: direction.normalize(),
+      ..Self::point(position, color, intensity, range)
+    }
+  }
+}
+
+impl Component for Light {}
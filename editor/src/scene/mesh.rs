@@ -0,0 +1,172 @@
+use std::f32::consts::PI;
+use std::path::PathBuf;
+
+use cgmath::{vec2, vec3, Array, InnerSpace, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use super::guid::Guid;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+  pub position: Vector3<f32>,
+  pub normal: Vector3<f32>,
+  pub uv: Vector2<f32>,
+}
+
+impl Vertex {
+  pub const fn new(position: Vector3<f32>, normal: Vector3<f32>, uv: Vector2<f32>) -> Self {
+    Self { position, normal, uv }
+  }
+}
+
+/// How a mesh is built, which is what scene files store instead of the vertices.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+  Cube,
+  Plane {
+    size: f32,
+  },
+  Sphere {
+    segments: u32,
+    rings: u32,
+  },
+  /// Imported from an `.obj` file through the asset database.
+  File(PathBuf),
+}
+
+impl MeshSource {
+  /// Meshes from a file start as a cube, their vertices come in once the file is imported.
+  pub fn build(&self) -> Mesh {
+    match self {
+      Self::Cube => Mesh::cube(),
+      Self::Plane { size } => Mesh::plane(*size),
+      Self::Sphere { segments, rings } => Mesh::sphere(*segments, *rings),
+      Self::File(path) => Mesh {
+        name: path.file_stem().unwrap_or_default().to_string_lossy().to_string(),
+        source: self.clone(),
+        ..Mesh::cube()
+      },
+    }
+  }
+}
+
+/// Indexed triangle list, counter-clockwise winding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh {
+  pub guid: Guid,
+  pub name: String,
+  pub source: MeshSource,
+  pub vertices: Vec<Vertex>,
+  pub indices: Vec<u32>,
+  /// Version of the imported file the vertices come from, 0 for built meshes.
+  pub revision: u64,
+}
+
+impl Mesh {
+  fn empty(name: &str, source: MeshSource) -> Self {
+    Self {
+      guid: Guid::new(),
+      name: name.to_string(),
+      source,
+      vertices: vec![],
+      indices: vec![],
+      revision: 0,
+    }
+  }
+
+  /// Local axis aligned bounds `(min, max)`.
+  pub fn bounds(&self) -> (Vector3<f32>, Vector3<f32>) {
+    let min = self.vertices.iter().fold(Vector3::from_value(f32::MAX), |m, v| {
+      vec3(m.x.min(v.position.x), m.y.min(v.position.y), m.z.min(v.position.z))
+    });
+    let max = self.vertices.iter().fold(Vector3::from_value(f32::MIN), |m, v| {
+      vec3(m.x.max(v.position.x), m.y.max(v.position.y), m.z.max(v.position.z))
+    });
+
+    (min, max)
+  }
+
+  /// Bounding sphere around the local origin, used for culling.
+  pub fn bounding_radius(&self) -> f32 {
+    self.vertices.iter().map(|v| v.position.magnitude()).fold(0.0, f32::max)
+  }
+
+  /// Unit cube centered at the origin.
+  pub fn cube() -> Self {
+    let faces = [
+      (vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0)),
+      (vec3(-1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0)),
+      (vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0)),
+      (vec3(0.0, -1.0, 0.0), vec3(1.0, 0.0, 0.0)),
+      (vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0)),
+      (vec3(0.0, 0.0, -1.0), vec3(-1.0, 0.0, 0.0)),
+    ];
+
+    let mut mesh = Self::empty("Cube", MeshSource::Cube);
+
+    for (normal, tangent) in faces {
+      let bitangent: Vector3<f32> = normal.cross(tangent);
+      let base = mesh.vertices.len() as u32;
+
+      for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+        let position = (normal + tangent * (u * 2.0 - 1.0) + bitangent * (v * 2.0 - 1.0)) * 0.5;
+        mesh.vertices.push(Vertex::new(position, normal, vec2(u, 1.0 - v)));
+      }
+
+      mesh
+        .indices
+        .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    mesh
+  }
+
+  /// Square on the XZ plane facing +Y.
+  pub fn plane(size: f32) -> Self {
+    let h = size * 0.5;
+    let normal = vec3(0.0, 1.0, 0.0);
+
+    Self {
+      vertices: vec![
+        Vertex::new(vec3(-h, 0.0, h), normal, vec2(0.0, 0.0)),
+        Vertex::new(vec3(h, 0.0, h), normal, vec2(1.0, 0.0)),
+        Vertex::new(vec3(h, 0.0, -h), normal, vec2(1.0, 1.0)),
+        Vertex::new(vec3(-h, 0.0, -h), normal, vec2(0.0, 1.0)),
+      ],
+      indices: vec![0, 1, 2, 0, 2, 3],
+      ..Self::empty("Plane", MeshSource::Plane { size })
+    }
+  }
+
+  /// UV sphere of radius 0.5.
+  pub fn sphere(segments: u32, rings: u32) -> Self {
+    let mut mesh = Self::empty("Sphere", MeshSource::Sphere { segments, rings });
+
+    for ring in 0..=rings {
+      let v = ring as f32 / rings as f32;
+      let theta = v * PI;
+
+      for segment in 0..=segments {
+        let u = segment as f32 / segments as f32;
+        let phi = u * PI * 2.0;
+        let normal = vec3(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+
+        mesh.vertices.push(Vertex::new(normal * 0.5, normal, vec2(u, v)));
+      }
+    }
+
+    let stride = segments + 1;
+
+    for ring in 0..rings {
+      for segment in 0..segments {
+        let a = ring * stride + segment;
+        let b = a + stride;
+
+        mesh.indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+      }
+    }
+
+    mesh
+  }
+}
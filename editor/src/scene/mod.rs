@@ -0,0 +1,267 @@
+pub mod camera;
+pub mod debug_draw;
+pub mod emitter;
+pub mod environment;
+pub mod file;
+pub mod frustum;
+pub mod grid;
+pub mod guid;
+pub mod light;
+pub mod mesh;
+pub mod prefab;
+pub mod prop;
+pub mod snapshot;
+pub mod transform;
+
+use cgmath::{point3, vec3, EuclideanSpace, Matrix4, Point3, Rad, Transform};
+use sagitario_ecs::{Bundle, Entity, Schedule, World};
+
+use camera::{Camera, SceneView};
+use emitter::ParticleEmitter;
+use environment::Environment;
+use grid::GroundGrid;
+use guid::Guid;
+use light::{Light, ShadowSettings};
+use mesh::Mesh;
+use prefab::Prefabs;
+use prop::{Material, MaterialId, MeshId, Prop};
+use transform::{propagate_transforms, GlobalTransform};
+
+/// Everything the viewport shows. Owned by the editor and handed to the renderer every frame.
+///
+/// Props, lights and particle emitters are components of the entities in `world`; meshes,
+/// materials and prefabs are shared assets they point into.
+#[derive(Debug)]
+pub struct Scene {
+  pub camera: Camera,
+  pub view: SceneView,
+  pub meshes: Vec<Mesh>,
+  pub materials: Vec<Material>,
+  /// Entity subtrees the world can hold instances of.
+  pub prefabs: Prefabs,
+  pub world: World,
+  /// Systems `update` runs over `world` every frame.
+  pub schedule: Schedule,
+  pub environment: Environment,
+  pub grid: GroundGrid,
+  /// Selected prop entities, in selection order; the last one is the primary selection.
+  pub selection: Vec<Entity>,
+}
+
+impl Scene {
+  /// A scene with these assets, no entities and the default camera and environment.
+  pub fn new(meshes: Vec<Mesh>, materials: Vec<Material>) -> Self {
+    let mut schedule = Schedule::new();
+    schedule.add_system(propagate_transforms());
+
+    Self {
+      camera: Camera::default(),
+      view: SceneView::default(),
+      meshes,
+      materials,
+      prefabs: Prefabs::new(),
+      world: World::new(),
+      schedule,
+      environment: Environment::default(),
+      grid: GroundGrid::default(),
+      selection: vec![],
+    }
+  }
+
+  /// Spawns an entity that is saved with the scene, under a new `Guid`.
+  pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+    self.world.spawn((Guid::new(), bundle))
+  }
+
+  /// The entity with this `Guid`, if it is alive.
+  pub fn entity(&self, guid: Guid) -> Option<Entity> {
+    let mut query = self.world.query::<(Entity, &Guid)>();
+    let found = query.iter().find(|(_, g)| **g == guid).map(|(e, _)| e);
+    found
+  }
+
+  /// Runs the scene systems; call once per frame before drawing.
+  pub fn update(&mut self) {
+    self.schedule.run(&mut self.world);
+  }
+
+  pub fn is_selected(&self, entity: Entity) -> bool {
+    self.selection.contains(&entity)
+  }
+
+  /// Selects only `entity`, or clears the selection with `None`.
+  pub fn select(&mut self, entity: Option<Entity>) {
+    self.selection.clear();
+    self.selection.extend(entity);
+  }
+
+  /// World space bounding box of a prop's mesh.
+  pub fn prop_bounds(&self, entity: Entity) -> Option<(Point3<f32>, Point3<f32>)> {
+    let prop = self.world.get::<Prop>(entity)?;
+    let transform = self.world.get::<GlobalTransform>(entity)?.0;
+    let (min, max) = self.meshes.get(prop.mesh.0)?.bounds();
+
+    let corners = (0..8).map(|i| {
+      let corner = vec3(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z },
+      );
+      transform.transform_point(Point3::from_vec(corner))
+    });
+
+    Some(bounds_of(corners))
+  }
+
+  /// Bounding box around every selected prop, `None` if nothing is selected.
+  pub fn selection_bounds(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+    let boxes = self
+      .selection
+      .iter()
+      .filter_map(|&e| self.prop_bounds(e))
+      .collect::<Vec<_>>();
+
+    (!boxes.is_empty()).then(|| bounds_of(boxes.into_iter().flat_map(|(min, max)| [min, max])))
+  }
+
+  /// Shift-click: adds `entity` as the primary selection, or removes it if it was already selected.
+  pub fn toggle_selected(&mut self, entity: Entity) {
+    if let Some(i) = self.selection.iter().position(|&e| e == entity) {
+      self.selection.remove(i);
+    } else {
+      self.selection.push(entity);
+    }
+  }
+
+  /// Every prop entity, in storage order.
+  pub fn props(&self) -> Vec<Entity> {
+    self.world.query::<(Entity, &Prop)>().iter().map(|(e, _)| e).collect()
+  }
+
+  /// Every light entity, in storage order; stable until a light is removed.
+  pub fn lights(&self) -> Vec<Entity> {
+    self.world.query::<(Entity, &Light)>().iter().map(|(e, _)| e).collect()
+  }
+}
+
+fn bounds_of(points: impl Iterator<Item = Point3<f32>>) -> (Point3<f32>, Point3<f32>) {
+  points.fold(
+    (
+      point3(f32::MAX, f32::MAX, f32::MAX),
+      point3(f32::MIN, f32::MIN, f32::MIN),
+    ),
+    |(lo, hi), p| {
+      (
+        point3(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z)),
+        point3(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z)),
+      )
+    },
+  )
+}
+
+impl Default for Scene {
+  fn default() -> Self {
+    let meshes = vec![Mesh::plane(1.0), Mesh::cube(), Mesh::sphere(24, 16)];
+    let materials = vec![
+      Material {
+        name: "Ground".to_string(),
+        base_color: [0.35, 0.35, 0.38, 1.0],
+        roughness: 0.9,
+        double_sided: true,
+        ..Default::default()
+      },
+      Material {
+        name: "Red".to_string(),
+        base_color: [0.8, 0.2, 0.15, 1.0],
+        roughness: 0.35,
+        ..Default::default()
+      },
+      Material {
+        name: "Green".to_string(),
+        base_color: [0.25, 0.7, 0.3, 1.0],
+        roughness: 0.7,
+        ..Default::default()
+      },
+      Material {
+        name: "Gold".to_string(),
+        base_color: [1.0, 0.77, 0.34, 1.0],
+        metallic: 1.0,
+        roughness: 0.25,
+        ..Default::default()
+      },
+    ];
+
+    let mut props = vec![(
+      Prop {
+        mesh: MeshId(0),
+        material: MaterialId(0),
+      },
+      Matrix4::from_nonuniform_scale(80.0, 1.0, 80.0),
+    )];
+
+    // Rejilla de props repetidos alrededor de la fuente.
+    for x in -16..16_i32 {
+      for z in -16..16 {
+        if (-2..2).contains(&x) && (-2..2).contains(&z) {
+          continue;
+        }
+
+        let cell = (x * 31 + z * 17).rem_euclid(7) as usize;
+        let position = vec3(x as f32 * 2.0 + 1.0, 0.5, z as f32 * 2.0 + 1.0);
+        let rotation = Matrix4::from_angle_y(Rad(cell as f32 * 0.4));
+
+        props.push((
+          Prop {
+            mesh: MeshId(1 + cell % 2),
+            material: MaterialId(1 + cell % 3),
+          },
+          Matrix4::from_translation(position) * rotation,
+        ));
+      }
+    }
+
+    let shadows = ShadowSettings {
+      enabled: true,
+      ..Default::default()
+    };
+
+    let mut lights = vec![
+      Light {
+        shadows,
+        ..Light::directional(vec3(-0.4, -1.0, -0.3), [1.0, 0.96, 0.9], 3.0)
+      },
+      Light {
+        shadows,
+        ..Light::spot(point3(0.0, 6.0, 0.0), vec3(0.2, -1.0, 0.1), [1.0, 0.6, 0.3], 60.0, 12.0)
+      },
+    ];
+
+    // Luces puntuales entre los props para probar el culling por clusters.
+    for x in -8..8 {
+      for z in -8..8 {
+        let hue = ((x + 8) * 16 + z + 8) as f32 / 256.0;
+        let color = [0, 2, 4].map(|k| (((hue * 6.0 + k as f32) % 6.0 - 3.0).abs() - 1.0).clamp(0.0, 1.0));
+
+        lights.push(Light::point(
+          point3(x as f32 * 4.0, 1.2, z as f32 * 4.0),
+          color,
+          4.0,
+          3.5,
+        ));
+      }
+    }
+
+    let mut scene = Self::new(meshes, materials);
+    scene.spawn(ParticleEmitter::default());
+
+    for (prop, matrix) in props {
+      scene.spawn((prop, transform::Transform::from_matrix(matrix), GlobalTransform(matrix)));
+    }
+
+    for light in lights {
+      scene.spawn(light);
+    }
+
+    scene
+  }
+}
@@ -0,0 +1,628 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use sagitario_ecs::{Component, Entity, World};
+use sagitario_reflect::{Reflect, Value};
+use serde::{Deserialize, Serialize};
+
+use super::file::{entity_entry, write_entries, EntityEntry, SceneError};
+use super::guid::Guid;
+use super::transform::{set_parent_local, subtree, Children};
+use super::Scene;
+
+/// Prefabs by GUID, as `Scene::prefabs` holds them.
+pub type Prefabs = BTreeMap<Guid, Prefab>;
+
+/// A saved entity subtree that can be instantiated many times. Entries can themselves be
+/// instances of other prefabs.
+///
+/// An instance derives the GUID of each of its entities by mixing the prefab's with a key, so
+/// the same entities come back on every load and overrides can name them by the prefab GUID.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Prefab {
+  pub guid: Guid,
+  pub name: String,
+  /// Entry the rest hang from. Instances are placed by overriding its transform.
+  pub root: Guid,
+  pub entities: Vec<EntityEntry>,
+}
+
+/// Makes an entry an instance of `prefab`, with the fields in `overrides` changed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct PrefabRef {
+  pub prefab: Guid,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub overrides: Vec<Override>,
+}
+
+/// A field an instance changed, e.g. `light.intensity`, with its `Value` as text. `entity` is the
+/// GUID the entity has inside the prefab.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct Override {
+  pub entity: Guid,
+  pub field: String,
+  pub value: String,
+}
+
+/// On the root of a prefab instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PrefabInstance {
+  pub prefab: Guid,
+}
+
+impl Component for PrefabInstance {}
+
+/// On every entity a prefab instance is made of, root included. They are rebuilt from the
+/// prefab on load instead of being saved one by one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PrefabPart {
+  /// `Guid` of the instance root.
+  pub instance: Guid,
+}
+
+impl Component for PrefabPart {}
+
+/// The instance root `entity` is part of, `None` outside instances.
+pub fn instance_root(scene: &Scene, entity: Entity) -> Option<Entity> {
+  let instance = scene.world.get::<PrefabPart>(entity)?.instance;
+  scene.entity(instance)
+}
+
+/// Spawns a new instance of `prefab` with `overrides` and returns its root.
+pub fn instantiate(scene: &mut Scene, prefab: Guid, overrides: &[Override]) -> Result<Entity, SceneError> {
+  let root = Guid::new();
+  let instance = PrefabRef {
+    prefab,
+    overrides: overrides.to_vec(),
+  };
+
+  let entries = instance_entries(&scene.prefabs, root, &instance)?;
+  let entities = write_entries(scene, &entries, HashMap::new())?;
+  tag_instance(&mut scene.world, root, prefab, entities.values().copied());
+  Ok(entities[&root])
+}
+
+/// Turns `root` and everything below it into a new prefab called `name`, and them into its first
+/// instance. Instances inside the subtree become nested prefabs.
+pub fn create_prefab(scene: &mut Scene, root: Entity, name: impl Into<String>) -> Result<Guid, SceneError> {
+  if scene.world.has::<PrefabPart>(root) && !scene.world.has::<PrefabInstance>(root) {
+    return Err(SceneError::PartOfInstance(root));
+  }
+
+  let root_guid = *scene.world.get::<Guid>(root).ok_or(SceneError::Unsaved(root))?;
+  let subtree = subtree(&scene.world, root);
+  let members = subtree.iter().filter_map(|&e| scene.world.get::<Guid>(e).map(|g| *g));
+  let members = members.collect::<HashSet<_>>();
+
+  let prefab = Prefab {
+    guid: Guid::new(),
+    name: name.into(),
+    root: Guid::new(),
+    entities: vec![],
+  };
+  let key = root_guid.mix(prefab.root);
+
+  let mut entities = subtree
+    .iter()
+    .filter_map(|&e| saved_entry(scene, e))
+    .collect::<Vec<_>>();
+
+  for entry in &mut entities {
+    entry.guid = entry.guid.mix(key);
+    entry.children.retain(|c| members.contains(c));
+    entry.children.iter_mut().for_each(|c| *c = c.mix(key));
+  }
+
+  entities.sort_by_key(|e| e.guid);
+  let guid = prefab.guid;
+  scene.prefabs.insert(guid, Prefab { entities, ..prefab });
+
+  // Lo que el prefab recrea pasa a ser parte de la nueva instancia, anidadas incluidas.
+  let parts = expand(&scene.prefabs, guid, key, &[], &mut vec![])?;
+  let parts = parts.into_iter().map(|e| e.guid).collect::<HashSet<_>>();
+  let parts = subtree.into_iter().filter(|&e| {
+    let guid = scene.world.get::<Guid>(e).map(|g| *g);
+    guid.is_some_and(|g| parts.contains(&g))
+  });
+  let parts = parts.collect::<Vec<_>>();
+
+  for &entity in &parts {
+    scene.world.remove::<PrefabInstance>(entity);
+  }
+
+  tag_instance(&mut scene.world, root_guid, guid, parts.into_iter());
+  Ok(guid)
+}
+
+/// Fields of the instance at `root` that differ from its prefab, sorted by entity and field.
+pub fn overrides(scene: &Scene, root: Entity) -> Result<Vec<Override>, SceneError> {
+  let instance = instance(scene, root)?;
+  let base = expand(&scene.prefabs, instance.prefab, instance.key, &[], &mut vec![])?;
+  let current = instance.parts.values().filter_map(|&e| entity_entry(scene, e));
+  let mut overrides = diff(&base, &current.collect::<Vec<_>>())?;
+
+  overrides.iter_mut().for_each(|o| o.entity = o.entity.mix(instance.key));
+  overrides.sort_by(|a, b| (a.entity, &a.field).cmp(&(b.entity, &b.field)));
+  Ok(overrides)
+}
+
+/// Resets every overridden field of the instance at `root` to the prefab's, except where it is
+/// placed.
+pub fn revert(scene: &mut Scene, root: Entity) -> Result<(), SceneError> {
+  let prefab_root = scene.prefabs[&instance(scene, root)?.prefab].root;
+  let mut overrides = overrides(scene, root)?;
+  overrides.retain(|o| is_placement(prefab_root, o));
+  rebuild(scene, root, &overrides)
+}
+
+/// Writes the overrides of the instance at `root` into its prefab, except where it is placed,
+/// and updates every instance. Returns how many fields were applied.
+pub fn apply_to_prefab(scene: &mut Scene, root: Entity) -> Result<usize, SceneError> {
+  let mut prefab = scene.prefabs[&instance(scene, root)?.prefab].clone();
+  let mut overrides = overrides(scene, root)?;
+  overrides.retain(|o| !is_placement(prefab.root, o));
+
+  for o in &overrides {
+    bake(&scene.prefabs, &mut prefab, o)?;
+  }
+
+  update_prefab(scene, prefab)?;
+  Ok(overrides.len())
+}
+
+/// Adds or replaces a prefab and updates its instances and those of every prefab nesting it.
+/// Overridden fields keep their values; the rest follow the new prefab.
+pub fn update_prefab(scene: &mut Scene, prefab: Prefab) -> Result<(), SceneError> {
+  let roots = scene
+    .world
+    .query::<(Entity, &PrefabInstance)>()
+    .iter()
+    .map(|(e, i)| (e, i.prefab))
+    .collect::<Vec<_>>();
+  let roots = roots
+    .into_iter()
+    .filter(|&(_, p)| depends_on(&scene.prefabs, p, prefab.guid, &mut vec![]));
+  let roots = roots.map(|(e, _)| e).collect::<Vec<_>>();
+
+  // Los overrides se miden contra el prefab anterior.
+  let overrides = roots
+    .iter()
+    .map(|&root| overrides(scene, root))
+    .collect::<Result<Vec<_>, _>>()?;
+  scene.prefabs.insert(prefab.guid, prefab);
+
+  for (root, overrides) in roots.into_iter().zip(overrides) {
+    rebuild(scene, root, &overrides)?;
+  }
+
+  Ok(())
+}
+
+/// `entity` as `SceneFile` stores it: an instance root as a `PrefabRef` with the children added
+/// to it, nothing for the rest of an instance, which the prefab rebuilds.
+pub(super) fn saved_entry(scene: &Scene, entity: Entity) -> Option<EntityEntry> {
+  let mut entry = entity_entry(scene, entity)?;
+
+  if !scene.world.has::<PrefabPart>(entity) {
+    return Some(entry);
+  }
+
+  let instance = instance(scene, entity).ok()?;
+  entry.children.retain(|c| !instance.parts.contains_key(c));
+
+  Some(EntityEntry {
+    guid: entry.guid,
+    children: entry.children,
+    prefab: Some(PrefabRef {
+      prefab: instance.prefab,
+      overrides: overrides(scene, entity).unwrap_or_default(),
+    }),
+    transform: None,
+    prop: None,
+    light: None,
+    emitter: None,
+  })
+}
+
+/// Entries of the instance whose root has the GUID `root`, GUIDs already mixed.
+pub(super) fn instance_entries(
+  prefabs: &Prefabs,
+  root: Guid,
+  instance: &PrefabRef,
+) -> Result<Vec<EntityEntry>, SceneError> {
+  let key = instance_key(prefabs, root, instance.prefab)?;
+  expand(prefabs, instance.prefab, key, &instance.overrides, &mut vec![])
+}
+
+/// Marks `parts` as the instance of `prefab` rooted at the entity with the GUID `root`.
+pub(super) fn tag_instance(world: &mut World, root: Guid, prefab: Guid, parts: impl Iterator<Item = Entity>) {
+  for entity in parts {
+    world.insert(entity, PrefabPart { instance: root });
+
+    if world.get::<Guid>(entity).is_some_and(|g| *g == root) {
+      world.insert(entity, PrefabInstance { prefab });
+    }
+  }
+}
+
+struct Instance {
+  prefab: Guid,
+  key: Guid,
+  guid: Guid,
+  /// Every part by GUID, root included.
+  parts: HashMap<Guid, Entity>,
+}
+
+fn instance(scene: &Scene, root: Entity) -> Result<Instance, SceneError> {
+  let world = &scene.world;
+  let prefab = world
+    .get::<PrefabInstance>(root)
+    .ok_or(SceneError::NotAnInstance(root))?
+    .prefab;
+  let guid = *world.get::<Guid>(root).ok_or(SceneError::Unsaved(root))?;
+
+  let mut query = world.query::<(Entity, &Guid, &PrefabPart)>();
+  let parts = query
+    .iter()
+    .filter(|(_, _, p)| p.instance == guid)
+    .map(|(e, g, _)| (*g, e));
+
+  Ok(Instance {
+    prefab,
+    key: instance_key(&scene.prefabs, guid, prefab)?,
+    guid,
+    parts: parts.collect(),
+  })
+}
+
+fn instance_key(prefabs: &Prefabs, root: Guid, prefab: Guid) -> Result<Guid, SceneError> {
+  let prefab = prefabs.get(&prefab).ok_or(SceneError::UnknownPrefab(prefab))?;
+  Ok(root.mix(prefab.root))
+}
+
+/// Whether the root transform of the instance sets it in place rather than changing the prefab.
+fn is_placement(prefab_root: Guid, o: &Override) -> bool {
+  o.entity == prefab_root && o.field.starts_with("transform.")
+}
+
+/// Rewrites the instance at `root` from its prefab and `overrides`. Entities the prefab still has
+/// are kept, so handles, selection and history stay valid; the rest are despawned.
+fn rebuild(scene: &mut Scene, root: Entity, overrides: &[Override]) -> Result<(), SceneError> {
+  let Instance {
+    prefab,
+    key,
+    guid,
+    mut parts,
+  } = instance(scene, root)?;
+  let entries = expand(&scene.prefabs, prefab, key, overrides, &mut vec![])?;
+  let kept = entries.iter().filter_map(|e| Some((e.guid, parts.remove(&e.guid)?)));
+  let kept = kept.collect::<HashMap<_, _>>();
+
+  // Lo que el prefab ya no tiene se suelta de la jerarquia antes de borrarlo.
+  for &stale in parts.values() {
+    let children = scene.world.get::<Children>(stale).map(|c| c.iter().collect::<Vec<_>>());
+
+    for child in children.unwrap_or_default() {
+      set_parent_local(&mut scene.world, child, None)?;
+    }
+
+    set_parent_local(&mut scene.world, stale, None)?;
+    scene.world.despawn(stale);
+  }
+
+  let entities = write_entries(scene, &entries, kept)?;
+  tag_instance(&mut scene.world, guid, prefab, entities.values().copied());
+  Ok(())
+}
+
+/// Entries of an instance of `prefab` with the key `key`, nested prefabs flattened and
+/// `overrides` applied. Overrides of entities or fields the prefab no longer has are dropped.
+fn expand(
+  prefabs: &Prefabs,
+  prefab: Guid,
+  key: Guid,
+  overrides: &[Override],
+  stack: &mut Vec<Guid>,
+) -> Result<Vec<EntityEntry>, SceneError> {
+  if stack.contains(&prefab) {
+    return Err(SceneError::PrefabCycle(prefab));
+  }
+
+  let definition = prefabs.get(&prefab).ok_or(SceneError::UnknownPrefab(prefab))?;
+  let mut entries = vec![];
+  stack.push(prefab);
+
+  for entry in &definition.entities {
+    let Some(nested) = &entry.prefab else {
+      entries.push(entry.clone());
+      continue;
+    };
+
+    let key = instance_key(prefabs, entry.guid, nested.prefab)?;
+    let mut inner = expand(prefabs, nested.prefab, key, &nested.overrides, stack)?;
+
+    if let Some(root) = inner.iter_mut().find(|e| e.guid == entry.guid) {
+      root.children.extend(&entry.children);
+    }
+
+    entries.extend(inner);
+  }
+
+  stack.pop();
+
+  for o in overrides {
+    if let Some(entry) = entries.iter_mut().find(|e| e.guid == o.entity) {
+      set_override(entry, o).ok();
+    }
+  }
+
+  for entry in &mut entries {
+    entry.guid = entry.guid.mix(key);
+    entry.children.iter_mut().for_each(|c| *c = c.mix(key));
+  }
+
+  Ok(entries)
+}
+
+/// Stores `o` in `prefab`: on its entry, or as an override of the nested instance it is in.
+fn bake(prefabs: &Prefabs, prefab: &mut Prefab, o: &Override) -> Result<(), SceneError> {
+  for entry in &mut prefab.entities {
+    let Some(nested) = &mut entry.prefab else {
+      if entry.guid == o.entity {
+        return set_override(entry, o);
+      }
+
+      continue;
+    };
+
+    let key = instance_key(prefabs, entry.guid, nested.prefab)?;
+    let inner = expand(prefabs, nested.prefab, key, &[], &mut vec![])?;
+
+    if inner.iter().any(|e| e.guid == o.entity) {
+      let entity = o.entity.mix(key);
+      nested.overrides.retain(|n| n.entity != entity || n.field != o.field);
+      nested.overrides.push(Override { entity, ..o.clone() });
+      nested
+        .overrides
+        .sort_by(|a, b| (a.entity, &a.field).cmp(&(b.entity, &b.field)));
+      return Ok(());
+    }
+  }
+
+  Ok(())
+}
+
+fn depends_on(prefabs: &Prefabs, prefab: Guid, on: Guid, stack: &mut Vec<Guid>) -> bool {
+  if prefab == on {
+    return true;
+  }
+
+  if stack.contains(&prefab) {
+    return false;
+  }
+
+  stack.push(prefab);
+  let nested = prefabs.get(&prefab).into_iter().flat_map(|p| &p.entities);
+  let found = nested
+    .filter_map(|e| e.prefab.as_ref())
+    .any(|n| depends_on(prefabs, n.prefab, on, stack));
+  stack.pop();
+  found
+}
+
+/// Fields of `current` whose value differs from the same entity in `base`.
+fn diff(base: &[EntityEntry], current: &[EntityEntry]) -> Result<Vec<Override>, SceneError> {
+  let current = current.iter().map(|e| (e.guid, e)).collect::<HashMap<_, _>>();
+  let mut overrides = vec![];
+
+  for entry in base {
+    let Some(now) = current.get(&entry.guid) else {
+      continue;
+    };
+
+    let now = entry_fields(now);
+
+    for (field, value) in entry_fields(entry) {
+      match now.get(&field) {
+        Some(changed) if *changed != value => overrides.push(Override {
+          entity: entry.guid,
+          field,
+          value: changed.to_string(),
+        }),
+        _ => {}
+      }
+    }
+  }
+
+  Ok(overrides)
+}
+
+/// Top level fields of the components of `entry`, by `component.field` name.
+fn entry_fields(entry: &EntityEntry) -> BTreeMap<String, Value> {
+  let mut all = BTreeMap::new();
+
+  for component in entry.fields().iter().filter(|c| !c.hidden) {
+    let Some(value) = entry.field(component.name) else {
+      continue;
+    };
+
+    // Un componente que la entidad no tiene no lista campos.
+    for field in value.fields() {
+      if let Some(field_value) = value.field(field.name) {
+        all.insert(format!("{}.{}", component.name, field.name), field_value.to_value());
+      }
+    }
+  }
+
+  all
+}
+
+fn set_override(entry: &mut EntityEntry, o: &Override) -> Result<(), SceneError> {
+  Ok(entry.set_path(&o.field, &Value::parse(&o.value)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::scene::file::tests::{find, guid_of, hierarchy};
+  use crate::scene::file::SceneFile;
+  use crate::scene::light::Light;
+  use crate::scene::transform::Transform;
+
+  /// The light of the prefab instance rooted at `root`.
+  fn light_of(scene: &Scene, root: Entity) -> Entity {
+    let instance = *scene.world.get::<Guid>(root).unwrap();
+    let mut query = scene.world.query::<(Entity, &PrefabPart, &Light)>();
+    let found = query.iter().find(|(_, p, _)| p.instance == instance).map(|(e, _, _)| e);
+    found.unwrap()
+  }
+
+  fn intensity(scene: &Scene, entity: Entity) -> f32 {
+    scene.world.get::<Light>(entity).unwrap().intensity
+  }
+
+  /// The `hierarchy` turned into a prefab, with its first instance and a second one.
+  fn instances() -> (Scene, Entity, Entity) {
+    let (mut scene, [root, ..]) = hierarchy();
+    let root = find(&scene, root);
+    let prefab = create_prefab(&mut scene, root, "Prefab").unwrap();
+    let second = instantiate(&mut scene, prefab, &[]).unwrap();
+    scene.update();
+    (scene, root, second)
+  }
+
+  #[test]
+  fn prefab_instances_round_trip() {
+    let (mut scene, first, second) = instances();
+    let light = light_of(&scene, second);
+    scene.world.get_mut::<Light>(light).unwrap().intensity = 7.5;
+    scene.world.get_mut::<Transform>(second).unwrap().translation.x += 4.0;
+
+    let text = scene.to_ron().unwrap();
+    let loaded = Scene::from_ron(&text).unwrap();
+    let (first, second) = (
+      find(&loaded, guid_of(&scene, first)),
+      find(&loaded, guid_of(&scene, second)),
+    );
+
+    assert_eq!(loaded.to_ron().unwrap(), text);
+    assert_eq!(loaded.world.len(), scene.world.len());
+    assert_eq!(overrides(&loaded, first).unwrap(), vec![]);
+
+    let mut fields = overrides(&loaded, second)
+      .unwrap()
+      .into_iter()
+      .map(|o| o.field)
+      .collect::<Vec<_>>();
+    fields.sort();
+    assert_eq!(fields, ["light.intensity", "transform.translation"]);
+    assert_eq!(intensity(&loaded, light_of(&loaded, second)), 7.5);
+  }
+
+  #[test]
+  fn prefab_edits_keep_overrides() {
+    let (mut scene, first, second) = instances();
+    let (light, other) = (light_of(&scene, first), light_of(&scene, second));
+    scene.world.get_mut::<Light>(other).unwrap().intensity = 7.5;
+    scene.world.get_mut::<Light>(light).unwrap().intensity = 2.0;
+    scene.world.get_mut::<Light>(light).unwrap().range = 20.0;
+    scene.world.get_mut::<Transform>(first).unwrap().translation.x += 4.0;
+
+    assert_eq!(apply_to_prefab(&mut scene, first).unwrap(), 2);
+    assert_eq!(intensity(&scene, other), 7.5);
+    assert_eq!(scene.world.get::<Light>(other).unwrap().range, 20.0);
+    assert_eq!(overrides(&scene, first).unwrap().len(), 1);
+
+    revert(&mut scene, second).unwrap();
+    assert_eq!(intensity(&scene, other), 2.0);
+    assert!(overrides(&scene, second).unwrap().is_empty());
+  }
+
+  #[test]
+  fn nested_prefabs_take_overrides() {
+    let (mut scene, [root, _, b, ..]) = hierarchy();
+    let (root, b) = (find(&scene, root), find(&scene, b));
+    let inner = create_prefab(&mut scene, b, "Inner").unwrap();
+    create_prefab(&mut scene, root, "Outer").unwrap();
+
+    let light = light_of(&scene, root);
+    scene.world.get_mut::<Light>(light).unwrap().intensity = 9.0;
+    apply_to_prefab(&mut scene, root).unwrap();
+
+    // El cambio queda como override de la instancia anidada, no en el prefab interior.
+    let outer = scene.prefabs.values().find(|p| p.guid != inner).unwrap();
+    let nested = outer.entities.iter().find_map(|e| e.prefab.as_ref()).unwrap();
+    assert_eq!(nested.prefab, inner);
+    assert_eq!(nested.overrides.len(), 1);
+    assert_ne!(
+      scene.prefabs[&inner]
+        .entities
+        .iter()
+        .find_map(|e| e.light.clone())
+        .unwrap()
+        .intensity,
+      9.0
+    );
+
+    let text = scene.to_ron().unwrap();
+    let loaded = Scene::from_ron(&text).unwrap();
+    assert_eq!(loaded.to_ron().unwrap(), text);
+    assert_eq!(
+      intensity(&loaded, light_of(&loaded, find(&loaded, guid_of(&scene, root)))),
+      9.0
+    );
+  }
+
+  #[test]
+  fn rejects_prefab_cycles() {
+    let (mut scene, [root, ..]) = hierarchy();
+    let root = find(&scene, root);
+    let prefab = create_prefab(&mut scene, root, "Prefab").unwrap();
+    let mut file = SceneFile::from_scene(&scene);
+    let definition = &mut file.prefabs[0];
+    let entry = definition
+      .entities
+      .iter_mut()
+      .find(|e| e.guid != definition.root)
+      .unwrap();
+    entry.prefab = Some(PrefabRef {
+      prefab,
+      overrides: vec![],
+    });
+
+    assert!(matches!(file.into_scene(), Err(SceneError::PrefabCycle(_))));
+  }
+
+  #[test]
+  fn applied_overrides_reach_every_instance_and_revert_restores_them() {
+    let (mut scene, first, second) = instances();
+    let prefab = scene.world.get::<PrefabInstance>(first).unwrap().prefab;
+    let (light, other) = (light_of(&scene, first), light_of(&scene, second));
+    scene.world.get_mut::<Light>(light).unwrap().intensity = 3.0;
+
+    assert_eq!(overrides(&scene, first).unwrap().len(), 1);
+    assert_eq!(apply_to_prefab(&mut scene, first).unwrap(), 1);
+    assert!(overrides(&scene, first).unwrap().is_empty());
+    assert_eq!(intensity(&scene, other), 3.0);
+
+    let baked = scene.prefabs[&prefab].entities.iter().find_map(|e| e.light.clone());
+    assert_eq!(baked.unwrap().intensity, 3.0);
+
+    // Revertir deja la colocacion de la instancia y devuelve el resto al prefab.
+    scene.world.get_mut::<Light>(other).unwrap().intensity = 5.0;
+    scene.world.get_mut::<Transform>(second).unwrap().translation.x += 4.0;
+    let placed = *scene.world.get::<Transform>(second).unwrap();
+
+    revert(&mut scene, second).unwrap();
+
+    assert_eq!(intensity(&scene, other), 3.0);
+    assert_eq!(*scene.world.get::<Transform>(second).unwrap(), placed);
+    assert_eq!(
+      overrides(&scene, second)
+        .unwrap()
+        .into_iter()
+        .map(|o| o.field)
+        .collect::<Vec<_>>(),
+      ["transform.translation"]
+    );
+  }
+}
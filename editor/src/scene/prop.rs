@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use sagitario_ecs::Component;
+use serde::{Deserialize, Serialize};
+
+use super::guid::Guid;
+
+/// Index into `Scene::meshes`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeshId(pub usize);
+
+/// Index into `Scene::materials`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(pub usize);
+
+/// Image files of a material, `None` uses the neutral value of that map.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialTextures {
+  /// sRGB base color, multiplied by `Material::base_color`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub albedo: Option<PathBuf>,
+  /// Tangent space normal map.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub normal: Option<PathBuf>,
+  /// glTF layout: roughness in G, metallic in B.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metallic_roughness: Option<PathBuf>,
+  /// Ambient occlusion in R.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub occlusion: Option<PathBuf>,
+  /// sRGB emissive color, multiplied by `Material::emissive`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub emissive: Option<PathBuf>,
+}
+
+/// Metallic-roughness PBR material; every factor multiplies its texture.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+  pub guid: Guid,
+  pub name: String,
+  pub base_color: [f32; 4],
+  pub metallic: f32,
+  pub roughness: f32,
+  /// Linear emitted radiance, can go above 1.
+  pub emissive: [f32; 3],
+  pub occlusion_strength: f32,
+  pub normal_scale: f32,
+  pub textures: MaterialTextures,
+  /// Drawn without back-face culling, which needs its own pipeline.
+  pub double_sided: bool,
+}
+
+impl Default for Material {
+  fn default() -> Self {
+    Self {
+      guid: Guid::new(),
+      name: "Default".to_string(),
+      base_color: [0.8, 0.8, 0.8, 1.0],
+      metallic: 0.0,
+      roughness: 0.5,
+      emissive: [0.0; 3],
+      occlusion_strength: 1.0,
+      normal_scale: 1.0,
+      textures: MaterialTextures::default(),
+      double_sided: false,
+    }
+  }
+}
+
+/// A mesh instance placed in the scene.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Prop {
+  pub mesh: MeshId,
+  pub material: MaterialId,
+}
+
+impl Component for Prop {}
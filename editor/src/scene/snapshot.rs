@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use sagitario_ecs::Entity;
+
+use super::file::{entity_entry, write_entries, EntityEntry, SceneError};
+use super::guid::Guid;
+use super::prefab::{PrefabInstance, PrefabPart};
+use super::transform::{parent_of, set_parent_local, subtree};
+use super::Scene;
+
+/// An entity and everything below it, with enough to spawn them again as they were, under the
+/// same GUIDs and parent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subtree {
+  /// Parents first, so the root comes first.
+  entries: Vec<EntityEntry>,
+  parent: Option<Guid>,
+  parts: Vec<(Guid, PrefabPart)>,
+  instances: Vec<(Guid, PrefabInstance)>,
+}
+
+impl Subtree {
+  /// `None` if `root` has no `Guid`.
+  pub fn capture(scene: &Scene, root: Entity) -> Option<Self> {
+    let world = &scene.world;
+    let guid = |e| world.get::<Guid>(e).map(|g| *g);
+    let entities = subtree(world, root);
+    guid(root)?;
+
+    Some(Self {
+      entries: entities.iter().filter_map(|&e| entity_entry(scene, e)).collect(),
+      parent: parent_of(world, root).and_then(guid),
+      parts: entities
+        .iter()
+        .filter_map(|&e| Some((guid(e)?, *world.get::<PrefabPart>(e)?)))
+        .collect(),
+      instances: entities
+        .iter()
+        .filter_map(|&e| Some((guid(e)?, *world.get::<PrefabInstance>(e)?)))
+        .collect(),
+    })
+  }
+
+  pub fn root(&self) -> Guid {
+    self.entries[0].guid
+  }
+
+  /// Spawns the entities again, attached to their old parent.
+  pub fn restore(&self, scene: &mut Scene) -> Result<(), SceneError> {
+    let entities = write_entries(scene, &self.entries, HashMap::new())?;
+
+    for (guid, part) in &self.parts {
+      scene.world.insert(entities[guid], *part);
+    }
+
+    for (guid, instance) in &self.instances {
+      scene.world.insert(entities[guid], *instance);
+    }
+
+    let parent = match self.parent {
+      Some(guid) => Some(scene.entity(guid).ok_or(SceneError::MissingReference(guid))?),
+      None => None,
+    };
+
+    set_parent_local(&mut scene.world, entities[&self.root()], parent)?;
+    Ok(())
+  }
+
+  /// Despawns the entities, detached from their parent first, and drops them from the selection.
+  pub fn despawn(&self, scene: &mut Scene) {
+    let Some(root) = scene.entity(self.root()) else {
+      return;
+    };
+
+    set_parent_local(&mut scene.world, root, None).ok();
+
+    for entity in subtree(&scene.world, root) {
+      scene.world.despawn(entity);
+      scene.selection.retain(|&e| e != entity);
+    }
+  }
+}
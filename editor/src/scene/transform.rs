@@ -0,0 +1,490 @@
+use std::collections::BTreeSet;
+
+use cgmath::{vec3, InnerSpace, Matrix3, Matrix4, One, Quaternion, SquareMatrix, Vector3, Zero};
+use sagitario_ecs::{Changed, Component, Entity, Query, System, World};
+use sagitario_reflect::Reflect;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Position, rotation and scale of an entity relative to its `Parent`, or to the world if it has
+/// none.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[serde(default)]
+pub struct Transform {
+  pub translation: Vector3<f32>,
+  pub rotation: Quaternion<f32>,
+  pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+  fn default() -> Self {
+    Self {
+      translation: Vector3::zero(),
+      rotation: Quaternion::one(),
+      scale: vec3(1.0, 1.0, 1.0),
+    }
+  }
+}
+
+impl Transform {
+  /// Splits an affine matrix into translation, rotation and scale. Shear can't be represented
+  /// and is dropped; a mirrored matrix gets a negative X scale.
+  pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+    let columns = [matrix.x, matrix.y, matrix.z].map(|c| c.truncate());
+    let mut scale = columns.map(|c| c.magnitude());
+
+    if Matrix3::from_cols(columns[0], columns[1], columns[2]).determinant() < 0.0 {
+      scale[0] = -scale[0];
+    }
+
+    // Una escala nula no tiene rotacion; se toma el eje del mundo.
+    let axes = [0, 1, 2].map(|i| match scale[i] {
+      s if s.abs() > f32::EPSILON => columns[i] / s,
+      _ => Matrix3::identity()[i],
+    });
+    let rotation = Matrix3::from_cols(axes[0], axes[1], axes[2]);
+
+    Self {
+      translation: matrix.w.truncate(),
+      rotation: Quaternion::from(rotation).normalize(),
+      scale: vec3(scale[0], scale[1], scale[2]),
+    }
+  }
+
+  pub fn matrix(&self) -> Matrix4<f32> {
+    Matrix4::from_translation(self.translation)
+      * Matrix4::from(self.rotation)
+      * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+  }
+}
+
+impl Component for Transform {}
+
+/// World matrix of an entity, written by `propagate_transforms`; edit `Transform` instead.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlobalTransform(pub Matrix4<f32>);
+
+impl Default for GlobalTransform {
+  fn default() -> Self {
+    Self(Matrix4::identity())
+  }
+}
+
+impl Component for GlobalTransform {}
+
+/// Entity this one is attached to. Changed only through `set_parent`, which keeps `Children`
+/// in sync.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Parent(Entity);
+
+impl Parent {
+  pub fn get(&self) -> Entity {
+    self.0
+  }
+}
+
+impl Component for Parent {}
+
+/// Entities attached to this one, in the order they were attached.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(Vec<Entity>);
+
+impl Children {
+  pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+    self.0.iter().copied()
+  }
+}
+
+impl Component for Children {}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HierarchyError {
+  #[error("entity {0} doesn't exist")]
+  NoSuchEntity(Entity),
+  #[error("{child} can't be attached to {parent}, it is one of its ancestors")]
+  Cycle { child: Entity, parent: Entity },
+  #[error("the world matrix of {0} can't be inverted")]
+  Singular(Entity),
+}
+
+pub fn parent_of(world: &World, entity: Entity) -> Option<Entity> {
+  world.get::<Parent>(entity).map(|p| p.get())
+}
+
+/// `entity`'s parent, grandparent and so on up to its root.
+pub fn ancestors(world: &World, entity: Entity) -> impl Iterator<Item = Entity> + '_ {
+  std::iter::successors(parent_of(world, entity), move |&e| parent_of(world, e))
+}
+
+/// `root` and everything below it, parents first.
+pub fn subtree(world: &World, root: Entity) -> Vec<Entity> {
+  let mut entities = vec![root];
+  let mut i = 0;
+
+  while let Some(&entity) = entities.get(i) {
+    if let Some(children) = world.get::<Children>(entity) {
+      entities.extend(children.iter());
+    }
+
+    i += 1;
+  }
+
+  entities
+}
+
+/// World matrix from the `Transform`s up the hierarchy, current even before
+/// `propagate_transforms` has run.
+pub fn world_matrix(world: &World, entity: Entity) -> Matrix4<f32> {
+  let local = |e| world.get::<Transform>(e).map_or(Matrix4::identity(), |t| t.matrix());
+
+  ancestors(world, entity).fold(local(entity), |matrix, ancestor| local(ancestor) * matrix)
+}
+
+/// Moves `entity` so its world matrix becomes `matrix`, rewriting its local `Transform`. The
+/// `GlobalTransform` is updated right away so tools see it before the next propagation.
+pub fn set_world_matrix(world: &mut World, entity: Entity, matrix: Matrix4<f32>) -> Result<(), HierarchyError> {
+  if !world.is_alive(entity) {
+    return Err(HierarchyError::NoSuchEntity(entity));
+  }
+
+  let parent = match parent_of(world, entity) {
+    Some(parent) => world_matrix(world, parent),
+    None => Matrix4::identity(),
+  };
+  let inverse = parent.invert().ok_or(HierarchyError::Singular(entity))?;
+  let transform = Transform::from_matrix(inverse * matrix);
+
+  world.insert(entity, (transform, GlobalTransform(parent * transform.matrix())));
+  Ok(())
+}
+
+/// Attaches `child` to `parent`, or detaches it with `None`, keeping its world transform.
+/// Fails instead of creating a cycle.
+pub fn set_parent(world: &mut World, child: Entity, parent: Option<Entity>) -> Result<(), HierarchyError> {
+  check_parent(world, child, parent)?;
+
+  if parent_of(world, child) == parent {
+    return Ok(());
+  }
+
+  if let Some(parent) = parent.filter(|&p| world_matrix(world, p).invert().is_none()) {
+    return Err(HierarchyError::Singular(parent));
+  }
+
+  let matrix = world_matrix(world, child);
+  relink(world, child, parent);
+  set_world_matrix(world, child, matrix)
+}
+
+/// Like `set_parent` but keeps the local `Transform`, so `child` moves with its new parent.
+/// Loaders use it to rebuild a saved hierarchy without touching the saved transforms.
+pub fn set_parent_local(world: &mut World, child: Entity, parent: Option<Entity>) -> Result<(), HierarchyError> {
+  check_parent(world, child, parent)?;
+
+  if parent_of(world, child) != parent {
+    relink(world, child, parent);
+  }
+
+  Ok(())
+}
+
+fn check_parent(world: &World, child: Entity, parent: Option<Entity>) -> Result<(), HierarchyError> {
+  for entity in std::iter::once(child).chain(parent) {
+    if !world.is_alive(entity) {
+      return Err(HierarchyError::NoSuchEntity(entity));
+    }
+  }
+
+  match parent {
+    Some(parent) if parent == child || ancestors(world, parent).any(|e| e == child) => {
+      Err(HierarchyError::Cycle { child, parent })
+    }
+    _ => Ok(()),
+  }
+}
+
+/// Moves `child` from its current parent's `Children` to `parent`'s.
+fn relink(world: &mut World, child: Entity, parent: Option<Entity>) {
+  if let Some(old) = parent_of(world, child) {
+    detach(world, old, child);
+  }
+
+  match parent {
+    Some(parent) => {
+      world.insert(child, Parent(parent));
+
+      match world.get_mut::<Children>(parent) {
+        Some(mut children) => children.0.push(child),
+        None => _ = world.insert(parent, Children(vec![child])),
+      }
+    }
+    None => _ = world.remove::<Parent>(child),
+  }
+
+  // Sin `Parent` no hay cambio que detectar; la propagacion parte del `Transform`.
+  if let Some(mut transform) = world.get_mut::<Transform>(child) {
+    transform.set_changed();
+  }
+}
+
+fn detach(world: &mut World, parent: Entity, child: Entity) {
+  let Some(mut children) = world.get_mut::<Children>(parent) else {
+    return;
+  };
+
+  children.0.retain(|&c| c != child);
+
+  if children.0.is_empty() {
+    world.remove::<Children>(parent);
+  }
+}
+
+type Nodes<'w> = Query<'w, (&'static Transform, Option<&'static Children>)>;
+type Globals<'w> = Query<'w, &'static mut GlobalTransform>;
+
+/// System that rewrites the `GlobalTransform` of every entity whose `Transform` or `Parent`
+/// changed since its last run, and of everything below them. Parents are written before their
+/// children and untouched branches are skipped.
+pub fn propagate_transforms() -> System {
+  System::new("propagate_transforms", |context| {
+    let mut dirty = BTreeSet::new();
+    dirty.extend(
+      context
+        .query_filtered::<(Entity, &Transform), Changed<Transform>>()
+        .iter()
+        .map(|(e, _)| e),
+    );
+    dirty.extend(
+      context
+        .query_filtered::<(Entity, &Parent), Changed<Parent>>()
+        .iter()
+        .map(|(e, _)| e),
+    );
+
+    let mut parents = context.query::<&Parent>();
+    let mut nodes = context.query::<(&Transform, Option<&Children>)>();
+    let mut globals = context.query::<&mut GlobalTransform>();
+
+    for &entity in &dirty {
+      let parent = parents.get(entity).map(|p| p.get());
+
+      // Un ancestro sucio ya recorre esta rama.
+      if std::iter::successors(parent, |&e| parents.get(e).map(|p| p.get())).any(|e| dirty.contains(&e)) {
+        continue;
+      }
+
+      let parent_matrix = parent.and_then(|p| globals.get(p).map(|g| g.0));
+      propagate(
+        &mut nodes,
+        &mut globals,
+        entity,
+        parent_matrix.unwrap_or(Matrix4::identity()),
+      );
+    }
+  })
+  .with_query::<(Entity, &Transform), Changed<Transform>>()
+  .with_query::<(Entity, &Parent), Changed<Parent>>()
+  .with_query::<(&Transform, Option<&Children>), ()>()
+  .with_query::<&mut GlobalTransform, ()>()
+}
+
+fn propagate(nodes: &mut Nodes, globals: &mut Globals, entity: Entity, parent: Matrix4<f32>) {
+  let Some((transform, children)) = nodes.get(entity) else {
+    return;
+  };
+
+  let matrix = parent * transform.matrix();
+  let children = children.map(|c| c.iter().collect::<Vec<_>>()).unwrap_or_default();
+
+  if let Some(mut global) = globals.get(entity) {
+    if global.0 != matrix {
+      global.0 = matrix;
+    }
+  }
+
+  for child in children {
+    propagate(nodes, globals, child, matrix);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{Deg, Rotation3};
+  use sagitario_ecs::Schedule;
+
+  use super::*;
+
+  fn transform(x: f32) -> Transform {
+    Transform {
+      translation: vec3(x, 2.0 * x, -x),
+      rotation: Quaternion::from_angle_y(Deg(x * 20.0)),
+      scale: vec3(1.0, 0.5 + x, 2.0),
+    }
+  }
+
+  fn spawn(world: &mut World, x: f32) -> Entity {
+    world.spawn((transform(x), GlobalTransform::default()))
+  }
+
+  fn assert_close(found: Matrix4<f32>, expected: Matrix4<f32>) {
+    assert!(
+      (0..4).all(|i| (found[i] - expected[i]).magnitude() < 1e-4),
+      "{:?} != {:?}",
+      found,
+      expected
+    );
+  }
+
+  fn global(world: &World, entity: Entity) -> Matrix4<f32> {
+    world.get::<GlobalTransform>(entity).unwrap().0
+  }
+
+  #[test]
+  fn reparenting_keeps_the_world_matrix() {
+    let mut world = World::new();
+    let parent = spawn(&mut world, 1.0);
+    let child = spawn(&mut world, 2.0);
+    let matrix = world_matrix(&world, child);
+    // Con escala no uniforme el hijo giraria con cizalla, que `Transform` no representa.
+    world.get_mut::<Transform>(parent).unwrap().scale = vec3(2.0, 2.0, 2.0);
+
+    set_parent(&mut world, child, Some(parent)).unwrap();
+
+    assert_eq!(parent_of(&world, child), Some(parent));
+    assert_eq!(
+      world.get::<Children>(parent).unwrap().iter().collect::<Vec<_>>(),
+      [child]
+    );
+    assert_close(world_matrix(&world, child), matrix);
+    assert_close(global(&world, child), matrix);
+
+    set_parent(&mut world, child, None).unwrap();
+
+    assert_eq!(parent_of(&world, child), None);
+    assert!(!world.has::<Children>(parent));
+    assert_close(world_matrix(&world, child), matrix);
+  }
+
+  #[test]
+  fn set_parent_local_keeps_the_local_transform() {
+    let mut world = World::new();
+    let parent = spawn(&mut world, 1.0);
+    let child = spawn(&mut world, 2.0);
+
+    set_parent_local(&mut world, child, Some(parent)).unwrap();
+
+    assert_eq!(*world.get::<Transform>(child).unwrap(), transform(2.0));
+    assert_close(
+      world_matrix(&world, child),
+      transform(1.0).matrix() * transform(2.0).matrix(),
+    );
+  }
+
+  #[test]
+  fn rejects_cycles_and_self_parenting() {
+    let mut world = World::new();
+    let [a, b, c] = [1.0, 2.0, 3.0].map(|x| spawn(&mut world, x));
+    set_parent(&mut world, b, Some(a)).unwrap();
+    set_parent(&mut world, c, Some(b)).unwrap();
+
+    assert_eq!(
+      set_parent(&mut world, a, Some(c)),
+      Err(HierarchyError::Cycle { child: a, parent: c })
+    );
+    assert_eq!(
+      set_parent(&mut world, b, Some(b)),
+      Err(HierarchyError::Cycle { child: b, parent: b })
+    );
+    assert_eq!(
+      set_parent_local(&mut world, a, Some(b)),
+      Err(HierarchyError::Cycle { child: a, parent: b })
+    );
+
+    // Nada cambia tras un error.
+    assert_eq!(parent_of(&world, a), None);
+    assert_eq!(parent_of(&world, b), Some(a));
+    assert_eq!(ancestors(&world, c).collect::<Vec<_>>(), [b, a]);
+  }
+
+  #[test]
+  fn rejects_dead_and_singular_parents() {
+    let mut world = World::new();
+    let [child, dead, flat] = [1.0, 2.0, 3.0].map(|x| spawn(&mut world, x));
+    world.despawn(dead);
+    world.get_mut::<Transform>(flat).unwrap().scale = vec3(1.0, 0.0, 1.0);
+
+    assert_eq!(
+      set_parent(&mut world, child, Some(dead)),
+      Err(HierarchyError::NoSuchEntity(dead))
+    );
+    assert_eq!(
+      set_parent(&mut world, child, Some(flat)),
+      Err(HierarchyError::Singular(flat))
+    );
+    assert_eq!(parent_of(&world, child), None);
+  }
+
+  #[test]
+  fn propagation_only_rewrites_dirty_branches() {
+    let mut world = World::new();
+    let [root, child, grandchild, other, other_child] = [1.0, 2.0, 3.0, 4.0, 5.0].map(|x| spawn(&mut world, x));
+    set_parent_local(&mut world, child, Some(root)).unwrap();
+    set_parent_local(&mut world, grandchild, Some(child)).unwrap();
+    set_parent_local(&mut world, other_child, Some(other)).unwrap();
+
+    let mut schedule = Schedule::new();
+    schedule.add_system(propagate_transforms());
+    schedule.run(&mut world);
+
+    for entity in [root, child, grandchild, other, other_child] {
+      assert_close(global(&world, entity), world_matrix(&world, entity));
+    }
+
+    let untouched = [other, other_child].map(|e| world.ticks::<GlobalTransform>(e).unwrap());
+
+    // Padre e hijo sucios a la vez: el hijo debe usar la matriz nueva del padre.
+    world.get_mut::<Transform>(root).unwrap().translation.x += 10.0;
+    world.get_mut::<Transform>(child).unwrap().scale.x = 3.0;
+    schedule.run(&mut world);
+
+    for entity in [root, child, grandchild] {
+      assert_close(global(&world, entity), world_matrix(&world, entity));
+    }
+
+    assert_eq!(
+      [other, other_child].map(|e| world.ticks::<GlobalTransform>(e).unwrap()),
+      untouched
+    );
+  }
+
+  #[test]
+  fn from_matrix_keeps_the_matrix() {
+    let matrix = Matrix4::from_translation(vec3(1.0, 2.0, 3.0)) * Matrix4::from_angle_x(Deg(30.0));
+    let transform = Transform::from_matrix(matrix);
+
+    assert_close(transform.matrix(), matrix);
+  }
+
+  #[test]
+  fn from_matrix_keeps_mirrored_matrices() {
+    let matrix = Matrix4::from_translation(vec3(1.0, -2.0, 0.5))
+      * Matrix4::from_angle_z(Deg(45.0))
+      * Matrix4::from_nonuniform_scale(1.5, -2.0, 1.0);
+    let transform = Transform::from_matrix(matrix);
+
+    assert!(transform.scale.x < 0.0);
+    assert!((transform.scale.x.abs() - 1.5).abs() < 1e-5);
+    assert_close(transform.matrix(), matrix);
+  }
+
+  #[test]
+  fn from_matrix_handles_zero_scale() {
+    let matrix = Matrix4::from_angle_x(Deg(30.0)) * Matrix4::from_nonuniform_scale(0.0, 2.0, 1.0);
+    let transform = Transform::from_matrix(matrix);
+
+    assert_eq!(transform.scale.x, 0.0);
+    assert!((transform.scale.y - 2.0).abs() < 1e-5);
+    assert!(transform.rotation.s.is_finite() && transform.rotation.v.magnitude().is_finite());
+    assert_close(transform.matrix(), matrix);
+  }
+}
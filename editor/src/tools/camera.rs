@@ -0,0 +1,443 @@
+use cgmath::{point3, vec3, Angle, EuclideanSpace, InnerSpace, Point3, Rad, Vector3};
+
+use crate::scene::camera::{Camera, Projection, SceneView};
+
+/// Radians per pixel of mouse movement when orbiting or looking around.
+const ROTATE_SPEED: f32 = 0.005;
+/// Camera distance (or orthographic height) factor per scroll line.
+const ZOOM_STEP: f32 = 0.85;
+/// Fly speed factor per scroll line while flying.
+const FLY_SPEED_STEP: f32 = 1.2;
+/// Orbit and look pitch limit, short of straight up or down where `up` degenerates.
+const MAX_PITCH: Rad<f32> = Rad(1.55);
+const TRANSITION_SECONDS: f32 = 0.3;
+/// Viewport height of a new 2D view.
+const DEFAULT_2D_HEIGHT: f32 = 20.0;
+
+/// Mouse drag the controller is handling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraDrag {
+  /// Alt + left mouse: rotate around the target.
+  Orbit,
+  /// Right mouse: fly camera, mouse look plus WASD.
+  Fly,
+  /// Middle mouse, or right mouse in 2D: move across the view plane.
+  Pan,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Transition {
+  from: Camera,
+  to: Camera,
+  elapsed: f32,
+}
+
+/// Editor camera input: orbit, fly and 2D pan/zoom controls over the scene camera, plus animated
+/// transitions between views.
+#[derive(Clone, Debug, Default)]
+pub struct CameraController {
+  drag: Option<CameraDrag>,
+  /// Fly movement input, forward, right and up, each in `[-1, 1]`.
+  movement: [f32; 3],
+  transition: Option<Transition>,
+}
+
+impl CameraController {
+  pub fn drag(&self) -> Option<CameraDrag> {
+    self.drag
+  }
+
+  /// Whether the camera keeps moving without input, so the editor must keep redrawing.
+  pub fn is_animating(&self) -> bool {
+    self.transition.is_some() || (self.drag == Some(CameraDrag::Fly) && self.movement != [0.0; 3])
+  }
+
+  pub fn begin_drag(&mut self, drag: CameraDrag) {
+    self.transition = None;
+    self.drag = Some(drag);
+  }
+
+  pub fn end_drag(&mut self, drag: CameraDrag) {
+    if self.drag == Some(drag) {
+      self.drag = None;
+      self.movement = [0.0; 3];
+    }
+  }
+
+  /// Sets the fly movement input, ignored unless flying.
+  pub fn set_movement(&mut self, movement: [f32; 3]) {
+    self.movement = match self.drag {
+      Some(CameraDrag::Fly) => movement,
+      _ => [0.0; 3],
+    };
+  }
+
+  /// Applies a mouse movement of `delta` pixels to the active drag.
+  pub fn cursor_moved(&mut self, camera: &mut Camera, delta: [f32; 2], viewport: [f32; 2]) {
+    let [dx, dy] = delta;
+
+    match self.drag {
+      Some(CameraDrag::Orbit) => {
+        let offset = rotate(camera.position - camera.target, dx, dy);
+        camera.position = camera.target + offset;
+      }
+      Some(CameraDrag::Fly) => {
+        let offset = rotate(camera.target - camera.position, dx, -dy);
+        camera.target = camera.position + offset;
+      }
+      Some(CameraDrag::Pan) => {
+        let world_per_pixel = camera.view_height_at(camera.target) / viewport[1].max(1.0);
+        let (right, up) = screen_axes(camera);
+        let offset = (up * dy - right * dx) * world_per_pixel;
+        camera.position += offset;
+        camera.target += offset;
+      }
+      None => {}
+    }
+  }
+
+  /// Scroll wheel: zooms towards the target, or towards the cursor in 2D; while flying it
+  /// changes the fly speed instead. `cursor` is the world point under the mouse.
+  pub fn scroll(&mut self, camera: &mut Camera, view: &mut SceneView, lines: f32, cursor: Option<Point3<f32>>) {
+    self.transition = None;
+
+    if self.drag == Some(CameraDrag::Fly) {
+      view.fly_speed = (view.fly_speed * FLY_SPEED_STEP.powf(lines)).clamp(0.1, 500.0);
+      return;
+    }
+
+    let factor = ZOOM_STEP.powf(lines);
+
+    match &mut camera.projection {
+      Projection::Perspective => {
+        let offset = camera.position - camera.target;
+        let distance = (offset.magnitude() * factor).clamp(camera.near * 2.0, camera.far * 0.5);
+        camera.position = camera.target + offset.normalize() * distance;
+      }
+      Projection::Orthographic { height } => {
+        *height = (*height * factor).clamp(0.01, 10_000.0);
+
+        // El punto bajo el cursor se queda fijo mientras se hace zoom.
+        if let Some(cursor) = cursor {
+          let forward = camera.forward();
+          let to_cursor = cursor - camera.target;
+          let offset = (to_cursor - forward * to_cursor.dot(forward)) * (1.0 - factor);
+          camera.position += offset;
+          camera.target += offset;
+        }
+      }
+    }
+  }
+
+  /// Moves the fly camera and advances transitions; returns whether the camera changed.
+  pub fn update(&mut self, camera: &mut Camera, view: &SceneView, speed_scale: f32, delta_time: f32) -> bool {
+    if let Some(transition) = &mut self.transition {
+      transition.elapsed += delta_time;
+      let t = (transition.elapsed / TRANSITION_SECONDS).min(1.0);
+      *camera = interpolate(&transition.from, &transition.to, t * t * (3.0 - 2.0 * t));
+
+      if t >= 1.0 {
+        self.transition = None;
+      }
+
+      return true;
+    }
+
+    let [forward, sideways, upwards] = self.movement;
+
+    if self.drag != Some(CameraDrag::Fly) {
+      return false;
+    }
+
+    let (right, _) = screen_axes(camera);
+    let direction = camera.forward() * forward + right * sideways + vec3(0.0, 1.0, 0.0) * upwards;
+
+    if direction.magnitude2() <= f32::EPSILON {
+      return false;
+    }
+
+    // Un stick a medio recorrido mueve mas lento, pero las diagonales no van mas rapido.
+    let direction = if direction.magnitude2() > 1.0 {
+      direction.normalize()
+    } else {
+      direction
+    };
+    let offset = direction * view.fly_speed * speed_scale * delta_time;
+    camera.position += offset;
+    camera.target += offset;
+    true
+  }
+
+  /// Animates the camera from `from` to `to`.
+  pub fn transition(&mut self, from: Camera, to: Camera) {
+    self.transition = Some(Transition { from, to, elapsed: 0.0 });
+  }
+
+  /// Animates to a view of the box `min`-`max` from the current direction.
+  pub fn frame(&mut self, camera: &Camera, min: Point3<f32>, max: Point3<f32>) {
+    let center = min.midpoint(max);
+    let radius = ((max - min).magnitude() * 0.5).max(0.1);
+    let forward = camera.forward();
+    let mut to = *camera;
+
+    match &mut to.projection {
+      Projection::Perspective => {
+        let distance = radius / (camera.fov_y / 2.0).sin() * 1.1;
+        to.position = center - forward * distance;
+      }
+      Projection::Orthographic { height } => {
+        *height = radius * 2.2;
+        to.position = center - forward * (camera.position - camera.target).magnitude();
+      }
+    }
+
+    to.target = center;
+    self.transition(*camera, to);
+  }
+
+  /// Switches between the 3D view and a 2D orthographic view of the XY plane, restoring what
+  /// each view looked at last.
+  pub fn toggle_2d(&mut self, camera: &Camera, view: &mut SceneView) {
+    let to = view.stashed.unwrap_or_else(|| match camera.projection {
+      Projection::Perspective => Camera {
+        position: point3(camera.target.x, camera.target.y, 50.0),
+        target: point3(camera.target.x, camera.target.y, 0.0),
+        up: vec3(0.0, 1.0, 0.0),
+        projection: Projection::Orthographic {
+          height: DEFAULT_2D_HEIGHT,
+        },
+        ..*camera
+      },
+      Projection::Orthographic { .. } => Camera::default(),
+    });
+
+    view.stashed = Some(*camera);
+    self.drag = None;
+    self.transition(*camera, to);
+  }
+}
+
+/// Rotates `offset` by a mouse movement: yaw around the world up, pitch around the camera right.
+fn rotate(offset: Vector3<f32>, dx: f32, dy: f32) -> Vector3<f32> {
+  let distance = offset.magnitude();
+  let yaw = Rad(offset.x.atan2(offset.z) - dx * ROTATE_SPEED);
+  let pitch = Rad((offset.y / distance).clamp(-1.0, 1.0).asin() + dy * ROTATE_SPEED);
+  let pitch = Rad(pitch.0.clamp(-MAX_PITCH.0, MAX_PITCH.0));
+
+  vec3(yaw.sin() * pitch.cos(), pitch.sin(), yaw.cos() * pitch.cos()) * distance
+}
+
+fn screen_axes(camera: &Camera) -> (Vector3<f32>, Vector3<f32>) {
+  let forward = camera.forward();
+  let right = forward.cross(camera.up).normalize();
+  (right, right.cross(forward))
+}
+
+fn interpolate(from: &Camera, to: &Camera, t: f32) -> Camera {
+  let lerp = |a: Point3<f32>, b: Point3<f32>| Point3::from_vec(a.to_vec() + (b - a) * t);
+
+  // Perspectiva y ortografica no se pueden mezclar: se cambia a mitad de la transicion.
+  let projection = match (from.projection, to.projection) {
+    (Projection::Orthographic { height: a }, Projection::Orthographic { height: b }) => Projection::Orthographic {
+      height: a + (b - a) * t,
+    },
+    _ if t < 0.5 => from.projection,
+    _ => to.projection,
+  };
+
+  Camera {
+    position: lerp(from.position, to.position),
+    target: lerp(from.target, to.target),
+    up: (from.up + (to.up - from.up) * t).normalize(),
+    projection,
+    ..*to
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use cgmath::{assert_abs_diff_eq, MetricSpace};
+
+  use super::*;
+
+  /// Runs the controller until its transition is over.
+  fn settle(controller: &mut CameraController, camera: &mut Camera) {
+    let view = SceneView::default();
+    while controller.update(camera, &view, 1.0, TRANSITION_SECONDS / 4.0) {}
+  }
+
+  fn camera_2d(height: f32) -> Camera {
+    Camera {
+      position: point3(0.0, 0.0, 50.0),
+      target: point3(0.0, 0.0, 0.0),
+      projection: Projection::Orthographic { height },
+      ..Camera::default()
+    }
+  }
+
+  #[test]
+  fn orbit_keeps_the_target_and_distance() {
+    let mut camera = Camera::default();
+    let before = camera;
+    let mut controller = CameraController::default();
+    controller.begin_drag(CameraDrag::Orbit);
+
+    controller.cursor_moved(&mut camera, [120.0, -40.0], [800.0, 600.0]);
+
+    assert_eq!(camera.target, before.target);
+    assert_ne!(camera.position, before.position);
+    assert_abs_diff_eq!(
+      camera.position.distance(camera.target),
+      before.position.distance(before.target),
+      epsilon = 1e-4
+    );
+  }
+
+  #[test]
+  fn orbit_stops_short_of_the_poles() {
+    let mut camera = Camera::default();
+    let mut controller = CameraController::default();
+    controller.begin_drag(CameraDrag::Orbit);
+
+    controller.cursor_moved(&mut camera, [0.0, 100_000.0], [800.0, 600.0]);
+
+    let offset = (camera.position - camera.target).normalize();
+    assert_abs_diff_eq!(offset.y, MAX_PITCH.0.sin(), epsilon = 1e-4);
+
+    // Sin arrastre el raton no mueve la camara.
+    controller.end_drag(CameraDrag::Orbit);
+    let before = camera;
+    controller.cursor_moved(&mut camera, [50.0, 50.0], [800.0, 600.0]);
+    assert_eq!(camera, before);
+  }
+
+  #[test]
+  fn scroll_zooms_towards_the_target() {
+    let mut camera = Camera::default();
+    let mut view = SceneView::default();
+    let distance = camera.position.distance(camera.target);
+    let forward = camera.forward();
+
+    CameraController::default().scroll(&mut camera, &mut view, 2.0, None);
+
+    assert_abs_diff_eq!(
+      camera.position.distance(camera.target),
+      distance * ZOOM_STEP * ZOOM_STEP,
+      epsilon = 1e-4
+    );
+    assert_abs_diff_eq!(camera.forward(), forward, epsilon = 1e-5);
+
+    // Nunca atraviesa el objetivo.
+    CameraController::default().scroll(&mut camera, &mut view, 1000.0, None);
+    assert_abs_diff_eq!(
+      camera.position.distance(camera.target),
+      camera.near * 2.0,
+      epsilon = 1e-4
+    );
+  }
+
+  #[test]
+  fn scroll_in_2d_keeps_the_point_under_the_cursor() {
+    let mut camera = camera_2d(10.0);
+    let mut view = SceneView::default();
+    let cursor = point3(4.0, -2.0, 0.0);
+    let viewport = [800.0, 600.0];
+    let pixel = |camera: &Camera| {
+      let clip = camera.projection(viewport[0] / viewport[1]) * camera.view() * cursor.to_homogeneous();
+      [clip.x / clip.w, clip.y / clip.w]
+    };
+    let before = pixel(&camera);
+
+    CameraController::default().scroll(&mut camera, &mut view, -1.0, Some(cursor));
+
+    assert_eq!(
+      camera.projection,
+      Projection::Orthographic {
+        height: 10.0 / ZOOM_STEP
+      }
+    );
+    let after = pixel(&camera);
+    assert_abs_diff_eq!(after[0], before[0], epsilon = 1e-5);
+    assert_abs_diff_eq!(after[1], before[1], epsilon = 1e-5);
+  }
+
+  #[test]
+  fn scroll_while_flying_changes_the_speed() {
+    let mut camera = Camera::default();
+    let before = camera;
+    let mut view = SceneView::default();
+    let mut controller = CameraController::default();
+    controller.begin_drag(CameraDrag::Fly);
+
+    controller.scroll(&mut camera, &mut view, 1.0, None);
+
+    assert_eq!(camera, before);
+    assert_abs_diff_eq!(view.fly_speed, SceneView::default().fly_speed * FLY_SPEED_STEP);
+  }
+
+  #[test]
+  fn frame_fits_the_box_from_the_current_direction() {
+    let mut camera = Camera::default();
+    let forward = camera.forward();
+    let (min, max) = (point3(2.0, 0.0, -1.0), point3(4.0, 2.0, 1.0));
+    let mut controller = CameraController::default();
+
+    controller.frame(&camera, min, max);
+    assert!(controller.is_animating());
+    settle(&mut controller, &mut camera);
+
+    let radius = 3f32.sqrt();
+    assert!(!controller.is_animating());
+    assert_eq!(camera.target, point3(3.0, 1.0, 0.0));
+    assert_abs_diff_eq!(camera.forward(), forward, epsilon = 1e-5);
+    assert_abs_diff_eq!(
+      camera.position.distance(camera.target),
+      radius / (camera.fov_y / 2.0).sin() * 1.1,
+      epsilon = 1e-4
+    );
+  }
+
+  #[test]
+  fn frame_in_2d_fits_the_height() {
+    let mut camera = camera_2d(10.0);
+    let mut controller = CameraController::default();
+
+    controller.frame(&camera, point3(-1.0, -1.0, 0.0), point3(1.0, 1.0, 0.0));
+    settle(&mut controller, &mut camera);
+
+    assert_eq!(camera.target, point3(0.0, 0.0, 0.0));
+    match camera.projection {
+      Projection::Orthographic { height } => assert_abs_diff_eq!(height, 2f32.sqrt() * 2.2, epsilon = 1e-4),
+      Projection::Perspective => panic!("2D framing switched to perspective"),
+    }
+  }
+
+  #[test]
+  fn toggle_2d_looks_at_the_xy_plane_and_back() {
+    let start = Camera::default();
+    let mut camera = start;
+    let mut view = SceneView::default();
+    let mut controller = CameraController::default();
+
+    controller.toggle_2d(&camera, &mut view);
+    settle(&mut controller, &mut camera);
+
+    assert_eq!(view.stashed, Some(start));
+    assert_eq!(camera.target, point3(start.target.x, start.target.y, 0.0));
+    assert_abs_diff_eq!(camera.forward(), vec3(0.0, 0.0, -1.0));
+    assert_eq!(
+      camera.projection,
+      Projection::Orthographic {
+        height: DEFAULT_2D_HEIGHT
+      }
+    );
+
+    // Volver a 3D recupera la camara de antes y guarda la 2D.
+    let flat = camera;
+    controller.toggle_2d(&camera, &mut view);
+    settle(&mut controller, &mut camera);
+
+    assert_eq!(camera, start);
+    assert_eq!(view.stashed, Some(flat));
+  }
+}
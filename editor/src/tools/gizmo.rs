@@ -0,0 +1,610 @@
+use cgmath::{vec3, Deg, EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, Rotation3, Vector3};
+use sagitario_ecs::Entity;
+
+use super::history::SetTransforms;
+use crate::scene::camera::{Camera, Ray};
+use crate::scene::debug_draw::{Color, DebugDraw};
+use crate::scene::guid::Guid;
+use crate::scene::transform::{ancestors, set_world_matrix, GlobalTransform};
+use crate::scene::Scene;
+
+const AXIS_COLORS: [Color; 3] = [[0.95, 0.25, 0.25, 1.0], [0.3, 0.9, 0.3, 1.0], [0.3, 0.45, 1.0, 1.0]];
+const FREE_COLOR: Color = [0.85, 0.85, 0.85, 1.0];
+const ACTIVE_COLOR: Color = [1.0, 0.85, 0.2, 1.0];
+
+/// Gizmo length as a fraction of the viewport height, so it keeps its size on screen.
+const SCREEN_SIZE: f32 = 0.13;
+/// Distance from a handle that still grabs it, as a fraction of the gizmo size.
+const GRAB_TOLERANCE: f32 = 0.06;
+/// Plane handles span this range of both axes, as a fraction of the gizmo size.
+const PLANE_HANDLE: (f32, f32) = (0.25, 0.45);
+/// Radius of the camera facing rotation ring, relative to the axis rings.
+const VIEW_RING: f32 = 1.15;
+/// Horizontal cursor movement that doubles the size with the uniform scale handle.
+const UNIFORM_SCALE_PIXELS: f32 = 150.0;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GizmoMode {
+  #[default]
+  Translate,
+  Rotate,
+  Scale,
+}
+
+/// Orientation of the gizmo axes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GizmoSpace {
+  #[default]
+  World,
+  /// Axes of the primary selection.
+  Local,
+}
+
+impl GizmoSpace {
+  pub fn next(self) -> Self {
+    match self {
+      Self::World => Self::Local,
+      Self::Local => Self::World,
+    }
+  }
+}
+
+/// Point the selection rotates and scales around.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PivotMode {
+  /// Average position of the selected props.
+  #[default]
+  Center,
+  /// Position of the primary (last selected) prop.
+  Primary,
+  /// Every prop around its own origin.
+  Individual,
+}
+
+impl PivotMode {
+  pub fn next(self) -> Self {
+    match self {
+      Self::Center => Self::Primary,
+      Self::Primary => Self::Individual,
+      Self::Individual => Self::Center,
+    }
+  }
+}
+
+/// Increments drags snap to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snapping {
+  pub enabled: bool,
+  /// World units, one ground grid cell by default.
+  pub translate: f32,
+  pub rotate: Deg<f32>,
+  /// Added to or removed from a scale factor of 1.
+  pub scale: f32,
+}
+
+impl Default for Snapping {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      translate: 1.0,
+      rotate: Deg(15.0),
+      scale: 0.1,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Handle {
+  /// Along (translate, scale) or around (rotate) one gizmo axis.
+  Axis(usize),
+  /// In the plane perpendicular to one gizmo axis, translate only.
+  Plane(usize),
+  /// Screen plane translation, camera axis rotation or uniform scale.
+  Free,
+}
+
+/// Position, axes and size of the gizmo in world space.
+#[derive(Copy, Clone, Debug)]
+struct Frame {
+  pivot: Point3<f32>,
+  axes: [Vector3<f32>; 3],
+  size: f32,
+  /// Camera forward, right and up.
+  view: [Vector3<f32>; 3],
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Grab {
+  /// Parameter along the axis line, in world units.
+  Line(f32),
+  Point(Point3<f32>),
+  Cursor(f32),
+}
+
+#[derive(Clone, Debug)]
+struct Drag {
+  handle: Handle,
+  frame: Frame,
+  grab: Grab,
+  /// Transforms of the selected props when the drag started.
+  start: Vec<(Entity, Matrix4<f32>)>,
+}
+
+/// Translate, rotate and scale handles over the selected props, drawn with `DebugDraw`.
+#[derive(Clone, Debug, Default)]
+pub struct Gizmo {
+  pub mode: GizmoMode,
+  pub space: GizmoSpace,
+  pub pivot: PivotMode,
+  pub snapping: Snapping,
+  hovered: Option<Handle>,
+  drag: Option<Drag>,
+}
+
+impl Gizmo {
+  pub fn is_dragging(&self) -> bool {
+    self.drag.is_some()
+  }
+
+  /// Highlights the handle under `ray`; returns whether the highlight changed.
+  pub fn hover(&mut self, scene: &Scene, ray: Ray) -> bool {
+    let hovered = self.frame(scene).and_then(|frame| self.hit(&frame, ray));
+    let changed = hovered != self.hovered;
+    self.hovered = hovered;
+    changed
+  }
+
+  /// Starts dragging the handle under `ray`, if any; `cursor` is in physical pixels.
+  pub fn begin_drag(&mut self, scene: &Scene, ray: Ray, cursor: [f32; 2]) -> bool {
+    let Some(frame) = self.frame(scene) else {
+      return false;
+    };
+
+    let Some(handle) = self.hit(&frame, ray) else {
+      return false;
+    };
+
+    let Some(grab) = self.grab(&frame, handle, ray, cursor) else {
+      return false;
+    };
+
+    let start = scene
+      .selection
+      .iter()
+      // Los hijos de otro prop seleccionado ya lo siguen.
+      .filter(|&&e| !ancestors(&scene.world, e).any(|a| scene.is_selected(a)))
+      .filter_map(|&e| scene.world.get::<GlobalTransform>(e).map(|g| (e, g.0)))
+      .collect();
+
+    self.hovered = Some(handle);
+    self.drag = Some(Drag {
+      handle,
+      frame,
+      grab,
+      start,
+    });
+
+    true
+  }
+
+  /// Moves the dragged props to follow the cursor; `snap` rounds the change to `snapping`.
+  pub fn drag(&mut self, scene: &mut Scene, ray: Ray, cursor: [f32; 2], snap: bool) {
+    let Some(drag) = &self.drag else {
+      return;
+    };
+
+    let Some(grab) = self.grab(&drag.frame, drag.handle, ray, cursor) else {
+      return;
+    };
+
+    let snapping = Snapping {
+      enabled: snap,
+      ..self.snapping
+    };
+    let frame = &drag.frame;
+    let edit = match self.mode {
+      GizmoMode::Translate => translate_edit(frame, drag.handle, drag.grab, grab, &snapping),
+      GizmoMode::Rotate => rotate_edit(frame, drag.handle, drag.grab, grab, &snapping),
+      GizmoMode::Scale => scale_edit(frame, drag.handle, drag.grab, grab, &snapping, self.space),
+    };
+
+    for &(entity, start) in &drag.start {
+      let pivot = if self.pivot == PivotMode::Individual {
+        Point3::from_homogeneous(start.w)
+      } else {
+        frame.pivot
+      };
+      set_world_matrix(&mut scene.world, entity, edit.apply(start, pivot)).ok();
+    }
+  }
+
+  /// Finishes the drag, returning the command that undoes it if anything moved.
+  pub fn end_drag(&mut self, scene: &Scene) -> Option<SetTransforms> {
+    let drag = self.drag.take()?;
+
+    let changes = drag
+      .start
+      .iter()
+      .filter_map(|&(entity, before)| {
+        let after = scene.world.get::<GlobalTransform>(entity)?.0;
+        let guid = *scene.world.get::<Guid>(entity)?;
+        (after != before).then_some((guid, before, after))
+      })
+      .collect::<Vec<_>>();
+
+    let name = match self.mode {
+      GizmoMode::Translate => "Move",
+      GizmoMode::Rotate => "Rotate",
+      GizmoMode::Scale => "Scale",
+    };
+
+    (!changes.is_empty()).then_some(SetTransforms { name, changes })
+  }
+
+  /// Puts the dragged props back where the drag started.
+  pub fn cancel_drag(&mut self, scene: &mut Scene) {
+    for (entity, start) in self.drag.take().map(|d| d.start).unwrap_or_default() {
+      set_world_matrix(&mut scene.world, entity, start).ok();
+    }
+  }
+
+  pub fn draw(&self, debug: &mut DebugDraw, scene: &Scene) {
+    let Some(frame) = self.frame(scene) else {
+      return;
+    };
+
+    let active = self.drag.as_ref().map(|d| d.handle).or(self.hovered);
+    let color = |handle: Handle, base: Color| if active == Some(handle) { ACTIVE_COLOR } else { base };
+    let Frame {
+      pivot,
+      axes,
+      size,
+      view: [_, right, up],
+    } = frame;
+
+    let depth_test = debug.depth_test;
+    debug.depth_test = false;
+
+    match self.mode {
+      GizmoMode::Translate => {
+        for i in 0..3 {
+          debug.arrow(pivot, pivot + axes[i] * size, color(Handle::Axis(i), AXIS_COLORS[i]));
+
+          let (a, b) = (axes[(i + 1) % 3], axes[(i + 2) % 3]);
+          let (lo, hi) = (PLANE_HANDLE.0 * size, PLANE_HANDLE.1 * size);
+          let corners = [a * lo + b * lo, a * hi + b * lo, a * hi + b * hi, a * lo + b * hi];
+          let plane_color = color(Handle::Plane(i), AXIS_COLORS[i]);
+
+          for k in 0..4 {
+            debug.line(pivot + corners[k], pivot + corners[(k + 1) % 4], plane_color);
+          }
+        }
+
+        debug.circle(pivot, right, up, size * 0.1, color(Handle::Free, FREE_COLOR));
+      }
+      GizmoMode::Rotate => {
+        for i in 0..3 {
+          let (a, b) = (axes[(i + 1) % 3], axes[(i + 2) % 3]);
+          debug.circle(pivot, a, b, size, color(Handle::Axis(i), AXIS_COLORS[i]));
+        }
+
+        debug.circle(pivot, right, up, size * VIEW_RING, color(Handle::Free, FREE_COLOR));
+      }
+      GizmoMode::Scale => {
+        for i in 0..3 {
+          let tip = pivot + axes[i] * size;
+          let axis_color = color(Handle::Axis(i), AXIS_COLORS[i]);
+          debug.line(pivot, tip, axis_color);
+          cube(debug, tip, axes, size * 0.05, axis_color);
+        }
+
+        cube(debug, pivot, axes, size * 0.08, color(Handle::Free, FREE_COLOR));
+      }
+    }
+
+    debug.depth_test = depth_test;
+  }
+
+  fn frame(&self, scene: &Scene) -> Option<Frame> {
+    let primary = scene.world.get::<GlobalTransform>(*scene.selection.last()?)?.0;
+
+    let pivot = match self.pivot {
+      PivotMode::Center => {
+        let positions = scene
+          .selection
+          .iter()
+          .filter_map(|&e| scene.world.get::<GlobalTransform>(e))
+          .map(|g| g.0.w.truncate())
+          .collect::<Vec<_>>();
+        Point3::from_vec(positions.iter().sum::<Vector3<f32>>() / positions.len() as f32)
+      }
+      PivotMode::Primary | PivotMode::Individual => Point3::from_homogeneous(primary.w),
+    };
+
+    let axes = match self.space {
+      GizmoSpace::World => [vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0)],
+      GizmoSpace::Local => {
+        let m = primary;
+        [m.x, m.y, m.z].map(|c| c.truncate().normalize())
+      }
+    };
+
+    Some(Frame {
+      pivot,
+      axes,
+      size: scene.camera.view_height_at(pivot) * SCREEN_SIZE,
+      view: view_basis(&scene.camera),
+    })
+  }
+
+  /// Closest handle of the current mode along `ray`.
+  fn hit(&self, frame: &Frame, ray: Ray) -> Option<Handle> {
+    let Frame {
+      pivot,
+      axes,
+      size,
+      view: [forward, ..],
+    } = *frame;
+    let tolerance = size * GRAB_TOLERANCE;
+    let mut hits = vec![];
+
+    match self.mode {
+      GizmoMode::Translate | GizmoMode::Scale => {
+        for (i, axis) in axes.iter().enumerate() {
+          if let Some((t, along, distance)) = ray_line(ray, pivot, *axis) {
+            if distance < tolerance && (0.0..=size * 1.05).contains(&along) {
+              hits.push((t, Handle::Axis(i)));
+            }
+          }
+        }
+
+        let center = if self.mode == GizmoMode::Scale { 0.08 } else { 0.1 };
+
+        if let Some(t) = ray_sphere(ray, pivot, size * center + tolerance) {
+          hits.push((t, Handle::Free));
+        }
+      }
+      GizmoMode::Rotate => {
+        for (i, axis) in axes.iter().enumerate() {
+          if let Some(t) = ray_ring(ray, pivot, *axis, size, tolerance) {
+            hits.push((t, Handle::Axis(i)));
+          }
+        }
+
+        if let Some(t) = ray_ring(ray, pivot, forward, size * VIEW_RING, tolerance) {
+          hits.push((t, Handle::Free));
+        }
+      }
+    }
+
+    if self.mode == GizmoMode::Translate {
+      for i in 0..3 {
+        let (a, b) = (axes[(i + 1) % 3], axes[(i + 2) % 3]);
+        let range = PLANE_HANDLE.0 * size..=PLANE_HANDLE.1 * size;
+
+        if let Some(t) = ray_plane(ray, pivot, axes[i]) {
+          let offset = ray.at(t) - pivot;
+
+          if range.contains(&offset.dot(a)) && range.contains(&offset.dot(b)) {
+            hits.push((t, Handle::Plane(i)));
+          }
+        }
+      }
+    }
+
+    hits
+      .into_iter()
+      .min_by(|a, b| a.0.total_cmp(&b.0))
+      .map(|(_, handle)| handle)
+  }
+
+  /// Where `ray` meets the constraint of `handle`.
+  fn grab(&self, frame: &Frame, handle: Handle, ray: Ray, cursor: [f32; 2]) -> Option<Grab> {
+    let Frame {
+      pivot,
+      axes,
+      view: [forward, ..],
+      ..
+    } = *frame;
+
+    let plane_point = |normal| ray_plane(ray, pivot, normal).map(|t| Grab::Point(ray.at(t)));
+
+    match (self.mode, handle) {
+      (GizmoMode::Translate | GizmoMode::Scale, Handle::Axis(i)) => {
+        ray_line(ray, pivot, axes[i]).map(|(_, along, _)| Grab::Line(along))
+      }
+      (GizmoMode::Translate, Handle::Plane(i)) => plane_point(axes[i]),
+      (GizmoMode::Translate, Handle::Free) => plane_point(forward),
+      (GizmoMode::Rotate, Handle::Axis(i)) => plane_point(axes[i]),
+      (GizmoMode::Rotate, Handle::Free) => plane_point(forward),
+      (GizmoMode::Scale, Handle::Free) => Some(Grab::Cursor(cursor[0])),
+      (_, Handle::Plane(_)) => None,
+    }
+  }
+}
+
+/// Change a drag makes to every dragged prop, relative to its transform at the start.
+#[derive(Copy, Clone, Debug)]
+enum Edit {
+  Translate(Vector3<f32>),
+  /// World space rotation around the pivot.
+  Rotate(Quaternion<f32>),
+  /// Factor along a world axis (`Some`) or uniform (`None`); `local` scales the prop along its
+  /// own axis `usize` instead of shearing it.
+  Scale {
+    factor: f32,
+    axis: Option<(Vector3<f32>, usize)>,
+    local: bool,
+  },
+}
+
+impl Edit {
+  fn apply(&self, start: Matrix4<f32>, pivot: Point3<f32>) -> Matrix4<f32> {
+    let position = Point3::from_homogeneous(start.w);
+    let linear = Matrix3::from_cols(start.x.truncate(), start.y.truncate(), start.z.truncate());
+
+    let (position, linear) = match *self {
+      Edit::Translate(delta) => (position + delta, linear),
+      Edit::Rotate(rotation) => (pivot + rotation * (position - pivot), Matrix3::from(rotation) * linear),
+      Edit::Scale { factor, axis: None, .. } => (pivot + (position - pivot) * factor, linear * factor),
+      Edit::Scale {
+        factor,
+        axis: Some((axis, index)),
+        local,
+      } => {
+        let stretch = |v: Vector3<f32>| v + axis * (v.dot(axis) * (factor - 1.0));
+        let linear = if local {
+          let mut linear = linear;
+          linear[index] *= factor;
+          linear
+        } else {
+          Matrix3::from_cols(stretch(linear.x), stretch(linear.y), stretch(linear.z))
+        };
+
+        (pivot + stretch(position - pivot), linear)
+      }
+    };
+
+    Matrix4::from_translation(position.to_vec()) * Matrix4::from(linear)
+  }
+}
+
+fn translate_edit(frame: &Frame, handle: Handle, from: Grab, to: Grab, snapping: &Snapping) -> Edit {
+  let step = snapping.translate;
+  let snap_along = |delta: Vector3<f32>, axes: &[Vector3<f32>]| {
+    axes
+      .iter()
+      .map(|axis| axis * snap(delta.dot(*axis), step, snapping.enabled))
+      .sum::<Vector3<f32>>()
+  };
+
+  let delta = match (handle, from, to) {
+    (Handle::Axis(i), Grab::Line(a), Grab::Line(b)) => frame.axes[i] * snap(b - a, step, snapping.enabled),
+    (Handle::Plane(i), Grab::Point(a), Grab::Point(b)) => {
+      snap_along(b - a, &[frame.axes[(i + 1) % 3], frame.axes[(i + 2) % 3]])
+    }
+    (_, Grab::Point(a), Grab::Point(b)) if snapping.enabled => snap_along(b - a, &frame.axes),
+    (_, Grab::Point(a), Grab::Point(b)) => b - a,
+    _ => Vector3::new(0.0, 0.0, 0.0),
+  };
+
+  Edit::Translate(delta)
+}
+
+fn rotate_edit(frame: &Frame, handle: Handle, from: Grab, to: Grab, snapping: &Snapping) -> Edit {
+  let axis = match handle {
+    Handle::Axis(i) => frame.axes[i],
+    _ => frame.view[0],
+  };
+
+  let angle = match (from, to) {
+    (Grab::Point(a), Grab::Point(b)) => {
+      let (a, b) = (a - frame.pivot, b - frame.pivot);
+      a.cross(b).dot(axis).atan2(a.dot(b))
+    }
+    _ => 0.0,
+  };
+
+  let step = Rad::from(snapping.rotate).0;
+  Edit::Rotate(Quaternion::from_axis_angle(
+    axis,
+    Rad(snap(angle, step, snapping.enabled)),
+  ))
+}
+
+fn scale_edit(frame: &Frame, handle: Handle, from: Grab, to: Grab, snapping: &Snapping, space: GizmoSpace) -> Edit {
+  let (factor, axis) = match (handle, from, to) {
+    (Handle::Axis(i), Grab::Line(a), Grab::Line(b)) if a.abs() > f32::EPSILON => (b / a, Some((frame.axes[i], i))),
+    (Handle::Free, Grab::Cursor(a), Grab::Cursor(b)) => (1.0 + (b - a) / UNIFORM_SCALE_PIXELS, None),
+    _ => (1.0, None),
+  };
+
+  let factor = (1.0 + snap(factor - 1.0, snapping.scale, snapping.enabled)).max(0.01);
+
+  Edit::Scale {
+    factor,
+    axis,
+    local: space == GizmoSpace::Local,
+  }
+}
+
+fn snap(value: f32, step: f32, enabled: bool) -> f32 {
+  if enabled && step > 0.0 {
+    (value / step).round() * step
+  } else {
+    value
+  }
+}
+
+/// Camera forward, right and up.
+fn view_basis(camera: &Camera) -> [Vector3<f32>; 3] {
+  let forward = camera.forward();
+  let right = forward.cross(camera.up).normalize();
+  [forward, right, right.cross(forward)]
+}
+
+/// Closest approach between `ray` and the line through `origin` along the unit `axis`: ray
+/// parameter, line parameter and distance between both points.
+fn ray_line(ray: Ray, origin: Point3<f32>, axis: Vector3<f32>) -> Option<(f32, f32, f32)> {
+  let w = ray.origin - origin;
+  let b = ray.direction.dot(axis);
+  let denom = 1.0 - b * b;
+
+  // Rayo casi paralelo al eje: no se puede arrastrar a lo largo de el.
+  if denom < 1e-4 {
+    return None;
+  }
+
+  let (d, e) = (ray.direction.dot(w), axis.dot(w));
+  let t = (b * e - d) / denom;
+  let along = (e - b * d) / denom;
+
+  if t < 0.0 {
+    return None;
+  }
+
+  let distance = (ray.at(t) - (origin + axis * along)).magnitude();
+  Some((t, along, distance))
+}
+
+fn ray_plane(ray: Ray, origin: Point3<f32>, normal: Vector3<f32>) -> Option<f32> {
+  let facing = ray.direction.dot(normal);
+
+  if facing.abs() < 1e-4 {
+    return None;
+  }
+
+  let t = (origin - ray.origin).dot(normal) / facing;
+  (t >= 0.0).then_some(t)
+}
+
+fn ray_sphere(ray: Ray, center: Point3<f32>, radius: f32) -> Option<f32> {
+  let to_center = center - ray.origin;
+  let t = to_center.dot(ray.direction);
+  let distance2 = to_center.magnitude2() - t * t;
+
+  (t >= 0.0 && distance2 <= radius * radius).then(|| t - (radius * radius - distance2).sqrt())
+}
+
+/// Hit with a ring of `radius` around `normal`, `tolerance` wide on each side.
+fn ray_ring(ray: Ray, center: Point3<f32>, normal: Vector3<f32>, radius: f32, tolerance: f32) -> Option<f32> {
+  let t = ray_plane(ray, center, normal)?;
+  let distance = (ray.at(t) - center).magnitude();
+  ((distance - radius).abs() < tolerance).then_some(t)
+}
+
+/// Cube of half size `half` at `center`, aligned with `axes`.
+fn cube(debug: &mut DebugDraw, center: Point3<f32>, axes: [Vector3<f32>; 3], half: f32, color: Color) {
+  let corner = |i: usize| {
+    let sign = |bit: usize| if i & bit == 0 { -half } else { half };
+    center + axes[0] * sign(1) + axes[1] * sign(2) + axes[2] * sign(4)
+  };
+
+  for i in 0..8 {
+    for bit in [1, 2, 4] {
+      if i & bit == 0 {
+        debug.line(corner(i), corner(i | bit), color);
+      }
+    }
+  }
+}
@@ -0,0 +1,675 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+use cgmath::Matrix4;
+use sagitario_ecs::Component;
+use sagitario_reflect::Value;
+
+use crate::scene::file::{set_component_field, SceneFile};
+use crate::scene::guid::Guid;
+use crate::scene::snapshot::Subtree;
+use crate::scene::transform::{set_parent, set_world_matrix};
+use crate::scene::Scene;
+
+/// Commands kept for undo unless `History::limit` says otherwise.
+pub const DEFAULT_HISTORY_LIMIT: usize = 256;
+
+/// How soon after a command the next one can still merge into it.
+const MERGE_WINDOW: Duration = Duration::from_millis(750);
+
+/// A reversible edit of the scene. Commands reach the history already applied, so `apply` is
+/// only called again to redo them. They refer to entities by `Guid`, which survives an undo
+/// despawning and respawning them.
+pub trait Command: Debug + Any {
+  fn name(&self) -> &str;
+  fn apply(&self, scene: &mut Scene);
+  fn revert(&self, scene: &mut Scene);
+
+  /// Folds `next`, applied right after this command, into it so both undo in one step. Returns
+  /// false if they don't combine.
+  fn merge(&mut self, _next: &dyn Command) -> bool {
+    false
+  }
+}
+
+/// New world matrices for a set of entities, e.g. the result of a gizmo drag. Merges with the
+/// next one of the same name on the same entities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetTransforms {
+  pub name: &'static str,
+  /// `(entity, before, after)`.
+  pub changes: Vec<(Guid, Matrix4<f32>, Matrix4<f32>)>,
+}
+
+impl Command for SetTransforms {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    for &(guid, _, after) in &self.changes {
+      if let Some(entity) = scene.entity(guid) {
+        set_world_matrix(&mut scene.world, entity, after).ok();
+      }
+    }
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    for &(guid, before, _) in &self.changes {
+      if let Some(entity) = scene.entity(guid) {
+        set_world_matrix(&mut scene.world, entity, before).ok();
+      }
+    }
+  }
+
+  fn merge(&mut self, next: &dyn Command) -> bool {
+    let Some(next) = (next as &dyn Any).downcast_ref::<Self>() else {
+      return false;
+    };
+
+    let same = |(a, b): (&(Guid, _, _), &(Guid, _, _))| a.0 == b.0;
+
+    if next.name != self.name
+      || next.changes.len() != self.changes.len()
+      || !self.changes.iter().zip(&next.changes).all(same)
+    {
+      return false;
+    }
+
+    for (change, next) in self.changes.iter_mut().zip(&next.changes) {
+      change.2 = next.2;
+    }
+
+    true
+  }
+}
+
+/// New parents for a set of entities; they keep their world transforms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetParents {
+  pub name: &'static str,
+  /// `(entity, before, after)`, `None` for no parent.
+  pub changes: Vec<(Guid, Option<Guid>, Option<Guid>)>,
+}
+
+impl SetParents {
+  fn set(scene: &mut Scene, guid: Guid, parent: Option<Guid>) {
+    let parent = match parent {
+      Some(parent) => match scene.entity(parent) {
+        Some(parent) => Some(parent),
+        None => return,
+      },
+      None => None,
+    };
+
+    if let Some(entity) = scene.entity(guid) {
+      set_parent(&mut scene.world, entity, parent).ok();
+    }
+  }
+}
+
+impl Command for SetParents {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    for &(guid, _, after) in &self.changes {
+      Self::set(scene, guid, after);
+    }
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    for &(guid, before, _) in self.changes.iter().rev() {
+      Self::set(scene, guid, before);
+    }
+  }
+}
+
+/// New value of a component of an entity, e.g. a light's shadow settings. Merges with the next
+/// one of the same name on the same entity, so repeated nudges undo together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetComponent<T> {
+  pub name: &'static str,
+  pub entity: Guid,
+  pub before: T,
+  pub after: T,
+}
+
+impl<T: Component + Clone + Debug> Command for SetComponent<T> {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    if let Some(entity) = scene.entity(self.entity) {
+      scene.world.insert(entity, self.after.clone());
+    }
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    if let Some(entity) = scene.entity(self.entity) {
+      scene.world.insert(entity, self.before.clone());
+    }
+  }
+
+  fn merge(&mut self, next: &dyn Command) -> bool {
+    match (next as &dyn Any).downcast_ref::<Self>() {
+      Some(next) if next.name == self.name && next.entity == self.entity => {
+        self.after = next.after.clone();
+        true
+      }
+      _ => false,
+    }
+  }
+}
+
+/// New value of one component field, e.g. set in the inspector. Merges with the next edit of the
+/// same field, so dragging a value undoes in one step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetField {
+  pub entity: Guid,
+  /// Path of the field, like `light.color.0`, which also names the command.
+  pub field: String,
+  pub before: Value,
+  pub after: Value,
+}
+
+impl SetField {
+  fn set(&self, scene: &mut Scene, value: &Value) {
+    if let Some(entity) = scene.entity(self.entity) {
+      set_component_field(scene, entity, &self.field, value).ok();
+    }
+  }
+}
+
+impl Command for SetField {
+  fn name(&self) -> &str {
+    &self.field
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    self.set(scene, &self.after);
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    self.set(scene, &self.before);
+  }
+
+  fn merge(&mut self, next: &dyn Command) -> bool {
+    match (next as &dyn Any).downcast_ref::<Self>() {
+      Some(next) if next.entity == self.entity && next.field == self.field => {
+        self.after = next.after.clone();
+        true
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Entities created, each with everything below it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnEntities {
+  pub name: &'static str,
+  pub subtrees: Vec<Subtree>,
+}
+
+impl Command for SpawnEntities {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    for subtree in &self.subtrees {
+      subtree.restore(scene).ok();
+    }
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    for subtree in self.subtrees.iter().rev() {
+      subtree.despawn(scene);
+    }
+  }
+}
+
+/// Entities deleted, each with everything below it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DespawnEntities {
+  pub name: &'static str,
+  pub subtrees: Vec<Subtree>,
+}
+
+impl Command for DespawnEntities {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    for subtree in &self.subtrees {
+      subtree.despawn(scene);
+    }
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    for subtree in self.subtrees.iter().rev() {
+      subtree.restore(scene).ok();
+    }
+  }
+}
+
+/// Everything the scene file stores before and after an edit too broad to track piece by
+/// piece, like applying a prefab to all its instances.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestoreScene {
+  pub name: &'static str,
+  pub before: SceneFile,
+  pub after: SceneFile,
+}
+
+impl Command for RestoreScene {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    scene.restore(&self.after).ok();
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    scene.restore(&self.before).ok();
+  }
+}
+
+/// Commands recorded between `History::begin` and `History::commit`, undone as one.
+#[derive(Debug)]
+pub struct Transaction {
+  name: String,
+  commands: Vec<Box<dyn Command>>,
+}
+
+impl Command for Transaction {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn apply(&self, scene: &mut Scene) {
+    for command in &self.commands {
+      command.apply(scene);
+    }
+  }
+
+  fn revert(&self, scene: &mut Scene) {
+    for command in self.commands.iter().rev() {
+      command.revert(scene);
+    }
+  }
+}
+
+/// Undo and redo stacks of the editor commands, and whether the scene changed since it was
+/// saved.
+///
+/// Every state the scene goes through gets an id, the one of the command on top of the undo
+/// stack, so undoing back to the saved state makes it clean again.
+#[derive(Debug)]
+pub struct History {
+  undo: VecDeque<(u64, Box<dyn Command>)>,
+  redo: Vec<(u64, Box<dyn Command>)>,
+  /// Open transactions, innermost last.
+  transactions: Vec<Transaction>,
+  /// Most commands kept for undo; the oldest are dropped past it.
+  pub limit: usize,
+  /// State with an empty undo stack, changed when old commands are dropped.
+  base: u64,
+  saved: u64,
+  last_id: u64,
+  last_push: Option<Instant>,
+}
+
+impl Default for History {
+  fn default() -> Self {
+    Self {
+      undo: VecDeque::new(),
+      redo: vec![],
+      transactions: vec![],
+      limit: DEFAULT_HISTORY_LIMIT,
+      base: 0,
+      saved: 0,
+      last_id: 0,
+      last_push: None,
+    }
+  }
+}
+
+impl History {
+  /// Records a command that has already been applied; anything that could be redone is dropped.
+  /// Inside a transaction it joins it; otherwise it merges into the previous command if they
+  /// came close together and combine.
+  pub fn push(&mut self, command: Box<dyn Command>) {
+    if let Some(transaction) = self.transactions.last_mut() {
+      let merged = transaction
+        .commands
+        .last_mut()
+        .is_some_and(|last| last.merge(command.as_ref()));
+
+      if !merged {
+        transaction.commands.push(command);
+      }
+
+      return;
+    }
+
+    self.redo.clear();
+    let now = Instant::now();
+    let recent = self.last_push.is_some_and(|t| now - t < MERGE_WINDOW);
+    self.last_push = Some(now);
+    self.last_id += 1;
+
+    if let Some((id, last)) = self.undo.back_mut().filter(|_| recent) {
+      if last.merge(command.as_ref()) {
+        *id = self.last_id;
+        return;
+      }
+    }
+
+    self.undo.push_back((self.last_id, command));
+
+    while self.undo.len() > self.limit.max(1) {
+      if let Some((id, _)) = self.undo.pop_front() {
+        self.base = id;
+      }
+    }
+  }
+
+  /// Starts grouping the commands pushed until the matching `commit` into one undo step named
+  /// `name`. Transactions nest.
+  pub fn begin(&mut self, name: impl Into<String>) {
+    self.transactions.push(Transaction {
+      name: name.into(),
+      commands: vec![],
+    });
+  }
+
+  /// Closes the innermost transaction and records it, unless nothing was pushed to it.
+  pub fn commit(&mut self) {
+    match self.transactions.pop() {
+      Some(transaction) if !transaction.commands.is_empty() => self.push(Box::new(transaction)),
+      _ => {}
+    }
+  }
+
+  /// Reverts the last command and returns its name, `None` if there is nothing to undo.
+  pub fn undo(&mut self, scene: &mut Scene) -> Option<&str> {
+    let (id, command) = self.undo.pop_back()?;
+    command.revert(scene);
+    self.last_push = None;
+    self.redo.push((id, command));
+    self.redo.last().map(|(_, c)| c.name())
+  }
+
+  /// Applies the last undone command again and returns its name.
+  pub fn redo(&mut self, scene: &mut Scene) -> Option<&str> {
+    let (id, command) = self.redo.pop()?;
+    command.apply(scene);
+    self.last_push = None;
+    self.undo.push_back((id, command));
+    self.undo.back().map(|(_, c)| c.name())
+  }
+
+  /// Name of the command `undo` would revert.
+  pub fn undo_name(&self) -> Option<&str> {
+    self.undo.back().map(|(_, c)| c.name())
+  }
+
+  /// Name of the command `redo` would apply.
+  pub fn redo_name(&self) -> Option<&str> {
+    self.redo.last().map(|(_, c)| c.name())
+  }
+
+  /// Whether the scene changed since `mark_saved`, or since it was opened.
+  pub fn is_dirty(&self) -> bool {
+    self.saved != self.state()
+  }
+
+  /// Call after saving the scene.
+  pub fn mark_saved(&mut self) {
+    self.saved = self.state();
+  }
+
+  fn state(&self) -> u64 {
+    self.undo.back().map_or(self.base, |&(id, _)| id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::scene::light::Light;
+  use crate::scene::transform::{GlobalTransform, Transform};
+
+  /// A scene with two lights, by `Guid`.
+  fn scene() -> (Scene, [Guid; 2]) {
+    let mut scene = Scene::default();
+    let lights = [(); 2].map(|_| {
+      let entity = scene.spawn((Transform::default(), GlobalTransform::default(), Light::default()));
+      *scene.world.get::<Guid>(entity).unwrap()
+    });
+    (scene, lights)
+  }
+
+  fn light(scene: &Scene, guid: Guid) -> Light {
+    scene.world.get::<Light>(scene.entity(guid).unwrap()).unwrap().clone()
+  }
+
+  /// Sets `field` of the light `entity` to `value` and records it, as the inspector does.
+  fn set(history: &mut History, scene: &mut Scene, entity: Guid, field: &str, value: f64) {
+    let light = light(scene, entity);
+    let before = match field {
+      "light.intensity" => light.intensity,
+      _ => light.range,
+    };
+    let command = SetField {
+      entity,
+      field: field.to_string(),
+      before: Value::Float(before.into()),
+      after: Value::Float(value),
+    };
+
+    command.apply(scene);
+    history.push(Box::new(command));
+  }
+
+  /// Breaks the merge window, like a pause between two drags.
+  fn pause(history: &mut History) {
+    history.last_push = None;
+  }
+
+  fn undo_all(history: &mut History, scene: &mut Scene) -> Vec<String> {
+    std::iter::from_fn(|| history.undo(scene).map(str::to_string)).collect()
+  }
+
+  #[test]
+  fn undo_and_redo_walk_the_stack_in_order() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History::default();
+    let start = light(&scene, a);
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+
+    assert_eq!(history.undo(&mut scene), Some("light.range"));
+    assert_eq!(light(&scene, a).range, start.range);
+    assert_eq!(light(&scene, a).intensity, 2.0);
+    assert_eq!(history.undo(&mut scene), Some("light.intensity"));
+    assert_eq!(light(&scene, a), start);
+    assert_eq!(history.undo(&mut scene), None);
+
+    assert_eq!(history.redo(&mut scene), Some("light.intensity"));
+    assert_eq!(history.redo(&mut scene), Some("light.range"));
+    assert_eq!(history.redo(&mut scene), None);
+    assert_eq!((light(&scene, a).intensity, light(&scene, a).range), (2.0, 30.0));
+  }
+
+  #[test]
+  fn pushing_drops_the_redo_stack() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History::default();
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    history.undo(&mut scene);
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+
+    assert_eq!(history.redo_name(), None);
+    assert_eq!(undo_all(&mut history, &mut scene), ["light.range"]);
+  }
+
+  #[test]
+  fn edits_of_one_field_merge_within_the_window() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History::default();
+    let start = light(&scene, a).intensity;
+
+    for value in [2.0, 3.0, 4.0] {
+      set(&mut history, &mut scene, a, "light.intensity", value);
+    }
+
+    assert_eq!(undo_all(&mut history, &mut scene), ["light.intensity"]);
+    assert_eq!(light(&scene, a).intensity, start);
+
+    history.redo(&mut scene);
+    assert_eq!(light(&scene, a).intensity, 4.0);
+  }
+
+  #[test]
+  fn edits_dont_merge_across_fields_entities_or_pauses() {
+    let (mut scene, [a, b]) = scene();
+    let mut history = History::default();
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+    set(&mut history, &mut scene, b, "light.range", 40.0);
+    pause(&mut history);
+    set(&mut history, &mut scene, b, "light.range", 50.0);
+
+    assert_eq!(undo_all(&mut history, &mut scene).len(), 4);
+  }
+
+  #[test]
+  fn undo_breaks_merging() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History::default();
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+    history.undo(&mut scene);
+    set(&mut history, &mut scene, a, "light.intensity", 3.0);
+
+    assert_eq!(undo_all(&mut history, &mut scene).len(), 2);
+  }
+
+  #[test]
+  fn nested_transactions_undo_as_one_step() {
+    let (mut scene, [a, b]) = scene();
+    let mut history = History::default();
+    let start = [light(&scene, a), light(&scene, b)];
+
+    history.begin("Outer");
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    history.begin("Inner");
+    set(&mut history, &mut scene, b, "light.range", 40.0);
+    history.commit();
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+    history.commit();
+
+    assert_eq!(undo_all(&mut history, &mut scene), ["Outer"]);
+    assert_eq!([light(&scene, a), light(&scene, b)], start);
+
+    history.redo(&mut scene);
+    assert_eq!((light(&scene, a).intensity, light(&scene, a).range), (2.0, 30.0));
+    assert_eq!(light(&scene, b).range, 40.0);
+  }
+
+  #[test]
+  fn empty_transactions_are_dropped() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History::default();
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+    history.undo(&mut scene);
+
+    history.begin("Outer");
+    history.begin("Inner");
+    history.commit();
+    history.commit();
+
+    // Sin nada que deshacer, la pila de rehacer sigue intacta.
+    assert_eq!(history.redo_name(), Some("light.range"));
+    assert_eq!(undo_all(&mut history, &mut scene), ["light.intensity"]);
+  }
+
+  #[test]
+  fn limit_drops_the_oldest_commands() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History {
+      limit: 2,
+      ..Default::default()
+    };
+
+    for value in [2.0, 3.0, 4.0] {
+      pause(&mut history);
+      set(&mut history, &mut scene, a, "light.intensity", value);
+    }
+
+    assert_eq!(history.undo.len(), 2);
+    assert_eq!(history.base, 1);
+    assert_eq!(undo_all(&mut history, &mut scene).len(), 2);
+    assert_eq!(light(&scene, a).intensity, 2.0);
+    assert_eq!(history.state(), history.base);
+  }
+
+  #[test]
+  fn undoing_back_to_the_saved_state_is_clean() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History::default();
+    assert!(!history.is_dirty());
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    history.mark_saved();
+    assert!(!history.is_dirty());
+
+    set(&mut history, &mut scene, a, "light.range", 30.0);
+    assert!(history.is_dirty());
+
+    history.undo(&mut scene);
+    assert!(!history.is_dirty());
+
+    history.undo(&mut scene);
+    assert!(history.is_dirty());
+
+    history.redo(&mut scene);
+    assert!(!history.is_dirty());
+  }
+
+  #[test]
+  fn evicting_past_the_saved_state_stays_dirty() {
+    let (mut scene, [a, _]) = scene();
+    let mut history = History {
+      limit: 2,
+      ..Default::default()
+    };
+
+    set(&mut history, &mut scene, a, "light.intensity", 2.0);
+    history.mark_saved();
+
+    for value in [3.0, 4.0, 5.0] {
+      pause(&mut history);
+      set(&mut history, &mut scene, a, "light.intensity", value);
+    }
+
+    // El estado guardado ya no se puede alcanzar deshaciendo.
+    undo_all(&mut history, &mut scene);
+    assert!(history.is_dirty());
+  }
+}
@@ -0,0 +1,3 @@
+pub mod camera;
+pub mod gizmo;
+pub mod history;
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use egui::{ColorImage, TextureHandle, TextureOptions, Ui};
+
+use crate::asset::meta::META_EXTENSION;
+
+/// Side of the thumbnails, in points.
+const THUMBNAIL_SIZE: f32 = 64.0;
+/// How often the open folder is listed again to pick up new files.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Formats the thumbnails are made for, loaded with `image`.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "tga", "gif", "hdr", "exr"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum AssetKind {
+  Folder,
+  Scene,
+  Image,
+  Other,
+}
+
+impl AssetKind {
+  fn of(path: &Path) -> Self {
+    let extension = path
+      .extension()
+      .map(|e| e.to_string_lossy().to_lowercase())
+      .unwrap_or_default();
+
+    match extension.as_str() {
+      _ if path.is_dir() => Self::Folder,
+      "scene" => Self::Scene,
+      e if IMAGE_EXTENSIONS.contains(&e) => Self::Image,
+      _ => Self::Other,
+    }
+  }
+
+  /// Glyph shown instead of a thumbnail.
+  fn icon(self) -> &'static str {
+    match self {
+      Self::Folder => "🗀",
+      Self::Scene => "🎬",
+      Self::Image => "🖼",
+      Self::Other => "🗋",
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+struct AssetEntry {
+  path: PathBuf,
+  name: String,
+  kind: AssetKind,
+  bytes: u64,
+}
+
+/// Files under the project directory, folders first, with thumbnails of the images. Asset
+/// sidecars are hidden. A double click opens a folder. Thumbnails load one per frame, so a folder of large images doesn't
+/// stall the editor.
+pub struct AssetBrowser {
+  root: PathBuf,
+  current: PathBuf,
+  entries: Vec<AssetEntry>,
+  listed: Option<Instant>,
+  /// `None` for images that failed to load.
+  thumbnails: HashMap<PathBuf, Option<TextureHandle>>,
+  filter: String,
+}
+
+impl AssetBrowser {
+  pub fn new(root: PathBuf) -> Self {
+    Self {
+      current: root.clone(),
+      root,
+      entries: vec![],
+      listed: None,
+      thumbnails: HashMap::new(),
+      filter: String::new(),
+    }
+  }
+
+  fn list(&mut self) {
+    let entries = match fs::read_dir(&self.current) {
+      Result::Ok(entries) => entries,
+      Err(error) => {
+        log::warn!("Can't list {}: {}", self.current.display(), error);
+        self.current = self.root.clone();
+        return;
+      }
+    };
+
+    self.entries = entries
+      .filter_map(|e| e.ok())
+      .map(|e| e.path())
+      .filter(|p| !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+      .filter(|p| p.extension().is_none_or(|e| e != META_EXTENSION))
+      .map(|path| AssetEntry {
+        name: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        kind: AssetKind::of(&path),
+        bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or_default(),
+        path,
+      })
+      .collect();
+
+    // Carpetas primero.
+    self
+      .entries
+      .sort_by_key(|e| (e.kind != AssetKind::Folder, e.name.to_lowercase()));
+    self.listed = Some(Instant::now());
+  }
+
+  fn open(&mut self, folder: PathBuf) {
+    self.current = folder;
+    self.listed = None;
+  }
+
+  pub fn show(&mut self, ui: &mut Ui) {
+    if self.listed.is_none_or(|t| t.elapsed() > REFRESH_INTERVAL) {
+      self.list();
+    }
+
+    ui.horizontal(|ui| {
+      let parent = self.current.parent().map(Path::to_path_buf);
+      let up = parent.filter(|_| self.current != self.root);
+
+      if ui.add_enabled(up.is_some(), egui::Button::new("⬆")).clicked() {
+        self.open(up.unwrap_or_default());
+      }
+
+      let relative = self.current.strip_prefix(&self.root).unwrap_or(&self.current);
+      ui.label(Path::new(".").join(relative).display().to_string());
+      ui.separator();
+      ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter"));
+    });
+
+    ui.separator();
+
+    let filter = self.filter.to_lowercase();
+    let mut opened = None;
+    let mut loaded = false;
+
+    egui::ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
+      ui.horizontal_wrapped(|ui| {
+        for entry in &self.entries {
+          if !filter.is_empty() && !entry.name.to_lowercase().contains(&filter) {
+            continue;
+          }
+
+          // Una miniatura nueva por frame.
+          if entry.kind == AssetKind::Image && !loaded && !self.thumbnails.contains_key(&entry.path) {
+            self
+              .thumbnails
+              .insert(entry.path.clone(), load_thumbnail(ui.ctx(), &entry.path));
+            loaded = true;
+          }
+
+          let thumbnail = self.thumbnails.get(&entry.path).and_then(|t| t.as_ref());
+          let response = tile(ui, entry, thumbnail);
+
+          if response.double_clicked() && entry.kind == AssetKind::Folder {
+            opened = Some(entry.path.clone());
+          }
+        }
+      });
+    });
+
+    if loaded {
+      ui.ctx().request_repaint();
+    }
+
+    if let Some(folder) = opened {
+      self.open(folder);
+    }
+  }
+}
+
+fn tile(ui: &mut Ui, entry: &AssetEntry, thumbnail: Option<&TextureHandle>) -> egui::Response {
+  let size = egui::vec2(THUMBNAIL_SIZE + 16.0, THUMBNAIL_SIZE + 32.0);
+
+  let response = ui
+    .allocate_ui(size, |ui| {
+      ui.set_min_size(size);
+      ui.vertical_centered(|ui| {
+        match thumbnail {
+          Some(texture) => {
+            let fit = texture.size_vec2() * (THUMBNAIL_SIZE / texture.size_vec2().max_elem());
+            ui.add_sized([THUMBNAIL_SIZE, THUMBNAIL_SIZE], egui::Image::new((texture.id(), fit)));
+          }
+          None => {
+            let icon = egui::RichText::new(entry.kind.icon()).size(THUMBNAIL_SIZE * 0.6);
+            ui.add_sized([THUMBNAIL_SIZE, THUMBNAIL_SIZE], egui::Label::new(icon));
+          }
+        }
+
+        ui.add(egui::Label::new(egui::RichText::new(&entry.name).small()).truncate());
+      });
+    })
+    .response;
+
+  let response = ui.interact(response.rect, ui.id().with(&entry.path), egui::Sense::click());
+
+  if response.hovered() {
+    let stroke = ui.visuals().widgets.hovered.bg_stroke;
+    ui.painter()
+      .rect_stroke(response.rect, 4.0, stroke, egui::StrokeKind::Inside);
+  }
+
+  let size = match entry.kind {
+    AssetKind::Folder => String::new(),
+    _ => format!("\n{:.1} KiB", entry.bytes as f64 / 1024.0),
+  };
+
+  response.on_hover_text(format!("{}{}", entry.path.display(), size))
+}
+
+/// The image at `path` scaled down to fit the thumbnail, `None` if it can't be read.
+fn load_thumbnail(context: &egui::Context, path: &Path) -> Option<TextureHandle> {
+  let image = match image::open(path) {
+    Result::Ok(image) => image,
+    Err(error) => {
+      log::warn!("Can't make a thumbnail of {}: {}", path.display(), error);
+      return None;
+    }
+  };
+
+  let side = (THUMBNAIL_SIZE * context.pixels_per_point()).ceil() as u32;
+  let image = image.thumbnail(side, side).into_rgba8();
+  let size = [image.width() as usize, image.height() as usize];
+  let pixels = ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+
+  Some(context.load_texture(path.display().to_string(), pixels, TextureOptions::LINEAR))
+}
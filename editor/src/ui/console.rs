@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use egui::{Color32, RichText, Ui};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Records the console keeps; older ones are dropped.
+const MAX_RECORDS: usize = 1000;
+
+static RECORDS: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+static START: OnceLock<Instant> = OnceLock::new();
+
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+  pub level: Level,
+  pub target: String,
+  pub message: String,
+  /// Since the logger was installed.
+  pub time: Duration,
+}
+
+/// Prints like `pretty_env_logger` and also keeps the records for the console, which always gets
+/// `Info` and above whatever `RUST_LOG` lets through to the terminal.
+struct ConsoleLogger {
+  terminal: Box<dyn Log>,
+}
+
+impl Log for ConsoleLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Info || self.terminal.enabled(metadata)
+  }
+
+  fn log(&self, record: &Record) {
+    if record.level() <= Level::Info {
+      let start = START.get_or_init(Instant::now);
+      let mut records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+
+      if records.len() == MAX_RECORDS {
+        records.pop_front();
+      }
+
+      records.push_back(LogRecord {
+        level: record.level(),
+        target: record.target().to_string(),
+        message: record.args().to_string(),
+        time: start.elapsed(),
+      });
+    }
+
+    // Filtra con RUST_LOG por su cuenta.
+    self.terminal.log(record);
+  }
+
+  fn flush(&self) {
+    self.terminal.flush();
+  }
+}
+
+/// Installs the logger; call once, instead of `pretty_env_logger::init`.
+pub fn init_logger() {
+  let mut builder = pretty_env_logger::formatted_builder();
+
+  if let Ok(filters) = std::env::var("RUST_LOG") {
+    builder.parse_filters(&filters);
+  }
+
+  let terminal = builder.build();
+  let max_level = terminal.filter().max(LevelFilter::Info);
+  START.get_or_init(Instant::now);
+
+  if log::set_boxed_logger(Box::new(ConsoleLogger {
+    terminal: Box::new(terminal),
+  }))
+  .is_ok()
+  {
+    log::set_max_level(max_level);
+  }
+}
+
+/// Log records of the session, filtered by level and text.
+#[derive(Clone, Debug)]
+pub struct Console {
+  show_errors: bool,
+  show_warnings: bool,
+  show_info: bool,
+  filter: String,
+}
+
+impl Default for Console {
+  fn default() -> Self {
+    Self {
+      show_errors: true,
+      show_warnings: true,
+      show_info: true,
+      filter: String::new(),
+    }
+  }
+}
+
+impl Console {
+  pub fn show(&mut self, ui: &mut Ui) {
+    let mut clear = false;
+
+    ui.horizontal(|ui| {
+      ui.toggle_value(&mut self.show_errors, "Errors");
+      ui.toggle_value(&mut self.show_warnings, "Warnings");
+      ui.toggle_value(&mut self.show_info, "Info");
+      ui.separator();
+      ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter"));
+      clear = ui.button("Clear").clicked();
+    });
+
+    let mut records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+
+    if clear {
+      records.clear();
+    }
+
+    let filter = self.filter.to_lowercase();
+    let shown = records
+      .iter()
+      .filter(|r| match r.level {
+        Level::Error => self.show_errors,
+        Level::Warn => self.show_warnings,
+        _ => self.show_info,
+      })
+      .filter(|r| filter.is_empty() || r.message.to_lowercase().contains(&filter))
+      .collect::<Vec<_>>();
+
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+    egui::ScrollArea::vertical()
+      .auto_shrink(false)
+      .stick_to_bottom(true)
+      .show_rows(ui, row_height, shown.len(), |ui, rows| {
+        for record in &shown[rows] {
+          let color = match record.level {
+            Level::Error => Color32::from_rgb(235, 90, 80),
+            Level::Warn => Color32::from_rgb(230, 180, 60),
+            _ => ui.visuals().text_color(),
+          };
+
+          ui.horizontal(|ui| {
+            let time = format!("{:>8.2}", record.time.as_secs_f32());
+            ui.label(RichText::new(time).monospace().weak());
+            ui.label(RichText::new(&record.message).monospace().color(color))
+              .on_hover_text(&record.target);
+          });
+        }
+      });
+  }
+}
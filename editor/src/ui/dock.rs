@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use egui::{Context, Sense, Ui};
+use serde::{Deserialize, Serialize};
+
+/// Name of the layout file in the user's config directory.
+const LAYOUT_FILE: &str = "layout.ron";
+
+/// A panel of the editor that can be docked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Tab {
+  Viewport,
+  Outliner,
+  Inspector,
+  Assets,
+  Console,
+  Stats,
+}
+
+impl Tab {
+  pub const ALL: [Self; 6] = [
+    Self::Viewport,
+    Self::Outliner,
+    Self::Inspector,
+    Self::Assets,
+    Self::Console,
+    Self::Stats,
+  ];
+
+  pub fn title(self) -> &'static str {
+    match self {
+      Self::Viewport => "Viewport",
+      Self::Outliner => "Outliner",
+      Self::Inspector => "Inspector",
+      Self::Assets => "Assets",
+      Self::Console => "Console",
+      Self::Stats => "Stats",
+    }
+  }
+
+  /// Where `DockLayout::open` docks it.
+  fn default_area(self) -> DockArea {
+    match self {
+      Self::Viewport => DockArea::Center,
+      Self::Outliner => DockArea::Left,
+      Self::Inspector | Self::Stats => DockArea::Right,
+      Self::Assets | Self::Console => DockArea::Bottom,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DockArea {
+  Left,
+  Right,
+  Bottom,
+  Center,
+}
+
+impl DockArea {
+  pub const ALL: [Self; 4] = [Self::Left, Self::Right, Self::Bottom, Self::Center];
+
+  pub fn title(self) -> &'static str {
+    match self {
+      Self::Left => "Left",
+      Self::Right => "Right",
+      Self::Bottom => "Bottom",
+      Self::Center => "Center",
+    }
+  }
+}
+
+/// Tabs docked in an area, the one shown and the width or height of the area in points.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dock {
+  pub tabs: Vec<Tab>,
+  pub active: usize,
+  pub size: f32,
+}
+
+impl Default for Dock {
+  fn default() -> Self {
+    Self {
+      tabs: vec![],
+      active: 0,
+      size: 240.0,
+    }
+  }
+}
+
+impl Dock {
+  fn new(tabs: Vec<Tab>, size: f32) -> Self {
+    Self { tabs, active: 0, size }
+  }
+
+  /// The tab shown, `None` if the area is empty.
+  pub fn active(&self) -> Option<Tab> {
+    self
+      .tabs
+      .get(self.active.min(self.tabs.len().saturating_sub(1)))
+      .copied()
+  }
+}
+
+/// Which panel is docked where: three resizable areas around the center one. Saved per user and
+/// restored on the next start.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DockLayout {
+  pub left: Dock,
+  pub right: Dock,
+  pub bottom: Dock,
+  pub center: Dock,
+}
+
+impl Default for DockLayout {
+  fn default() -> Self {
+    Self {
+      left: Dock::new(vec![Tab::Outliner], 240.0),
+      right: Dock::new(vec![Tab::Inspector, Tab::Stats], 300.0),
+      bottom: Dock::new(vec![Tab::Assets, Tab::Console], 220.0),
+      center: Dock::new(vec![Tab::Viewport], 0.0),
+    }
+  }
+}
+
+impl DockLayout {
+  pub fn from_ron(source: &str) -> Result<Self> {
+    Ok(ron::from_str(source)?)
+  }
+
+  pub fn to_ron(&self) -> Result<String> {
+    Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+    Self::from_ron(&fs::read_to_string(path)?)
+  }
+
+  /// Writes the layout, creating its directory if needed.
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    Ok(fs::write(path, self.to_ron()?)?)
+  }
+
+  pub fn dock(&self, area: DockArea) -> &Dock {
+    match area {
+      DockArea::Left => &self.left,
+      DockArea::Right => &self.right,
+      DockArea::Bottom => &self.bottom,
+      DockArea::Center => &self.center,
+    }
+  }
+
+  pub fn dock_mut(&mut self, area: DockArea) -> &mut Dock {
+    match area {
+      DockArea::Left => &mut self.left,
+      DockArea::Right => &mut self.right,
+      DockArea::Bottom => &mut self.bottom,
+      DockArea::Center => &mut self.center,
+    }
+  }
+
+  pub fn is_open(&self, tab: Tab) -> bool {
+    DockArea::ALL.iter().any(|&a| self.dock(a).tabs.contains(&tab))
+  }
+
+  /// Shows `tab`, docking it in its default area if it was closed.
+  pub fn open(&mut self, tab: Tab) {
+    let area = DockArea::ALL
+      .into_iter()
+      .find(|&a| self.dock(a).tabs.contains(&tab))
+      .unwrap_or(tab.default_area());
+
+    self.move_tab(tab, area);
+  }
+
+  pub fn close(&mut self, tab: Tab) {
+    for area in DockArea::ALL {
+      let dock = self.dock_mut(area);
+
+      if let Some(i) = dock.tabs.iter().position(|&t| t == tab) {
+        dock.tabs.remove(i);
+
+        if dock.active > i {
+          dock.active -= 1;
+        }
+      }
+    }
+  }
+
+  /// Docks `tab` in `area`, at the end unless it's already there, and shows it.
+  pub fn move_tab(&mut self, tab: Tab, area: DockArea) {
+    if !self.dock(area).tabs.contains(&tab) {
+      self.close(tab);
+      self.dock_mut(area).tabs.push(tab);
+    }
+
+    let dock = self.dock_mut(area);
+    dock.active = dock.tabs.iter().position(|&t| t == tab).unwrap_or(0);
+  }
+
+  /// Lays out the areas around the center one and calls `contents` for the tab shown in each.
+  /// Tabs are dragged by their title onto another area; while one is dragged, the empty areas
+  /// show up so it can be dropped on them.
+  pub fn show(&mut self, context: &Context, mut contents: impl FnMut(&mut Ui, Tab)) {
+    let dragging = egui::DragAndDrop::has_payload_of_type::<Tab>(context);
+    let mut moves = vec![];
+
+    for area in [DockArea::Left, DockArea::Right, DockArea::Bottom] {
+      let Dock { tabs, size, .. } = self.dock(area);
+
+      if tabs.is_empty() && !dragging {
+        continue;
+      }
+
+      let (empty, size) = (tabs.is_empty(), *size);
+      let id = format!("dock_{}", area.title());
+      let mut show = |ui: &mut Ui| self.show_dock(ui, area, &mut contents, &mut moves);
+
+      let rect = match area {
+        DockArea::Left => egui::SidePanel::left(id).default_width(size).show(context, show),
+        DockArea::Right => egui::SidePanel::right(id).default_width(size).show(context, show),
+        _ => egui::TopBottomPanel::bottom(id)
+          .resizable(true)
+          .default_height(size)
+          .show(context, &mut show),
+      }
+      .response
+      .rect;
+
+      if !empty {
+        self.dock_mut(area).size = if area == DockArea::Bottom {
+          rect.height()
+        } else {
+          rect.width()
+        };
+      }
+    }
+
+    egui::CentralPanel::default().show(context, |ui| {
+      self.show_dock(ui, DockArea::Center, &mut contents, &mut moves)
+    });
+
+    for (tab, area) in moves {
+      match area {
+        Some(area) => self.move_tab(tab, area),
+        None => self.close(tab),
+      }
+    }
+  }
+
+  /// Tab bar and active tab of `area`. Moves asked for from the tab menus or by dropping a tab
+  /// go to `moves`, `None` closing the tab.
+  fn show_dock(
+    &mut self,
+    ui: &mut Ui,
+    area: DockArea,
+    contents: &mut impl FnMut(&mut Ui, Tab),
+    moves: &mut Vec<(Tab, Option<DockArea>)>,
+  ) {
+    let dock = self.dock_mut(area);
+
+    ui.horizontal(|ui| {
+      for (i, &tab) in dock.tabs.iter().enumerate() {
+        let button = egui::Button::selectable(i == dock.active, tab.title()).sense(Sense::click_and_drag());
+        let response = ui.add(button);
+
+        if response.clicked() {
+          dock.active = i;
+        }
+
+        response.dnd_set_drag_payload(tab);
+        response.context_menu(|ui| {
+          for target in DockArea::ALL.into_iter().filter(|&a| a != area) {
+            if ui.button(format!("Move to {}", target.title())).clicked() {
+              moves.push((tab, Some(target)));
+              ui.close();
+            }
+          }
+
+          ui.separator();
+
+          if ui.button("Close").clicked() {
+            moves.push((tab, None));
+            ui.close();
+          }
+        });
+      }
+    });
+
+    ui.separator();
+
+    match dock.active() {
+      Some(tab) => contents(ui, tab),
+      None => {
+        ui.centered_and_justified(|ui| ui.weak("Drop a tab here"));
+      }
+    }
+
+    // Toda el area recibe las pestañas que se sueltan encima.
+    let zone = ui.interact(ui.max_rect(), ui.id().with("dock_drop"), Sense::hover());
+
+    if zone.dnd_hover_payload::<Tab>().is_some() {
+      let stroke = ui.visuals().selection.stroke;
+      ui.painter()
+        .rect_stroke(zone.rect, 4.0, stroke, egui::StrokeKind::Inside);
+    }
+
+    if let Some(tab) = zone.dnd_release_payload::<Tab>() {
+      moves.push((*tab, Some(area)));
+    }
+  }
+}
+
+/// `layout.ron` in the user's config directory: `%APPDATA%\sagitario` on Windows,
+/// `~/Library/Application Support/sagitario` on macOS and `$XDG_CONFIG_HOME/sagitario`, or
+/// `~/.config/sagitario`, elsewhere. `None` if the variables aren't set.
+pub fn user_layout_path() -> Option<PathBuf> {
+  let var = |name| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+
+  let config = if cfg!(target_os = "windows") {
+    var("APPDATA")?
+  } else if cfg!(target_os = "macos") {
+    var("HOME")?.join("Library/Application Support")
+  } else {
+    var("XDG_CONFIG_HOME").or_else(|| Some(var("HOME")?.join(".config")))?
+  };
+
+  Some(config.join("sagitario").join(LAYOUT_FILE))
+}
@@ -0,0 +1,68 @@
+use winit::event::MouseButton;
+use winit::keyboard::{Key, ModifiersState, PhysicalKey};
+use winit::window::CursorIcon;
+
+pub fn modifiers(state: ModifiersState) -> egui::Modifiers {
+  egui::Modifiers {
+    alt: state.alt_key(),
+    ctrl: state.control_key(),
+    shift: state.shift_key(),
+    mac_cmd: cfg!(target_os = "macos") && state.super_key(),
+    command: if cfg!(target_os = "macos") {
+      state.super_key()
+    } else {
+      state.control_key()
+    },
+  }
+}
+
+pub fn pointer_button(button: MouseButton) -> Option<egui::PointerButton> {
+  Some(match button {
+    MouseButton::Left => egui::PointerButton::Primary,
+    MouseButton::Right => egui::PointerButton::Secondary,
+    MouseButton::Middle => egui::PointerButton::Middle,
+    MouseButton::Back => egui::PointerButton::Extra1,
+    MouseButton::Forward => egui::PointerButton::Extra2,
+    MouseButton::Other(_) => return None,
+  })
+}
+
+/// Key the layout produces, e.g. `Z` on the physical `Y` of a German keyboard.
+pub fn logical_key(key: &Key) -> Option<egui::Key> {
+  match key {
+    Key::Named(named) => egui::Key::from_name(&format!("{:?}", named)),
+    Key::Character(text) => egui::Key::from_name(text),
+    _ => None,
+  }
+}
+
+/// Key at the position of a US keyboard, whatever the layout.
+pub fn physical_key(key: PhysicalKey) -> Option<egui::Key> {
+  let PhysicalKey::Code(code) = key else {
+    return None;
+  };
+
+  // Los nombres de winit son `KeyA`, `Digit0`, `ArrowUp`...
+  let name = format!("{:?}", code);
+  egui::Key::from_name(name.strip_prefix("Key").unwrap_or(&name))
+}
+
+pub fn cursor_icon(icon: egui::CursorIcon) -> Option<CursorIcon> {
+  Some(match icon {
+    egui::CursorIcon::None => return None,
+    egui::CursorIcon::PointingHand => CursorIcon::Pointer,
+    egui::CursorIcon::Text => CursorIcon::Text,
+    egui::CursorIcon::Crosshair => CursorIcon::Crosshair,
+    egui::CursorIcon::Move => CursorIcon::Move,
+    egui::CursorIcon::NotAllowed | egui::CursorIcon::NoDrop => CursorIcon::NotAllowed,
+    egui::CursorIcon::Grab => CursorIcon::Grab,
+    egui::CursorIcon::Grabbing => CursorIcon::Grabbing,
+    egui::CursorIcon::ResizeHorizontal | egui::CursorIcon::ResizeColumn => CursorIcon::EwResize,
+    egui::CursorIcon::ResizeVertical | egui::CursorIcon::ResizeRow => CursorIcon::NsResize,
+    egui::CursorIcon::ResizeNeSw => CursorIcon::NeswResize,
+    egui::CursorIcon::ResizeNwSe => CursorIcon::NwseResize,
+    egui::CursorIcon::Wait => CursorIcon::Wait,
+    egui::CursorIcon::Progress => CursorIcon::Progress,
+    _ => CursorIcon::Default,
+  })
+}
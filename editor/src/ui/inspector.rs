@@ -0,0 +1,263 @@
+use egui::{Key, Ui};
+use sagitario_ecs::Entity;
+use sagitario_reflect::{Reflect, Value};
+
+use super::outliner::entity_name;
+use super::panels::SceneEdit;
+use crate::scene::file::reflected_entry;
+use crate::scene::guid::Guid;
+use crate::scene::Scene;
+
+/// Components of the primary selection, walked through `Reflect` on the entry the scene file
+/// saves: every component and field shows up without code of its own. Each value gets the
+/// widget of its kind, with the range and the tooltip of its field.
+#[derive(Clone, Debug, Default)]
+pub struct Inspector {
+  /// Text field being typed in and its text so far.
+  editing: Option<(Entity, String, String)>,
+}
+
+impl Inspector {
+  pub fn show(&mut self, ui: &mut Ui, scene: &Scene, edits: &mut Vec<SceneEdit>) {
+    let Some(&entity) = scene.selection.last() else {
+      ui.weak("Nothing selected");
+      return;
+    };
+
+    let entry = match reflected_entry(scene, entity) {
+      Result::Ok(entry) => entry,
+      Err(error) => {
+        ui.weak(error.to_string());
+        return;
+      }
+    };
+
+    ui.heading(entity_name(scene, entity));
+
+    if let Some(guid) = scene.world.get::<Guid>(entity) {
+      ui.weak(guid.to_string());
+    }
+
+    egui::ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
+      for info in entry.fields().iter().filter(|f| !f.hidden) {
+        // Los componentes que la entidad no tiene son `None`, sin campos.
+        let Some(component) = entry.field(info.name).filter(|c| !c.fields().is_empty()) else {
+          continue;
+        };
+
+        egui::CollapsingHeader::new(info.name)
+          .default_open(true)
+          .show(ui, |ui| {
+            egui::Grid::new(("inspector", info.name))
+              .num_columns(2)
+              .striped(true)
+              .show(ui, |ui| {
+                let field = Field {
+                  entity,
+                  path: info.name.to_string(),
+                  range: info.range,
+                };
+                self.children(ui, &field, component, 0, edits);
+              });
+          });
+      }
+    });
+  }
+
+  /// A row per field or item of `value`, below the row of `value` itself.
+  fn children(&mut self, ui: &mut Ui, parent: &Field, value: &dyn Reflect, depth: usize, edits: &mut Vec<SceneEdit>) {
+    let current = value.to_value();
+    let Some(entries) = current.entries() else {
+      return;
+    };
+
+    for (name, _) in entries {
+      let info = value.fields().iter().find(|f| f.name == name);
+
+      if info.is_some_and(|f| f.hidden) {
+        continue;
+      }
+
+      let Some(child) = value.field(&name) else {
+        continue;
+      };
+
+      let mut field = parent.child(&name);
+      field.range = info.and_then(|f| f.range).or(parent.range);
+
+      ui.horizontal(|ui| {
+        ui.add_space(depth as f32 * 12.0);
+        let label = ui.label(&name);
+
+        if let Some(tooltip) = info.and_then(|f| f.tooltip) {
+          label.on_hover_text(tooltip);
+        }
+      });
+
+      let nested = self.widget(ui, &field, child, edits);
+      ui.end_row();
+
+      if nested {
+        self.children(ui, &field, child, depth + 1, edits);
+      }
+    }
+  }
+
+  /// The widget for `value`; `true` if its fields go in rows of their own below it.
+  fn widget(&mut self, ui: &mut Ui, field: &Field, value: &dyn Reflect, edits: &mut Vec<SceneEdit>) -> bool {
+    let current = value.to_value();
+
+    if !value.variants().is_empty() {
+      let selected = current.variant().unwrap_or_default().to_string();
+
+      egui::ComboBox::from_id_salt(("variant", &field.path))
+        .selected_text(&selected)
+        .show_ui(ui, |ui| {
+          for &variant in value.variants() {
+            if ui.selectable_label(variant == selected, variant).clicked() && variant != selected {
+              // Los campos de la variante nueva empiezan a cero.
+              edits.push(field.edit(Value::Variant(variant.to_string(), vec![])));
+            }
+          }
+        });
+
+      return !value.fields().is_empty();
+    }
+
+    match &current {
+      Value::Bool(on) => {
+        let mut on = *on;
+
+        if ui.checkbox(&mut on, "").changed() {
+          edits.push(field.edit(Value::Bool(on)));
+        }
+
+        false
+      }
+      Value::Int(_) | Value::Float(_) => {
+        if let Some(value) = drag(ui, &current, field.range) {
+          edits.push(field.edit(value));
+        }
+
+        false
+      }
+      Value::Text(text) => {
+        self.text(ui, field, text, edits);
+        false
+      }
+      // Vectores, colores y demas grupos de numeros van en una sola fila.
+      value if numeric(value) => {
+        ui.horizontal_wrapped(|ui| {
+          let named = matches!(value, Value::Struct(_));
+
+          for (name, item) in value.entries().unwrap_or_default() {
+            if named {
+              ui.weak(&name);
+            }
+
+            if let Some(item) = drag(ui, item, field.range) {
+              edits.push(field.child(&name).edit(item));
+            }
+          }
+        });
+
+        false
+      }
+      Value::Unit => {
+        ui.weak("None");
+        false
+      }
+      _ => {
+        ui.weak(value.type_name());
+        true
+      }
+    }
+  }
+
+  /// Committed when it loses focus; Escape discards what was typed.
+  fn text(&mut self, ui: &mut Ui, field: &Field, value: &str, edits: &mut Vec<SceneEdit>) {
+    let editing = self
+      .editing
+      .as_ref()
+      .filter(|(e, p, _)| *e == field.entity && *p == field.path)
+      .map(|(_, _, text)| text.clone());
+    let mut text = editing.unwrap_or_else(|| value.to_string());
+    let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(f32::INFINITY));
+
+    if response.has_focus() {
+      self.editing = Some((field.entity, field.path.clone(), text.clone()));
+    }
+
+    if response.lost_focus() {
+      if text != value && !ui.input(|i| i.key_pressed(Key::Escape)) {
+        edits.push(field.edit(Value::Text(text)));
+      }
+
+      self.editing = None;
+    }
+  }
+}
+
+/// Where a value shown in the inspector is, and the range its widgets keep to. Ranges apply to
+/// the numbers inside the field too, like the channels of a color.
+struct Field {
+  entity: Entity,
+  path: String,
+  range: Option<(f64, f64)>,
+}
+
+impl Field {
+  fn child(&self, name: &str) -> Self {
+    Self {
+      entity: self.entity,
+      path: format!("{}.{}", self.path, name),
+      range: self.range,
+    }
+  }
+
+  fn edit(&self, value: Value) -> SceneEdit {
+    SceneEdit::SetField {
+      entity: self.entity,
+      field: self.path.clone(),
+      value,
+    }
+  }
+}
+
+/// A struct, tuple or list of numbers only.
+fn numeric(value: &Value) -> bool {
+  let entries = match value {
+    Value::Struct(_) | Value::Tuple(_) | Value::List(_) => value.entries().unwrap_or_default(),
+    _ => return false,
+  };
+
+  !entries.is_empty()
+    && entries
+      .iter()
+      .all(|(_, v)| matches!(v, Value::Int(_) | Value::Float(_)))
+}
+
+/// A drag value for a number, kept in `range`; the new number if it changed.
+fn drag(ui: &mut Ui, value: &Value, range: Option<(f64, f64)>) -> Option<Value> {
+  match *value {
+    Value::Int(mut int) => {
+      let mut drag = egui::DragValue::new(&mut int).speed(0.1);
+
+      if let Some((min, max)) = range {
+        drag = drag.range(min as i64..=max as i64);
+      }
+
+      ui.add(drag).changed().then_some(Value::Int(int))
+    }
+    Value::Float(mut float) => {
+      let mut drag = egui::DragValue::new(&mut float).speed(0.01);
+
+      if let Some((min, max)) = range {
+        drag = drag.range(min..=max);
+      }
+
+      ui.add(drag).changed().then_some(Value::Float(float))
+    }
+    _ => None,
+  }
+}
@@ -0,0 +1,254 @@
+use std::time::Instant;
+
+use egui::{
+  ClippedPrimitive, Context, Event, MouseWheelUnit, Order, Pos2, RawInput, Rect, TextureId, TexturesDelta, ViewportId,
+};
+use winit::event::{ElementState, MouseScrollDelta, WindowEvent};
+use winit::window::{CursorIcon, Window};
+
+pub mod assets;
+pub mod console;
+pub mod dock;
+pub mod input;
+pub mod inspector;
+pub mod outliner;
+pub mod panels;
+
+/// The scene as rendered for the viewport tab, bound by `vulkan::ui`.
+pub const VIEWPORT_TEXTURE: TextureId = TextureId::User(0);
+
+/// Tessellated UI of a frame, drawn by `vulkan::ui` over the presented image.
+pub struct UiOutput {
+  pub primitives: Vec<ClippedPrimitive>,
+  /// Texture changes not applied yet; they add up if a frame is skipped.
+  pub textures: TexturesDelta,
+  pub pixels_per_point: f32,
+}
+
+impl Default for UiOutput {
+  fn default() -> Self {
+    Self {
+      primitives: vec![],
+      textures: TexturesDelta::default(),
+      pixels_per_point: 1.0,
+    }
+  }
+}
+
+/// What the UI did with a window event.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EventResponse {
+  /// The pointer or keyboard was on the UI: the viewport shouldn't react to it.
+  pub consumed: bool,
+  /// The UI has to be laid out again to show the event.
+  pub repaint: bool,
+}
+
+/// Immediate-mode editor UI: egui fed with the window events and laid out in logical points.
+/// Winit reports physical pixels, so positions and sizes are divided by the window scale factor,
+/// which egui also uses as its pixels per point.
+pub struct EditorUi {
+  context: Context,
+  input: RawInput,
+  start: Instant,
+  scale_factor: f64,
+  cursor: Option<Pos2>,
+  modifiers: egui::Modifiers,
+  focused: bool,
+  max_texture_side: usize,
+  cursor_icon: Option<CursorIcon>,
+  repaint: bool,
+  /// Viewport tab of the last frame, in points.
+  viewport: Option<Rect>,
+}
+
+impl EditorUi {
+  pub fn new(window: &Window, max_texture_side: usize) -> Self {
+    let context = Context::default();
+    context.set_visuals(egui::Visuals::dark());
+
+    Self {
+      context,
+      input: RawInput::default(),
+      start: Instant::now(),
+      scale_factor: window.scale_factor(),
+      cursor: None,
+      modifiers: egui::Modifiers::default(),
+      focused: true,
+      max_texture_side,
+      cursor_icon: Some(CursorIcon::Default),
+      repaint: true,
+      viewport: None,
+    }
+  }
+
+  /// Where the viewport tab was laid out, `None` if it's hidden.
+  pub fn set_viewport(&mut self, rect: Option<Rect>) {
+    self.viewport = rect;
+  }
+
+  /// The viewport tab in physical pixels: x, y, width and height.
+  pub fn viewport(&self) -> Option<[f32; 4]> {
+    let rect = self.viewport?;
+    let scale = self.scale_factor as f32;
+
+    Some([
+      rect.min.x * scale,
+      rect.min.y * scale,
+      rect.width() * scale,
+      rect.height() * scale,
+    ])
+  }
+
+  /// The cursor is on the viewport tab and no window or popup covers it there.
+  fn over_viewport(&self) -> bool {
+    let (Some(cursor), Some(viewport)) = (self.cursor, self.viewport) else {
+      return false;
+    };
+
+    viewport.contains(cursor)
+      && self
+        .context
+        .layer_id_at(cursor)
+        .is_none_or(|layer| layer.order == Order::Background)
+  }
+
+  /// Queues `event` for the next frame. Presses and the wheel over the UI, and keys while it has
+  /// the keyboard focus, are consumed; releases never are, so held buttons can't get stuck. The
+  /// viewport tab is part of the UI but lets the pointer through to the scene.
+  pub fn handle(&mut self, event: &WindowEvent) -> EventResponse {
+    let context = &self.context;
+    let modifiers = self.modifiers;
+
+    let consumed = match event {
+      WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+        self.scale_factor = *scale_factor;
+        false
+      }
+      WindowEvent::Focused(focused) => {
+        self.focused = *focused;
+        self.input.events.push(Event::WindowFocused(*focused));
+        false
+      }
+      WindowEvent::ModifiersChanged(state) => {
+        self.modifiers = input::modifiers(state.state());
+        false
+      }
+      WindowEvent::CursorMoved { position, .. } => {
+        let position = position.to_logical::<f32>(self.scale_factor);
+        let position = Pos2::new(position.x, position.y);
+        self.cursor = Some(position);
+        self.input.events.push(Event::PointerMoved(position));
+        context.is_using_pointer()
+      }
+      WindowEvent::CursorLeft { .. } => {
+        self.cursor = None;
+        self.input.events.push(Event::PointerGone);
+        false
+      }
+      WindowEvent::MouseInput { state, button, .. } => {
+        let (Some(pos), Some(button)) = (self.cursor, input::pointer_button(*button)) else {
+          return EventResponse::default();
+        };
+
+        let pressed = *state == ElementState::Pressed;
+        self.input.events.push(Event::PointerButton {
+          pos,
+          button,
+          pressed,
+          modifiers,
+        });
+        pressed && context.wants_pointer_input() && !self.over_viewport()
+      }
+      WindowEvent::MouseWheel { delta, .. } => {
+        let (unit, delta) = match delta {
+          MouseScrollDelta::LineDelta(x, y) => (MouseWheelUnit::Line, egui::vec2(*x, *y)),
+          MouseScrollDelta::PixelDelta(position) => {
+            let position = position.to_logical::<f32>(self.scale_factor);
+            (MouseWheelUnit::Point, egui::vec2(position.x, position.y))
+          }
+        };
+
+        self.input.events.push(Event::MouseWheel { unit, delta, modifiers });
+        context.wants_pointer_input() && !self.over_viewport()
+      }
+      WindowEvent::KeyboardInput { event, .. } => {
+        let pressed = event.state == ElementState::Pressed;
+
+        if let Some(key) = input::logical_key(&event.logical_key) {
+          self.input.events.push(Event::Key {
+            key,
+            physical_key: input::physical_key(event.physical_key),
+            pressed,
+            repeat: event.repeat,
+            modifiers,
+          });
+        }
+
+        // Los atajos con Ctrl / Cmd no escriben texto.
+        let text = event
+          .text
+          .as_ref()
+          .filter(|_| pressed && !modifiers.ctrl && !modifiers.command);
+
+        if let Some(text) = text.filter(|t| t.chars().all(|c| !c.is_control())) {
+          self.input.events.push(Event::Text(text.to_string()));
+        }
+
+        pressed && context.wants_keyboard_input()
+      }
+      _ => return EventResponse::default(),
+    };
+
+    EventResponse {
+      consumed,
+      repaint: true,
+    }
+  }
+
+  /// Lays out a frame with `build` and tessellates it for the window's scale factor.
+  pub fn run(&mut self, window: &Window, build: impl FnMut(&Context)) -> UiOutput {
+    let size = window.inner_size().to_logical::<f32>(self.scale_factor);
+    let mut input = std::mem::take(&mut self.input);
+    input.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, egui::vec2(size.width, size.height)));
+    input.time = Some(self.start.elapsed().as_secs_f64());
+    input.modifiers = self.modifiers;
+    input.focused = self.focused;
+    input.max_texture_side = Some(self.max_texture_side);
+    input
+      .viewports
+      .entry(ViewportId::ROOT)
+      .or_default()
+      .native_pixels_per_point = Some(self.scale_factor as f32);
+
+    let output = self.context.run(input, build);
+
+    self.repaint = output
+      .viewport_output
+      .get(&ViewportId::ROOT)
+      .is_some_and(|v| v.repaint_delay.is_zero());
+
+    let icon = input::cursor_icon(output.platform_output.cursor_icon);
+
+    if icon != self.cursor_icon {
+      window.set_cursor_visible(icon.is_some());
+
+      if let Some(icon) = icon {
+        window.set_cursor(icon);
+      }
+
+      self.cursor_icon = icon;
+    }
+
+    UiOutput {
+      primitives: self.context.tessellate(output.shapes, output.pixels_per_point),
+      textures: output.textures_delta,
+      pixels_per_point: output.pixels_per_point,
+    }
+  }
+
+  /// The last frame is animating and wants another one right away.
+  pub fn needs_repaint(&self) -> bool {
+    self.repaint
+  }
+}
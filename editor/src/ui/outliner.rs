@@ -0,0 +1,118 @@
+use egui::collapsing_header::CollapsingState;
+use egui::{Sense, Ui};
+use sagitario_ecs::Entity;
+
+use super::panels::SceneEdit;
+use crate::scene::emitter::ParticleEmitter;
+use crate::scene::guid::Guid;
+use crate::scene::light::Light;
+use crate::scene::prefab::PrefabInstance;
+use crate::scene::prop::Prop;
+use crate::scene::transform::{parent_of, Children};
+use crate::scene::Scene;
+
+/// What the outliner and the inspector call an entity: its prefab, light or mesh name.
+pub fn entity_name(scene: &Scene, entity: Entity) -> String {
+  let world = &scene.world;
+
+  if let Some(instance) = world.get::<PrefabInstance>(entity) {
+    if let Some(prefab) = scene.prefabs.get(&instance.prefab) {
+      return prefab.name.clone();
+    }
+  }
+
+  if let Some(light) = world.get::<Light>(entity) {
+    return light.name.clone();
+  }
+
+  if let Some(mesh) = world.get::<Prop>(entity).and_then(|p| scene.meshes.get(p.mesh.0)) {
+    return mesh.name.clone();
+  }
+
+  if world.has::<ParticleEmitter>(entity) {
+    return "Emitter".to_string();
+  }
+
+  format!("Entity {}", entity)
+}
+
+/// Tree of the saved entities. Clicking one selects it and shift-click adds or removes it;
+/// dropping one on another parents it to it, and on the space below the tree unparents it.
+pub fn show(ui: &mut Ui, scene: &Scene, edits: &mut Vec<SceneEdit>) {
+  let roots = {
+    let mut query = scene.world.query::<(Entity, &Guid)>();
+    let entities = query.iter().map(|(e, _)| e).collect::<Vec<_>>();
+    entities
+      .into_iter()
+      .filter(|&e| parent_of(&scene.world, e).is_none())
+      .collect::<Vec<_>>()
+  };
+
+  egui::ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
+    for root in roots {
+      node(ui, scene, root, edits);
+    }
+
+    let height = (ui.clip_rect().bottom() - ui.cursor().top()).max(24.0);
+    let (_, rest) = ui.allocate_exact_size(egui::vec2(ui.available_width(), height), Sense::hover());
+
+    if let Some(entity) = rest.dnd_release_payload::<Entity>() {
+      edits.push(SceneEdit::SetParent {
+        entity: *entity,
+        parent: None,
+      });
+    }
+  });
+}
+
+fn node(ui: &mut Ui, scene: &Scene, entity: Entity, edits: &mut Vec<SceneEdit>) {
+  let children = scene
+    .world
+    .get::<Children>(entity)
+    .map(|c| c.iter().collect::<Vec<_>>())
+    .unwrap_or_default();
+
+  if children.is_empty() {
+    ui.horizontal(|ui| {
+      ui.add_space(ui.spacing().indent);
+      row(ui, scene, entity, edits);
+    });
+    return;
+  }
+
+  let id = ui.make_persistent_id(("outliner", entity));
+  CollapsingState::load_with_default_open(ui.ctx(), id, true)
+    .show_header(ui, |ui| row(ui, scene, entity, edits))
+    .body(|ui| {
+      for child in children {
+        node(ui, scene, child, edits);
+      }
+    });
+}
+
+fn row(ui: &mut Ui, scene: &Scene, entity: Entity, edits: &mut Vec<SceneEdit>) {
+  let label = egui::Button::selectable(scene.is_selected(entity), entity_name(scene, entity));
+  let response = ui.add(label.sense(Sense::click_and_drag()));
+
+  if response.clicked() {
+    edits.push(SceneEdit::Select {
+      entity,
+      additive: ui.input(|i| i.modifiers.shift),
+    });
+  }
+
+  response.dnd_set_drag_payload(entity);
+
+  if response.dnd_hover_payload::<Entity>().is_some_and(|e| *e != entity) {
+    let stroke = ui.visuals().selection.stroke;
+    ui.painter()
+      .rect_stroke(response.rect, 2.0, stroke, egui::StrokeKind::Outside);
+  }
+
+  if let Some(child) = response.dnd_release_payload::<Entity>().filter(|e| **e != entity) {
+    edits.push(SceneEdit::SetParent {
+      entity: *child,
+      parent: Some(entity),
+    });
+  }
+}
@@ -0,0 +1,278 @@
+use std::path::PathBuf;
+
+use egui::{Color32, Context, Pos2, Rect, Sense, Ui};
+use sagitario_ecs::Entity;
+use sagitario_input::Input;
+use sagitario_reflect::Value;
+
+use super::assets::AssetBrowser;
+use super::console::Console;
+use super::dock::{DockLayout, Tab};
+use super::inspector::Inspector;
+use super::{outliner, VIEWPORT_TEXTURE};
+use crate::asset::loader::LoadProgress;
+use crate::scene::Scene;
+
+/// What the stats tab and the menus show, gathered by the app before the layout.
+#[derive(Clone, Debug, Default)]
+pub struct SceneStats {
+  pub entities: usize,
+  pub props: usize,
+  pub lights: usize,
+  pub prefabs: usize,
+  pub selected: usize,
+  /// Names of the commands undo and redo would apply.
+  pub undo: Option<String>,
+  pub redo: Option<String>,
+  pub dirty: bool,
+  pub grid: bool,
+  pub bounds: bool,
+  /// Asset imports, shown in the status bar while they run.
+  pub loading: LoadProgress,
+}
+
+/// Change to the scene asked for from the outliner or the inspector. The app applies it, through
+/// the undo history when it edits the scene.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneEdit {
+  Select {
+    entity: Entity,
+    additive: bool,
+  },
+  SetParent {
+    entity: Entity,
+    parent: Option<Entity>,
+  },
+  /// `field` is a path into the reflected `EntityEntry`, like `light.color.0`.
+  SetField {
+    entity: Entity,
+    field: String,
+    value: Value,
+  },
+}
+
+/// What the panels did in a frame.
+#[derive(Clone, Debug, Default)]
+pub struct PanelOutput {
+  /// Input actions triggered from the menus.
+  pub actions: Vec<&'static str>,
+  pub edits: Vec<SceneEdit>,
+  /// Where the viewport tab is, in points; `None` if it isn't shown.
+  pub viewport: Option<Rect>,
+}
+
+/// Menu bar and docked panels of the editor. Menu items trigger the input action of their
+/// shortcut, so the app handles them exactly like the keys.
+pub struct Panels {
+  pub layout: DockLayout,
+  /// Where the layout is saved, `None` to keep it for the session only.
+  layout_path: Option<PathBuf>,
+  inspector: Inspector,
+  assets: AssetBrowser,
+  console: Console,
+}
+
+impl Default for Panels {
+  fn default() -> Self {
+    Self::new(None, PathBuf::from("."))
+  }
+}
+
+impl Panels {
+  /// Panels with the layout saved at `layout_path`, or the default one, and the asset browser
+  /// rooted at `assets`.
+  pub fn new(layout_path: Option<PathBuf>, assets: PathBuf) -> Self {
+    let layout = match layout_path.as_ref().filter(|p| p.exists()).map(DockLayout::load) {
+      Some(Result::Ok(layout)) => layout,
+      Some(Err(error)) => {
+        log::warn!("Can't load the editor layout: {}", error);
+        DockLayout::default()
+      }
+      None => DockLayout::default(),
+    };
+
+    Self {
+      layout,
+      layout_path,
+      inspector: Inspector::default(),
+      assets: AssetBrowser::new(assets),
+      console: Console::default(),
+    }
+  }
+
+  pub fn save_layout(&self) {
+    let Some(path) = &self.layout_path else {
+      return;
+    };
+
+    match self.layout.save(path) {
+      Result::Ok(()) => log::info!("[+] layout saved to {}", path.display()),
+      Err(error) => log::warn!("Can't save the editor layout: {}", error),
+    }
+  }
+
+  /// Lays out the panels and returns what was done in them.
+  pub fn show(&mut self, context: &Context, input: &Input, scene: &Scene, stats: &SceneStats) -> PanelOutput {
+    let mut output = PanelOutput::default();
+    let actions = &mut output.actions;
+
+    egui::TopBottomPanel::top("menu_bar").show(context, |ui| {
+      egui::MenuBar::new().ui(ui, |ui| {
+        ui.menu_button("File", |ui| {
+          let save = if stats.dirty { "Save *" } else { "Save" };
+          menu_item(ui, input, save, "save_scene", actions);
+          menu_item(ui, input, "Screenshot", "screenshot", actions);
+        });
+
+        ui.menu_button("Edit", |ui| {
+          let undo = stats
+            .undo
+            .as_ref()
+            .map_or("Undo".into(), |name| format!("Undo {}", name));
+          let redo = stats
+            .redo
+            .as_ref()
+            .map_or("Redo".into(), |name| format!("Redo {}", name));
+
+          ui.add_enabled_ui(stats.undo.is_some(), |ui| menu_item(ui, input, &undo, "undo", actions));
+          ui.add_enabled_ui(stats.redo.is_some(), |ui| menu_item(ui, input, &redo, "redo", actions));
+          ui.separator();
+          menu_item(ui, input, "Delete", "delete_selected", actions);
+          menu_item(ui, input, "Make Prefab", "make_prefab", actions);
+          menu_item(ui, input, "Instantiate Prefab", "instantiate_prefab", actions);
+        });
+
+        ui.menu_button("Scene", |ui| {
+          menu_item(ui, input, "Add Light", "add_light", actions);
+          menu_item(ui, input, "Toggle Particles", "toggle_particles", actions);
+          menu_item(ui, input, "Next Background", "next_background", actions);
+        });
+
+        ui.menu_button("View", |ui| {
+          toggle_item(ui, input, "Grid", stats.grid, "toggle_grid", actions);
+          toggle_item(ui, input, "Bounds", stats.bounds, "toggle_bounds", actions);
+          ui.separator();
+
+          for tab in Tab::ALL {
+            let mut open = self.layout.is_open(tab);
+
+            if ui.checkbox(&mut open, tab.title()).changed() {
+              match open {
+                true => self.layout.open(tab),
+                false => self.layout.close(tab),
+              }
+            }
+          }
+
+          if ui.button("Reset Layout").clicked() {
+            self.layout = DockLayout::default();
+            ui.close();
+          }
+
+          ui.separator();
+          menu_item(ui, input, "Frame Selected", "frame_selected", actions);
+          menu_item(ui, input, "Toggle 2D", "toggle_2d", actions);
+          menu_item(ui, input, "Next View Mode", "next_view_mode", actions);
+        });
+      });
+    });
+
+    if stats.loading.is_loading() {
+      egui::TopBottomPanel::bottom("status_bar").show(context, |ui| show_status(ui, &stats.loading));
+    }
+
+    let Self {
+      layout,
+      inspector,
+      assets,
+      console,
+      ..
+    } = self;
+    let (edits, viewport) = (&mut output.edits, &mut output.viewport);
+
+    layout.show(context, |ui, tab| match tab {
+      Tab::Viewport => {
+        // La escena se dibuja en la textura del viewport, al tamaño de este rectangulo.
+        let rect = ui.available_rect_before_wrap();
+        let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+        ui.painter().image(VIEWPORT_TEXTURE, rect, uv, Color32::WHITE);
+        ui.allocate_rect(rect, Sense::hover());
+        *viewport = Some(rect);
+      }
+      Tab::Outliner => outliner::show(ui, scene, edits),
+      Tab::Inspector => inspector.show(ui, scene, edits),
+      Tab::Assets => assets.show(ui),
+      Tab::Console => console.show(ui),
+      Tab::Stats => show_stats(ui, stats),
+    });
+
+    output
+  }
+}
+
+fn show_stats(ui: &mut Ui, stats: &SceneStats) {
+  let dt = ui.input(|i| i.stable_dt).max(1e-4);
+
+  egui::Grid::new("stats").num_columns(2).show(ui, |ui| {
+    for (label, value) in [
+      ("Frame", format!("{:.2} ms ({:.0} fps)", dt * 1000.0, 1.0 / dt)),
+      ("Entities", stats.entities.to_string()),
+      ("Props", stats.props.to_string()),
+      ("Lights", stats.lights.to_string()),
+      ("Prefabs", stats.prefabs.to_string()),
+      ("Selected", stats.selected.to_string()),
+      ("Unsaved", if stats.dirty { "yes" } else { "no" }.to_string()),
+    ] {
+      ui.label(label);
+      ui.label(value);
+      ui.end_row();
+    }
+  });
+}
+
+fn show_status(ui: &mut Ui, loading: &LoadProgress) {
+  ui.horizontal(|ui| {
+    ui.label(format!("Loading assets {} / {}", loading.finished, loading.queued));
+    ui.add(
+      egui::ProgressBar::new(loading.fraction())
+        .desired_width(200.0)
+        .show_percentage(),
+    );
+
+    if loading.failed > 0 {
+      ui.colored_label(ui.visuals().warn_fg_color, format!("{} failed", loading.failed));
+    }
+  });
+}
+
+/// Button labelled with the first binding of `action`.
+fn menu_item(ui: &mut Ui, input: &Input, label: &str, action: &'static str, actions: &mut Vec<&'static str>) {
+  menu_button(ui, input, egui::Button::new(label), action, actions);
+}
+
+/// Same as `menu_item`, shown as selected while `on`.
+fn toggle_item(
+  ui: &mut Ui,
+  input: &Input,
+  label: &str,
+  on: bool,
+  action: &'static str,
+  actions: &mut Vec<&'static str>,
+) {
+  menu_button(ui, input, egui::Button::new(label).selected(on), action, actions);
+}
+
+fn menu_button(
+  ui: &mut Ui,
+  input: &Input,
+  button: egui::Button,
+  action: &'static str,
+  actions: &mut Vec<&'static str>,
+) {
+  let shortcut = input.map().bindings(action).first().map(|b| b.to_string());
+
+  if ui.add(button.shortcut_text(shortcut.unwrap_or_default())).clicked() {
+    actions.push(action);
+    ui.close();
+  }
+}
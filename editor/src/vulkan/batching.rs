@@ -0,0 +1,202 @@
+use cgmath::{InnerSpace, Matrix4, Point3};
+use log::warn;
+use sagitario_ecs::Entity;
+use vulkanalia::vk;
+
+use super::geometry::MeshRange;
+use crate::scene::frustum::Frustum;
+use crate::scene::prop::{Material, MaterialId, Prop};
+use crate::scene::transform::GlobalTransform;
+use crate::scene::Scene;
+
+pub const MAX_INSTANCES: usize = 16384;
+pub const MAX_DRAWS: usize = 1024;
+pub const MAX_MATERIALS: usize = 256;
+
+/// Graphics pipelines props can be drawn with, in the order batches are recorded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MeshPipeline {
+  Opaque,
+  DoubleSided,
+}
+
+impl MeshPipeline {
+  pub const ALL: [Self; 2] = [Self::Opaque, Self::DoubleSided];
+
+  pub fn of(material: &Material) -> Self {
+    if material.double_sided {
+      Self::DoubleSided
+    } else {
+      Self::Opaque
+    }
+  }
+}
+
+/// std430 layout of `Instance` in `common/instances.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GpuInstance {
+  pub model: Matrix4<f32>,
+  /// World space bounding sphere, xyz: center, w: radius.
+  pub bounds: [f32; 4],
+  /// x: material index, y: draw command, z: entity index, w: 1 if the prop is selected.
+  pub info: [u32; 4],
+}
+
+/// std430 layout of `Material` in `common/instances.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GpuMaterial {
+  pub base_color: [f32; 4],
+  /// rgb: emitted radiance.
+  pub emissive: [f32; 4],
+  /// x: metallic, y: roughness, z: occlusion strength, w: normal scale.
+  pub params: [f32; 4],
+}
+
+/// A run of consecutive draw commands sharing one pipeline and material.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Batch {
+  pub pipeline: MeshPipeline,
+  pub material: MaterialId,
+  pub first_command: u32,
+  pub command_count: u32,
+}
+
+/// Per-frame draw data. Instances are sorted by pipeline, material and mesh so every
+/// (material, mesh) pair is one indexed indirect command.
+#[derive(Clone, Debug, Default)]
+pub struct FrameBatches {
+  pub instances: Vec<GpuInstance>,
+  /// Indices into `instances` read through `gl_InstanceIndex`, each command owns the slots
+  /// starting at its `first_instance`.
+  pub visible: Vec<u32>,
+  pub commands: Vec<vk::DrawIndexedIndirectCommand>,
+  pub batches: Vec<Batch>,
+  /// Every instance of every group without culling, drawn through `instances` directly by the
+  /// shadow passes.
+  pub shadow_commands: Vec<vk::DrawIndexedIndirectCommand>,
+}
+
+pub fn build_materials(materials: &[Material]) -> Vec<GpuMaterial> {
+  if materials.len() > MAX_MATERIALS {
+    warn!("Only the first {} materials are uploaded.", MAX_MATERIALS);
+  }
+
+  materials
+    .iter()
+    .take(MAX_MATERIALS)
+    .map(|m| GpuMaterial {
+      base_color: m.base_color,
+      emissive: [m.emissive[0], m.emissive[1], m.emissive[2], 0.0],
+      params: [m.metallic, m.roughness, m.occlusion_strength, m.normal_scale],
+    })
+    .collect()
+}
+
+/// Groups the scene props into draw commands. With a `frustum` the props are culled on the CPU
+/// and the commands are final; without one every command starts with zero instances and the
+/// culling compute pass fills `instance_count` and the visible list.
+pub fn build_batches(scene: &Scene, ranges: &[MeshRange], frustum: Option<&Frustum>) -> FrameBatches {
+  let mut props = scene.world.query::<(Entity, &Prop, &GlobalTransform)>();
+  let mut keyed = props
+    .iter()
+    .filter(|(_, p, _)| p.mesh.0 < ranges.len() && p.material.0 < scene.materials.len().min(MAX_MATERIALS))
+    .map(|(e, p, g)| {
+      (
+        MeshPipeline::of(&scene.materials[p.material.0]),
+        p.material,
+        p.mesh,
+        e,
+        g.0,
+      )
+    })
+    .collect::<Vec<_>>();
+
+  if keyed.len() > MAX_INSTANCES {
+    warn!("Only the first {} props are drawn.", MAX_INSTANCES);
+    keyed.truncate(MAX_INSTANCES);
+  }
+
+  keyed.sort_unstable_by_key(|&(pipeline, material, mesh, entity, _)| (pipeline, material, mesh, entity));
+
+  let mut frame = FrameBatches::default();
+
+  for group in keyed.chunk_by(|a, b| (a.0, a.1, a.2) == (b.0, b.1, b.2)) {
+    if frame.commands.len() == MAX_DRAWS {
+      warn!("Only the first {} draw commands are recorded.", MAX_DRAWS);
+      break;
+    }
+
+    let (pipeline, material, mesh, _, _) = group[0];
+    let range = ranges[mesh.0];
+    let command_index = frame.commands.len() as u32;
+    let first_instance = frame.instances.len() as u32;
+    let first_visible = frame.visible.len() as u32;
+
+    for &(_, _, _, entity, transform) in group {
+      let center = Point3::from_homogeneous(transform.w);
+      let scale = transform
+        .x
+        .truncate()
+        .magnitude()
+        .max(transform.y.truncate().magnitude())
+        .max(transform.z.truncate().magnitude());
+      let radius = range.bounding_radius * scale;
+
+      if let Some(frustum) = frustum {
+        if frustum.intersects_sphere(center, radius) {
+          frame.visible.push(frame.instances.len() as u32);
+        }
+      }
+
+      frame.instances.push(GpuInstance {
+        model: transform,
+        bounds: [center.x, center.y, center.z, radius],
+        info: [
+          material.0 as u32,
+          command_index,
+          entity.index(),
+          scene.is_selected(entity) as u32,
+        ],
+      });
+    }
+
+    frame.shadow_commands.push(vk::DrawIndexedIndirectCommand {
+      index_count: range.index_count,
+      instance_count: group.len() as u32,
+      first_index: range.first_index,
+      vertex_offset: range.vertex_offset,
+      first_instance,
+    });
+
+    let (instance_count, first_instance) = match frustum {
+      Some(_) => (frame.visible.len() as u32 - first_visible, first_visible),
+      None => (0, first_instance),
+    };
+
+    if frustum.is_some() && instance_count == 0 {
+      continue;
+    }
+
+    frame.commands.push(vk::DrawIndexedIndirectCommand {
+      index_count: range.index_count,
+      instance_count,
+      first_index: range.first_index,
+      vertex_offset: range.vertex_offset,
+      first_instance,
+    });
+
+    match frame.batches.last_mut() {
+      Some(batch) if batch.pipeline == pipeline && batch.material == material => batch.command_count += 1,
+      _ => frame.batches.push(Batch {
+        pipeline,
+        material,
+        first_command: frame.commands.len() as u32 - 1,
+        command_count: 1,
+      }),
+    }
+  }
+
+  frame
+}
@@ -0,0 +1,149 @@
+use std::mem::size_of_val;
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use anyhow::{anyhow, Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0},
+  Device, Instance,
+};
+use vulkanalia_sys::Handle;
+
+use super::VulkanAppData;
+
+pub unsafe fn get_memory_type_index(
+  instance: &Instance,
+  data: &VulkanAppData,
+  properties: vk::MemoryPropertyFlags,
+  requirements: vk::MemoryRequirements,
+) -> Result<u32> {
+  let memory = instance.get_physical_device_memory_properties(data.physical_device);
+
+  (0..memory.memory_type_count)
+    .find(|i| {
+      let suitable = (requirements.memory_type_bits & (1 << i)) != 0;
+      let memory_type = memory.memory_types[*i as usize];
+
+      suitable && memory_type.property_flags.contains(properties)
+    })
+    .ok_or_else(|| anyhow!("Failed to find suitable memory type."))
+}
+
+pub unsafe fn create_buffer(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  size: vk::DeviceSize,
+  usage: vk::BufferUsageFlags,
+  properties: vk::MemoryPropertyFlags,
+) -> Result<(vk::Buffer, vk::DeviceMemory)> {
+  let buffer_info = vk::BufferCreateInfo::builder()
+    .size(size)
+    .usage(usage)
+    .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+  let buffer = device.create_buffer(&buffer_info, None)?;
+
+  let requirements = device.get_buffer_memory_requirements(buffer);
+  let memory_info = vk::MemoryAllocateInfo::builder()
+    .allocation_size(requirements.size)
+    .memory_type_index(get_memory_type_index(instance, data, properties, requirements)?);
+
+  let buffer_memory = device.allocate_memory(&memory_info, None)?;
+
+  device.bind_buffer_memory(buffer, buffer_memory, 0)?;
+
+  Ok((buffer, buffer_memory))
+}
+
+/// Host-visible buffer rewritten by the CPU every frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HostBuffer {
+  pub buffer: vk::Buffer,
+  pub memory: vk::DeviceMemory,
+}
+
+pub unsafe fn create_host_buffer(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  size: usize,
+  usage: vk::BufferUsageFlags,
+) -> Result<HostBuffer> {
+  let (buffer, memory) = create_buffer(
+    instance,
+    device,
+    data,
+    size as vk::DeviceSize,
+    usage,
+    vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+  )?;
+
+  Ok(HostBuffer { buffer, memory })
+}
+
+pub unsafe fn destroy_host_buffer(device: &Device, buffer: &HostBuffer) {
+  device.destroy_buffer(buffer.buffer, None);
+  device.free_memory(buffer.memory, None);
+}
+
+pub unsafe fn begin_single_time_commands(device: &Device, data: &VulkanAppData) -> Result<vk::CommandBuffer> {
+  let info = vk::CommandBufferAllocateInfo::builder()
+    .level(vk::CommandBufferLevel::PRIMARY)
+    .command_pool(data.command_pool)
+    .command_buffer_count(1);
+
+  let command_buffer = device.allocate_command_buffers(&info)?[0];
+
+  let info = vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+  device.begin_command_buffer(command_buffer, &info)?;
+
+  Ok(command_buffer)
+}
+
+pub unsafe fn end_single_time_commands(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+) -> Result<()> {
+  device.end_command_buffer(command_buffer)?;
+
+  let command_buffers = &[command_buffer];
+  let info = vk::SubmitInfo::builder().command_buffers(command_buffers);
+
+  device.queue_submit(data.graphics_queue, &[info], vk::Fence::null())?;
+  device.queue_wait_idle(data.graphics_queue)?;
+
+  device.free_command_buffers(data.command_pool, &[command_buffer]);
+
+  Ok(())
+}
+
+/// Copies `values` into host-visible, host-coherent `memory`. Empty `values` write nothing, so
+/// per-frame lists that can be empty, like the visible instances, don't map zero bytes.
+pub unsafe fn write_memory<T: Copy>(device: &Device, memory: vk::DeviceMemory, values: &[T]) -> Result<()> {
+  write_memory_at(device, memory, 0, values)
+}
+
+/// Same as `write_memory` starting `offset` bytes into `memory`.
+pub unsafe fn write_memory_at<T: Copy>(
+  device: &Device,
+  memory: vk::DeviceMemory,
+  offset: vk::DeviceSize,
+  values: &[T],
+) -> Result<()> {
+  let size = size_of_val(values);
+
+  // vkMapMemory no admite tamaño cero.
+  if size == 0 {
+    return Ok(());
+  }
+
+  let destination = device.map_memory(memory, offset, size as vk::DeviceSize, vk::MemoryMapFlags::empty())?;
+
+  memcpy(values.as_ptr(), destination.cast(), values.len());
+
+  device.unmap_memory(memory);
+
+  Ok(())
+}
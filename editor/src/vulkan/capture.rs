@@ -0,0 +1,568 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr::copy_nonoverlapping as memcpy;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Ok, Result};
+use log::{error, info, warn};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{begin_single_time_commands, create_buffer, end_single_time_commands};
+use super::output::DisplayOutput;
+use super::VulkanAppData;
+
+pub const CAPTURE_DIR: &str = "captures";
+pub const DEFAULT_SEQUENCE_FRAMES: u32 = 120;
+
+/// Captures waiting for the PNG writer before `render` blocks on it.
+const PENDING_WRITES: usize = 4;
+
+const REC2020_TO_REC709: [[f32; 3]; 3] = [
+  [1.6605, -0.5876, -0.0728],
+  [-0.1246, 1.1329, -0.0083],
+  [-0.0182, -0.1006, 1.1187],
+];
+
+#[derive(Debug)]
+struct FrameSequence {
+  directory: PathBuf,
+  next: u32,
+  count: u32,
+}
+
+/// Thread encoding and writing the captured PNGs in order, fed through a bounded channel so a
+/// long sequence can't queue up more frames than it writes.
+#[derive(Debug)]
+struct PngWriter {
+  sender: SyncSender<(CapturedImage, PathBuf)>,
+  thread: JoinHandle<()>,
+}
+
+impl PngWriter {
+  fn start() -> Result<Self> {
+    let (sender, receiver) = mpsc::sync_channel::<(CapturedImage, PathBuf)>(PENDING_WRITES);
+    let thread = thread::Builder::new()
+      .name("capture-writer".into())
+      .spawn(move || receiver.into_iter().for_each(|(image, path)| save_png(&image, &path)))?;
+
+    Ok(Self { sender, thread })
+  }
+}
+
+/// Pending captures; `render` asks for the target of the current frame with `next_target`.
+#[derive(Debug, Default)]
+pub struct CaptureQueue {
+  screenshot: Option<PathBuf>,
+  sequence: Option<FrameSequence>,
+  writer: Option<PngWriter>,
+}
+
+impl CaptureQueue {
+  pub fn screenshot(&mut self, path: PathBuf) {
+    self.screenshot = Some(path);
+  }
+
+  /// Captures the next `count` frames into `directory`; a count of 0 captures nothing.
+  pub fn record(&mut self, directory: PathBuf, count: u32) {
+    if count == 0 {
+      warn!("Can't record a sequence of 0 frames");
+      return;
+    }
+
+    self.sequence = Some(FrameSequence {
+      directory,
+      next: 0,
+      count,
+    });
+  }
+
+  /// Drops the screenshot and the sequence still to capture, after a capture failed.
+  pub fn cancel(&mut self) {
+    if self.screenshot.take().is_some() || self.sequence.take().is_some() {
+      warn!("Capture cancelled");
+    }
+  }
+
+  /// Hands a captured frame to the writer thread, started with the first one. Blocks while the
+  /// writer is `PENDING_WRITES` frames behind.
+  pub fn write(&mut self, image: CapturedImage, path: PathBuf) -> Result<()> {
+    if self.writer.is_none() {
+      self.writer = Some(PngWriter::start()?);
+    }
+
+    let writer = self.writer.as_ref().ok_or_else(|| anyhow!("No capture writer"))?;
+    writer
+      .sender
+      .send((image, path))
+      .map_err(|_| anyhow!("The capture writer stopped"))
+  }
+
+  /// Blocks until every capture has been written to disk.
+  pub fn wait(&mut self) {
+    let Some(writer) = self.writer.take() else {
+      return;
+    };
+
+    // Sin emisor, el hilo sale al escribir lo que queda.
+    drop(writer.sender);
+
+    if writer.thread.join().is_err() {
+      error!("The capture writer thread panicked");
+    }
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.screenshot.is_some() || self.sequence.is_some()
+  }
+
+  pub fn next_target(&mut self) -> Option<PathBuf> {
+    if let Some(path) = self.screenshot.take() {
+      return Some(path);
+    }
+
+    let sequence = self.sequence.as_mut()?;
+    let path = sequence.directory.join(format!("frame_{:04}.png", sequence.next));
+
+    sequence.next += 1;
+
+    if sequence.next >= sequence.count {
+      info!("[+] capture -> sequence of {} frames done", sequence.count);
+      self.sequence = None;
+    }
+
+    Some(path)
+  }
+}
+
+fn timestamp() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default()
+}
+
+pub fn default_screenshot_path() -> PathBuf {
+  PathBuf::from(CAPTURE_DIR).join(format!("screenshot_{}.png", timestamp()))
+}
+
+pub fn default_sequence_dir() -> PathBuf {
+  PathBuf::from(CAPTURE_DIR).join(format!("sequence_{}", timestamp()))
+}
+
+/// Raw texels read back from the GPU, still in the source image format.
+pub struct CapturedImage {
+  pub width: u32,
+  pub height: u32,
+  pub format: vk::Format,
+  pub output: DisplayOutput,
+  pub paper_white_nits: f32,
+  pub texels: Vec<u8>,
+}
+
+fn bytes_per_texel(format: vk::Format) -> Result<u32> {
+  match format {
+    vk::Format::B8G8R8A8_SRGB
+    | vk::Format::B8G8R8A8_UNORM
+    | vk::Format::R8G8B8A8_SRGB
+    | vk::Format::R8G8B8A8_UNORM
+    | vk::Format::A2B10G10R10_UNORM_PACK32
+    | vk::Format::A2R10G10B10_UNORM_PACK32 => Ok(4),
+    vk::Format::R16G16B16A16_SFLOAT => Ok(8),
+    _ => Err(anyhow!("Capture of {:?} images is not supported.", format)),
+  }
+}
+
+/// Copies `image` (currently in `layout`) into a host buffer and restores its layout. The caller
+/// must make sure the GPU is done writing it, i.e. wait for the frame's fence first.
+pub unsafe fn capture_image(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  image: vk::Image,
+  format: vk::Format,
+  extent: vk::Extent2D,
+  layout: vk::ImageLayout,
+) -> Result<CapturedImage> {
+  let size = (extent.width * extent.height * bytes_per_texel(format)?) as vk::DeviceSize;
+
+  let (buffer, buffer_memory) = create_buffer(
+    instance,
+    device,
+    data,
+    size,
+    vk::BufferUsageFlags::TRANSFER_DST,
+    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+  )?;
+
+  let subresource_range = vk::ImageSubresourceRange::builder()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .base_mip_level(0)
+    .level_count(1)
+    .base_array_layer(0)
+    .layer_count(1);
+
+  let to_transfer = vk::ImageMemoryBarrier::builder()
+    .old_layout(layout)
+    .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .image(image)
+    .subresource_range(subresource_range)
+    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+    .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+
+  let to_original = vk::ImageMemoryBarrier::builder()
+    .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+    .new_layout(layout)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .image(image)
+    .subresource_range(subresource_range)
+    .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+    .dst_access_mask(vk::AccessFlags::MEMORY_READ);
+
+  let region = vk::BufferImageCopy::builder()
+    .buffer_offset(0)
+    .buffer_row_length(0)
+    .buffer_image_height(0)
+    .image_subresource(vk::ImageSubresourceLayers {
+      aspect_mask: vk::ImageAspectFlags::COLOR,
+      mip_level: 0,
+      base_array_layer: 0,
+      layer_count: 1,
+    })
+    .image_offset(vk::Offset3D::default())
+    .image_extent(vk::Extent3D {
+      width: extent.width,
+      height: extent.height,
+      depth: 1,
+    });
+
+  let command_buffer = begin_single_time_commands(device, data)?;
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+    vk::PipelineStageFlags::TRANSFER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[] as &[vk::BufferMemoryBarrier],
+    &[to_transfer],
+  );
+  device.cmd_copy_image_to_buffer(
+    command_buffer,
+    image,
+    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+    buffer,
+    &[region],
+  );
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::TRANSFER,
+    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[] as &[vk::BufferMemoryBarrier],
+    &[to_original],
+  );
+
+  end_single_time_commands(device, data, command_buffer)?;
+
+  let memory = device.map_memory(buffer_memory, 0, size, vk::MemoryMapFlags::empty())?;
+  let mut texels = vec![0u8; size as usize];
+
+  memcpy(memory.cast::<u8>(), texels.as_mut_ptr(), size as usize);
+
+  device.unmap_memory(buffer_memory);
+  device.destroy_buffer(buffer, None);
+  device.free_memory(buffer_memory, None);
+
+  Ok(CapturedImage {
+    width: extent.width,
+    height: extent.height,
+    format,
+    output: data.display_output,
+    paper_white_nits: data.present_config.hdr.paper_white_nits,
+    texels,
+  })
+}
+
+fn srgb_encode(linear: f32) -> u8 {
+  let linear = linear.clamp(0.0, 1.0);
+  let encoded = if linear <= 0.0031308 {
+    linear * 12.92
+  } else {
+    1.055 * linear.powf(1.0 / 2.4) - 0.055
+  };
+
+  (encoded * 255.0).round() as u8
+}
+
+fn pq_decode(encoded: f32) -> f32 {
+  const M1: f32 = 0.159_301_76;
+  const M2: f32 = 78.84375;
+  const C1: f32 = 0.8359375;
+  const C2: f32 = 18.851_563;
+  const C3: f32 = 18.6875;
+
+  let e = encoded.powf(1.0 / M2);
+  let y = ((e - C1).max(0.0) / (C2 - C3 * e)).powf(1.0 / M1);
+
+  y * 10000.0
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+  let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+  let exponent = ((bits >> 10) & 0x1f) as i32;
+  let mantissa = (bits & 0x3ff) as f32;
+
+  match exponent {
+    0 => sign * mantissa * 2f32.powi(-24),
+    31 if mantissa == 0.0 => sign * f32::INFINITY,
+    31 => f32::NAN,
+    _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+  }
+}
+
+impl CapturedImage {
+  /// Converts the texels to 8-bit sRGB RGBA. HDR captures are mapped back to SDR with paper
+  /// white at 1.0, anything brighter is clipped.
+  pub fn to_rgba8(&self) -> Vec<u8> {
+    let texel_count = (self.width * self.height) as usize;
+    let mut rgba = Vec::with_capacity(texel_count * 4);
+
+    match self.format {
+      vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => self
+        .texels
+        .chunks_exact(4)
+        .for_each(|t| rgba.extend_from_slice(&[t[2], t[1], t[0], 255])),
+      vk::Format::A2B10G10R10_UNORM_PACK32 | vk::Format::A2R10G10B10_UNORM_PACK32 => {
+        let rgb_first = self.format == vk::Format::A2B10G10R10_UNORM_PACK32;
+
+        for t in self.texels.chunks_exact(4) {
+          let packed = u32::from_le_bytes([t[0], t[1], t[2], t[3]]);
+          let low = (packed & 0x3ff) as f32 / 1023.0;
+          let mid = ((packed >> 10) & 0x3ff) as f32 / 1023.0;
+          let high = ((packed >> 20) & 0x3ff) as f32 / 1023.0;
+          let encoded = if rgb_first { [low, mid, high] } else { [high, mid, low] };
+
+          rgba.extend_from_slice(&self.hdr10_to_srgb(encoded));
+        }
+      }
+      vk::Format::R16G16B16A16_SFLOAT => {
+        let scale = 80.0 / self.paper_white_nits;
+
+        for t in self.texels.chunks_exact(8) {
+          let channel = |i: usize| f16_to_f32(u16::from_le_bytes([t[i * 2], t[i * 2 + 1]])) * scale;
+
+          rgba.extend_from_slice(&[
+            srgb_encode(channel(0)),
+            srgb_encode(channel(1)),
+            srgb_encode(channel(2)),
+            255,
+          ]);
+        }
+      }
+      _ => self
+        .texels
+        .chunks_exact(4)
+        .for_each(|t| rgba.extend_from_slice(&[t[0], t[1], t[2], 255])),
+    }
+
+    rgba
+  }
+
+  fn hdr10_to_srgb(&self, encoded: [f32; 3]) -> [u8; 4] {
+    if self.output != DisplayOutput::Hdr10 {
+      let unorm = |v: f32| (v * 255.0).round() as u8;
+      return [unorm(encoded[0]), unorm(encoded[1]), unorm(encoded[2]), 255];
+    }
+
+    let rec2020 = encoded.map(|e| pq_decode(e) / self.paper_white_nits);
+    let rec709 = REC2020_TO_REC709.map(|row| row[0] * rec2020[0] + row[1] * rec2020[1] + row[2] * rec2020[2]);
+
+    [
+      srgb_encode(rec709[0]),
+      srgb_encode(rec709[1]),
+      srgb_encode(rec709[2]),
+      255,
+    ]
+  }
+}
+
+/// Encodes and writes the PNG, on the writer thread so recording sequences doesn't stall the frame.
+fn save_png(image: &CapturedImage, path: &Path) {
+  let result = (|| {
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    let rgba = image.to_rgba8();
+    image::save_buffer(path, &rgba, image.width, image.height, image::ColorType::Rgba8)?;
+
+    Ok(())
+  })();
+
+  match result {
+    Result::Ok(()) => info!("[+] capture -> saved {}", path.display()),
+    Err(e) => error!("Failed to save capture {}: {}", path.display(), e),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// PQ code of 100 nits, from the ST.2084 tables.
+  const PQ_100_NITS: f32 = 0.508_078;
+
+  fn image(format: vk::Format, output: DisplayOutput, texels: Vec<u8>) -> CapturedImage {
+    CapturedImage {
+      width: (texels.len() / bytes_per_texel(format).unwrap() as usize) as u32,
+      height: 1,
+      format,
+      output,
+      paper_white_nits: 80.0,
+      texels,
+    }
+  }
+
+  fn f16_texel(rgb: [u16; 3]) -> Vec<u8> {
+    [rgb[0], rgb[1], rgb[2], 0x3c00]
+      .iter()
+      .flat_map(|c| c.to_le_bytes())
+      .collect()
+  }
+
+  /// An A2B10G10R10 texel with each channel quantized to 10 bits.
+  fn a2b10g10r10(rgb: [f32; 3]) -> Vec<u8> {
+    let [r, g, b] = rgb.map(|c| (c * 1023.0).round() as u32);
+    (r | g << 10 | b << 20 | 3 << 30).to_le_bytes().to_vec()
+  }
+
+  #[test]
+  fn swaps_bgra_to_rgba() {
+    let texels = vec![10, 20, 30, 40, 1, 2, 3, 4];
+
+    for format in [vk::Format::B8G8R8A8_SRGB, vk::Format::B8G8R8A8_UNORM] {
+      let image = image(format, DisplayOutput::Srgb, texels.clone());
+      assert_eq!(image.to_rgba8(), [30, 20, 10, 255, 3, 2, 1, 255]);
+    }
+
+    let rgba = image(vk::Format::R8G8B8A8_UNORM, DisplayOutput::SrgbUnorm, texels);
+    assert_eq!(rgba.to_rgba8(), [10, 20, 30, 255, 1, 2, 3, 255]);
+  }
+
+  #[test]
+  fn decodes_half_floats() {
+    assert_eq!(f16_to_f32(0x0000), 0.0);
+    assert_eq!(f16_to_f32(0x3c00), 1.0);
+    assert_eq!(f16_to_f32(0x3800), 0.5);
+    assert_eq!(f16_to_f32(0xc000), -2.0);
+    assert_eq!(f16_to_f32(0x7bff), 65504.0);
+    assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+    assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+    assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+    assert!(f16_to_f32(0x7e00).is_nan());
+  }
+
+  #[test]
+  fn maps_scrgb_to_srgb8() {
+    // 1.0, 0.5, 0.0 y un negativo fuera de gama.
+    let mut scrgb = image(
+      vk::Format::R16G16B16A16_SFLOAT,
+      DisplayOutput::ScRgb,
+      [f16_texel([0x3c00, 0x3800, 0x0000]), f16_texel([0xbc00, 0x4000, 0x3c00])].concat(),
+    );
+
+    assert_eq!(scrgb.to_rgba8(), [255, 188, 0, 255, 0, 255, 255, 255]);
+
+    // Con el blanco a 160 nits, 1.0 (80 nits) es medio blanco.
+    scrgb.paper_white_nits = 160.0;
+    assert_eq!(&scrgb.to_rgba8()[..4], [188, 137, 0, 255]);
+  }
+
+  #[test]
+  fn decodes_pq_at_known_points() {
+    assert_eq!(pq_decode(0.0), 0.0);
+    assert!((pq_decode(1.0) - 10000.0).abs() < 1.0, "{}", pq_decode(1.0));
+    assert!(
+      (pq_decode(PQ_100_NITS) - 100.0).abs() < 0.1,
+      "{}",
+      pq_decode(PQ_100_NITS)
+    );
+  }
+
+  #[test]
+  fn maps_hdr10_paper_white_to_sdr_white() {
+    let mut hdr10 = image(
+      vk::Format::A2B10G10R10_UNORM_PACK32,
+      DisplayOutput::Hdr10,
+      [a2b10g10r10([PQ_100_NITS; 3]), a2b10g10r10([0.0; 3])].concat(),
+    );
+    hdr10.paper_white_nits = 100.0;
+
+    assert_eq!(hdr10.to_rgba8(), [255, 255, 255, 255, 0, 0, 0, 255]);
+
+    // Con el blanco a 200 nits, 100 nits es la mitad en lineal.
+    hdr10.paper_white_nits = 200.0;
+    let half = hdr10.to_rgba8()[0];
+    assert!((187..=189).contains(&half), "{}", half);
+  }
+
+  #[test]
+  fn reads_10_bit_channels_in_format_order() {
+    let texel = a2b10g10r10([1.0, 0.0, 0.0]);
+    let rgb_first = image(
+      vk::Format::A2B10G10R10_UNORM_PACK32,
+      DisplayOutput::SrgbUnorm,
+      texel.clone(),
+    );
+    let bgr_first = image(vk::Format::A2R10G10B10_UNORM_PACK32, DisplayOutput::SrgbUnorm, texel);
+
+    assert_eq!(rgb_first.to_rgba8(), [255, 0, 0, 255]);
+    assert_eq!(bgr_first.to_rgba8(), [0, 0, 255, 255]);
+  }
+
+  #[test]
+  fn numbers_sequence_frames() {
+    let mut queue = CaptureQueue::default();
+    queue.record(PathBuf::from("out"), 3);
+
+    let targets = std::iter::from_fn(|| queue.next_target()).collect::<Vec<_>>();
+
+    assert_eq!(
+      targets,
+      ["frame_0000.png", "frame_0001.png", "frame_0002.png"].map(|f| Path::new("out").join(f))
+    );
+    assert!(!queue.is_active());
+  }
+
+  #[test]
+  fn screenshots_go_before_the_sequence() {
+    let mut queue = CaptureQueue::default();
+    queue.record(PathBuf::from("out"), 2);
+    queue.screenshot(PathBuf::from("shot.png"));
+
+    assert_eq!(queue.next_target(), Some(PathBuf::from("shot.png")));
+    assert_eq!(queue.next_target(), Some(Path::new("out").join("frame_0000.png")));
+    assert!(queue.is_active());
+
+    queue.cancel();
+    assert_eq!(queue.next_target(), None);
+  }
+
+  #[test]
+  fn empty_sequences_capture_nothing() {
+    let mut queue = CaptureQueue::default();
+    queue.record(PathBuf::from("out"), 0);
+
+    assert!(!queue.is_active());
+    assert_eq!(queue.next_target(), None);
+  }
+}
@@ -4,12 +4,25 @@ use vulkanalia::{
   Device, Instance,
 };
 
+use super::debug_draw::record_debug_draw;
+use super::environment::record_skybox;
+use super::instancing::{record_instance_culling, record_instance_draws};
+use super::lighting::record_light_culling;
+use super::particles::{record_particle_draw, record_particle_simulation};
+use super::picking::record_pick_readback;
+use super::post::record_post;
+use super::shadows::record_shadow_pass;
+use super::ui::record_ui;
+use super::view_mode::ViewMode;
 use super::{queue_family::QueueFamilyIndices, VulkanAppData};
 
 pub unsafe fn create_command_pool(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
   let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
 
-  let info = vk::CommandPoolCreateInfo::builder().queue_family_index(indices.graphics);
+  // Los command buffers se graban de nuevo cada frame.
+  let info = vk::CommandPoolCreateInfo::builder()
+    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+    .queue_family_index(indices.graphics);
 
   data.command_pool = device.create_command_pool(&info, None)?;
 
@@ -20,39 +33,71 @@ pub unsafe fn create_command_buffers(device: &Device, data: &mut VulkanAppData)
   let allocate_info = vk::CommandBufferAllocateInfo::builder()
     .command_pool(data.command_pool)
     .level(vk::CommandBufferLevel::PRIMARY)
-    .command_buffer_count(data.framebuffers.len() as u32);
+    .command_buffer_count(data.swapchain_images.len() as u32);
 
   data.command_buffers = device.allocate_command_buffers(&allocate_info)?;
 
-  for (i, command_buffer) in data.command_buffers.iter().enumerate() {
-    let info = vk::CommandBufferBeginInfo::builder();
+  Ok(())
+}
+
+/// Records the frame for `image_index`, once the per-frame buffers of that image are up to date.
+pub unsafe fn record_command_buffer(device: &Device, data: &VulkanAppData, image_index: usize) -> Result<()> {
+  let command_buffer = data.command_buffers[image_index];
+
+  device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+
+  let info = vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+  device.begin_command_buffer(command_buffer, &info)?;
 
-    device.begin_command_buffer(*command_buffer, &info)?;
+  record_particle_simulation(device, data, command_buffer, image_index);
+  record_instance_culling(device, data, command_buffer, image_index);
+  record_light_culling(device, data, command_buffer, image_index);
+  record_shadow_pass(device, data, command_buffer, image_index);
 
-    let render_area = vk::Rect2D::builder()
-      .offset(vk::Offset2D::default())
-      .extent(data.swapchain_extent);
+  let render_area = vk::Rect2D::builder()
+    .offset(vk::Offset2D::default())
+    .extent(data.render_extent());
 
-    let color_clean_value = vk::ClearValue {
-      color: vk::ClearColorValue {
-        float32: [0.0, 0.0, 0.0, 1.0],
-      },
-    };
+  let color_clean_value = vk::ClearValue {
+    color: vk::ClearColorValue {
+      float32: [0.0, 0.0, 0.0, 1.0],
+    },
+  };
 
-    let clear_values = &[color_clean_value];
-    let info = vk::RenderPassBeginInfo::builder()
-      .render_pass(data.render_pass)
-      .framebuffer(data.framebuffers[i])
-      .render_area(render_area)
-      .clear_values(clear_values);
+  // 0 = ningun objeto.
+  let id_clear_value = vk::ClearValue {
+    color: vk::ClearColorValue { uint32: [0; 4] },
+  };
 
-    device.cmd_begin_render_pass(*command_buffer, &info, vk::SubpassContents::INLINE);
-    device.cmd_bind_pipeline(*command_buffer, vk::PipelineBindPoint::GRAPHICS, data.pipeline);
-    device.cmd_draw(*command_buffer, 3, 1, 0, 0);
-    device.cmd_end_render_pass(*command_buffer);
+  let depth_clear_value = vk::ClearValue {
+    depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+  };
 
-    device.end_command_buffer(*command_buffer)?;
+  let clear_values = &[color_clean_value, id_clear_value, depth_clear_value];
+  let info = vk::RenderPassBeginInfo::builder()
+    .render_pass(data.render_pass)
+    .framebuffer(data.framebuffer)
+    .render_area(render_area)
+    .clear_values(clear_values);
+
+  device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+  record_instance_draws(device, data, command_buffer, image_index);
+
+  // En overdraw solo cuentan los props: el cielo taparia el recuento.
+  if data.view_mode != ViewMode::Overdraw {
+    record_skybox(device, data, command_buffer, image_index);
+    record_particle_draw(device, data, command_buffer, image_index);
+    record_debug_draw(device, data, command_buffer, image_index);
   }
 
+  device.cmd_end_render_pass(command_buffer);
+
+  record_pick_readback(device, data, command_buffer, image_index);
+  record_post(device, data, command_buffer, image_index);
+  record_ui(device, data, command_buffer, image_index);
+
+  device.end_command_buffer(command_buffer)?;
+
   Ok(())
 }
@@ -0,0 +1,187 @@
+use std::mem::{offset_of, size_of};
+
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{create_host_buffer, destroy_host_buffer, write_memory, write_memory_at, HostBuffer};
+use super::pipe::{create_graphics_pipeline, BlendMode, IdAttachment, PipelineDesc};
+use super::VulkanAppData;
+use crate::scene::debug_draw::{DebugDraw, DebugVertex};
+
+/// Vertices uploaded per frame, depth-tested and overlay lines together; the rest are dropped.
+const MAX_DEBUG_VERTICES: usize = 1 << 17;
+
+#[derive(Copy, Clone, Debug, Default)]
+struct DebugFrame {
+  vertices: HostBuffer,
+  depth_tested: u32,
+  overlay: u32,
+}
+
+/// Line-list pipelines and per swapchain image vertex buffers of `DebugDraw`.
+#[derive(Clone, Debug, Default)]
+pub struct DebugDrawData {
+  pipeline_layout: vk::PipelineLayout,
+  depth_pipeline: vk::Pipeline,
+  overlay_pipeline: vk::Pipeline,
+  frames: Vec<DebugFrame>,
+}
+
+pub unsafe fn create_debug_draw_system(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let set_layouts = &[data.frame_set_layout];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+
+  data.debug_draw.pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+
+  Ok(())
+}
+
+pub unsafe fn create_debug_draw_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let mut frames = vec![];
+
+  for _ in 0..data.swapchain_images.len() {
+    let vertices = create_host_buffer(
+      instance,
+      device,
+      data,
+      MAX_DEBUG_VERTICES * size_of::<DebugVertex>(),
+      vk::BufferUsageFlags::VERTEX_BUFFER,
+    )?;
+
+    frames.push(DebugFrame {
+      vertices,
+      ..Default::default()
+    });
+  }
+
+  let vert = include_bytes!("./pipe/shader/.tmp/debug/vert.spv");
+  let frag = include_bytes!("./pipe/shader/.tmp/debug/frag.spv");
+
+  let vertex_bindings = [vk::VertexInputBindingDescription::builder()
+    .binding(0)
+    .stride(size_of::<DebugVertex>() as u32)
+    .input_rate(vk::VertexInputRate::VERTEX)
+    .build()];
+
+  let attribute = |location: u32, format: vk::Format, offset: usize| {
+    vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(location)
+      .format(format)
+      .offset(offset as u32)
+      .build()
+  };
+
+  let vertex_attributes = [
+    attribute(0, vk::Format::R32G32B32_SFLOAT, offset_of!(DebugVertex, position)),
+    attribute(1, vk::Format::R32G32B32A32_SFLOAT, offset_of!(DebugVertex, color)),
+  ];
+
+  let mut desc = PipelineDesc::new(
+    &vert[..],
+    &frag[..],
+    data.debug_draw.pipeline_layout,
+    data.render_pass,
+    data.render_extent(),
+  );
+  desc.vertex_bindings = &vertex_bindings;
+  desc.vertex_attributes = &vertex_attributes;
+  desc.topology = vk::PrimitiveTopology::LINE_LIST;
+  desc.cull_mode = vk::CullModeFlags::NONE;
+  desc.blend = BlendMode::Alpha;
+  desc.depth_write = false;
+  desc.depth_compare = vk::CompareOp::LESS_OR_EQUAL;
+  desc.id_attachment = IdAttachment::Masked;
+
+  data.debug_draw.depth_pipeline = create_graphics_pipeline(device, &desc)?;
+
+  desc.depth_test = false;
+  data.debug_draw.overlay_pipeline = create_graphics_pipeline(device, &desc)?;
+  data.debug_draw.frames = frames;
+
+  Ok(())
+}
+
+/// Uploads this frame's lines for `image_index`; the caller clears `debug` afterwards.
+pub unsafe fn update_debug_draw(
+  device: &Device,
+  data: &mut VulkanAppData,
+  image_index: usize,
+  debug: &DebugDraw,
+) -> Result<()> {
+  let (depth_tested, overlay) = debug.vertices();
+  // Siempre un numero par de vertices, para no cortar una linea por la mitad.
+  let depth_tested = &depth_tested[..depth_tested.len().min(MAX_DEBUG_VERTICES) & !1];
+  let overlay = &overlay[..overlay.len().min(MAX_DEBUG_VERTICES - depth_tested.len()) & !1];
+
+  let frame = &mut data.debug_draw.frames[image_index];
+  write_memory(device, frame.vertices.memory, depth_tested)?;
+  write_memory_at(
+    device,
+    frame.vertices.memory,
+    size_of_val(depth_tested) as vk::DeviceSize,
+    overlay,
+  )?;
+
+  frame.depth_tested = depth_tested.len() as u32;
+  frame.overlay = overlay.len() as u32;
+
+  Ok(())
+}
+
+/// Draws the lines inside the scene render pass, overlay lines last so they stay on top.
+pub unsafe fn record_debug_draw(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let debug_draw = &data.debug_draw;
+  let frame = &debug_draw.frames[image_index];
+
+  if frame.depth_tested + frame.overlay == 0 {
+    return;
+  }
+
+  device.cmd_bind_vertex_buffers(command_buffer, 0, &[frame.vertices.buffer], &[0]);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    debug_draw.pipeline_layout,
+    0,
+    &[data.frame_descriptor_sets[image_index]],
+    &[],
+  );
+
+  for (pipeline, first, count) in [
+    (debug_draw.depth_pipeline, 0, frame.depth_tested),
+    (debug_draw.overlay_pipeline, frame.depth_tested, frame.overlay),
+  ] {
+    if count > 0 {
+      device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+      device.cmd_draw(command_buffer, count, 1, first, 0);
+    }
+  }
+}
+
+pub unsafe fn destroy_debug_draw_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  let debug_draw = &mut data.debug_draw;
+
+  device.destroy_pipeline(debug_draw.depth_pipeline, None);
+  device.destroy_pipeline(debug_draw.overlay_pipeline, None);
+  debug_draw
+    .frames
+    .drain(..)
+    .for_each(|f| destroy_host_buffer(device, &f.vertices));
+}
+
+pub unsafe fn destroy_debug_draw_system(device: &Device, data: &mut VulkanAppData) {
+  device.destroy_pipeline_layout(data.debug_draw.pipeline_layout, None);
+}
@@ -0,0 +1,52 @@
+use anyhow::{anyhow, Ok, Result};
+use vulkanalia::{
+  vk::{self, InstanceV1_0},
+  Device, Instance,
+};
+
+use super::images::{create_image, create_image_view};
+use super::VulkanAppData;
+
+const DEPTH_CANDIDATES: &[vk::Format] = &[
+  vk::Format::D32_SFLOAT,
+  vk::Format::D32_SFLOAT_S8_UINT,
+  vk::Format::D24_UNORM_S8_UINT,
+];
+
+pub unsafe fn get_depth_format(instance: &Instance, data: &VulkanAppData) -> Result<vk::Format> {
+  DEPTH_CANDIDATES
+    .iter()
+    .cloned()
+    .find(|f| {
+      let properties = instance.get_physical_device_format_properties(data.physical_device, *f);
+      properties
+        .optimal_tiling_features
+        .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+    })
+    .ok_or_else(|| anyhow!("Failed to find supported depth format."))
+}
+
+pub unsafe fn create_depth_objects(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let format = get_depth_format(instance, data)?;
+  let extent = data.render_extent();
+
+  let (depth_image, depth_image_memory) = create_image(
+    instance,
+    device,
+    data,
+    extent.width,
+    extent.height,
+    1,
+    format,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  data.depth_format = format;
+  data.depth_image = depth_image;
+  data.depth_image_memory = depth_image_memory;
+  data.depth_image_view = create_image_view(device, depth_image, format, vk::ImageAspectFlags::DEPTH, 1)?;
+
+  Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device,
+};
+
+use super::VulkanAppData;
+
+/// Sets allocated per swapchain image: frame uniforms, particles, instances and lights.
+const SETS_PER_IMAGE: u32 = 4;
+
+pub fn layout_binding(
+  binding: u32,
+  descriptor_type: vk::DescriptorType,
+  stage_flags: vk::ShaderStageFlags,
+) -> vk::DescriptorSetLayoutBinding {
+  vk::DescriptorSetLayoutBinding::builder()
+    .binding(binding)
+    .descriptor_type(descriptor_type)
+    .descriptor_count(1)
+    .stage_flags(stage_flags)
+    .build()
+}
+
+pub unsafe fn create_descriptor_set_layout(
+  device: &Device,
+  bindings: &[vk::DescriptorSetLayoutBinding],
+) -> Result<vk::DescriptorSetLayout> {
+  let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+
+  Ok(device.create_descriptor_set_layout(&info, None)?)
+}
+
+/// Pool for every per-swapchain-image set, recreated together with the swapchain.
+pub unsafe fn create_descriptor_pool(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let images = data.swapchain_images.len() as u32;
+
+  let uniform_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::UNIFORM_BUFFER)
+    .descriptor_count(images * 3);
+
+  let storage_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::STORAGE_BUFFER)
+    .descriptor_count(images * 9);
+
+  let sampler_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    .descriptor_count(images * 5);
+
+  let pool_sizes = &[uniform_size, storage_size, sampler_size];
+  let info = vk::DescriptorPoolCreateInfo::builder()
+    .pool_sizes(pool_sizes)
+    .max_sets(images * SETS_PER_IMAGE);
+
+  data.descriptor_pool = device.create_descriptor_pool(&info, None)?;
+
+  Ok(())
+}
+
+pub unsafe fn allocate_descriptor_sets(
+  device: &Device,
+  pool: vk::DescriptorPool,
+  layout: vk::DescriptorSetLayout,
+  count: usize,
+) -> Result<Vec<vk::DescriptorSet>> {
+  let layouts = vec![layout; count];
+  let info = vk::DescriptorSetAllocateInfo::builder()
+    .descriptor_pool(pool)
+    .set_layouts(&layouts);
+
+  Ok(device.allocate_descriptor_sets(&info)?)
+}
+
+pub unsafe fn write_buffer_descriptor(
+  device: &Device,
+  set: vk::DescriptorSet,
+  binding: u32,
+  descriptor_type: vk::DescriptorType,
+  buffer: vk::Buffer,
+  range: vk::DeviceSize,
+) {
+  let info = vk::DescriptorBufferInfo::builder()
+    .buffer(buffer)
+    .offset(0)
+    .range(range);
+
+  let buffer_info = &[info];
+  let write = vk::WriteDescriptorSet::builder()
+    .dst_set(set)
+    .dst_binding(binding)
+    .dst_array_element(0)
+    .descriptor_type(descriptor_type)
+    .buffer_info(buffer_info);
+
+  device.update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+}
+
+pub unsafe fn write_image_descriptor(
+  device: &Device,
+  set: vk::DescriptorSet,
+  binding: u32,
+  view: vk::ImageView,
+  sampler: vk::Sampler,
+) {
+  let info = vk::DescriptorImageInfo::builder()
+    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    .image_view(view)
+    .sampler(sampler);
+
+  let image_info = &[info];
+  let write = vk::WriteDescriptorSet::builder()
+    .dst_set(set)
+    .dst_binding(binding)
+    .dst_array_element(0)
+    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    .image_info(image_info);
+
+  device.update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+}
+
+/// Binds `view` as a storage image, which compute shaders access in the `GENERAL` layout.
+pub unsafe fn write_storage_image_descriptor(
+  device: &Device,
+  set: vk::DescriptorSet,
+  binding: u32,
+  view: vk::ImageView,
+) {
+  let info = vk::DescriptorImageInfo::builder()
+    .image_layout(vk::ImageLayout::GENERAL)
+    .image_view(view);
+
+  let image_info = &[info];
+  let write = vk::WriteDescriptorSet::builder()
+    .dst_set(set)
+    .dst_binding(binding)
+    .dst_array_element(0)
+    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+    .image_info(image_info);
+
+  device.update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+}
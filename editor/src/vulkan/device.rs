@@ -2,7 +2,7 @@ use std::collections::HashSet;
 
 use anyhow::{Ok, Result};
 use vulkanalia::{
-  vk::{self, DeviceV1_0, HasBuilder},
+  vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0},
   Device, Entry, Instance,
 };
 
@@ -17,6 +17,7 @@ pub unsafe fn create_logical(entry: &Entry, instance: &Instance, data: &mut Vulk
   let mut unique_indices = HashSet::new();
   unique_indices.insert(indices.graphics);
   unique_indices.insert(indices.present);
+  unique_indices.insert(indices.transfer);
 
   let queue_priorities = &[1.0];
   let queue_infos = unique_indices
@@ -41,7 +42,12 @@ pub unsafe fn create_logical(entry: &Entry, instance: &Instance, data: &mut Vulk
   }
 
   // Features
-  let features = vk::PhysicalDeviceFeatures::builder();
+  let supported = instance.get_physical_device_features(data.physical_device);
+  let features = vk::PhysicalDeviceFeatures::builder()
+    .multi_draw_indirect(supported.multi_draw_indirect == vk::TRUE)
+    .draw_indirect_first_instance(supported.draw_indirect_first_instance == vk::TRUE)
+    .sampler_anisotropy(supported.sampler_anisotropy == vk::TRUE)
+    .fill_mode_non_solid(supported.fill_mode_non_solid == vk::TRUE);
 
   // Create
   let info = vk::DeviceCreateInfo::builder()
@@ -52,9 +58,12 @@ pub unsafe fn create_logical(entry: &Entry, instance: &Instance, data: &mut Vulk
 
   let device = instance.create_device(data.physical_device, &info, None)?;
 
+  data.features = features.build();
+
   // Queues
   data.graphics_queue = device.get_device_queue(indices.graphics, 0);
   data.present_queue = device.get_device_queue(indices.present, 0);
+  data.transfer_queue = device.get_device_queue(indices.transfer, 0);
 
   Ok(device)
 }
@@ -0,0 +1,755 @@
+use std::path::Path;
+
+use anyhow::{Ok, Result};
+use half::f16;
+use log::{info, warn};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{begin_single_time_commands, end_single_time_commands, get_memory_type_index};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_image_descriptor,
+  write_storage_image_descriptor,
+};
+use super::images::{create_image, create_image_view};
+use super::lighting::lighting_set;
+use super::pipe::compute::{create_compute_pipeline, group_count};
+use super::pipe::{create_graphics_pipeline, IdAttachment, PipelineDesc};
+use super::textures::{create_texture, destroy_texture, Texture};
+use super::utils::bytes::as_bytes;
+use super::VulkanAppData;
+use crate::scene::environment::{Background, Environment};
+
+const CUBE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTER_SIZE: u32 = 128;
+/// Mips of the prefiltered specular map, roughness 0 to 1; must match `common/environment.glsl`.
+pub const PREFILTER_MIPS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+const WORKGROUP_SIZE: u32 = 8;
+
+const SOURCE_EQUIRECT: u32 = 0;
+const SOURCE_GRADIENT: u32 = 1;
+
+/// Push constants of `env_cube/shader.comp`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct CubeParams {
+  /// x: `SOURCE_EQUIRECT` or `SOURCE_GRADIENT`.
+  source: [u32; 4],
+  /// x: rotation in radians, y: lod of the equirectangular image.
+  params: [f32; 4],
+  zenith: [f32; 4],
+  horizon: [f32; 4],
+  ground: [f32; 4],
+}
+
+/// Push constants of `prefilter/shader.comp`, x: roughness, y: size of the environment map.
+type PrefilterParams = [f32; 4];
+
+/// Cube image with a sampled view of every mip and one storage view (2D array) per mip.
+#[derive(Clone, Debug, Default)]
+struct Cubemap {
+  image: vk::Image,
+  memory: vk::DeviceMemory,
+  view: vk::ImageView,
+  mip_views: Vec<vk::ImageView>,
+  size: u32,
+  mip_levels: u32,
+}
+
+/// Environment cubemap and the image based lighting maps baked from it: diffuse irradiance,
+/// prefiltered specular and the split-sum BRDF LUT. The images keep their size across bakes, so
+/// the lighting descriptor sets never have to be rewritten.
+#[derive(Clone, Debug, Default)]
+pub struct EnvironmentData {
+  sampler: vk::Sampler,
+  bake_set_layout: vk::DescriptorSetLayout,
+  bake_pipeline_layout: vk::PipelineLayout,
+  bake_pool: vk::DescriptorPool,
+  cube_pipeline: vk::Pipeline,
+  irradiance_pipeline: vk::Pipeline,
+  prefilter_pipeline: vk::Pipeline,
+  brdf_pipeline: vk::Pipeline,
+  environment: Cubemap,
+  irradiance: Cubemap,
+  prefiltered: Cubemap,
+  brdf_lut: Texture,
+  /// Background and rotation of the last bake.
+  baked: Option<(Background, f32)>,
+  skybox_pipeline_layout: vk::PipelineLayout,
+  skybox_pipeline: vk::Pipeline,
+}
+
+unsafe fn create_cubemap(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  size: u32,
+  mip_levels: u32,
+) -> Result<Cubemap> {
+  let info = vk::ImageCreateInfo::builder()
+    .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+    .image_type(vk::ImageType::_2D)
+    .extent(vk::Extent3D {
+      width: size,
+      height: size,
+      depth: 1,
+    })
+    .mip_levels(mip_levels)
+    .array_layers(6)
+    .format(CUBE_FORMAT)
+    .tiling(vk::ImageTiling::OPTIMAL)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .usage(
+      vk::ImageUsageFlags::STORAGE
+        | vk::ImageUsageFlags::SAMPLED
+        | vk::ImageUsageFlags::TRANSFER_SRC
+        | vk::ImageUsageFlags::TRANSFER_DST,
+    )
+    .samples(vk::SampleCountFlags::_1)
+    .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+  let image = device.create_image(&info, None)?;
+
+  let requirements = device.get_image_memory_requirements(image);
+  let info = vk::MemoryAllocateInfo::builder()
+    .allocation_size(requirements.size)
+    .memory_type_index(get_memory_type_index(
+      instance,
+      data,
+      vk::MemoryPropertyFlags::DEVICE_LOCAL,
+      requirements,
+    )?);
+
+  let memory = device.allocate_memory(&info, None)?;
+
+  device.bind_image_memory(image, memory, 0)?;
+
+  let view = |view_type, base_mip, levels| {
+    let subresource_range = vk::ImageSubresourceRange::builder()
+      .aspect_mask(vk::ImageAspectFlags::COLOR)
+      .base_mip_level(base_mip)
+      .level_count(levels)
+      .base_array_layer(0)
+      .layer_count(6);
+
+    let info = vk::ImageViewCreateInfo::builder()
+      .image(image)
+      .view_type(view_type)
+      .format(CUBE_FORMAT)
+      .subresource_range(subresource_range);
+
+    device.create_image_view(&info, None)
+  };
+
+  let cube_view = view(vk::ImageViewType::CUBE, 0, mip_levels)?;
+  let mip_views = (0..mip_levels)
+    .map(|mip| view(vk::ImageViewType::_2D_ARRAY, mip, 1))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  Ok(Cubemap {
+    image,
+    memory,
+    view: cube_view,
+    mip_views,
+    size,
+    mip_levels,
+  })
+}
+
+unsafe fn destroy_cubemap(device: &Device, cubemap: &Cubemap) {
+  cubemap
+    .mip_views
+    .iter()
+    .for_each(|v| device.destroy_image_view(*v, None));
+  device.destroy_image_view(cubemap.view, None);
+  device.destroy_image(cubemap.image, None);
+  device.free_memory(cubemap.memory, None);
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn image_barrier(
+  device: &Device,
+  command_buffer: vk::CommandBuffer,
+  image: vk::Image,
+  mips: (u32, u32),
+  layers: u32,
+  (old_layout, new_layout): (vk::ImageLayout, vk::ImageLayout),
+  (src_stage, src_access): (vk::PipelineStageFlags, vk::AccessFlags),
+  (dst_stage, dst_access): (vk::PipelineStageFlags, vk::AccessFlags),
+) {
+  let subresource = vk::ImageSubresourceRange::builder()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .base_mip_level(mips.0)
+    .level_count(mips.1)
+    .base_array_layer(0)
+    .layer_count(layers);
+
+  let barrier = vk::ImageMemoryBarrier::builder()
+    .old_layout(old_layout)
+    .new_layout(new_layout)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .image(image)
+    .subresource_range(subresource)
+    .src_access_mask(src_access)
+    .dst_access_mask(dst_access);
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    src_stage,
+    dst_stage,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[] as &[vk::BufferMemoryBarrier],
+    &[barrier],
+  );
+}
+
+/// Decodes an `.hdr` / `.exr` image into RGBA16F texels.
+fn load_equirect(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+  let image = image::open(path)?.to_rgba32f();
+  let (width, height) = image.dimensions();
+  let pixels = image
+    .into_raw()
+    .into_iter()
+    .flat_map(|v| f16::from_f32(v).to_le_bytes())
+    .collect();
+
+  Ok((width, height, pixels))
+}
+
+pub unsafe fn create_environment_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let sampler_info = vk::SamplerCreateInfo::builder()
+    .mag_filter(vk::Filter::LINEAR)
+    .min_filter(vk::Filter::LINEAR)
+    // El equirectangular se repite en horizontal; en los cubemaps no afecta.
+    .address_mode_u(vk::SamplerAddressMode::REPEAT)
+    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+    .min_lod(0.0)
+    .max_lod(vk::LOD_CLAMP_NONE);
+
+  let sampler = device.create_sampler(&sampler_info, None)?;
+
+  let bindings = &[
+    layout_binding(
+      0,
+      vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      vk::ShaderStageFlags::COMPUTE,
+    ),
+    layout_binding(1, vk::DescriptorType::STORAGE_IMAGE, vk::ShaderStageFlags::COMPUTE),
+  ];
+
+  let bake_set_layout = create_descriptor_set_layout(device, bindings)?;
+
+  let range = vk::PushConstantRange::builder()
+    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+    .offset(0)
+    .size(size_of::<CubeParams>() as u32);
+
+  let set_layouts = &[bake_set_layout];
+  let push_constant_ranges = &[range];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder()
+    .set_layouts(set_layouts)
+    .push_constant_ranges(push_constant_ranges);
+
+  let bake_pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+
+  // Un set por pase de bake: cubemap, irradiance, cada mip del prefiltrado y la LUT.
+  let max_sets = PREFILTER_MIPS + 3;
+  let sampler_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    .descriptor_count(max_sets);
+  let storage_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::STORAGE_IMAGE)
+    .descriptor_count(max_sets);
+
+  let pool_sizes = &[sampler_size, storage_size];
+  let pool_info = vk::DescriptorPoolCreateInfo::builder()
+    .pool_sizes(pool_sizes)
+    .max_sets(max_sets);
+
+  let bake_pool = device.create_descriptor_pool(&pool_info, None)?;
+
+  let cube = include_bytes!("./pipe/shader/.tmp/env_cube/comp.spv");
+  let irradiance = include_bytes!("./pipe/shader/.tmp/irradiance/comp.spv");
+  let prefilter = include_bytes!("./pipe/shader/.tmp/prefilter/comp.spv");
+  let brdf = include_bytes!("./pipe/shader/.tmp/brdf_lut/comp.spv");
+
+  let environment_mips = ENVIRONMENT_SIZE.ilog2() + 1;
+
+  let (brdf_image, brdf_memory) = create_image(
+    instance,
+    device,
+    data,
+    BRDF_LUT_SIZE,
+    BRDF_LUT_SIZE,
+    1,
+    CUBE_FORMAT,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  let brdf_view = create_image_view(device, brdf_image, CUBE_FORMAT, vk::ImageAspectFlags::COLOR, 1)?;
+
+  let skybox_layouts = &[data.frame_set_layout, data.lighting.set_layout];
+  let skybox_layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(skybox_layouts);
+
+  data.environment = EnvironmentData {
+    sampler,
+    bake_set_layout,
+    bake_pipeline_layout,
+    bake_pool,
+    cube_pipeline: create_compute_pipeline(device, bake_pipeline_layout, &cube[..])?,
+    irradiance_pipeline: create_compute_pipeline(device, bake_pipeline_layout, &irradiance[..])?,
+    prefilter_pipeline: create_compute_pipeline(device, bake_pipeline_layout, &prefilter[..])?,
+    brdf_pipeline: create_compute_pipeline(device, bake_pipeline_layout, &brdf[..])?,
+    environment: create_cubemap(instance, device, data, ENVIRONMENT_SIZE, environment_mips)?,
+    irradiance: create_cubemap(instance, device, data, IRRADIANCE_SIZE, 1)?,
+    prefiltered: create_cubemap(instance, device, data, PREFILTER_SIZE, PREFILTER_MIPS)?,
+    brdf_lut: Texture {
+      image: brdf_image,
+      memory: brdf_memory,
+      view: brdf_view,
+    },
+    skybox_pipeline_layout: device.create_pipeline_layout(&skybox_layout_info, None)?,
+    ..Default::default()
+  };
+
+  bake_brdf_lut(device, data)?;
+
+  Ok(())
+}
+
+unsafe fn bake_brdf_lut(device: &Device, data: &VulkanAppData) -> Result<()> {
+  let env = &data.environment;
+  let set = allocate_descriptor_sets(device, env.bake_pool, env.bake_set_layout, 1)?[0];
+  // El binding 0 no se usa, pero tiene que ser valido.
+  write_image_descriptor(device, set, 0, env.environment.view, env.sampler);
+  write_storage_image_descriptor(device, set, 1, env.brdf_lut.view);
+
+  let command_buffer = begin_single_time_commands(device, data)?;
+  let compute = vk::PipelineStageFlags::COMPUTE_SHADER;
+
+  image_barrier(
+    device,
+    command_buffer,
+    env.environment.image,
+    (0, env.environment.mip_levels),
+    6,
+    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+    (vk::PipelineStageFlags::TOP_OF_PIPE, vk::AccessFlags::empty()),
+    (compute, vk::AccessFlags::SHADER_READ),
+  );
+  image_barrier(
+    device,
+    command_buffer,
+    env.brdf_lut.image,
+    (0, 1),
+    1,
+    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL),
+    (vk::PipelineStageFlags::TOP_OF_PIPE, vk::AccessFlags::empty()),
+    (compute, vk::AccessFlags::SHADER_WRITE),
+  );
+
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, env.brdf_pipeline);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::COMPUTE,
+    env.bake_pipeline_layout,
+    0,
+    &[set],
+    &[],
+  );
+
+  let groups = group_count(BRDF_LUT_SIZE, WORKGROUP_SIZE);
+  device.cmd_dispatch(command_buffer, groups, groups, 1);
+
+  image_barrier(
+    device,
+    command_buffer,
+    env.brdf_lut.image,
+    (0, 1),
+    1,
+    (vk::ImageLayout::GENERAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+    (compute, vk::AccessFlags::SHADER_WRITE),
+    (vk::PipelineStageFlags::FRAGMENT_SHADER, vk::AccessFlags::SHADER_READ),
+  );
+
+  end_single_time_commands(device, data, command_buffer)?;
+  device.reset_descriptor_pool(env.bake_pool, vk::DescriptorPoolResetFlags::empty())?;
+
+  Ok(())
+}
+
+/// Bakes the environment cubemap, irradiance and prefiltered maps again when the background or
+/// its rotation changed.
+pub unsafe fn sync_environment(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  environment: &Environment,
+) -> Result<()> {
+  let key = (environment.background.clone(), environment.rotation);
+
+  if data.environment.baked.as_ref() == Some(&key) {
+    return Ok(());
+  }
+
+  // Los mapas se reescriben en el mismo sitio: ningun frame puede estar leyendolos.
+  device.device_wait_idle()?;
+
+  let mut params = CubeParams {
+    source: [SOURCE_GRADIENT, 0, 0, 0],
+    params: [environment.rotation.to_radians(), 0.0, 0.0, 0.0],
+    ..Default::default()
+  };
+
+  let gradient = |params: &mut CubeParams, zenith: [f32; 3], horizon: [f32; 3], ground: [f32; 3]| {
+    let rgba = |[r, g, b]: [f32; 3]| [r, g, b, 1.0];
+    params.zenith = rgba(zenith);
+    params.horizon = rgba(horizon);
+    params.ground = rgba(ground);
+  };
+
+  let mut source = None;
+
+  match &environment.background {
+    Background::Solid(color) => gradient(&mut params, *color, *color, *color),
+    Background::Gradient {
+      zenith,
+      horizon,
+      ground,
+    } => gradient(&mut params, *zenith, *horizon, *ground),
+    Background::Hdri(path) => match load_equirect(path) {
+      Result::Ok((width, height, pixels)) => {
+        info!("[+] environment -> {} ({}x{})", path.display(), width, height);
+        let texture = create_texture(instance, device, data, width, height, CUBE_FORMAT, &pixels)?;
+        // Texels del equirectangular por texel de una cara del cubemap.
+        params.params[1] = (width as f32 / (4 * ENVIRONMENT_SIZE) as f32).log2().max(0.0);
+        params.source[0] = SOURCE_EQUIRECT;
+        source = Some(texture);
+      }
+      Err(e) => {
+        warn!(
+          "Failed to load environment {}: {}, using the default gradient.",
+          path.display(),
+          e
+        );
+
+        if let Background::Gradient {
+          zenith,
+          horizon,
+          ground,
+        } = Background::default()
+        {
+          gradient(&mut params, zenith, horizon, ground);
+        }
+      }
+    },
+  }
+
+  // El shader siempre declara el equirectangular; con un gradiente se enlaza una textura vacia.
+  let source = match source {
+    Some(texture) => texture,
+    None => create_texture(instance, device, data, 1, 1, CUBE_FORMAT, &[0; 8])?,
+  };
+
+  let result = bake_environment(device, data, source.view, &params);
+  destroy_texture(device, &source);
+  result?;
+
+  data.environment.baked = Some(key);
+
+  Ok(())
+}
+
+unsafe fn bake_environment(
+  device: &Device,
+  data: &VulkanAppData,
+  source: vk::ImageView,
+  params: &CubeParams,
+) -> Result<()> {
+  let env = &data.environment;
+  let sets = allocate_descriptor_sets(device, env.bake_pool, env.bake_set_layout, 2 + PREFILTER_MIPS as usize)?;
+
+  write_image_descriptor(device, sets[0], 0, source, env.sampler);
+  write_storage_image_descriptor(device, sets[0], 1, env.environment.mip_views[0]);
+  write_image_descriptor(device, sets[1], 0, env.environment.view, env.sampler);
+  write_storage_image_descriptor(device, sets[1], 1, env.irradiance.mip_views[0]);
+
+  for mip in 0..PREFILTER_MIPS as usize {
+    write_image_descriptor(device, sets[2 + mip], 0, env.environment.view, env.sampler);
+    write_storage_image_descriptor(device, sets[2 + mip], 1, env.prefiltered.mip_views[mip]);
+  }
+
+  let command_buffer = begin_single_time_commands(device, data)?;
+  let compute = vk::PipelineStageFlags::COMPUTE_SHADER;
+  let transfer = vk::PipelineStageFlags::TRANSFER;
+  let fragment = vk::PipelineStageFlags::FRAGMENT_SHADER;
+  let environment = &env.environment;
+
+  let dispatch = |pipeline, set, size: u32, push: &[u8]| {
+    device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline);
+    device.cmd_bind_descriptor_sets(
+      command_buffer,
+      vk::PipelineBindPoint::COMPUTE,
+      env.bake_pipeline_layout,
+      0,
+      &[set],
+      &[],
+    );
+
+    if !push.is_empty() {
+      device.cmd_push_constants(
+        command_buffer,
+        env.bake_pipeline_layout,
+        vk::ShaderStageFlags::COMPUTE,
+        0,
+        push,
+      );
+    }
+
+    let groups = group_count(size, WORKGROUP_SIZE);
+    device.cmd_dispatch(command_buffer, groups, groups, 6);
+  };
+
+  // 1. Cubemap del entorno, mip 0 por compute y el resto con blits.
+  image_barrier(
+    device,
+    command_buffer,
+    environment.image,
+    (0, environment.mip_levels),
+    6,
+    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL),
+    (fragment, vk::AccessFlags::SHADER_READ),
+    (compute, vk::AccessFlags::SHADER_WRITE),
+  );
+
+  dispatch(env.cube_pipeline, sets[0], environment.size, as_bytes(params));
+
+  image_barrier(
+    device,
+    command_buffer,
+    environment.image,
+    (0, 1),
+    6,
+    (vk::ImageLayout::GENERAL, vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+    (compute, vk::AccessFlags::SHADER_WRITE),
+    (transfer, vk::AccessFlags::TRANSFER_READ),
+  );
+
+  for mip in 1..environment.mip_levels {
+    let size = |mip: u32| (environment.size >> mip).max(1) as i32;
+
+    image_barrier(
+      device,
+      command_buffer,
+      environment.image,
+      (mip, 1),
+      6,
+      (vk::ImageLayout::GENERAL, vk::ImageLayout::TRANSFER_DST_OPTIMAL),
+      (compute, vk::AccessFlags::empty()),
+      (transfer, vk::AccessFlags::TRANSFER_WRITE),
+    );
+
+    let subresource = |mip| {
+      vk::ImageSubresourceLayers::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(mip)
+        .base_array_layer(0)
+        .layer_count(6)
+    };
+
+    let blit = vk::ImageBlit::builder()
+      .src_offsets([
+        vk::Offset3D::default(),
+        vk::Offset3D {
+          x: size(mip - 1),
+          y: size(mip - 1),
+          z: 1,
+        },
+      ])
+      .src_subresource(subresource(mip - 1))
+      .dst_offsets([
+        vk::Offset3D::default(),
+        vk::Offset3D {
+          x: size(mip),
+          y: size(mip),
+          z: 1,
+        },
+      ])
+      .dst_subresource(subresource(mip));
+
+    device.cmd_blit_image(
+      command_buffer,
+      environment.image,
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      environment.image,
+      vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+      &[blit],
+      vk::Filter::LINEAR,
+    );
+
+    image_barrier(
+      device,
+      command_buffer,
+      environment.image,
+      (mip, 1),
+      6,
+      (
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      ),
+      (transfer, vk::AccessFlags::TRANSFER_WRITE),
+      (transfer, vk::AccessFlags::TRANSFER_READ),
+    );
+  }
+
+  image_barrier(
+    device,
+    command_buffer,
+    environment.image,
+    (0, environment.mip_levels),
+    6,
+    (
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    ),
+    (transfer, vk::AccessFlags::TRANSFER_WRITE),
+    (compute | fragment, vk::AccessFlags::SHADER_READ),
+  );
+
+  // 2. Irradiance y especular prefiltrado a partir del cubemap.
+  for cubemap in [&env.irradiance, &env.prefiltered] {
+    image_barrier(
+      device,
+      command_buffer,
+      cubemap.image,
+      (0, cubemap.mip_levels),
+      6,
+      (vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL),
+      (fragment, vk::AccessFlags::SHADER_READ),
+      (compute, vk::AccessFlags::SHADER_WRITE),
+    );
+  }
+
+  dispatch(env.irradiance_pipeline, sets[1], env.irradiance.size, &[]);
+
+  for mip in 0..PREFILTER_MIPS {
+    let roughness = mip as f32 / (PREFILTER_MIPS - 1) as f32;
+    let params: PrefilterParams = [roughness, environment.size as f32, 0.0, 0.0];
+    let size = (env.prefiltered.size >> mip).max(1);
+
+    dispatch(env.prefilter_pipeline, sets[2 + mip as usize], size, as_bytes(&params));
+  }
+
+  for cubemap in [&env.irradiance, &env.prefiltered] {
+    image_barrier(
+      device,
+      command_buffer,
+      cubemap.image,
+      (0, cubemap.mip_levels),
+      6,
+      (vk::ImageLayout::GENERAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+      (compute, vk::AccessFlags::SHADER_WRITE),
+      (fragment, vk::AccessFlags::SHADER_READ),
+    );
+  }
+
+  end_single_time_commands(device, data, command_buffer)?;
+  device.reset_descriptor_pool(env.bake_pool, vk::DescriptorPoolResetFlags::empty())?;
+
+  Ok(())
+}
+
+/// Views sampled by the mesh and skybox shaders: irradiance, prefiltered, BRDF LUT and environment.
+pub fn environment_views(data: &VulkanAppData) -> ([vk::ImageView; 4], vk::Sampler) {
+  let env = &data.environment;
+  let views = [
+    env.irradiance.view,
+    env.prefiltered.view,
+    env.brdf_lut.view,
+    env.environment.view,
+  ];
+
+  (views, env.sampler)
+}
+
+pub unsafe fn create_environment_swapchain_resources(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let vert = include_bytes!("./pipe/shader/.tmp/skybox/vert.spv");
+  let frag = include_bytes!("./pipe/shader/.tmp/skybox/frag.spv");
+
+  let mut desc = PipelineDesc::new(
+    &vert[..],
+    &frag[..],
+    data.environment.skybox_pipeline_layout,
+    data.render_pass,
+    data.render_extent(),
+  );
+  // El triangulo se dibuja en el plano lejano, solo donde no hay geometria.
+  desc.cull_mode = vk::CullModeFlags::NONE;
+  desc.depth_write = false;
+  desc.depth_compare = vk::CompareOp::LESS_OR_EQUAL;
+  desc.id_attachment = IdAttachment::Masked;
+
+  data.environment.skybox_pipeline = create_graphics_pipeline(device, &desc)?;
+
+  Ok(())
+}
+
+/// Draws the environment behind the scene; must be inside the render pass, after the opaque geometry.
+pub unsafe fn record_skybox(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let env = &data.environment;
+
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, env.skybox_pipeline);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    env.skybox_pipeline_layout,
+    0,
+    &[data.frame_descriptor_sets[image_index], lighting_set(data, image_index)],
+    &[],
+  );
+  device.cmd_draw(command_buffer, 3, 1, 0, 0);
+}
+
+pub unsafe fn destroy_environment_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  device.destroy_pipeline(data.environment.skybox_pipeline, None);
+}
+
+pub unsafe fn destroy_environment_system(device: &Device, data: &mut VulkanAppData) {
+  let env = &mut data.environment;
+
+  destroy_cubemap(device, &env.environment);
+  destroy_cubemap(device, &env.irradiance);
+  destroy_cubemap(device, &env.prefiltered);
+  destroy_texture(device, &env.brdf_lut);
+
+  for pipeline in [
+    env.cube_pipeline,
+    env.irradiance_pipeline,
+    env.prefilter_pipeline,
+    env.brdf_pipeline,
+  ] {
+    device.destroy_pipeline(pipeline, None);
+  }
+
+  device.destroy_pipeline_layout(env.skybox_pipeline_layout, None);
+  device.destroy_pipeline_layout(env.bake_pipeline_layout, None);
+  device.destroy_descriptor_pool(env.bake_pool, None);
+  device.destroy_descriptor_set_layout(env.bake_set_layout, None);
+  device.destroy_sampler(env.sampler, None);
+}
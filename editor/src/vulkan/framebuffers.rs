@@ -6,22 +6,19 @@ use vulkanalia::{
 
 use super::VulkanAppData;
 
-pub unsafe fn create_framebuffers(device: &Device, data: &mut VulkanAppData) -> Result<()> {
-  data.framebuffers = data
-    .swapchain_images_views
-    .iter()
-    .map(|i| {
-      let attachments = &[*i];
-      let create_info = vk::FramebufferCreateInfo::builder()
-        .render_pass(data.render_pass)
-        .attachments(attachments)
-        .width(data.swapchain_extent.width)
-        .height(data.swapchain_extent.height)
-        .layers(1);
+/// Scene framebuffer over the HDR color target, the object ids and the depth buffer; the
+/// swapchain images, or the viewport texture, are only written by the last post-processing pass.
+pub unsafe fn create_framebuffer(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let extent = data.render_extent();
+  let attachments = &[data.post.hdr.view, data.picking.id_view, data.depth_image_view];
+  let create_info = vk::FramebufferCreateInfo::builder()
+    .render_pass(data.render_pass)
+    .attachments(attachments)
+    .width(extent.width)
+    .height(extent.height)
+    .layers(1);
 
-      device.create_framebuffer(&create_info, None)
-    })
-    .collect::<Result<Vec<_>, _>>()?;
+  data.framebuffer = device.create_framebuffer(&create_info, None)?;
 
   Ok(())
 }
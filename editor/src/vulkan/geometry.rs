@@ -0,0 +1,128 @@
+use std::mem::{offset_of, size_of, take};
+
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::uploads::{destroy_uploaded_buffer, upload_buffer};
+use super::VulkanAppData;
+use crate::scene::guid::Guid;
+use crate::scene::mesh::{Mesh, Vertex};
+
+/// Where a mesh lives inside the shared vertex and index buffers.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MeshRange {
+  pub first_index: u32,
+  pub index_count: u32,
+  pub vertex_offset: i32,
+  pub bounding_radius: f32,
+}
+
+/// Every scene mesh packed into one vertex and one index buffer, so all indirect draws share
+/// the same bindings. Rebuilt when a mesh is added or removed, or its file is imported.
+#[derive(Clone, Debug, Default)]
+pub struct GeometryData {
+  vertex_buffer: vk::Buffer,
+  vertex_buffer_memory: vk::DeviceMemory,
+  index_buffer: vk::Buffer,
+  index_buffer_memory: vk::DeviceMemory,
+  pub ranges: Vec<MeshRange>,
+  /// Meshes in the buffers and the revision of each.
+  uploaded: Option<Vec<(Guid, u64)>>,
+}
+
+pub fn vertex_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+  [vk::VertexInputBindingDescription::builder()
+    .binding(0)
+    .stride(size_of::<Vertex>() as u32)
+    .input_rate(vk::VertexInputRate::VERTEX)
+    .build()]
+}
+
+pub fn vertex_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
+  let attribute = |location: u32, format: vk::Format, offset: usize| {
+    vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(location)
+      .format(format)
+      .offset(offset as u32)
+      .build()
+  };
+
+  [
+    attribute(0, vk::Format::R32G32B32_SFLOAT, offset_of!(Vertex, position)),
+    attribute(1, vk::Format::R32G32B32_SFLOAT, offset_of!(Vertex, normal)),
+    attribute(2, vk::Format::R32G32_SFLOAT, offset_of!(Vertex, uv)),
+  ]
+}
+
+pub unsafe fn sync_geometry(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  meshes: &[Mesh],
+) -> Result<()> {
+  let uploaded = meshes.iter().map(|m| (m.guid, m.revision)).collect::<Vec<_>>();
+
+  if data.geometry.uploaded.as_ref() == Some(&uploaded) {
+    return Ok(());
+  }
+
+  // Los buffers anteriores pueden estar en uso por frames en vuelo.
+  if data.geometry.uploaded.is_some() {
+    device.device_wait_idle()?;
+    destroy_geometry(device, data);
+  }
+
+  let mut vertices = vec![];
+  let mut indices = vec![];
+  let mut ranges = vec![];
+
+  for mesh in meshes {
+    ranges.push(MeshRange {
+      first_index: indices.len() as u32,
+      index_count: mesh.indices.len() as u32,
+      vertex_offset: vertices.len() as i32,
+      bounding_radius: mesh.bounding_radius(),
+    });
+
+    vertices.extend_from_slice(&mesh.vertices);
+    indices.extend_from_slice(&mesh.indices);
+  }
+
+  // Vulkan no permite buffers de tamaño cero.
+  if vertices.is_empty() {
+    vertices.push(Vertex::new([0.0; 3].into(), [0.0; 3].into(), [0.0; 2].into()));
+    indices.push(0);
+  }
+
+  let (vertex_buffer, vertex_buffer_memory) =
+    upload_buffer(instance, device, data, vk::BufferUsageFlags::VERTEX_BUFFER, &vertices)?;
+  let (index_buffer, index_buffer_memory) =
+    upload_buffer(instance, device, data, vk::BufferUsageFlags::INDEX_BUFFER, &indices)?;
+
+  data.geometry = GeometryData {
+    vertex_buffer,
+    vertex_buffer_memory,
+    index_buffer,
+    index_buffer_memory,
+    ranges,
+    uploaded: Some(uploaded),
+  };
+
+  Ok(())
+}
+
+pub unsafe fn bind_geometry(device: &Device, data: &VulkanAppData, command_buffer: vk::CommandBuffer) {
+  device.cmd_bind_vertex_buffers(command_buffer, 0, &[data.geometry.vertex_buffer], &[0]);
+  device.cmd_bind_index_buffer(command_buffer, data.geometry.index_buffer, 0, vk::IndexType::UINT32);
+}
+
+pub unsafe fn destroy_geometry(device: &Device, data: &mut VulkanAppData) {
+  let geometry = take(&mut data.geometry);
+
+  destroy_uploaded_buffer(device, data, geometry.vertex_buffer, geometry.vertex_buffer_memory);
+  destroy_uploaded_buffer(device, data, geometry.index_buffer, geometry.index_buffer_memory);
+}
@@ -0,0 +1,86 @@
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::get_memory_type_index;
+use super::VulkanAppData;
+
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn create_image(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  width: u32,
+  height: u32,
+  mip_levels: u32,
+  format: vk::Format,
+  tiling: vk::ImageTiling,
+  usage: vk::ImageUsageFlags,
+  properties: vk::MemoryPropertyFlags,
+) -> Result<(vk::Image, vk::DeviceMemory)> {
+  let info = vk::ImageCreateInfo::builder()
+    .image_type(vk::ImageType::_2D)
+    .extent(vk::Extent3D {
+      width,
+      height,
+      depth: 1,
+    })
+    .mip_levels(mip_levels)
+    .array_layers(1)
+    .format(format)
+    .tiling(tiling)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .usage(usage)
+    .samples(vk::SampleCountFlags::_1)
+    .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+  let image = device.create_image(&info, None)?;
+
+  let requirements = device.get_image_memory_requirements(image);
+  let info = vk::MemoryAllocateInfo::builder()
+    .allocation_size(requirements.size)
+    .memory_type_index(get_memory_type_index(instance, data, properties, requirements)?);
+
+  let image_memory = device.allocate_memory(&info, None)?;
+
+  device.bind_image_memory(image, image_memory, 0)?;
+
+  Ok((image, image_memory))
+}
+
+pub unsafe fn create_image_view(
+  device: &Device,
+  image: vk::Image,
+  format: vk::Format,
+  aspects: vk::ImageAspectFlags,
+  mip_levels: u32,
+) -> Result<vk::ImageView> {
+  create_mip_range_view(device, image, format, aspects, 0, mip_levels)
+}
+
+/// View of `mip_levels` levels starting at `base_mip`, e.g. to render into a single mip.
+pub unsafe fn create_mip_range_view(
+  device: &Device,
+  image: vk::Image,
+  format: vk::Format,
+  aspects: vk::ImageAspectFlags,
+  base_mip: u32,
+  mip_levels: u32,
+) -> Result<vk::ImageView> {
+  let subresource_range = vk::ImageSubresourceRange::builder()
+    .aspect_mask(aspects)
+    .base_mip_level(base_mip)
+    .level_count(mip_levels)
+    .base_array_layer(0)
+    .layer_count(1);
+
+  let info = vk::ImageViewCreateInfo::builder()
+    .image(image)
+    .view_type(vk::ImageViewType::_2D)
+    .format(format)
+    .subresource_range(subresource_range);
+
+  Ok(device.create_image_view(&info, None)?)
+}
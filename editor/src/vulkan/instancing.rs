@@ -0,0 +1,516 @@
+use std::mem::size_of;
+
+use anyhow::{Ok, Result};
+use log::info;
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::batching::{
+  build_batches, build_materials, Batch, FrameBatches, GpuInstance, GpuMaterial, MeshPipeline, MAX_DRAWS,
+  MAX_INSTANCES, MAX_MATERIALS,
+};
+use super::buffers::{create_host_buffer, destroy_host_buffer, write_memory, HostBuffer};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_buffer_descriptor,
+};
+use super::geometry::{bind_geometry, vertex_attribute_descriptions, vertex_binding_descriptions};
+use super::lighting::lighting_set;
+use super::materials::material_set;
+use super::pipe::compute::{create_compute_pipeline, group_count};
+use super::pipe::{create_graphics_pipeline, BlendMode, IdAttachment, PipelineDesc};
+use super::view_mode::ViewMode;
+use super::VulkanAppData;
+use crate::scene::frustum::Frustum;
+use crate::scene::Scene;
+
+const WORKGROUP_SIZE: u32 = 64;
+const COMMAND_STRIDE: u32 = size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+
+/// std140 layout of `Culling` in `common/instances.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct GpuCulling {
+  planes: [[f32; 4]; 6],
+  /// x: number of instances.
+  counts: [u32; 4],
+}
+
+/// Buffers written by the CPU for one swapchain image.
+#[derive(Clone, Debug, Default)]
+struct InstanceFrame {
+  instances: HostBuffer,
+  visible: HostBuffer,
+  materials: HostBuffer,
+  commands: HostBuffer,
+  culling: HostBuffer,
+  descriptor_set: vk::DescriptorSet,
+  instance_count: u32,
+  batches: Vec<Batch>,
+  /// CPU copy of the commands, drawn directly when indirect draws can't use `first_instance`.
+  commands_cpu: Vec<vk::DrawIndexedIndirectCommand>,
+  shadow_commands: HostBuffer,
+  shadow_commands_cpu: Vec<vk::DrawIndexedIndirectCommand>,
+}
+
+/// Instanced prop rendering: per-instance transforms and materials live in storage buffers and
+/// props are drawn with one `cmd_draw_indexed_indirect` per pipeline batch.
+#[derive(Clone, Debug, Default)]
+pub struct InstancingData {
+  pub set_layout: vk::DescriptorSetLayout,
+  pipeline_layout: vk::PipelineLayout,
+  cull_pipeline: vk::Pipeline,
+  /// One pipeline per `MeshPipeline` for each `MeshPass`, pass major.
+  pipelines: Vec<vk::Pipeline>,
+  frames: Vec<InstanceFrame>,
+  gpu_culling: bool,
+}
+
+/// Rasterization variant of the mesh pipelines, picked from the view mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MeshPass {
+  Fill,
+  /// `PolygonMode::LINE`, needs `fillModeNonSolid`; falls back to `Fill` without it.
+  Wireframe,
+  /// No depth test and additive blending, every fragment adds one to the red channel.
+  Overdraw,
+}
+
+impl MeshPass {
+  const ALL: [Self; 3] = [Self::Fill, Self::Wireframe, Self::Overdraw];
+
+  fn of(view_mode: ViewMode) -> Self {
+    match view_mode {
+      ViewMode::Wireframe => Self::Wireframe,
+      ViewMode::Overdraw => Self::Overdraw,
+      _ => Self::Fill,
+    }
+  }
+}
+
+/// Set layout, pipeline layout and the culling compute pipeline, which survive swapchain recreation.
+pub unsafe fn create_instancing_system(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let stages = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT | vk::ShaderStageFlags::COMPUTE;
+  let bindings = &[
+    layout_binding(0, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(1, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(2, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(3, vk::DescriptorType::STORAGE_BUFFER, vk::ShaderStageFlags::COMPUTE),
+    layout_binding(4, vk::DescriptorType::UNIFORM_BUFFER, vk::ShaderStageFlags::COMPUTE),
+  ];
+
+  let set_layout = create_descriptor_set_layout(device, bindings)?;
+
+  let set_layouts = &[
+    data.frame_set_layout,
+    set_layout,
+    data.materials.set_layout,
+    data.lighting.set_layout,
+  ];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+
+  let pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+
+  let comp = include_bytes!("./pipe/shader/.tmp/cull/comp.spv");
+  let cull_pipeline = create_compute_pipeline(device, pipeline_layout, &comp[..])?;
+
+  // firstInstance distinto de cero en un draw indirecto necesita drawIndirectFirstInstance.
+  let gpu_culling = data.features.draw_indirect_first_instance == vk::TRUE;
+
+  if !gpu_culling {
+    info!("[INFO]: drawIndirectFirstInstance not supported, props are culled on the CPU");
+  }
+
+  data.instancing = InstancingData {
+    set_layout,
+    pipeline_layout,
+    cull_pipeline,
+    gpu_culling,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+/// Per swapchain image instance buffers, descriptor sets and the graphics pipelines of every
+/// `MeshPass` and `MeshPipeline`.
+pub unsafe fn create_instancing_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let storage = vk::BufferUsageFlags::STORAGE_BUFFER;
+  let sizes = [
+    MAX_INSTANCES * size_of::<GpuInstance>(),
+    MAX_INSTANCES * size_of::<u32>(),
+    MAX_MATERIALS * size_of::<GpuMaterial>(),
+    MAX_DRAWS * COMMAND_STRIDE as usize,
+    size_of::<GpuCulling>(),
+  ];
+
+  let sets = allocate_descriptor_sets(
+    device,
+    data.descriptor_pool,
+    data.instancing.set_layout,
+    data.swapchain_images.len(),
+  )?;
+
+  let mut frames = vec![];
+
+  for set in sets {
+    let frame = InstanceFrame {
+      instances: create_host_buffer(instance, device, data, sizes[0], storage)?,
+      visible: create_host_buffer(instance, device, data, sizes[1], storage)?,
+      materials: create_host_buffer(instance, device, data, sizes[2], storage)?,
+      commands: create_host_buffer(
+        instance,
+        device,
+        data,
+        sizes[3],
+        storage | vk::BufferUsageFlags::INDIRECT_BUFFER,
+      )?,
+      culling: create_host_buffer(instance, device, data, sizes[4], vk::BufferUsageFlags::UNIFORM_BUFFER)?,
+      shadow_commands: create_host_buffer(instance, device, data, sizes[3], vk::BufferUsageFlags::INDIRECT_BUFFER)?,
+      descriptor_set: set,
+      ..Default::default()
+    };
+
+    let buffers = [
+      frame.instances,
+      frame.visible,
+      frame.materials,
+      frame.commands,
+      frame.culling,
+    ];
+
+    for (binding, (buffer, size)) in buffers.iter().zip(sizes).enumerate() {
+      let descriptor_type = if binding == 4 {
+        vk::DescriptorType::UNIFORM_BUFFER
+      } else {
+        vk::DescriptorType::STORAGE_BUFFER
+      };
+
+      write_buffer_descriptor(
+        device,
+        set,
+        binding as u32,
+        descriptor_type,
+        buffer.buffer,
+        size as vk::DeviceSize,
+      );
+    }
+
+    frames.push(frame);
+  }
+
+  let vert = include_bytes!("./pipe/shader/.tmp/mesh/vert.spv");
+  let frag = include_bytes!("./pipe/shader/.tmp/mesh/frag.spv");
+  let vertex_bindings = vertex_binding_descriptions();
+  let vertex_attributes = vertex_attribute_descriptions();
+
+  let mut pipelines = vec![];
+  let wireframe = data.features.fill_mode_non_solid == vk::TRUE;
+
+  for (pass, pipeline) in MeshPass::ALL
+    .iter()
+    .flat_map(|pass| MeshPipeline::ALL.map(|pipeline| (*pass, pipeline)))
+  {
+    let mut desc = PipelineDesc::new(
+      &vert[..],
+      &frag[..],
+      data.instancing.pipeline_layout,
+      data.render_pass,
+      data.render_extent(),
+    );
+    desc.vertex_bindings = &vertex_bindings;
+    desc.vertex_attributes = &vertex_attributes;
+    desc.front_face = vk::FrontFace::COUNTER_CLOCKWISE;
+    desc.id_attachment = IdAttachment::Written;
+    desc.cull_mode = match pipeline {
+      MeshPipeline::Opaque => vk::CullModeFlags::BACK,
+      MeshPipeline::DoubleSided => vk::CullModeFlags::NONE,
+    };
+
+    match pass {
+      MeshPass::Fill => {}
+      MeshPass::Wireframe if wireframe => desc.polygon_mode = vk::PolygonMode::LINE,
+      MeshPass::Wireframe => {}
+      MeshPass::Overdraw => {
+        desc.blend = BlendMode::Additive;
+        desc.depth_test = false;
+        desc.depth_write = false;
+      }
+    }
+
+    pipelines.push(create_graphics_pipeline(device, &desc)?);
+  }
+
+  data.instancing.pipelines = pipelines;
+  data.instancing.frames = frames;
+
+  Ok(())
+}
+
+pub fn set_gpu_culling(data: &mut VulkanAppData, enabled: bool) {
+  let supported = data.features.draw_indirect_first_instance == vk::TRUE;
+  data.instancing.gpu_culling = enabled && supported;
+
+  info!("[+] instancing -> gpu culling: {}", data.instancing.gpu_culling);
+}
+
+pub fn gpu_culling(data: &VulkanAppData) -> bool {
+  data.instancing.gpu_culling
+}
+
+/// Batches the scene props and uploads instances, materials and draw commands for `image_index`.
+pub unsafe fn update_instances(
+  device: &Device,
+  data: &mut VulkanAppData,
+  image_index: usize,
+  scene: &Scene,
+) -> Result<()> {
+  let extent = data.render_extent();
+  let aspect = extent.width as f32 / extent.height.max(1) as f32;
+  let frustum = Frustum::from_matrix(scene.camera.projection(aspect) * scene.camera.view());
+
+  let gpu_culling = data.instancing.gpu_culling;
+  let FrameBatches {
+    instances,
+    visible,
+    commands,
+    batches,
+    shadow_commands,
+  } = build_batches(scene, &data.geometry.ranges, (!gpu_culling).then_some(&frustum));
+
+  let frame = &mut data.instancing.frames[image_index];
+
+  write_memory(device, frame.instances.memory, &instances)?;
+  write_memory(device, frame.visible.memory, &visible)?;
+  write_memory(device, frame.commands.memory, &commands)?;
+  write_memory(device, frame.shadow_commands.memory, &shadow_commands)?;
+  write_memory(device, frame.materials.memory, &build_materials(&scene.materials))?;
+
+  let culling = GpuCulling {
+    planes: frustum.planes.map(|p| p.into()),
+    counts: [instances.len() as u32, 0, 0, 0],
+  };
+
+  write_memory(device, frame.culling.memory, &[culling])?;
+
+  frame.instance_count = instances.len() as u32;
+  frame.batches = batches;
+  frame.commands_cpu = commands;
+  frame.shadow_commands_cpu = shadow_commands;
+
+  Ok(())
+}
+
+/// Records the culling dispatch that fills the draw commands; must be outside of a render pass.
+pub unsafe fn record_instance_culling(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let instancing = &data.instancing;
+  let frame = &instancing.frames[image_index];
+
+  if !instancing.gpu_culling || frame.instance_count == 0 {
+    return;
+  }
+
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, instancing.cull_pipeline);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::COMPUTE,
+    instancing.pipeline_layout,
+    0,
+    &[data.frame_descriptor_sets[image_index], frame.descriptor_set],
+    &[],
+  );
+  device.cmd_dispatch(command_buffer, group_count(frame.instance_count, WORKGROUP_SIZE), 1, 1);
+
+  let barrier = |buffer: vk::Buffer, access: vk::AccessFlags| {
+    vk::BufferMemoryBarrier::builder()
+      .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+      .dst_access_mask(access)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .buffer(buffer)
+      .offset(0)
+      .size(vk::WHOLE_SIZE as u64)
+  };
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::COMPUTE_SHADER,
+    vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::VERTEX_SHADER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[
+      barrier(frame.commands.buffer, vk::AccessFlags::INDIRECT_COMMAND_READ),
+      barrier(frame.visible.buffer, vk::AccessFlags::SHADER_READ),
+    ],
+    &[] as &[vk::ImageMemoryBarrier],
+  );
+}
+
+/// Records the pipeline and material binds of every batch and its indirect draws; must be inside
+/// the render pass.
+pub unsafe fn record_instance_draws(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let instancing = &data.instancing;
+  let frame = &instancing.frames[image_index];
+
+  if frame.batches.is_empty() {
+    return;
+  }
+
+  bind_geometry(device, data, command_buffer);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    instancing.pipeline_layout,
+    0,
+    &[data.frame_descriptor_sets[image_index], frame.descriptor_set],
+    &[],
+  );
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    instancing.pipeline_layout,
+    3,
+    &[lighting_set(data, image_index)],
+    &[],
+  );
+
+  let mut bound = None;
+  let first_pipeline = MeshPass::of(data.view_mode) as usize * MeshPipeline::ALL.len();
+
+  for batch in &frame.batches {
+    if bound != Some(batch.pipeline) {
+      let pipeline = instancing.pipelines[first_pipeline + batch.pipeline as usize];
+      device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+      bound = Some(batch.pipeline);
+    }
+
+    device.cmd_bind_descriptor_sets(
+      command_buffer,
+      vk::PipelineBindPoint::GRAPHICS,
+      instancing.pipeline_layout,
+      2,
+      &[material_set(data, batch.material.0)],
+      &[],
+    );
+
+    let first = batch.first_command as usize;
+    let count = batch.command_count as usize;
+    draw_commands(
+      device,
+      data,
+      command_buffer,
+      frame.commands.buffer,
+      &frame.commands_cpu[first..first + count],
+      first,
+    );
+  }
+}
+
+/// Records every instance without culling, for passes that bind their own pipeline with sets
+/// 0 and 1 laid out like the instancing ones (frame and instances).
+pub unsafe fn record_instance_shadow_draws(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let frame = &data.instancing.frames[image_index];
+
+  if frame.shadow_commands_cpu.is_empty() {
+    return;
+  }
+
+  bind_geometry(device, data, command_buffer);
+  draw_commands(
+    device,
+    data,
+    command_buffer,
+    frame.shadow_commands.buffer,
+    &frame.shadow_commands_cpu,
+    0,
+  );
+}
+
+pub fn instance_set(data: &VulkanAppData, image_index: usize) -> vk::DescriptorSet {
+  data.instancing.frames[image_index].descriptor_set
+}
+
+/// Draws `commands`, which start at command `first` of the indirect `buffer`. Uses multi draw
+/// indirect when available and falls back to one draw per command.
+unsafe fn draw_commands(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  buffer: vk::Buffer,
+  commands: &[vk::DrawIndexedIndirectCommand],
+  first: usize,
+) {
+  let first_instance = data.features.draw_indirect_first_instance == vk::TRUE;
+  let multi_draw = data.features.multi_draw_indirect == vk::TRUE;
+
+  if !first_instance {
+    for command in commands {
+      device.cmd_draw_indexed(
+        command_buffer,
+        command.index_count,
+        command.instance_count,
+        command.first_index,
+        command.vertex_offset,
+        command.first_instance,
+      );
+    }
+  } else if multi_draw {
+    let offset = (first * COMMAND_STRIDE as usize) as vk::DeviceSize;
+    device.cmd_draw_indexed_indirect(command_buffer, buffer, offset, commands.len() as u32, COMMAND_STRIDE);
+  } else {
+    for i in first..first + commands.len() {
+      let offset = (i * COMMAND_STRIDE as usize) as vk::DeviceSize;
+      device.cmd_draw_indexed_indirect(command_buffer, buffer, offset, 1, COMMAND_STRIDE);
+    }
+  }
+}
+
+pub unsafe fn destroy_instancing_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  let instancing = &mut data.instancing;
+
+  instancing
+    .pipelines
+    .iter()
+    .for_each(|p| device.destroy_pipeline(*p, None));
+
+  for frame in &instancing.frames {
+    for buffer in [
+      frame.instances,
+      frame.visible,
+      frame.materials,
+      frame.commands,
+      frame.culling,
+      frame.shadow_commands,
+    ] {
+      destroy_host_buffer(device, &buffer);
+    }
+  }
+}
+
+pub unsafe fn destroy_instancing_system(device: &Device, data: &mut VulkanAppData) {
+  let instancing = &mut data.instancing;
+
+  device.destroy_pipeline(instancing.cull_pipeline, None);
+  device.destroy_pipeline_layout(instancing.pipeline_layout, None);
+  device.destroy_descriptor_set_layout(instancing.set_layout, None);
+}
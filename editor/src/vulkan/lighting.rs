@@ -0,0 +1,321 @@
+use std::mem::size_of;
+
+use anyhow::{Ok, Result};
+use cgmath::Deg;
+use log::warn;
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{
+  create_buffer, create_host_buffer, destroy_host_buffer, write_memory, write_memory_at, HostBuffer,
+};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_buffer_descriptor,
+  write_image_descriptor,
+};
+use super::environment::environment_views;
+use super::pipe::compute::{create_compute_pipeline, group_count};
+use super::shadows::shadow_views_buffer;
+use super::VulkanAppData;
+use crate::scene::light::{Light, LightKind};
+
+pub const MAX_LIGHTS: usize = 1024;
+/// Cluster grid, must match `common/lights.glsl`.
+const CLUSTERS: [u32; 3] = [16, 9, 24];
+const CLUSTER_COUNT: u32 = CLUSTERS[0] * CLUSTERS[1] * CLUSTERS[2];
+const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+const WORKGROUP_SIZE: u32 = 64;
+
+const LIGHT_DIRECTIONAL: u32 = 0;
+const LIGHT_POINT: u32 = 1;
+const LIGHT_SPOT: u32 = 2;
+
+/// std430 layout of `Light` in `common/lights.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct GpuLight {
+  /// xyz: world position, w: range.
+  position: [f32; 4],
+  /// xyz: direction the light travels.
+  direction: [f32; 4],
+  /// rgb: color premultiplied by intensity.
+  color: [f32; 4],
+  /// x/y: cosines of the spot inner and outer angles.
+  cone: [f32; 4],
+  /// x: `LIGHT_DIRECTIONAL`, `LIGHT_POINT` or `LIGHT_SPOT`, y: first shadow view, z: shadow views.
+  kind: [u32; 4],
+}
+
+/// Header of the `Lights` buffer, x: lights, y: directional lights (stored first).
+type GpuLightCounts = [u32; 4];
+
+#[derive(Clone, Debug, Default)]
+struct LightFrame {
+  lights: HostBuffer,
+  cluster_counts: vk::Buffer,
+  cluster_counts_memory: vk::DeviceMemory,
+  cluster_lights: vk::Buffer,
+  cluster_lights_memory: vk::DeviceMemory,
+  descriptor_set: vk::DescriptorSet,
+}
+
+/// Forward+ light list: a compute pass assigns point and spot lights to view space clusters and
+/// the mesh fragment shader only loops over the lights of its cluster.
+#[derive(Clone, Debug, Default)]
+pub struct LightingData {
+  pub set_layout: vk::DescriptorSetLayout,
+  pipeline_layout: vk::PipelineLayout,
+  cull_pipeline: vk::Pipeline,
+  frames: Vec<LightFrame>,
+}
+
+pub unsafe fn create_lighting_system(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let stages = vk::ShaderStageFlags::FRAGMENT | vk::ShaderStageFlags::COMPUTE;
+  let bindings = &[
+    layout_binding(0, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(1, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(2, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(3, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(4, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, stages),
+    // Image based lighting: irradiance, prefiltered, BRDF LUT y el cubemap del entorno.
+    layout_binding(
+      5,
+      vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      vk::ShaderStageFlags::FRAGMENT,
+    ),
+    layout_binding(
+      6,
+      vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      vk::ShaderStageFlags::FRAGMENT,
+    ),
+    layout_binding(
+      7,
+      vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      vk::ShaderStageFlags::FRAGMENT,
+    ),
+    layout_binding(
+      8,
+      vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      vk::ShaderStageFlags::FRAGMENT,
+    ),
+  ];
+
+  let set_layout = create_descriptor_set_layout(device, bindings)?;
+
+  let set_layouts = &[data.frame_set_layout, set_layout];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+
+  let pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+
+  let comp = include_bytes!("./pipe/shader/.tmp/clusters/comp.spv");
+  let cull_pipeline = create_compute_pipeline(device, pipeline_layout, &comp[..])?;
+
+  data.lighting = LightingData {
+    set_layout,
+    pipeline_layout,
+    cull_pipeline,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+pub unsafe fn create_lighting_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let lights_size = size_of::<GpuLightCounts>() + MAX_LIGHTS * size_of::<GpuLight>();
+  let counts_size = (CLUSTER_COUNT as usize * size_of::<u32>()) as vk::DeviceSize;
+  let indices_size = (CLUSTER_COUNT as usize * MAX_LIGHTS_PER_CLUSTER as usize * size_of::<u32>()) as vk::DeviceSize;
+
+  let sets = allocate_descriptor_sets(
+    device,
+    data.descriptor_pool,
+    data.lighting.set_layout,
+    data.swapchain_images.len(),
+  )?;
+
+  let mut frames = vec![];
+
+  for (i, set) in sets.into_iter().enumerate() {
+    let lights = create_host_buffer(
+      instance,
+      device,
+      data,
+      lights_size,
+      vk::BufferUsageFlags::STORAGE_BUFFER,
+    )?;
+
+    let (cluster_counts, cluster_counts_memory) = create_buffer(
+      instance,
+      device,
+      data,
+      counts_size,
+      vk::BufferUsageFlags::STORAGE_BUFFER,
+      vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let (cluster_lights, cluster_lights_memory) = create_buffer(
+      instance,
+      device,
+      data,
+      indices_size,
+      vk::BufferUsageFlags::STORAGE_BUFFER,
+      vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let storage = vk::DescriptorType::STORAGE_BUFFER;
+    write_buffer_descriptor(device, set, 0, storage, lights.buffer, lights_size as vk::DeviceSize);
+    write_buffer_descriptor(device, set, 1, storage, cluster_counts, counts_size);
+    write_buffer_descriptor(device, set, 2, storage, cluster_lights, indices_size);
+
+    let (shadow_views, shadow_views_size) = shadow_views_buffer(data, i);
+    write_buffer_descriptor(device, set, 3, storage, shadow_views, shadow_views_size);
+    write_image_descriptor(device, set, 4, data.shadows.atlas_view, data.shadows.sampler);
+
+    let (environment_views, environment_sampler) = environment_views(data);
+    for (binding, view) in (5..).zip(environment_views) {
+      write_image_descriptor(device, set, binding, view, environment_sampler);
+    }
+
+    frames.push(LightFrame {
+      lights,
+      cluster_counts,
+      cluster_counts_memory,
+      cluster_lights,
+      cluster_lights_memory,
+      descriptor_set: set,
+    });
+  }
+
+  data.lighting.frames = frames;
+
+  Ok(())
+}
+
+fn gpu_light(light: &Light, (first_shadow, shadow_count): (u32, u32)) -> GpuLight {
+  let (kind, cone) = match light.kind {
+    LightKind::Directional => (LIGHT_DIRECTIONAL, (Deg(0.0), Deg(0.0))),
+    LightKind::Point => (LIGHT_POINT, (Deg(0.0), Deg(0.0))),
+    LightKind::Spot {
+      inner_angle,
+      outer_angle,
+    } => (LIGHT_SPOT, (inner_angle, outer_angle)),
+  };
+
+  let cos = |angle: Deg<f32>| angle.0.to_radians().cos();
+  let (p, d) = (light.position, light.direction);
+  let [r, g, b] = light.color.map(|c| c * light.intensity);
+
+  GpuLight {
+    position: [p.x, p.y, p.z, light.range],
+    direction: [d.x, d.y, d.z, 0.0],
+    color: [r, g, b, 0.0],
+    cone: [cos(cone.0), cos(cone.1), 0.0, 0.0],
+    kind: [kind, first_shadow, shadow_count, 0],
+  }
+}
+
+/// Uploads the enabled lights for `image_index`, directional lights first. `shadows` holds the
+/// `(first view, view count)` returned by `update_shadows` for each light.
+pub unsafe fn update_lights(
+  device: &Device,
+  data: &VulkanAppData,
+  image_index: usize,
+  lights: &[&Light],
+  shadows: &[(u32, u32)],
+) -> Result<()> {
+  let mut gpu_lights = lights
+    .iter()
+    .zip(shadows.iter().copied())
+    .filter(|(l, _)| l.enabled)
+    .map(|(l, s)| gpu_light(l, s))
+    .collect::<Vec<_>>();
+
+  if gpu_lights.len() > MAX_LIGHTS {
+    warn!("Only the first {} lights are used.", MAX_LIGHTS);
+    gpu_lights.truncate(MAX_LIGHTS);
+  }
+
+  gpu_lights.sort_by_key(|l| l.kind[0] != LIGHT_DIRECTIONAL);
+
+  let directional = gpu_lights.iter().filter(|l| l.kind[0] == LIGHT_DIRECTIONAL).count();
+  let counts: GpuLightCounts = [gpu_lights.len() as u32, directional as u32, 0, 0];
+
+  let memory = data.lighting.frames[image_index].lights.memory;
+  write_memory(device, memory, &[counts])?;
+  write_memory_at(
+    device,
+    memory,
+    size_of::<GpuLightCounts>() as vk::DeviceSize,
+    &gpu_lights,
+  )
+}
+
+/// Records the cluster assignment dispatch; must be outside of a render pass.
+pub unsafe fn record_light_culling(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let lighting = &data.lighting;
+  let frame = &lighting.frames[image_index];
+
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, lighting.cull_pipeline);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::COMPUTE,
+    lighting.pipeline_layout,
+    0,
+    &[data.frame_descriptor_sets[image_index], frame.descriptor_set],
+    &[],
+  );
+  device.cmd_dispatch(command_buffer, group_count(CLUSTER_COUNT, WORKGROUP_SIZE), 1, 1);
+
+  let barrier = |buffer: vk::Buffer| {
+    vk::BufferMemoryBarrier::builder()
+      .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+      .dst_access_mask(vk::AccessFlags::SHADER_READ)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .buffer(buffer)
+      .offset(0)
+      .size(vk::WHOLE_SIZE as u64)
+  };
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::COMPUTE_SHADER,
+    vk::PipelineStageFlags::FRAGMENT_SHADER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[barrier(frame.cluster_counts), barrier(frame.cluster_lights)],
+    &[] as &[vk::ImageMemoryBarrier],
+  );
+}
+
+pub fn lighting_set(data: &VulkanAppData, image_index: usize) -> vk::DescriptorSet {
+  data.lighting.frames[image_index].descriptor_set
+}
+
+pub unsafe fn destroy_lighting_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  for frame in &data.lighting.frames {
+    destroy_host_buffer(device, &frame.lights);
+    device.destroy_buffer(frame.cluster_counts, None);
+    device.free_memory(frame.cluster_counts_memory, None);
+    device.destroy_buffer(frame.cluster_lights, None);
+    device.free_memory(frame.cluster_lights_memory, None);
+  }
+}
+
+pub unsafe fn destroy_lighting_system(device: &Device, data: &mut VulkanAppData) {
+  let lighting = &mut data.lighting;
+
+  device.destroy_pipeline(lighting.cull_pipeline, None);
+  device.destroy_pipeline_layout(lighting.pipeline_layout, None);
+  device.destroy_descriptor_set_layout(lighting.set_layout, None);
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Ok, Result};
+use log::{info, warn};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::batching::MAX_MATERIALS;
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_image_descriptor,
+};
+use super::textures::{create_sampler, create_solid_texture, create_texture, destroy_texture, Texture};
+use super::uploads::{destroy_uploaded_texture, upload_texture};
+use super::VulkanAppData;
+use crate::asset::handle::{Handle, LoadState};
+use crate::asset::import::{Asset, TextureAsset};
+use crate::asset::AssetDatabase;
+use crate::scene::prop::Material;
+
+/// Texture maps per material at `set = 2`, see `mesh/shader.frag`.
+const MAPS_PER_MATERIAL: usize = 5;
+
+/// Material texture maps: one descriptor set per scene material, rebuilt when the textures it
+/// shows change: a file is assigned, finishes loading or is reimported. Loaded textures stay
+/// cached by path.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialData {
+  pub set_layout: vk::DescriptorSetLayout,
+  pool: vk::DescriptorPool,
+  sampler: vk::Sampler,
+  white_srgb: Texture,
+  white_linear: Texture,
+  flat_normal: Texture,
+  /// Checkerboard shown as albedo while its texture loads.
+  loading: Texture,
+  textures: HashMap<(PathBuf, bool), LoadedTexture>,
+  sets: Vec<vk::DescriptorSet>,
+  /// Views each set was written with.
+  bound: Option<Vec<[vk::ImageView; MAPS_PER_MATERIAL]>>,
+}
+
+/// A texture uploaded from an asset, and the import it was made from.
+#[derive(Clone, Debug)]
+struct LoadedTexture {
+  /// `None` if the file can't be loaded.
+  asset: Option<Handle<TextureAsset>>,
+  /// 0 until the first import is uploaded.
+  version: u64,
+  texture: Option<Texture>,
+}
+
+pub unsafe fn create_material_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let bindings = (0..MAPS_PER_MATERIAL as u32)
+    .map(|b| {
+      layout_binding(
+        b,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        vk::ShaderStageFlags::FRAGMENT,
+      )
+    })
+    .collect::<Vec<_>>();
+
+  let set_layout = create_descriptor_set_layout(device, &bindings)?;
+
+  let sampler_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    .descriptor_count((MAX_MATERIALS * MAPS_PER_MATERIAL) as u32);
+
+  let pool_sizes = &[sampler_size];
+  let info = vk::DescriptorPoolCreateInfo::builder()
+    .pool_sizes(pool_sizes)
+    .max_sets(MAX_MATERIALS as u32);
+
+  let pool = device.create_descriptor_pool(&info, None)?;
+  let placeholder = TextureAsset::placeholder();
+
+  data.materials = MaterialData {
+    set_layout,
+    pool,
+    sampler: create_sampler(instance, device, data)?,
+    white_srgb: create_solid_texture(instance, device, data, vk::Format::R8G8B8A8_SRGB, [255; 4])?,
+    white_linear: create_solid_texture(instance, device, data, vk::Format::R8G8B8A8_UNORM, [255; 4])?,
+    flat_normal: create_solid_texture(instance, device, data, vk::Format::R8G8B8A8_UNORM, [128, 128, 255, 255])?,
+    loading: create_texture(
+      instance,
+      device,
+      data,
+      placeholder.width,
+      placeholder.height,
+      vk::Format::R8G8B8A8_SRGB,
+      &placeholder.levels[0],
+    )?,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+/// The view to bind for the texture at `path`: the uploaded texture once it's imported,
+/// `loading` until then and `fallback` if it can't be. Reimports are uploaded again.
+unsafe fn texture_view(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  assets: &mut AssetDatabase,
+  path: &Path,
+  srgb: bool,
+  (fallback, loading): (vk::ImageView, vk::ImageView),
+) -> Result<vk::ImageView> {
+  let key = (path.to_path_buf(), srgb);
+
+  if !data.materials.textures.contains_key(&key) {
+    let asset = assets
+      .load::<TextureAsset>(path)
+      .inspect_err(|e| warn!("Failed to load texture {}: {}", path.display(), e))
+      .ok();
+
+    let loaded = LoadedTexture {
+      asset,
+      version: 0,
+      texture: None,
+    };
+    data.materials.textures.insert(key.clone(), loaded);
+  }
+
+  let loaded = &data.materials.textures[&key];
+  let Some(asset) = loaded.asset.clone() else {
+    return Ok(fallback);
+  };
+  let previous = loaded.texture;
+
+  if asset.state() != LoadState::Ready || asset.version() == loaded.version {
+    return Ok(match (previous, asset.state()) {
+      (Some(texture), _) => texture.view,
+      (None, LoadState::Loading) => loading,
+      (None, _) => fallback,
+    });
+  }
+
+  // Los frames en vuelo pueden estar usando la textura anterior.
+  if let Some(previous) = previous {
+    device.device_wait_idle()?;
+    destroy_uploaded_texture(device, data, &previous);
+  }
+
+  let format = if srgb {
+    vk::Format::R8G8B8A8_SRGB
+  } else {
+    vk::Format::R8G8B8A8_UNORM
+  };
+
+  let version = asset.version();
+  let image = asset.get();
+  let texture = upload_texture(instance, device, data, image.width, image.height, format, &image.levels)
+    .inspect_err(|e| warn!("Failed to upload texture {}: {}", path.display(), e))
+    .ok();
+
+  if texture.is_some() {
+    info!("[+] materials -> uploading {} ({})", path.display(), asset.guid());
+  }
+
+  let loaded = LoadedTexture {
+    asset: Some(asset),
+    version,
+    texture,
+  };
+  data.materials.textures.insert(key, loaded);
+
+  Ok(texture.map_or(fallback, |t| t.view))
+}
+
+/// Rebuilds the material descriptor sets when the textures `materials` show changed, loading
+/// them through `assets`.
+pub unsafe fn sync_materials(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  assets: &mut AssetDatabase,
+  materials: &[Material],
+) -> Result<()> {
+  let mut views = vec![];
+
+  for material in materials.iter().take(MAX_MATERIALS) {
+    let textures = &material.textures;
+    let m = &data.materials;
+    let maps = [
+      (&textures.albedo, true, m.white_srgb.view, m.loading.view),
+      (&textures.normal, false, m.flat_normal.view, m.flat_normal.view),
+      (
+        &textures.metallic_roughness,
+        false,
+        m.white_linear.view,
+        m.white_linear.view,
+      ),
+      (&textures.occlusion, false, m.white_linear.view, m.white_linear.view),
+      (&textures.emissive, true, m.white_srgb.view, m.white_srgb.view),
+    ];
+
+    let mut set = [vk::ImageView::default(); MAPS_PER_MATERIAL];
+
+    for (view, (path, srgb, fallback, loading)) in set.iter_mut().zip(maps) {
+      *view = match path {
+        Some(path) => texture_view(instance, device, data, assets, path, srgb, (fallback, loading))?,
+        None => fallback,
+      };
+    }
+
+    views.push(set);
+  }
+
+  if data.materials.bound.as_ref() == Some(&views) {
+    return Ok(());
+  }
+
+  // Los sets anteriores pueden estar en uso por frames en vuelo.
+  device.device_wait_idle()?;
+  device.reset_descriptor_pool(data.materials.pool, vk::DescriptorPoolResetFlags::empty())?;
+
+  let sets = if views.is_empty() {
+    vec![]
+  } else {
+    allocate_descriptor_sets(device, data.materials.pool, data.materials.set_layout, views.len())?
+  };
+
+  for (set, views) in sets.iter().zip(views.iter()) {
+    for (binding, view) in views.iter().enumerate() {
+      write_image_descriptor(device, *set, binding as u32, *view, data.materials.sampler);
+    }
+  }
+
+  data.materials.sets = sets;
+  data.materials.bound = Some(views);
+
+  Ok(())
+}
+
+pub fn material_set(data: &VulkanAppData, material: usize) -> vk::DescriptorSet {
+  data.materials.sets[material]
+}
+
+pub unsafe fn destroy_material_system(device: &Device, data: &mut VulkanAppData) {
+  let materials = &mut data.materials;
+
+  materials
+    .textures
+    .values()
+    .filter_map(|t| t.texture)
+    .for_each(|t| destroy_texture(device, &t));
+  destroy_texture(device, &materials.white_srgb);
+  destroy_texture(device, &materials.white_linear);
+  destroy_texture(device, &materials.flat_normal);
+  destroy_texture(device, &materials.loading);
+  device.destroy_sampler(materials.sampler, None);
+  device.destroy_descriptor_pool(materials.pool, None);
+  device.destroy_descriptor_set_layout(materials.set_layout, None);
+}
@@ -1,5 +1,9 @@
-use anyhow::{anyhow, Ok, Result};
-use log::info;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
 use spawnchain::{create_swapchain, create_swapchain_image_views};
 use vulkanalia::loader::{LibloadingLoader, LIBRARY};
 use vulkanalia::prelude::v1_0::*;
@@ -12,28 +16,103 @@ use vulkanalia::{
 };
 use winit::window::Window;
 
+use crate::asset::AssetDatabase;
+use crate::scene::{debug_draw::DebugDraw, emitter::ParticleEmitter, light::Light, Scene};
+use crate::ui::UiOutput;
+
 // check vulkan version
 use vulkanalia::Version;
 
 // vk-sagitario
+pub mod batching;
+pub mod buffers;
+pub mod capture;
 pub mod commands;
+pub mod debug_draw;
+pub mod depth;
+pub mod descriptors;
 pub mod device;
+pub mod environment;
 pub mod framebuffers;
+pub mod geometry;
+pub mod images;
+pub mod instancing;
+pub mod lighting;
+pub mod materials;
+pub mod output;
+pub mod particles;
 pub mod physical_device;
+pub mod picking;
 pub mod pipe;
+pub mod post;
+pub mod present;
 pub mod queue_family;
 pub mod semaphore;
+pub mod shadows;
 pub mod spawnchain;
+pub mod textures;
+pub mod ui;
+pub mod uniforms;
+pub mod uploads;
 pub mod utils;
 pub mod validation_vk;
+pub mod view_mode;
 
-use commands::{create_command_buffers, create_command_pool};
+use capture::{capture_image, CaptureQueue};
+use commands::{create_command_buffers, create_command_pool, record_command_buffer};
+use debug_draw::{
+  create_debug_draw_swapchain_resources, create_debug_draw_system, destroy_debug_draw_swapchain_resources,
+  destroy_debug_draw_system, update_debug_draw, DebugDrawData,
+};
+use depth::create_depth_objects;
+use descriptors::create_descriptor_pool;
 use device::create_logical as create_logical_device;
-use framebuffers::create_framebuffers;
+use environment::{
+  create_environment_swapchain_resources, create_environment_system, destroy_environment_swapchain_resources,
+  destroy_environment_system, sync_environment, EnvironmentData,
+};
+use framebuffers::create_framebuffer;
+use geometry::{destroy_geometry, sync_geometry, GeometryData};
+use instancing::{
+  create_instancing_swapchain_resources, create_instancing_system, destroy_instancing_swapchain_resources,
+  destroy_instancing_system, update_instances, InstancingData,
+};
+use lighting::{
+  create_lighting_swapchain_resources, create_lighting_system, destroy_lighting_swapchain_resources,
+  destroy_lighting_system, update_lights, LightingData,
+};
+use materials::{create_material_system, destroy_material_system, sync_materials, MaterialData};
+use output::DisplayOutput;
+use particles::{
+  create_particle_swapchain_resources, create_particle_system, destroy_particle_swapchain_resources,
+  destroy_particle_system, update_particles, ParticleData,
+};
 use physical_device::pick_physical_device;
-use pipe::{create_pipeline, render_pass::create_render_pass};
+use picking::{
+  begin_pick, create_picking_swapchain_resources, create_picking_system, destroy_picking_swapchain_resources,
+  destroy_picking_system, poll_pick, submit_pick, PickRequest, PickResult, PickingData,
+};
+use pipe::render_pass::create_render_pass;
+use post::{
+  create_post_swapchain_resources, create_post_system, destroy_post_swapchain_resources, destroy_post_system,
+  sync_post_lut, PostData, PostSettings,
+};
+use present::PresentConfig;
 use semaphore::create_sync_objects;
+use shadows::{
+  create_shadow_swapchain_resources, create_shadow_system, destroy_shadow_swapchain_resources, destroy_shadow_system,
+  update_shadows, ShadowData,
+};
+use ui::{
+  create_ui_swapchain_resources, create_ui_system, destroy_ui_swapchain_resources, destroy_ui_system, sync_ui_textures,
+  update_ui, UiData,
+};
+use uniforms::{
+  create_frame_descriptor_set_layout, create_frame_descriptor_sets, create_uniform_buffers, update_frame_uniforms,
+};
+use uploads::{create_upload_system, destroy_upload_system, flush_uploads, UploadData};
 use validation_vk::{debug_callback, validations_layers, VALIDATION_ENABLED};
+use view_mode::ViewMode;
 
 const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
@@ -45,65 +124,89 @@ pub struct VulkanApp {
   data: VulkanAppData,
   device: Device,
   frame: usize,
+  pub resized: bool,
+  capture: CaptureQueue,
+  start: Instant,
+  /// Lines queued for the next frame, cleared once they are uploaded.
+  debug: DebugDraw,
+  /// Last finished pick, until `take_pick` hands it out.
+  pick: Option<PickResult>,
+  last_frame: Instant,
+  /// Editor UI drawn over every frame until `set_ui` replaces it.
+  ui: UiOutput,
+  /// Viewport size asked for with `set_viewport`, applied before the next frame.
+  viewport: Option<vk::Extent2D>,
 }
 
+#[derive(Default)]
 pub struct VulkanAppData {
   messenger: vk::DebugUtilsMessengerEXT,
   surface: vk::SurfaceKHR,
   physical_device: vk::PhysicalDevice,
   graphics_queue: vk::Queue,
   present_queue: vk::Queue,
+  transfer_queue: vk::Queue,
   swapchain_format: vk::Format,
   swapchain_extent: vk::Extent2D,
+  /// Size of the viewport texture the scene is drawn into, `None` to draw it on the swapchain.
+  viewport: Option<vk::Extent2D>,
   swapchain: vk::SwapchainKHR,
   swapchain_images: Vec<vk::Image>,
   swapchain_images_views: Vec<vk::ImageView>,
   render_pass: vk::RenderPass,
-  pipeline_layout: vk::PipelineLayout,
-  pipeline: vk::Pipeline,
-  framebuffers: Vec<vk::Framebuffer>,
+  framebuffer: vk::Framebuffer,
   command_pool: vk::CommandPool,
   command_buffers: Vec<vk::CommandBuffer>,
   image_available_semaphore: Vec<vk::Semaphore>,
   render_finished_semaphore: Vec<vk::Semaphore>,
   in_flight_fences: Vec<vk::Fence>,
   images_in_flight: Vec<vk::Fence>,
+  present_config: PresentConfig,
+  swapchain_colorspace_ext: bool,
+  display_output: DisplayOutput,
+  swapchain_capturable: bool,
+  frame_set_layout: vk::DescriptorSetLayout,
+  descriptor_pool: vk::DescriptorPool,
+  frame_descriptor_sets: Vec<vk::DescriptorSet>,
+  uniform_buffers: Vec<vk::Buffer>,
+  uniform_buffers_memory: Vec<vk::DeviceMemory>,
+  particles: ParticleData,
+  features: vk::PhysicalDeviceFeatures,
+  depth_format: vk::Format,
+  depth_image: vk::Image,
+  depth_image_memory: vk::DeviceMemory,
+  depth_image_view: vk::ImageView,
+  geometry: GeometryData,
+  instancing: InstancingData,
+  materials: MaterialData,
+  lighting: LightingData,
+  shadows: ShadowData,
+  view_mode: ViewMode,
+  post: PostData,
+  environment: EnvironmentData,
+  debug_draw: DebugDrawData,
+  picking: PickingData,
+  ui: UiData,
+  uploads: UploadData,
 }
 
-impl Default for VulkanAppData {
-  fn default() -> Self {
-    Self {
-      messenger: vk::DebugUtilsMessengerEXT::default(),
-      physical_device: vk::PhysicalDevice::default(),
-      graphics_queue: vk::Queue::default(),
-      surface: vk::SurfaceKHR::default(),
-      present_queue: vk::Queue::default(),
-      swapchain_format: vk::Format::default(),
-      swapchain_extent: vk::Extent2D::default(),
-      swapchain: vk::SwapchainKHR::default(),
-      swapchain_images: Vec::default(),
-      swapchain_images_views: Vec::default(),
-      pipeline_layout: vk::PipelineLayout::default(),
-      render_pass: vk::RenderPass::default(),
-      pipeline: vk::Pipeline::default(),
-      command_pool: vk::CommandPool::default(),
-      framebuffers: Vec::default(),
-      command_buffers: Vec::default(),
-      image_available_semaphore: Vec::default(),
-      render_finished_semaphore: Vec::default(),
-      in_flight_fences: Vec::default(),
-      images_in_flight: Vec::default(),
-    }
+impl VulkanAppData {
+  /// Size of the scene targets: the viewport texture, or the swapchain without one.
+  fn render_extent(&self) -> vk::Extent2D {
+    self.viewport.unwrap_or(self.swapchain_extent)
   }
 }
 
 impl VulkanApp {
-  pub unsafe fn create(window: &Window) -> Result<Self> {
+  pub unsafe fn create(window: &Window, present_config: PresentConfig) -> Result<Self> {
     info!("[+] VulkanApp::create -> starting");
 
     let loader = LibloadingLoader::new(LIBRARY)?;
     let entry = Entry::new(loader).map_err(|b| anyhow!("{}", b))?;
-    let mut data = VulkanAppData::default();
+    let mut data = VulkanAppData {
+      present_config,
+      ..Default::default()
+    };
     let instance = create_vk_instance(window, &entry, &mut data)?;
     data.surface = vk_window::create_surface(&instance, &window, &window)?;
 
@@ -113,10 +216,34 @@ impl VulkanApp {
 
     create_swapchain(window, &instance, &device, &mut data)?;
     create_swapchain_image_views(&device, &mut data)?;
-    create_render_pass(&instance, &device, &mut data)?;
-    create_pipeline(&device, &mut data)?;
-    create_framebuffers(&device, &mut data)?;
     create_command_pool(&instance, &device, &mut data)?;
+    create_upload_system(&instance, &device, &mut data)?;
+    create_depth_objects(&instance, &device, &mut data)?;
+    create_picking_system(&device, &mut data)?;
+    create_picking_swapchain_resources(&instance, &device, &mut data)?;
+    create_post_system(&instance, &device, &mut data)?;
+    create_post_swapchain_resources(&instance, &device, &mut data)?;
+    create_render_pass(&instance, &device, &mut data)?;
+    create_framebuffer(&device, &mut data)?;
+    create_frame_descriptor_set_layout(&device, &mut data)?;
+    create_particle_system(&instance, &device, &mut data)?;
+    create_material_system(&instance, &device, &mut data)?;
+    create_lighting_system(&device, &mut data)?;
+    create_instancing_system(&device, &mut data)?;
+    create_shadow_system(&instance, &device, &mut data)?;
+    create_environment_system(&instance, &device, &mut data)?;
+    create_debug_draw_system(&device, &mut data)?;
+    create_ui_system(&instance, &device, &mut data)?;
+    create_uniform_buffers(&instance, &device, &mut data)?;
+    create_descriptor_pool(&device, &mut data)?;
+    create_frame_descriptor_sets(&device, &mut data)?;
+    create_particle_swapchain_resources(&instance, &device, &mut data)?;
+    create_instancing_swapchain_resources(&instance, &device, &mut data)?;
+    create_shadow_swapchain_resources(&instance, &device, &mut data)?;
+    create_lighting_swapchain_resources(&instance, &device, &mut data)?;
+    create_environment_swapchain_resources(&device, &mut data)?;
+    create_debug_draw_swapchain_resources(&instance, &device, &mut data)?;
+    create_ui_swapchain_resources(&instance, &device, &mut data)?;
     create_command_buffers(&device, &mut data)?;
     create_sync_objects(&device, &mut data)?;
 
@@ -126,23 +253,144 @@ impl VulkanApp {
       data,
       device,
       frame: 0,
+      resized: false,
+      capture: CaptureQueue::default(),
+      start: Instant::now(),
+      debug: DebugDraw::default(),
+      pick: None,
+      last_frame: Instant::now(),
+      ui: UiOutput::default(),
+      viewport: None,
     })
   }
 
-  pub unsafe fn render(&mut self, _window: &Window) -> Result<()> {
+  /// Applies a new presentation config; the swapchain is rebuilt on the next rendered frame.
+  pub fn set_present_config(&mut self, config: PresentConfig) {
+    if self.data.present_config != config {
+      info!("[+] VulkanApp::set_present_config -> {:?}", config);
+      self.data.present_config = config;
+      self.resized = true;
+    }
+  }
+
+  pub fn capture_screenshot(&mut self, path: PathBuf) {
+    self.capture.screenshot(path);
+  }
+
+  /// Saves the next `count` presented frames as `frame_0000.png`, `frame_0001.png`... in `directory`.
+  pub fn record_frames(&mut self, directory: PathBuf, count: u32) {
+    info!("[+] capture -> recording {} frames into {}", count, directory.display());
+    self.capture.record(directory, count);
+  }
+
+  /// Switches between compute shader and CPU frustum culling of the scene props.
+  pub fn set_gpu_culling(&mut self, enabled: bool) {
+    instancing::set_gpu_culling(&mut self.data, enabled);
+  }
+
+  pub fn gpu_culling(&self) -> bool {
+    instancing::gpu_culling(&self.data)
+  }
+
+  pub fn set_post_settings(&mut self, settings: PostSettings) {
+    if self.data.post.settings != settings {
+      info!("[+] VulkanApp::set_post_settings -> {:?}", settings);
+      self.data.post.settings = settings;
+    }
+  }
+
+  pub fn set_view_mode(&mut self, mode: ViewMode) {
+    info!("[+] VulkanApp::set_view_mode -> {}", mode);
+
+    if mode == ViewMode::Wireframe && self.data.features.fill_mode_non_solid != vk::TRUE {
+      warn!("fillModeNonSolid not supported, the wireframe view mode draws filled triangles");
+    }
+
+    self.data.view_mode = mode;
+  }
+
+  pub fn view_mode(&self) -> ViewMode {
+    self.data.view_mode
+  }
+
+  /// Helper lines for the next frame; anything added here is drawn once and then cleared.
+  pub fn debug_draw(&mut self) -> &mut DebugDraw {
+    &mut self.debug
+  }
+
+  /// Reads back the object id under the physical pixel `(x, y)`; the result is ready a frame or
+  /// two later through `take_pick`.
+  pub fn request_pick(&mut self, x: u32, y: u32, additive: bool) {
+    picking::request_pick(&mut self.data, PickRequest { x, y, additive });
+  }
+
+  pub fn take_pick(&mut self) -> Option<PickResult> {
+    self.pick.take()
+  }
+
+  /// Replaces the UI drawn over the frames. Texture changes the renderer hasn't applied yet are
+  /// kept, egui only sends them once.
+  pub fn set_ui(&mut self, mut output: UiOutput) {
+    let mut textures = std::mem::take(&mut self.ui.textures);
+    textures.append(output.textures);
+    output.textures = textures;
+    self.ui = output;
+  }
+
+  /// Draws the scene into a `size` texture the UI shows as `ui::VIEWPORT_TEXTURE`, or straight
+  /// on the swapchain with `None`. The targets are rebuilt before the next frame if it changed.
+  pub fn set_viewport(&mut self, size: Option<[u32; 2]>) {
+    self.viewport = size.map(|[width, height]| vk::Extent2D {
+      width: width.max(1),
+      height: height.max(1),
+    });
+  }
+
+  /// Largest side of a texture the UI can upload.
+  pub fn max_texture_side(&self) -> usize {
+    self.data.ui.max_texture_side
+  }
+
+  pub fn is_capturing(&self) -> bool {
+    self.capture.is_active()
+  }
+
+  /// Draws `scene`; the textures it uses are loaded through `assets`, and new meshes and
+  /// textures are uploaded on the transfer queue before it's drawn.
+  pub unsafe fn render(&mut self, window: &Window, scene: &Scene, assets: &mut AssetDatabase) -> Result<()> {
+    sync_geometry(&self.instance, &self.device, &mut self.data, &scene.meshes)?;
+    sync_materials(&self.instance, &self.device, &mut self.data, assets, &scene.materials)?;
+    sync_post_lut(&self.instance, &self.device, &mut self.data)?;
+    sync_environment(&self.instance, &self.device, &mut self.data, &scene.environment)?;
+    sync_ui_textures(&self.instance, &self.device, &mut self.data, &mut self.ui.textures)?;
+
+    if self.viewport != self.data.viewport {
+      self.recreate_swapchain(window)?;
+    }
+
     let in_flight_fence = self.data.in_flight_fences[self.frame];
 
     self.device.wait_for_fences(&[in_flight_fence], true, u64::MAX)?;
 
-    let image_index = self
-      .device
-      .acquire_next_image_khr(
-        self.data.swapchain,
-        u64::MAX,
-        self.data.image_available_semaphore[self.frame],
-        vk::Fence::null(),
-      )?
-      .0 as usize;
+    if let Some(pick) = poll_pick(&self.device, &mut self.data)? {
+      self.pick = Some(pick);
+    }
+
+    let result = self.device.acquire_next_image_khr(
+      self.data.swapchain,
+      u64::MAX,
+      self.data.image_available_semaphore[self.frame],
+      vk::Fence::null(),
+    );
+
+    let image_index = match result {
+      Ok((image_index, _)) => image_index as usize,
+      Err(vk::ErrorCode::OUT_OF_DATE_KHR) => {
+        self.debug.clear();
+        return self.recreate_swapchain(window);
+      }
+      Err(e) => return Err(anyhow!(e)),
+    };
 
     let image_in_flight = self.data.images_in_flight[image_index];
 
@@ -152,13 +400,41 @@ impl VulkanApp {
 
     self.data.images_in_flight[image_index] = in_flight_fence;
 
-    let wait_semaphores = &[self.data.image_available_semaphore[self.frame]];
-    let wait_stages = &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+    let now = Instant::now();
+    let time = (now - self.start).as_secs_f32();
+    let delta_time = (now - self.last_frame).as_secs_f32();
+    self.last_frame = now;
+
+    let mut emitter_query = scene.world.query::<&ParticleEmitter>();
+    let emitters = emitter_query.iter().collect::<Vec<_>>();
+    let mut light_query = scene.world.query::<&Light>();
+    let lights = light_query.iter().collect::<Vec<_>>();
+
+    update_frame_uniforms(&self.device, &self.data, image_index, scene, time, delta_time)?;
+    update_particles(&self.device, &mut self.data, image_index, &emitters, delta_time)?;
+    update_instances(&self.device, &mut self.data, image_index, scene)?;
+    let shadows = update_shadows(&self.device, &mut self.data, image_index, &lights, &scene.camera)?;
+    update_lights(&self.device, &self.data, image_index, &lights, &shadows)?;
+    self.debug.resolve_text(&scene.camera);
+    update_debug_draw(&self.device, &mut self.data, image_index, &self.debug)?;
+    self.debug.clear();
+    update_ui(&self.instance, &self.device, &mut self.data, image_index, &self.ui)?;
+    begin_pick(&mut self.data);
+    record_command_buffer(&self.device, &self.data, image_index)?;
+
+    let mut wait_semaphores = vec![self.data.image_available_semaphore[self.frame]];
+    let mut wait_stages = vec![vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+
+    if let Some(uploaded) = flush_uploads(&self.instance, &self.device, &mut self.data, self.frame)? {
+      wait_semaphores.push(uploaded);
+      wait_stages.push(vk::PipelineStageFlags::ALL_COMMANDS);
+    }
+
     let command_buffers = &[self.data.command_buffers[image_index]];
     let signal_semaphores = &[self.data.render_finished_semaphore[self.frame]];
     let submit_info = vk::SubmitInfo::builder()
-      .wait_semaphores(wait_semaphores)
-      .wait_dst_stage_mask(wait_stages)
+      .wait_semaphores(&wait_semaphores)
+      .wait_dst_stage_mask(&wait_stages)
       .command_buffers(command_buffers)
       .signal_semaphores(signal_semaphores);
 
@@ -167,6 +443,15 @@ impl VulkanApp {
     self
       .device
       .queue_submit(self.data.graphics_queue, &[submit_info], in_flight_fence)?;
+    submit_pick(&mut self.data, image_index, in_flight_fence);
+
+    // Una captura fallida se cancela, pero la imagen se presenta igual.
+    if let Some(path) = self.capture.next_target() {
+      if let Err(error) = self.capture_frame(image_index, in_flight_fence, path) {
+        warn!("Can't capture the frame: {}", error);
+        self.capture.cancel();
+      }
+    }
 
     let swapchains = &[self.data.swapchain];
     let image_indices = &[image_index as u32];
@@ -175,15 +460,115 @@ impl VulkanApp {
       .swapchains(swapchains)
       .image_indices(image_indices);
 
-    self.device.queue_present_khr(self.data.present_queue, &present_info)?;
+    let result = self.device.queue_present_khr(self.data.present_queue, &present_info);
+    let changed = result == Ok(vk::SuccessCode::SUBOPTIMAL_KHR) || result == Err(vk::ErrorCode::OUT_OF_DATE_KHR);
+
+    if self.resized || changed {
+      self.resized = false;
+      self.recreate_swapchain(window)?;
+    } else if let Err(e) = result {
+      return Err(anyhow!(e));
+    }
 
     self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT;
 
     Ok(())
   }
 
+  unsafe fn capture_frame(&mut self, image_index: usize, fence: vk::Fence, path: PathBuf) -> Result<()> {
+    if !self.data.swapchain_capturable {
+      return Err(anyhow!("The surface doesn't allow reading back swapchain images."));
+    }
+
+    self.device.wait_for_fences(&[fence], true, u64::MAX)?;
+
+    let image = capture_image(
+      &self.instance,
+      &self.device,
+      &self.data,
+      self.data.swapchain_images[image_index],
+      self.data.swapchain_format,
+      self.data.swapchain_extent,
+      vk::ImageLayout::PRESENT_SRC_KHR,
+    )?;
+
+    self.capture.write(image, path)
+  }
+
+  unsafe fn recreate_swapchain(&mut self, window: &Window) -> Result<()> {
+    info!("[+] VulkanApp::recreate_swapchain");
+
+    self.device.device_wait_idle()?;
+    self.destroy_swapchain();
+    self.data.viewport = self.viewport;
+
+    create_swapchain(window, &self.instance, &self.device, &mut self.data)?;
+    create_swapchain_image_views(&self.device, &mut self.data)?;
+    create_depth_objects(&self.instance, &self.device, &mut self.data)?;
+    create_picking_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_post_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_render_pass(&self.instance, &self.device, &mut self.data)?;
+    create_framebuffer(&self.device, &mut self.data)?;
+    create_uniform_buffers(&self.instance, &self.device, &mut self.data)?;
+    create_descriptor_pool(&self.device, &mut self.data)?;
+    create_frame_descriptor_sets(&self.device, &mut self.data)?;
+    create_particle_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_instancing_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_shadow_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_lighting_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_environment_swapchain_resources(&self.device, &mut self.data)?;
+    create_debug_draw_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_ui_swapchain_resources(&self.instance, &self.device, &mut self.data)?;
+    create_command_buffers(&self.device, &mut self.data)?;
+
+    self
+      .data
+      .images_in_flight
+      .resize(self.data.swapchain_images.len(), vk::Fence::null());
+
+    Ok(())
+  }
+
+  unsafe fn destroy_swapchain(&mut self) {
+    destroy_ui_swapchain_resources(&self.device, &mut self.data);
+    destroy_debug_draw_swapchain_resources(&self.device, &mut self.data);
+    destroy_environment_swapchain_resources(&self.device, &mut self.data);
+    destroy_lighting_swapchain_resources(&self.device, &mut self.data);
+    destroy_shadow_swapchain_resources(&self.device, &mut self.data);
+    destroy_instancing_swapchain_resources(&self.device, &mut self.data);
+    destroy_particle_swapchain_resources(&self.device, &mut self.data);
+    self.device.destroy_descriptor_pool(self.data.descriptor_pool, None);
+    self
+      .data
+      .uniform_buffers
+      .iter()
+      .for_each(|b| self.device.destroy_buffer(*b, None));
+    self
+      .data
+      .uniform_buffers_memory
+      .iter()
+      .for_each(|m| self.device.free_memory(*m, None));
+    self.device.destroy_framebuffer(self.data.framebuffer, None);
+    destroy_post_swapchain_resources(&self.device, &mut self.data);
+    destroy_picking_swapchain_resources(&self.device, &mut self.data);
+    self.device.destroy_image_view(self.data.depth_image_view, None);
+    self.device.free_memory(self.data.depth_image_memory, None);
+    self.device.destroy_image(self.data.depth_image, None);
+    self
+      .device
+      .free_command_buffers(self.data.command_pool, &self.data.command_buffers);
+    self.device.destroy_render_pass(self.data.render_pass, None);
+    self
+      .data
+      .swapchain_images_views
+      .iter()
+      .for_each(|v| self.device.destroy_image_view(*v, None));
+    self.device.destroy_swapchain_khr(self.data.swapchain, None);
+  }
+
   pub unsafe fn destroy(&mut self) {
     self.device.device_wait_idle().unwrap();
+    self.capture.wait();
 
     self
       .data
@@ -201,22 +586,23 @@ impl VulkanApp {
       .iter()
       .for_each(|s| self.device.destroy_semaphore(*s, None));
 
-    self.device.destroy_command_pool(self.data.command_pool, None);
-    self
-      .data
-      .framebuffers
-      .iter()
-      .for_each(|f| self.device.destroy_framebuffer(*f, None));
-    self.device.destroy_pipeline(self.data.pipeline, None);
-    self.device.destroy_pipeline_layout(self.data.pipeline_layout, None);
-    self.device.destroy_render_pass(self.data.render_pass, None);
+    self.destroy_swapchain();
+    destroy_particle_system(&self.device, &mut self.data);
+    destroy_post_system(&self.device, &mut self.data);
+    destroy_picking_system(&self.device, &mut self.data);
+    destroy_debug_draw_system(&self.device, &mut self.data);
+    destroy_ui_system(&self.device, &mut self.data);
+    destroy_environment_system(&self.device, &mut self.data);
+    destroy_shadow_system(&self.device, &mut self.data);
+    destroy_instancing_system(&self.device, &mut self.data);
+    destroy_lighting_system(&self.device, &mut self.data);
+    destroy_material_system(&self.device, &mut self.data);
+    destroy_geometry(&self.device, &mut self.data);
+    destroy_upload_system(&self.device, &mut self.data);
     self
-      .data
-      .swapchain_images_views
-      .iter()
-      .for_each(|v| self.device.destroy_image_view(*v, None));
-
-    self.device.destroy_swapchain_khr(self.data.swapchain, None);
+      .device
+      .destroy_descriptor_set_layout(self.data.frame_set_layout, None);
+    self.device.destroy_command_pool(self.data.command_pool, None);
     self.device.destroy_device(None);
     self.instance.destroy_surface_khr(self.data.surface, None);
 
@@ -261,6 +647,20 @@ unsafe fn create_vk_instance(window: &Window, entry: &Entry, data: &mut VulkanAp
     extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
   }
 
+  // HDR10 / scRGB color spaces
+  let available_extensions = entry
+    .enumerate_instance_extension_properties(None)?
+    .iter()
+    .map(|e| e.extension_name)
+    .collect::<HashSet<_>>();
+
+  if available_extensions.contains(&vk::EXT_SWAPCHAIN_COLORSPACE_EXTENSION.name) {
+    extensions.push(vk::EXT_SWAPCHAIN_COLORSPACE_EXTENSION.name.as_ptr());
+    data.swapchain_colorspace_ext = true;
+  } else {
+    info!("[INFO]: VK_EXT_swapchain_colorspace not available, HDR output disabled");
+  }
+
   let mut info = vk::InstanceCreateInfo::builder()
     .application_info(&application_info)
     .enabled_layer_names(&layers)
@@ -0,0 +1,268 @@
+use log::warn;
+use vulkanalia::vk;
+
+/// Which kind of swapchain the user asked for. `Auto` takes HDR when the surface offers it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HdrMode {
+  Off,
+  Auto,
+  Hdr10,
+  ScRgb,
+}
+
+impl HdrMode {
+  pub fn next(self) -> Self {
+    match self {
+      Self::Off => Self::Auto,
+      Self::Auto => Self::Hdr10,
+      Self::Hdr10 => Self::ScRgb,
+      Self::ScRgb => Self::Off,
+    }
+  }
+}
+
+/// How the final shader stage has to encode its output for the selected swapchain format.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DisplayOutput {
+  /// `*_SRGB` format: the hardware applies the sRGB curve on write.
+  #[default]
+  Srgb,
+  /// `*_UNORM` format in the sRGB color space: the shader applies the curve itself.
+  SrgbUnorm,
+  /// 10-bit Rec.2020 primaries with the ST.2084 (PQ) curve.
+  Hdr10,
+  /// Linear Rec.709 primaries in a float target, 1.0 = 80 nits.
+  ScRgb,
+  /// Display-linear color left as is, for the viewport texture the UI encodes when it draws it.
+  Linear,
+}
+
+impl DisplayOutput {
+  pub fn is_hdr(self) -> bool {
+    matches!(self, Self::Hdr10 | Self::ScRgb)
+  }
+
+  /// Value of `transfer` in `OutputParams`, must match `common/output.glsl`.
+  pub fn transfer(self) -> u32 {
+    match self {
+      Self::Srgb => 0,
+      Self::SrgbUnorm => 1,
+      Self::Hdr10 => 2,
+      Self::ScRgb => 3,
+      Self::Linear => 4,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HdrConfig {
+  pub mode: HdrMode,
+  /// Brightness of SDR white (1.0 in scene values) on an HDR display.
+  pub paper_white_nits: f32,
+  /// Peak brightness the tonemapper rolls highlights off towards.
+  pub max_nits: f32,
+}
+
+impl Default for HdrConfig {
+  fn default() -> Self {
+    Self {
+      mode: HdrMode::Off,
+      paper_white_nits: 203.0,
+      max_nits: 1000.0,
+    }
+  }
+}
+
+/// `OutputParams` of `common/output.glsl`, pushed to the post-processing passes that tonemap and
+/// encode the scene color.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OutputParams {
+  pub transfer: u32,
+  pub paper_white_nits: f32,
+  pub max_nits: f32,
+  /// Linear scale applied to the scene color before tonemapping.
+  pub exposure: f32,
+}
+
+impl OutputParams {
+  pub fn new(output: DisplayOutput, config: &HdrConfig, exposure: f32) -> Self {
+    Self {
+      transfer: output.transfer(),
+      paper_white_nits: config.paper_white_nits,
+      max_nits: config.max_nits,
+      exposure,
+    }
+  }
+}
+
+const HDR10_FORMATS: &[vk::Format] = &[
+  vk::Format::A2B10G10R10_UNORM_PACK32,
+  vk::Format::A2R10G10B10_UNORM_PACK32,
+];
+const SCRGB_FORMATS: &[vk::Format] = &[vk::Format::R16G16B16A16_SFLOAT];
+const SRGB_FORMATS: &[vk::Format] = &[vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB];
+const UNORM_FORMATS: &[vk::Format] = &[vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM];
+
+/// Picks the swapchain format for `mode`. The fallback order is:
+///
+/// 1. the HDR output requested (`Auto` tries HDR10 and then scRGB), only when
+///    `VK_EXT_swapchain_colorspace` is enabled,
+/// 2. an 8-bit `*_SRGB` format with `SRGB_NONLINEAR`,
+/// 3. an 8-bit `*_UNORM` format with `SRGB_NONLINEAR` (encoded in the shader),
+/// 4. whatever the surface lists first, treated as UNORM sRGB.
+pub fn select_surface_format(
+  formats: &[vk::SurfaceFormatKHR],
+  mode: HdrMode,
+  colorspace_ext: bool,
+) -> (vk::SurfaceFormatKHR, DisplayOutput) {
+  let find = |candidates: &[vk::Format], color_space: vk::ColorSpaceKHR| {
+    candidates.iter().find_map(|format| {
+      formats
+        .iter()
+        .cloned()
+        .find(|f| f.format == *format && f.color_space == color_space)
+    })
+  };
+
+  let hdr_order: &[DisplayOutput] = match mode {
+    _ if !colorspace_ext => &[],
+    HdrMode::Off => &[],
+    HdrMode::Auto => &[DisplayOutput::Hdr10, DisplayOutput::ScRgb],
+    HdrMode::Hdr10 => &[DisplayOutput::Hdr10],
+    HdrMode::ScRgb => &[DisplayOutput::ScRgb],
+  };
+
+  for output in hdr_order {
+    let found = match output {
+      DisplayOutput::Hdr10 => find(HDR10_FORMATS, vk::ColorSpaceKHR::HDR10_ST2084_EXT),
+      _ => find(SCRGB_FORMATS, vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT),
+    };
+
+    if let Some(format) = found {
+      return (format, *output);
+    }
+  }
+
+  if mode != HdrMode::Off {
+    warn!(
+      "HDR output ({:?}) not available on this surface, falling back to SDR.",
+      mode
+    );
+  }
+
+  if let Some(format) = find(SRGB_FORMATS, vk::ColorSpaceKHR::SRGB_NONLINEAR) {
+    return (format, DisplayOutput::Srgb);
+  }
+
+  if let Some(format) = find(UNORM_FORMATS, vk::ColorSpaceKHR::SRGB_NONLINEAR) {
+    return (format, DisplayOutput::SrgbUnorm);
+  }
+
+  warn!("No preferred surface format available, using {:?}.", formats[0]);
+
+  (formats[0], DisplayOutput::SrgbUnorm)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HDR10: (vk::Format, vk::ColorSpaceKHR) = (
+    vk::Format::A2B10G10R10_UNORM_PACK32,
+    vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+  );
+  const SCRGB: (vk::Format, vk::ColorSpaceKHR) = (
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+  );
+  const SRGB: (vk::Format, vk::ColorSpaceKHR) = (vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+  const UNORM: (vk::Format, vk::ColorSpaceKHR) = (vk::Format::R8G8B8A8_UNORM, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+  const OTHER: (vk::Format, vk::ColorSpaceKHR) = (vk::Format::R5G6B5_UNORM_PACK16, vk::ColorSpaceKHR::SRGB_NONLINEAR);
+
+  fn formats(list: &[(vk::Format, vk::ColorSpaceKHR)]) -> Vec<vk::SurfaceFormatKHR> {
+    list
+      .iter()
+      .map(|&(format, color_space)| vk::SurfaceFormatKHR { format, color_space })
+      .collect()
+  }
+
+  fn select(
+    list: &[(vk::Format, vk::ColorSpaceKHR)],
+    mode: HdrMode,
+    colorspace_ext: bool,
+  ) -> (vk::Format, DisplayOutput) {
+    let (format, output) = select_surface_format(&formats(list), mode, colorspace_ext);
+    (format.format, output)
+  }
+
+  #[test]
+  fn follows_the_fallback_order() {
+    let everything = [OTHER, UNORM, SRGB, SCRGB, HDR10];
+
+    #[rustfmt::skip]
+    let cases = [
+      // (formats, mode, expected format, expected output)
+      (&everything[..], HdrMode::Auto, HDR10.0, DisplayOutput::Hdr10),
+      (&everything[..4], HdrMode::Auto, SCRGB.0, DisplayOutput::ScRgb),
+      (&everything[..3], HdrMode::Auto, SRGB.0, DisplayOutput::Srgb),
+      (&everything[..2], HdrMode::Auto, UNORM.0, DisplayOutput::SrgbUnorm),
+      (&everything[..1], HdrMode::Auto, OTHER.0, DisplayOutput::SrgbUnorm),
+    ];
+
+    for (list, mode, format, output) in cases {
+      assert_eq!(select(list, mode, true), (format, output), "{:?}", list);
+    }
+  }
+
+  #[test]
+  fn explicit_hdr_modes_only_take_their_output() {
+    let both = [SRGB, SCRGB, HDR10];
+
+    assert_eq!(select(&both, HdrMode::Hdr10, true), (HDR10.0, DisplayOutput::Hdr10));
+    assert_eq!(select(&both, HdrMode::ScRgb, true), (SCRGB.0, DisplayOutput::ScRgb));
+    assert_eq!(
+      select(&[SRGB, SCRGB], HdrMode::Hdr10, true),
+      (SRGB.0, DisplayOutput::Srgb)
+    );
+    assert_eq!(
+      select(&[SRGB, HDR10], HdrMode::ScRgb, true),
+      (SRGB.0, DisplayOutput::Srgb)
+    );
+  }
+
+  #[test]
+  fn hdr_disabled_picks_sdr() {
+    let everything = [SCRGB, HDR10, UNORM, SRGB];
+
+    assert_eq!(select(&everything, HdrMode::Off, true), (SRGB.0, DisplayOutput::Srgb));
+    assert_eq!(
+      select(&[HDR10, UNORM], HdrMode::Off, true),
+      (UNORM.0, DisplayOutput::SrgbUnorm)
+    );
+  }
+
+  #[test]
+  fn hdr_needs_the_colorspace_extension() {
+    let everything = [SCRGB, HDR10, SRGB];
+
+    for mode in [HdrMode::Auto, HdrMode::Hdr10, HdrMode::ScRgb] {
+      assert_eq!(select(&everything, mode, false), (SRGB.0, DisplayOutput::Srgb));
+    }
+  }
+
+  #[test]
+  fn formats_need_the_matching_color_space() {
+    // Un formato HDR10 en espacio sRGB no es salida HDR10.
+    let wrong_space = [
+      (vk::Format::A2B10G10R10_UNORM_PACK32, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+      (vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::HDR10_ST2084_EXT),
+      UNORM,
+    ];
+
+    assert_eq!(
+      select(&wrong_space, HdrMode::Auto, true),
+      (UNORM.0, DisplayOutput::SrgbUnorm)
+    );
+  }
+}
@@ -0,0 +1,360 @@
+use std::mem::size_of;
+
+use anyhow::{Ok, Result};
+use log::warn;
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{begin_single_time_commands, create_buffer, end_single_time_commands, write_memory};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_buffer_descriptor,
+};
+use super::pipe::compute::{create_compute_pipeline, group_count};
+use super::pipe::{create_graphics_pipeline, BlendMode, IdAttachment, PipelineDesc};
+use super::VulkanAppData;
+use crate::scene::emitter::ParticleEmitter;
+
+pub const MAX_EMITTERS: usize = 8;
+pub const PARTICLES_PER_EMITTER: u32 = 4096;
+pub const MAX_PARTICLES: u32 = MAX_EMITTERS as u32 * PARTICLES_PER_EMITTER;
+/// Samples of the color-over-life gradient sent to the GPU, must match the shaders.
+const COLOR_KEYS: usize = 8;
+const WORKGROUP_SIZE: u32 = 256;
+
+/// std430 layout of `Particle` in `particles/shader.comp`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct GpuParticle {
+  /// xyz: position, w: age.
+  position: [f32; 4],
+  /// xyz: velocity, w: lifetime.
+  velocity: [f32; 4],
+}
+
+/// std140 layout of `Emitter` in `particles/shader.comp`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct GpuEmitter {
+  /// xyz: position, w: spawn radius.
+  position: [f32; 4],
+  /// xyz: velocity, w: velocity spread.
+  velocity: [f32; 4],
+  gravity: [f32; 4],
+  /// x/y: lifetime range, z/w: size at spawn and at death.
+  life_size: [f32; 4],
+  /// x: first ring slot to spawn into, y: particles to spawn this frame, z: random seed.
+  spawn: [u32; 4],
+  colors: [[f32; 4]; COLOR_KEYS],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+struct GpuEmitterBlock {
+  emitters: [GpuEmitter; MAX_EMITTERS],
+  /// x: number of active emitters.
+  counts: [u32; 4],
+}
+
+/// GPU particle simulation: a compute pass integrates a ring buffer of particles per emitter and
+/// the same buffer is read by the vertex shader to expand instanced billboards.
+#[derive(Clone, Debug, Default)]
+pub struct ParticleData {
+  particle_buffer: vk::Buffer,
+  particle_buffer_memory: vk::DeviceMemory,
+  emitter_buffers: Vec<vk::Buffer>,
+  emitter_buffers_memory: Vec<vk::DeviceMemory>,
+  set_layout: vk::DescriptorSetLayout,
+  descriptor_sets: Vec<vk::DescriptorSet>,
+  pipeline_layout: vk::PipelineLayout,
+  compute_pipeline: vk::Pipeline,
+  render_pipeline: vk::Pipeline,
+  spawn_accumulators: [f32; MAX_EMITTERS],
+  ring_cursors: [u32; MAX_EMITTERS],
+  seed: u32,
+}
+
+/// Resources that survive swapchain recreation: particle storage, layouts and the compute pipeline.
+pub unsafe fn create_particle_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let size = (MAX_PARTICLES as usize * size_of::<GpuParticle>()) as vk::DeviceSize;
+
+  let (buffer, buffer_memory) = create_buffer(
+    instance,
+    device,
+    data,
+    size,
+    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  // Edad y vida en cero: todas las particulas empiezan muertas.
+  let command_buffer = begin_single_time_commands(device, data)?;
+  device.cmd_fill_buffer(command_buffer, buffer, 0, size, 0);
+  end_single_time_commands(device, data, command_buffer)?;
+
+  let stages = vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::VERTEX;
+  let bindings = &[
+    layout_binding(0, vk::DescriptorType::STORAGE_BUFFER, stages),
+    layout_binding(1, vk::DescriptorType::UNIFORM_BUFFER, stages),
+  ];
+
+  let set_layout = create_descriptor_set_layout(device, bindings)?;
+
+  let set_layouts = &[data.frame_set_layout, set_layout];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(set_layouts);
+
+  let pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+
+  let comp = include_bytes!("./pipe/shader/.tmp/particles/comp.spv");
+  let compute_pipeline = create_compute_pipeline(device, pipeline_layout, &comp[..])?;
+
+  data.particles = ParticleData {
+    particle_buffer: buffer,
+    particle_buffer_memory: buffer_memory,
+    set_layout,
+    pipeline_layout,
+    compute_pipeline,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+/// Per swapchain image emitter uniforms, descriptor sets and the billboard pipeline.
+pub unsafe fn create_particle_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let size = size_of::<GpuEmitterBlock>() as vk::DeviceSize;
+
+  let mut buffers = vec![];
+  let mut buffers_memory = vec![];
+
+  for _ in 0..data.swapchain_images.len() {
+    let (buffer, buffer_memory) = create_buffer(
+      instance,
+      device,
+      data,
+      size,
+      vk::BufferUsageFlags::UNIFORM_BUFFER,
+      vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+    )?;
+
+    write_memory(device, buffer_memory, &[GpuEmitterBlock::default()])?;
+
+    buffers.push(buffer);
+    buffers_memory.push(buffer_memory);
+  }
+
+  let sets = allocate_descriptor_sets(
+    device,
+    data.descriptor_pool,
+    data.particles.set_layout,
+    data.swapchain_images.len(),
+  )?;
+
+  let particles_size = (MAX_PARTICLES as usize * size_of::<GpuParticle>()) as vk::DeviceSize;
+
+  for (set, buffer) in sets.iter().zip(buffers.iter()) {
+    write_buffer_descriptor(
+      device,
+      *set,
+      0,
+      vk::DescriptorType::STORAGE_BUFFER,
+      data.particles.particle_buffer,
+      particles_size,
+    );
+    write_buffer_descriptor(device, *set, 1, vk::DescriptorType::UNIFORM_BUFFER, *buffer, size);
+  }
+
+  let vert = include_bytes!("./pipe/shader/.tmp/particles/vert.spv");
+  let frag = include_bytes!("./pipe/shader/.tmp/particles/frag.spv");
+
+  let mut desc = PipelineDesc::new(
+    &vert[..],
+    &frag[..],
+    data.particles.pipeline_layout,
+    data.render_pass,
+    data.render_extent(),
+  );
+  desc.cull_mode = vk::CullModeFlags::NONE;
+  desc.blend = BlendMode::Additive;
+  desc.depth_write = false;
+  desc.id_attachment = IdAttachment::Masked;
+
+  data.particles.render_pipeline = create_graphics_pipeline(device, &desc)?;
+  data.particles.emitter_buffers = buffers;
+  data.particles.emitter_buffers_memory = buffers_memory;
+  data.particles.descriptor_sets = sets;
+
+  Ok(())
+}
+
+/// Advances the spawn accumulators and uploads this frame's emitter block for `image_index`.
+pub unsafe fn update_particles(
+  device: &Device,
+  data: &mut VulkanAppData,
+  image_index: usize,
+  emitters: &[&ParticleEmitter],
+  delta_time: f32,
+) -> Result<()> {
+  if emitters.len() > MAX_EMITTERS {
+    warn!("Only the first {} particle emitters are simulated.", MAX_EMITTERS);
+  }
+
+  let particles = &mut data.particles;
+  let mut block = GpuEmitterBlock::default();
+
+  particles.seed = particles.seed.wrapping_add(1);
+
+  for (i, emitter) in emitters.iter().take(MAX_EMITTERS).enumerate() {
+    let mut spawn_count = 0;
+
+    if emitter.enabled {
+      particles.spawn_accumulators[i] += emitter.spawn_rate.max(0.0) * delta_time;
+      spawn_count = (particles.spawn_accumulators[i] as u32).min(PARTICLES_PER_EMITTER);
+      particles.spawn_accumulators[i] -= spawn_count as f32;
+    }
+
+    let first = particles.ring_cursors[i];
+    particles.ring_cursors[i] = (first + spawn_count) % PARTICLES_PER_EMITTER;
+
+    let p = emitter.position;
+    let v = emitter.velocity;
+    let g = emitter.gravity;
+
+    block.emitters[i] = GpuEmitter {
+      position: [p.x, p.y, p.z, emitter.spawn_radius],
+      velocity: [v.x, v.y, v.z, emitter.velocity_spread],
+      gravity: [g.x, g.y, g.z, 0.0],
+      life_size: [emitter.lifetime.0, emitter.lifetime.1, emitter.size.0, emitter.size.1],
+      spawn: [first, spawn_count, particles.seed, 0],
+      colors: std::array::from_fn(|k| emitter.color_over_life.sample(k as f32 / (COLOR_KEYS - 1) as f32)),
+    };
+  }
+
+  block.counts[0] = emitters.len().min(MAX_EMITTERS) as u32;
+
+  write_memory(device, particles.emitter_buffers_memory[image_index], &[block])
+}
+
+/// Records the simulation dispatch; must be outside of a render pass.
+pub unsafe fn record_particle_simulation(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let particles = &data.particles;
+
+  // El frame anterior todavia puede estar leyendo el buffer en el vertex shader.
+  let before = vk::BufferMemoryBarrier::builder()
+    .src_access_mask(vk::AccessFlags::SHADER_READ)
+    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .buffer(particles.particle_buffer)
+    .offset(0)
+    .size(vk::WHOLE_SIZE as u64);
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::VERTEX_SHADER,
+    vk::PipelineStageFlags::COMPUTE_SHADER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[before],
+    &[] as &[vk::ImageMemoryBarrier],
+  );
+
+  device.cmd_bind_pipeline(
+    command_buffer,
+    vk::PipelineBindPoint::COMPUTE,
+    particles.compute_pipeline,
+  );
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::COMPUTE,
+    particles.pipeline_layout,
+    0,
+    &[
+      data.frame_descriptor_sets[image_index],
+      particles.descriptor_sets[image_index],
+    ],
+    &[],
+  );
+  device.cmd_dispatch(command_buffer, group_count(MAX_PARTICLES, WORKGROUP_SIZE), 1, 1);
+
+  let after = vk::BufferMemoryBarrier::builder()
+    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .buffer(particles.particle_buffer)
+    .offset(0)
+    .size(vk::WHOLE_SIZE as u64);
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::COMPUTE_SHADER,
+    vk::PipelineStageFlags::VERTEX_SHADER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[after],
+    &[] as &[vk::ImageMemoryBarrier],
+  );
+}
+
+/// Records the billboards draw (6 vertices per particle instance); must be inside the render pass.
+pub unsafe fn record_particle_draw(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let particles = &data.particles;
+
+  device.cmd_bind_pipeline(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    particles.render_pipeline,
+  );
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    particles.pipeline_layout,
+    0,
+    &[
+      data.frame_descriptor_sets[image_index],
+      particles.descriptor_sets[image_index],
+    ],
+    &[],
+  );
+  device.cmd_draw(command_buffer, 6, MAX_PARTICLES, 0, 0);
+}
+
+pub unsafe fn destroy_particle_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  let particles = &mut data.particles;
+
+  device.destroy_pipeline(particles.render_pipeline, None);
+  particles
+    .emitter_buffers
+    .iter()
+    .for_each(|b| device.destroy_buffer(*b, None));
+  particles
+    .emitter_buffers_memory
+    .iter()
+    .for_each(|m| device.free_memory(*m, None));
+}
+
+pub unsafe fn destroy_particle_system(device: &Device, data: &mut VulkanAppData) {
+  let particles = &mut data.particles;
+
+  device.destroy_pipeline(particles.compute_pipeline, None);
+  device.destroy_pipeline_layout(particles.pipeline_layout, None);
+  device.destroy_descriptor_set_layout(particles.set_layout, None);
+  device.destroy_buffer(particles.particle_buffer, None);
+  device.free_memory(particles.particle_buffer_memory, None);
+}
@@ -0,0 +1,289 @@
+use std::mem::size_of;
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{create_host_buffer, destroy_host_buffer, HostBuffer};
+use super::images::{create_image, create_image_view};
+use super::VulkanAppData;
+
+/// Object id attachment of the scene pass. Props write their entity index + 1, so 0 is empty.
+pub const OBJECT_ID_FORMAT: vk::Format = vk::Format::R32_UINT;
+/// Set on the ids of selected props, used by the outline; must match `common/instances.glsl`.
+pub const SELECTED_BIT: u32 = 1 << 31;
+
+/// Click in the viewport, in physical pixels from the top left corner.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PickRequest {
+  pub x: u32,
+  pub y: u32,
+  /// Add to the selection (shift-click) instead of replacing it.
+  pub additive: bool,
+}
+
+/// Prop under the cursor of a `PickRequest`, `None` when the click hit the background.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PickResult {
+  /// Entity index, resolved with `World::entity_at`.
+  pub prop: Option<u32>,
+  pub additive: bool,
+}
+
+/// The object id target and the buffers its clicked pixel is copied into. The copy is recorded
+/// with a regular frame and read once that frame's fence signals, so picking never stalls.
+#[derive(Clone, Debug, Default)]
+pub struct PickingData {
+  id_image: vk::Image,
+  id_memory: vk::DeviceMemory,
+  pub id_view: vk::ImageView,
+  /// Nearest sampler, integer formats can't be filtered.
+  pub sampler: vk::Sampler,
+  readback: Vec<HostBuffer>,
+  /// Waiting for a frame to record its copy.
+  pending: Option<PickRequest>,
+  /// Copied by the frame being recorded now.
+  recording: Option<PickRequest>,
+  /// Copy submitted into `readback[image_index]`, done once `fence` signals.
+  in_flight: Option<(PickRequest, usize, vk::Fence)>,
+}
+
+pub unsafe fn create_picking_system(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let info = vk::SamplerCreateInfo::builder()
+    .mag_filter(vk::Filter::NEAREST)
+    .min_filter(vk::Filter::NEAREST)
+    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+    .max_lod(0.0);
+
+  data.picking.sampler = device.create_sampler(&info, None)?;
+
+  Ok(())
+}
+
+/// Object id image at the render size and one readback buffer per swapchain image; must be
+/// created before the post-processing resources, which sample the ids for the outline.
+pub unsafe fn create_picking_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let extent = data.render_extent();
+
+  let (id_image, id_memory) = create_image(
+    instance,
+    device,
+    data,
+    extent.width,
+    extent.height,
+    1,
+    OBJECT_ID_FORMAT,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  let id_view = create_image_view(device, id_image, OBJECT_ID_FORMAT, vk::ImageAspectFlags::COLOR, 1)?;
+
+  let readback = (0..data.swapchain_images.len())
+    .map(|_| {
+      create_host_buffer(
+        instance,
+        device,
+        data,
+        size_of::<u32>(),
+        vk::BufferUsageFlags::TRANSFER_DST,
+      )
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  let picking = &mut data.picking;
+  picking.id_image = id_image;
+  picking.id_memory = id_memory;
+  picking.id_view = id_view;
+  picking.readback = readback;
+  // Las copias en vuelo apuntan a buffers que ya no existen.
+  picking.recording = None;
+  picking.in_flight = None;
+
+  Ok(())
+}
+
+/// Queues a pick; only the latest request is kept while another one is in flight.
+pub fn request_pick(data: &mut VulkanAppData, request: PickRequest) {
+  let extent = data.render_extent();
+
+  if request.x < extent.width && request.y < extent.height {
+    data.picking.pending = Some(request);
+  }
+}
+
+/// Hands the pending request to the frame about to be recorded, if no copy is in flight.
+pub fn begin_pick(data: &mut VulkanAppData) {
+  let picking = &mut data.picking;
+
+  if picking.in_flight.is_none() {
+    picking.recording = picking.pending.take();
+  }
+}
+
+/// Marks the copy recorded for `image_index` as submitted with `fence`.
+pub fn submit_pick(data: &mut VulkanAppData, image_index: usize, fence: vk::Fence) {
+  let picking = &mut data.picking;
+
+  if let Some(request) = picking.recording.take() {
+    picking.in_flight = Some((request, image_index, fence));
+  }
+}
+
+/// Reads the id of a submitted pick once its frame has finished.
+pub unsafe fn poll_pick(device: &Device, data: &mut VulkanAppData) -> Result<Option<PickResult>> {
+  let Some((request, image_index, fence)) = data.picking.in_flight else {
+    return Ok(None);
+  };
+
+  if device.get_fence_status(fence)? != vk::SuccessCode::SUCCESS {
+    return Ok(None);
+  }
+
+  data.picking.in_flight = None;
+
+  let memory = data.picking.readback[image_index].memory;
+  let mapped = device.map_memory(
+    memory,
+    0,
+    size_of::<u32>() as vk::DeviceSize,
+    vk::MemoryMapFlags::empty(),
+  )?;
+  let mut id = 0u32;
+  memcpy(mapped.cast::<u32>(), &mut id, 1);
+  device.unmap_memory(memory);
+
+  let id = id & !SELECTED_BIT;
+
+  Ok(Some(PickResult {
+    prop: id.checked_sub(1),
+    additive: request.additive,
+  }))
+}
+
+/// Copies the clicked pixel of the id target into this frame's readback buffer; must run after
+/// the scene render pass and before the post passes sample the ids.
+pub unsafe fn record_pick_readback(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let picking = &data.picking;
+
+  let Some(request) = picking.recording else {
+    return;
+  };
+
+  let subresource = vk::ImageSubresourceRange::builder()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .base_mip_level(0)
+    .level_count(1)
+    .base_array_layer(0)
+    .layer_count(1);
+
+  let barrier = |old_layout, new_layout, src_access, dst_access| {
+    vk::ImageMemoryBarrier::builder()
+      .old_layout(old_layout)
+      .new_layout(new_layout)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .image(picking.id_image)
+      .subresource_range(subresource)
+      .src_access_mask(src_access)
+      .dst_access_mask(dst_access)
+  };
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::FRAGMENT_SHADER,
+    vk::PipelineStageFlags::TRANSFER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[] as &[vk::BufferMemoryBarrier],
+    &[barrier(
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      vk::AccessFlags::empty(),
+      vk::AccessFlags::TRANSFER_READ,
+    )],
+  );
+
+  let region = vk::BufferImageCopy::builder()
+    .buffer_offset(0)
+    .buffer_row_length(0)
+    .buffer_image_height(0)
+    .image_subresource(vk::ImageSubresourceLayers {
+      aspect_mask: vk::ImageAspectFlags::COLOR,
+      mip_level: 0,
+      base_array_layer: 0,
+      layer_count: 1,
+    })
+    .image_offset(vk::Offset3D {
+      x: request.x as i32,
+      y: request.y as i32,
+      z: 0,
+    })
+    .image_extent(vk::Extent3D {
+      width: 1,
+      height: 1,
+      depth: 1,
+    });
+
+  let buffer = picking.readback[image_index].buffer;
+  device.cmd_copy_image_to_buffer(
+    command_buffer,
+    picking.id_image,
+    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+    buffer,
+    &[region],
+  );
+
+  let host_barrier = vk::BufferMemoryBarrier::builder()
+    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+    .dst_access_mask(vk::AccessFlags::HOST_READ)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .buffer(buffer)
+    .offset(0)
+    .size(vk::WHOLE_SIZE as vk::DeviceSize);
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::TRANSFER,
+    vk::PipelineStageFlags::HOST | vk::PipelineStageFlags::FRAGMENT_SHADER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[host_barrier],
+    &[barrier(
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      vk::AccessFlags::empty(),
+      vk::AccessFlags::SHADER_READ,
+    )],
+  );
+}
+
+pub unsafe fn destroy_picking_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  let picking = &mut data.picking;
+
+  device.destroy_image_view(picking.id_view, None);
+  device.destroy_image(picking.id_image, None);
+  device.free_memory(picking.id_memory, None);
+  picking.readback.drain(..).for_each(|b| destroy_host_buffer(device, &b));
+}
+
+pub unsafe fn destroy_picking_system(device: &Device, data: &mut VulkanAppData) {
+  device.destroy_sampler(data.picking.sampler, None);
+}
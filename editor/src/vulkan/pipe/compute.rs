@@ -0,0 +1,36 @@
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device,
+};
+use vulkanalia_sys::Handle;
+
+use super::shader::create_shader_module;
+
+pub unsafe fn create_compute_pipeline(
+  device: &Device,
+  layout: vk::PipelineLayout,
+  bytecode: &[u8],
+) -> Result<vk::Pipeline> {
+  let module = create_shader_module(device, bytecode)?;
+
+  let stage = vk::PipelineShaderStageCreateInfo::builder()
+    .stage(vk::ShaderStageFlags::COMPUTE)
+    .module(module)
+    .name(b"main\0");
+
+  let info = vk::ComputePipelineCreateInfo::builder().stage(stage).layout(layout);
+
+  let pipeline = device
+    .create_compute_pipelines(vk::PipelineCache::null(), &[info], None)?
+    .0[0];
+
+  device.destroy_shader_module(module, None);
+
+  Ok(pipeline)
+}
+
+/// Number of workgroups needed to cover `count` invocations.
+pub fn group_count(count: u32, workgroup_size: u32) -> u32 {
+  count.div_ceil(workgroup_size)
+}
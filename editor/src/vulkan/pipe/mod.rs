@@ -5,26 +5,104 @@ use vulkanalia::{
 };
 use vulkanalia_sys::Handle;
 
-use super::VulkanAppData;
-
+pub mod compute;
 pub mod render_pass;
 pub mod shader;
 
 use shader::create_shader_module;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+  Opaque,
+  Additive,
+  Alpha,
+  /// Alpha blending of colors already multiplied by their alpha, e.g. the editor UI.
+  Premultiplied,
+}
+
+/// What a pipeline of the scene pass does with the object id attachment (`picking.rs`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IdAttachment {
+  /// The render pass has no id attachment.
+  None,
+  /// Present but left untouched, e.g. particles and helper lines can't be picked.
+  Masked,
+  /// Written by the fragment shader at location 1.
+  Written,
+}
+
+/// Fixed-function state of a graphics pipeline; `new` fills in the defaults of opaque geometry.
+#[derive(Copy, Clone, Debug)]
+pub struct PipelineDesc<'a> {
+  pub vert: &'a [u8],
+  pub frag: &'a [u8],
+  pub layout: vk::PipelineLayout,
+  pub render_pass: vk::RenderPass,
+  pub extent: vk::Extent2D,
+  pub vertex_bindings: &'a [vk::VertexInputBindingDescription],
+  pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+  pub topology: vk::PrimitiveTopology,
+  pub polygon_mode: vk::PolygonMode,
+  pub cull_mode: vk::CullModeFlags,
+  pub front_face: vk::FrontFace,
+  pub blend: BlendMode,
+  pub depth_test: bool,
+  pub depth_write: bool,
+  pub depth_compare: vk::CompareOp,
+  /// Depth bias set with `cmd_set_depth_bias` while recording.
+  pub depth_bias: bool,
+  /// Viewport and scissor set with `cmd_set_viewport` / `cmd_set_scissor` while recording.
+  pub dynamic_viewport: bool,
+  /// No fragment stage and no color attachment, for depth-only passes.
+  pub depth_only: bool,
+  pub id_attachment: IdAttachment,
+}
+
+impl<'a> PipelineDesc<'a> {
+  pub fn new(
+    vert: &'a [u8],
+    frag: &'a [u8],
+    layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+  ) -> Self {
+    Self {
+      vert,
+      frag,
+      layout,
+      render_pass,
+      extent,
+      vertex_bindings: &[],
+      vertex_attributes: &[],
+      topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+      polygon_mode: vk::PolygonMode::FILL,
+      cull_mode: vk::CullModeFlags::BACK,
+      front_face: vk::FrontFace::CLOCKWISE,
+      blend: BlendMode::Opaque,
+      depth_test: true,
+      depth_write: true,
+      depth_compare: vk::CompareOp::LESS,
+      depth_bias: false,
+      dynamic_viewport: false,
+      depth_only: false,
+      id_attachment: IdAttachment::None,
+    }
+  }
+}
+
 /**
  * Objetivo: Llegar a cargar los binarios, y compilarlos en tiempo de ejecucion del motor grafico
  * con ello poder ver cambios que produzca el usuario al interactuar con los objetos o entidades
  * en la "surface" de vulkan, como un editor grafico orientado a videojuegos
  * (Unity / Godot / Unreal Engine)
  */
-
-pub unsafe fn create_pipeline(device: &Device, data: &mut VulkanAppData) -> Result<()> {
-  let vert = include_bytes!("./shader/.tmp/triangle/vert.spv");
-  let frag = include_bytes!("./shader/.tmp/triangle/frag.spv");
-
-  let vert_shader_module = create_shader_module(device, &vert[..])?;
-  let frag_shader_module = create_shader_module(device, &frag[..])?;
+pub unsafe fn create_graphics_pipeline(device: &Device, desc: &PipelineDesc) -> Result<vk::Pipeline> {
+  let vert_shader_module = create_shader_module(device, desc.vert)?;
+  let frag_shader_module = if desc.depth_only {
+    vk::ShaderModule::null()
+  } else {
+    create_shader_module(device, desc.frag)?
+  };
 
   // [!stage]
   let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
@@ -37,23 +115,25 @@ pub unsafe fn create_pipeline(device: &Device, data: &mut VulkanAppData) -> Resu
     .module(frag_shader_module)
     .name(b"main\0");
 
-  let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+  let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+    .vertex_binding_descriptions(desc.vertex_bindings)
+    .vertex_attribute_descriptions(desc.vertex_attributes);
 
   let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
-    .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+    .topology(desc.topology)
     .primitive_restart_enable(false);
 
   let viewport = vk::Viewport::builder()
     .x(0.0)
     .y(0.0)
-    .width(data.swapchain_extent.width as f32)
-    .height(data.swapchain_extent.height as f32)
+    .width(desc.extent.width as f32)
+    .height(desc.extent.height as f32)
     .min_depth(0.0)
     .max_depth(1.0);
 
   let scissor = vk::Rect2D::builder()
     .offset(vk::Offset2D { x: 0, y: 0 })
-    .extent(data.swapchain_extent);
+    .extent(desc.extent);
 
   let viewports = &[viewport];
   let scissors = &[scissor];
@@ -64,38 +144,93 @@ pub unsafe fn create_pipeline(device: &Device, data: &mut VulkanAppData) -> Resu
   let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
     .depth_clamp_enable(false)
     .rasterizer_discard_enable(false)
-    .polygon_mode(vk::PolygonMode::FILL)
+    .polygon_mode(desc.polygon_mode)
     .line_width(1.0)
-    .cull_mode(vk::CullModeFlags::BACK)
-    .front_face(vk::FrontFace::CLOCKWISE)
-    .depth_bias_enable(false);
+    .cull_mode(desc.cull_mode)
+    .front_face(desc.front_face)
+    .depth_bias_enable(desc.depth_bias);
 
   let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
     .sample_shading_enable(false)
     .rasterization_samples(vk::SampleCountFlags::_1);
 
-  let attachment = vk::PipelineColorBlendAttachmentState::builder()
-    .color_write_mask(vk::ColorComponentFlags::all())
-    .blend_enable(false)
-    // .src_color_blend_factor(vk::BlendFactor::ONE)  // Optional
-    // .dst_color_blend_factor(vk::BlendFactor::ZERO) // Optional
-    // .color_blend_op(vk::BlendOp::ADD)              // Optional
-    // .src_alpha_blend_factor(vk::BlendFactor::ONE)  // Optional
-    // .dst_alpha_blend_factor(vk::BlendFactor::ZERO) // Optional
-    // .alpha_blend_op(vk::BlendOp::ADD);             // Optional
-  ;
-  let attachments = &[attachment];
+  let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+    .depth_test_enable(desc.depth_test)
+    .depth_write_enable(desc.depth_write)
+    .depth_compare_op(desc.depth_compare)
+    .depth_bounds_test_enable(false)
+    .stencil_test_enable(false);
+
+  let attachment = match desc.blend {
+    BlendMode::Opaque => vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(false),
+    BlendMode::Additive => vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(true)
+      .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+      .dst_color_blend_factor(vk::BlendFactor::ONE)
+      .color_blend_op(vk::BlendOp::ADD)
+      .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+      .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+      .alpha_blend_op(vk::BlendOp::ADD),
+    BlendMode::Alpha => vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(true)
+      .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+      .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+      .color_blend_op(vk::BlendOp::ADD)
+      .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+      .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+      .alpha_blend_op(vk::BlendOp::ADD),
+    BlendMode::Premultiplied => vk::PipelineColorBlendAttachmentState::builder()
+      .color_write_mask(vk::ColorComponentFlags::all())
+      .blend_enable(true)
+      .src_color_blend_factor(vk::BlendFactor::ONE)
+      .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+      .color_blend_op(vk::BlendOp::ADD)
+      .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+      .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+      .alpha_blend_op(vk::BlendOp::ADD),
+  };
+
+  // Los ids son enteros: sin blending, y con mascara vacia si el pipeline no los escribe.
+  let id_attachment = vk::PipelineColorBlendAttachmentState::builder()
+    .color_write_mask(match desc.id_attachment {
+      IdAttachment::Written => vk::ColorComponentFlags::R,
+      _ => vk::ColorComponentFlags::empty(),
+    })
+    .blend_enable(false);
+
+  let attachments: &[_] = match (desc.depth_only, desc.id_attachment) {
+    (true, _) => &[],
+    (false, IdAttachment::None) => &[attachment],
+    (false, _) => &[attachment, id_attachment],
+  };
   let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
     .logic_op_enable(false)
     .logic_op(vk::LogicOp::COPY)
     .attachments(attachments)
     .blend_constants([0.0, 0.0, 0.0, 0.0]);
 
-  let layout_info = vk::PipelineLayoutCreateInfo::builder();
+  let mut dynamic_states = vec![];
+
+  if desc.dynamic_viewport {
+    dynamic_states.extend([vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]);
+  }
+
+  if desc.depth_bias {
+    dynamic_states.push(vk::DynamicState::DEPTH_BIAS);
+  }
+
+  let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
 
-  data.pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+  let stages: &[_] = if desc.depth_only {
+    &[vert_stage]
+  } else {
+    &[vert_stage, frag_stage]
+  };
 
-  let stages = &[vert_stage, frag_stage];
   let info = vk::GraphicsPipelineCreateInfo::builder()
     .stages(stages)
     .vertex_input_state(&vertex_input_state)
@@ -103,20 +238,22 @@ pub unsafe fn create_pipeline(device: &Device, data: &mut VulkanAppData) -> Resu
     .viewport_state(&viewport_state)
     .rasterization_state(&rasterization_state)
     .multisample_state(&multisample_state)
+    .depth_stencil_state(&depth_stencil_state)
     .color_blend_state(&color_blend_state)
-    .layout(data.pipeline_layout)
-    .render_pass(data.render_pass)
+    .dynamic_state(&dynamic_state)
+    .layout(desc.layout)
+    .render_pass(desc.render_pass)
     .subpass(0)
     // .base_pipeline_handle(vk::Pipeline::null()) // Optional.
     // .base_pipeline_index(-1)                    // Optional.
     ;
 
-  data.pipeline = device
+  let pipeline = device
     .create_graphics_pipelines(vk::PipelineCache::null(), &[info], None)?
     .0[0];
 
   device.destroy_shader_module(vert_shader_module, None);
   device.destroy_shader_module(frag_shader_module, None);
 
-  Ok(())
+  Ok(pipeline)
 }
@@ -4,33 +4,91 @@ use vulkanalia::{
   Device, Instance,
 };
 
+use crate::vulkan::picking::OBJECT_ID_FORMAT;
+use crate::vulkan::post::HDR_FORMAT;
 use crate::vulkan::VulkanAppData;
 
 pub unsafe fn create_render_pass(_instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
   let color_attachment = vk::AttachmentDescription::builder()
-    .format(data.swapchain_format)
+    .format(HDR_FORMAT)
     .samples(vk::SampleCountFlags::_1)
     .load_op(vk::AttachmentLoadOp::CLEAR)
     .store_op(vk::AttachmentStoreOp::STORE)
     .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
     .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
     .initial_layout(vk::ImageLayout::UNDEFINED)
-    .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+    .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
 
   let color_attachment_ref = vk::AttachmentReference::builder()
     .attachment(0)
     .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
 
-  let color_attachments = &[color_attachment_ref];
+  // Ids de objeto para el picking y el contorno de la seleccion.
+  let id_attachment = vk::AttachmentDescription::builder()
+    .format(OBJECT_ID_FORMAT)
+    .samples(vk::SampleCountFlags::_1)
+    .load_op(vk::AttachmentLoadOp::CLEAR)
+    .store_op(vk::AttachmentStoreOp::STORE)
+    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+  let id_attachment_ref = vk::AttachmentReference::builder()
+    .attachment(1)
+    .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+  let depth_stencil_attachment = vk::AttachmentDescription::builder()
+    .format(data.depth_format)
+    .samples(vk::SampleCountFlags::_1)
+    .load_op(vk::AttachmentLoadOp::CLEAR)
+    .store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+  let depth_stencil_attachment_ref = vk::AttachmentReference::builder()
+    .attachment(2)
+    .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+  let color_attachments = &[color_attachment_ref, id_attachment_ref];
   let subpass = vk::SubpassDescription::builder()
     .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-    .color_attachments(color_attachments);
+    .color_attachments(color_attachments)
+    .depth_stencil_attachment(&depth_stencil_attachment_ref);
+
+  // El depth buffer y los targets HDR e ids son compartidos entre frames: esperar a que el anterior
+  // termine de usarlos, incluida la copia del picking.
+  let dependency = vk::SubpassDependency::builder()
+    .src_subpass(vk::SUBPASS_EXTERNAL)
+    .dst_subpass(0)
+    .src_stage_mask(
+      vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+        | vk::PipelineStageFlags::FRAGMENT_SHADER
+        | vk::PipelineStageFlags::TRANSFER,
+    )
+    .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+    .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+    .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+  // El post-procesado muestrea el color y los ids de la escena.
+  let output_dependency = vk::SubpassDependency::builder()
+    .src_subpass(0)
+    .dst_subpass(vk::SUBPASS_EXTERNAL)
+    .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+    .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+    .dst_access_mask(vk::AccessFlags::SHADER_READ);
 
-  let attachments = &[color_attachment];
+  let attachments = &[color_attachment, id_attachment, depth_stencil_attachment];
   let subpasses = &[subpass];
+  let dependencies = &[dependency, output_dependency];
   let info = vk::RenderPassCreateInfo::builder()
     .attachments(attachments)
-    .subpasses(subpasses);
+    .subpasses(subpasses)
+    .dependencies(dependencies);
 
   data.render_pass = device.create_render_pass(&info, None)?;
 
@@ -0,0 +1,55 @@
+#version 450
+
+// 13-tap downsample (Jimenez, "Next Generation Post Processing in Call of Duty").
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform BloomParams {
+  // x: threshold, y: knee, z: 1 on the prefiltering pass, w: radius
+  vec4 params;
+} bloom;
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 tap(vec2 offset) {
+  vec2 texel = 1.0 / vec2(textureSize(source, 0));
+  return texture(source, frag_uv + offset * texel).rgb;
+}
+
+// Soft threshold: quadratic curve around the threshold instead of a hard cut.
+vec3 prefilter(vec3 color) {
+  float brightness = max(color.r, max(color.g, color.b));
+  float knee = bloom.params.x * bloom.params.y + 1e-5;
+  float soft = clamp(brightness - bloom.params.x + knee, 0.0, 2.0 * knee);
+  soft = soft * soft / (4.0 * knee);
+  float contribution = max(soft, brightness - bloom.params.x) / max(brightness, 1e-5);
+  return color * contribution;
+}
+
+void main() {
+  vec3 a = tap(vec2(-2.0, -2.0));
+  vec3 b = tap(vec2(0.0, -2.0));
+  vec3 c = tap(vec2(2.0, -2.0));
+  vec3 d = tap(vec2(-2.0, 0.0));
+  vec3 e = tap(vec2(0.0, 0.0));
+  vec3 f = tap(vec2(2.0, 0.0));
+  vec3 g = tap(vec2(-2.0, 2.0));
+  vec3 h = tap(vec2(0.0, 2.0));
+  vec3 i = tap(vec2(2.0, 2.0));
+  vec3 j = tap(vec2(-1.0, -1.0));
+  vec3 k = tap(vec2(1.0, -1.0));
+  vec3 l = tap(vec2(-1.0, 1.0));
+  vec3 m = tap(vec2(1.0, 1.0));
+
+  vec3 color = e * 0.125;
+  color += (a + c + g + i) * 0.03125;
+  color += (b + d + f + h) * 0.0625;
+  color += (j + k + l + m) * 0.125;
+
+  if (bloom.params.z > 0.0) {
+    color = prefilter(color);
+  }
+
+  outColor = vec4(max(color, vec3(0.0)), 1.0);
+}
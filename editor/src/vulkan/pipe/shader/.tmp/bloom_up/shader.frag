@@ -0,0 +1,29 @@
+#version 450
+
+// 3x3 tent upsample, blended additively over the larger mip.
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform BloomParams {
+  // x: threshold, y: knee, z: 1 on the prefiltering pass, w: radius
+  vec4 params;
+} bloom;
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  vec2 texel = bloom.params.w / vec2(textureSize(source, 0));
+
+  vec3 color = texture(source, frag_uv).rgb * 4.0;
+  color += texture(source, frag_uv + vec2(-texel.x, 0.0)).rgb * 2.0;
+  color += texture(source, frag_uv + vec2(texel.x, 0.0)).rgb * 2.0;
+  color += texture(source, frag_uv + vec2(0.0, -texel.y)).rgb * 2.0;
+  color += texture(source, frag_uv + vec2(0.0, texel.y)).rgb * 2.0;
+  color += texture(source, frag_uv + vec2(-texel.x, -texel.y)).rgb;
+  color += texture(source, frag_uv + vec2(texel.x, -texel.y)).rgb;
+  color += texture(source, frag_uv + vec2(-texel.x, texel.y)).rgb;
+  color += texture(source, frag_uv + vec2(texel.x, texel.y)).rgb;
+
+  outColor = vec4(color / 16.0, 1.0);
+}
@@ -0,0 +1,26 @@
+// Image based lighting maps baked by environment.rs. Needs frame.glsl.
+#ifndef LIGHTING_SET
+#define LIGHTING_SET 3
+#endif
+
+layout(set = LIGHTING_SET, binding = 5) uniform samplerCube irradiance_map;
+layout(set = LIGHTING_SET, binding = 6) uniform samplerCube prefiltered_map;
+layout(set = LIGHTING_SET, binding = 7) uniform sampler2D brdf_lut;
+layout(set = LIGHTING_SET, binding = 8) uniform samplerCube environment_map;
+
+// Split-sum approximation: diffuse irradiance plus prefiltered specular scaled by the BRDF LUT.
+vec3 environment_lighting(vec3 N, vec3 V, vec3 albedo, float metallic, float roughness) {
+  float n_dot_v = max(dot(N, V), 1e-4);
+  vec3 f0 = mix(vec3(0.04), albedo, metallic);
+  vec3 F = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+
+  vec3 kd = (1.0 - F) * (1.0 - metallic);
+  vec3 diffuse = texture(irradiance_map, N).rgb * albedo;
+
+  vec3 R = reflect(-V, N);
+  vec3 prefiltered = textureLod(prefiltered_map, R, roughness * frame.environment.w).rgb;
+  vec2 brdf = texture(brdf_lut, vec2(n_dot_v, roughness)).rg;
+  vec3 specular = prefiltered * (F * brdf.x + brdf.y);
+
+  return (kd * diffuse + specular) * frame.environment.x;
+}
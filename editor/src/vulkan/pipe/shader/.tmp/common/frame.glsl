@@ -0,0 +1,32 @@
+// Per-frame uniforms, see FrameUniforms in uniforms.rs.
+layout(set = 0, binding = 0) uniform Frame {
+  mat4 view;
+  mat4 proj;
+  mat4 view_proj;
+  mat4 inverse_proj;
+  vec4 camera_position;
+  // x: time, y: delta time, zw: viewport size
+  vec4 time;
+  // x: near, y: far
+  vec4 clip;
+  // x: ibl intensity, y: sky intensity, z: sky blur, w: max lod of the prefiltered map
+  vec4 environment;
+  // x: view mode
+  uvec4 debug;
+} frame;
+
+// ViewMode in view_mode.rs.
+#define VIEW_MODE_LIT 0
+#define VIEW_MODE_SHADOW_CASCADES 1
+#define VIEW_MODE_WIREFRAME 2
+#define VIEW_MODE_NORMALS 3
+#define VIEW_MODE_TANGENTS 4
+#define VIEW_MODE_UV_CHECKER 5
+#define VIEW_MODE_MIP_LEVEL 6
+#define VIEW_MODE_OVERDRAW 7
+#define VIEW_MODE_DEPTH 8
+
+// Modes that show data instead of lighting, see ViewMode::is_debug.
+bool is_debug_view() {
+  return frame.debug.x > VIEW_MODE_SHADOW_CASCADES;
+}
@@ -0,0 +1,53 @@
+// Helpers of the image based lighting bake shaders (compute only, see environment.rs).
+const float IBL_PI = 3.14159265359;
+
+// World direction through the texel `uv` of a cube face, in the Vulkan face order +X -X +Y -Y +Z -Z.
+vec3 cube_direction(uint face, vec2 uv) {
+  vec2 st = uv * 2.0 - 1.0;
+
+  switch (face) {
+    case 0: return normalize(vec3(1.0, -st.y, -st.x));
+    case 1: return normalize(vec3(-1.0, -st.y, st.x));
+    case 2: return normalize(vec3(st.x, 1.0, st.y));
+    case 3: return normalize(vec3(st.x, -1.0, -st.y));
+    case 4: return normalize(vec3(st.x, -st.y, 1.0));
+    default: return normalize(vec3(-st.x, -st.y, -1.0));
+  }
+}
+
+// Orthonormal basis around N, used to move tangent space samples to world space.
+mat3 tangent_basis(vec3 N) {
+  vec3 up = abs(N.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+  vec3 T = normalize(cross(up, N));
+  vec3 B = cross(N, T);
+  return mat3(T, B, N);
+}
+
+float radical_inverse(uint bits) {
+  bits = (bits << 16u) | (bits >> 16u);
+  bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+  bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+  bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+  bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+  return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint count) {
+  return vec2(float(i) / float(count), radical_inverse(i));
+}
+
+// Half vector in tangent space, distributed with the GGX lobe of `roughness`.
+vec3 importance_sample_ggx(vec2 xi, float roughness) {
+  float a = roughness * roughness;
+  float phi = 2.0 * IBL_PI * xi.x;
+  float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+  float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+  return vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+float ibl_distribution_ggx(float n_dot_h, float roughness) {
+  float a = roughness * roughness;
+  float a2 = a * a;
+  float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+  return a2 / (IBL_PI * d * d);
+}
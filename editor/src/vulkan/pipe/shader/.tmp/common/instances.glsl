@@ -0,0 +1,39 @@
+// Must match GpuInstance / GpuMaterial in batching.rs and GpuCulling in instancing.rs.
+struct Instance {
+  mat4 model;
+  vec4 bounds; // world space sphere, xyz: center, w: radius
+  uvec4 info;  // x: material, y: draw command, z: prop, w: selected
+};
+
+// Must match SELECTED_BIT in picking.rs; ids are prop + 1, 0 is empty.
+const uint SELECTED_BIT = 0x80000000u;
+
+uint object_id(Instance instance) {
+  return (instance.info.z + 1u) | (instance.info.w != 0u ? SELECTED_BIT : 0u);
+}
+
+struct Material {
+  vec4 base_color;
+  vec4 emissive; // rgb
+  vec4 params;   // metallic, roughness, occlusion strength, normal scale
+};
+
+struct DrawCommand {
+  uint index_count;
+  uint instance_count;
+  uint first_index;
+  int vertex_offset;
+  uint first_instance;
+};
+
+layout(std430, set = 1, binding = 0) buffer Instances {
+  Instance instances[];
+};
+
+layout(std430, set = 1, binding = 1) buffer Visible {
+  uint visible[];
+};
+
+layout(std430, set = 1, binding = 2) buffer Materials {
+  Material materials[];
+};
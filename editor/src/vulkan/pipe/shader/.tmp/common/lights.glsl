@@ -0,0 +1,49 @@
+// Must match GpuLight and the cluster grid in lighting.rs. Needs frame.glsl.
+#ifndef LIGHTING_SET
+#define LIGHTING_SET 3
+#endif
+
+#define CLUSTERS_X 16
+#define CLUSTERS_Y 9
+#define CLUSTERS_Z 24
+#define CLUSTER_COUNT (CLUSTERS_X * CLUSTERS_Y * CLUSTERS_Z)
+#define MAX_LIGHTS_PER_CLUSTER 128
+
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+struct Light {
+  vec4 position;  // xyz, w: range
+  vec4 direction; // xyz
+  vec4 color;     // rgb * intensity
+  vec4 cone;      // cos inner, cos outer
+  uvec4 kind;
+};
+
+layout(std430, set = LIGHTING_SET, binding = 0) buffer Lights {
+  uvec4 light_counts; // x: lights, y: directional lights (first)
+  Light lights[];
+};
+
+layout(std430, set = LIGHTING_SET, binding = 1) buffer ClusterCounts {
+  uint cluster_counts[];
+};
+
+layout(std430, set = LIGHTING_SET, binding = 2) buffer ClusterLights {
+  uint cluster_lights[];
+};
+
+// Exponential depth slices between the near and far planes.
+float cluster_slice_depth(uint slice) {
+  return frame.clip.x * pow(frame.clip.y / frame.clip.x, float(slice) / float(CLUSTERS_Z));
+}
+
+uint cluster_index(vec2 frag_coord, float view_depth) {
+  vec2 tile = frag_coord / frame.time.zw * vec2(CLUSTERS_X, CLUSTERS_Y);
+  float slice = log(view_depth / frame.clip.x) / log(frame.clip.y / frame.clip.x) * float(CLUSTERS_Z);
+
+  ivec3 cell = clamp(ivec3(ivec2(tile), int(slice)), ivec3(0), ivec3(CLUSTERS_X - 1, CLUSTERS_Y - 1, CLUSTERS_Z - 1));
+
+  return uint(cell.x + cell.y * CLUSTERS_X + cell.z * CLUSTERS_X * CLUSTERS_Y);
+}
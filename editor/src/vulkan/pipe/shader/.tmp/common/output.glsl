@@ -0,0 +1,125 @@
+// Output helpers: tonemapping and encoding of the linear scene color for the swapchain (see output.rs).
+struct OutputParams {
+  uint transfer;
+  float paper_white_nits;
+  float max_nits;
+  float exposure;
+};
+
+const uint TRANSFER_SRGB = 0;
+const uint TRANSFER_SRGB_UNORM = 1;
+const uint TRANSFER_HDR10 = 2;
+const uint TRANSFER_SCRGB = 3;
+const uint TRANSFER_LINEAR = 4;
+
+// Tonemapper in post.rs.
+const uint TONEMAP_NONE = 0;
+const uint TONEMAP_REINHARD = 1;
+const uint TONEMAP_ACES = 2;
+const uint TONEMAP_FILMIC = 3;
+
+const mat3 REC709_TO_REC2020 = mat3(
+  0.6274, 0.0691, 0.0164,
+  0.3293, 0.9195, 0.0880,
+  0.0433, 0.0114, 0.8956
+);
+
+bool is_hdr_output(OutputParams params) {
+  return params.transfer == TRANSFER_HDR10 || params.transfer == TRANSFER_SCRGB;
+}
+
+vec3 aces_fitted(vec3 x) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+vec3 reinhard_extended(vec3 color, float white) {
+  float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+  float mapped = luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance);
+  return luminance > 0.0 ? color * (mapped / luminance) : vec3(0.0);
+}
+
+vec3 hable_partial(vec3 x) {
+  const float a = 0.15;
+  const float b = 0.50;
+  const float c = 0.10;
+  const float d = 0.20;
+  const float e = 0.02;
+  const float f = 0.30;
+  return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+vec3 hable_filmic(vec3 color) {
+  const float white = 11.2;
+  return clamp(hable_partial(color * 2.0) / hable_partial(vec3(white)), 0.0, 1.0);
+}
+
+// Exposed scene color to display-linear color: [0, 1] for SDR, relative to paper white for HDR.
+vec3 tonemap(vec3 color, uint tonemapper, OutputParams params) {
+  if (tonemapper == TONEMAP_NONE) {
+    return color;
+  }
+
+  if (is_hdr_output(params)) {
+    return reinhard_extended(color, max(params.max_nits / params.paper_white_nits, 1.0));
+  }
+
+  if (tonemapper == TONEMAP_REINHARD) {
+    return reinhard_extended(color, 4.0);
+  }
+
+  if (tonemapper == TONEMAP_FILMIC) {
+    return hable_filmic(color);
+  }
+
+  return aces_fitted(color);
+}
+
+vec3 srgb_encode(vec3 color) {
+  vec3 low = color * 12.92;
+  vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+  return mix(low, high, step(vec3(0.0031308), color));
+}
+
+vec3 srgb_decode(vec3 color) {
+  vec3 low = color / 12.92;
+  vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+  return mix(low, high, step(vec3(0.04045), color));
+}
+
+vec3 pq_encode(vec3 nits) {
+  const float m1 = 0.1593017578125;
+  const float m2 = 78.84375;
+  const float c1 = 0.8359375;
+  const float c2 = 18.8515625;
+  const float c3 = 18.6875;
+  vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+  return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+// Display-linear color to what the swapchain format expects.
+vec3 encode_output(vec3 color, OutputParams params) {
+  if (params.transfer == TRANSFER_LINEAR) {
+    return color;
+  }
+
+  if (params.transfer == TRANSFER_HDR10) {
+    return pq_encode(REC709_TO_REC2020 * color * params.paper_white_nits);
+  }
+
+  if (params.transfer == TRANSFER_SCRGB) {
+    return color * (params.paper_white_nits / 80.0);
+  }
+
+  color = clamp(color, 0.0, 1.0);
+
+  if (params.transfer == TRANSFER_SRGB_UNORM) {
+    return srgb_encode(color);
+  }
+
+  return color;
+}
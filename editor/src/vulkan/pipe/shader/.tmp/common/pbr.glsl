@@ -0,0 +1,66 @@
+// Cook-Torrance BRDF: GGX distribution, Smith-Schlick geometry and Schlick fresnel.
+const float PI = 3.14159265359;
+
+float distribution_ggx(float n_dot_h, float roughness) {
+  float a = roughness * roughness;
+  float a2 = a * a;
+  float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+  return a2 / (PI * d * d);
+}
+
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+  float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+  float gv = n_dot_v / (n_dot_v * (1.0 - k) + k);
+  float gl = n_dot_l / (n_dot_l * (1.0 - k) + k);
+  return gv * gl;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+  return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Fresnel for image based lighting, where rough surfaces reflect less at grazing angles.
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+  return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Outgoing radiance towards V for light arriving from L with the given radiance.
+vec3 brdf(vec3 N, vec3 V, vec3 L, vec3 radiance, vec3 albedo, float metallic, float roughness) {
+  vec3 H = normalize(V + L);
+  float n_dot_l = max(dot(N, L), 0.0);
+  float n_dot_v = max(dot(N, V), 1e-4);
+  float n_dot_h = max(dot(N, H), 0.0);
+
+  vec3 f0 = mix(vec3(0.04), albedo, metallic);
+  vec3 F = fresnel_schlick(max(dot(H, V), 0.0), f0);
+  float D = distribution_ggx(n_dot_h, roughness);
+  float G = geometry_smith(n_dot_v, n_dot_l, roughness);
+
+  vec3 specular = D * G * F / (4.0 * n_dot_v * n_dot_l + 1e-4);
+  vec3 diffuse = (1.0 - F) * (1.0 - metallic) * albedo / PI;
+
+  return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// Normal mapping without vertex tangents, from screen space derivatives.
+mat3 cotangent_frame(vec3 N, vec3 position, vec2 uv) {
+  vec3 dp1 = dFdx(position);
+  vec3 dp2 = dFdy(position);
+  vec2 duv1 = dFdx(uv);
+  vec2 duv2 = dFdy(uv);
+
+  vec3 dp2_perp = cross(dp2, N);
+  vec3 dp1_perp = cross(N, dp1);
+  vec3 T = dp2_perp * duv1.x + dp1_perp * duv2.x;
+  vec3 B = dp2_perp * duv1.y + dp1_perp * duv2.y;
+
+  float inv_max = inversesqrt(max(max(dot(T, T), dot(B, B)), 1e-12));
+  return mat3(T * inv_max, B * inv_max, N);
+}
+
+// Windowed inverse square falloff reaching zero at `range`.
+float range_attenuation(float distance, float range) {
+  float ratio = distance / max(range, 1e-4);
+  float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+  return window * window / max(distance * distance, 1e-4);
+}
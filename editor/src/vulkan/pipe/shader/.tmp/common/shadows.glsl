@@ -0,0 +1,84 @@
+// Must match GpuShadowView in shadows.rs. Needs lights.glsl.
+struct ShadowView {
+  mat4 view_proj;
+  vec4 rect;   // atlas uv, xy: offset, zw: size
+  vec4 params; // x: normal bias, y: PCF radius, z: cascade far view depth
+};
+
+layout(std430, set = LIGHTING_SET, binding = 3) buffer ShadowViews {
+  ShadowView shadow_views[];
+};
+
+layout(set = LIGHTING_SET, binding = 4) uniform sampler2DShadow shadow_atlas;
+
+float sample_shadow_view(uint index, vec3 world_position, vec3 normal) {
+  ShadowView view = shadow_views[index];
+  vec4 clip = view.view_proj * vec4(world_position + normal * view.params.x, 1.0);
+  vec3 ndc = clip.xyz / clip.w;
+
+  if (any(greaterThan(abs(ndc.xy), vec2(1.0))) || ndc.z > 1.0) {
+    return 1.0;
+  }
+
+  vec2 texel = 1.0 / vec2(textureSize(shadow_atlas, 0));
+  vec2 uv = view.rect.xy + (ndc.xy * 0.5 + 0.5) * view.rect.zw;
+  // No leer texels de los tiles vecinos.
+  vec2 lo = view.rect.xy + texel * 0.5;
+  vec2 hi = view.rect.xy + view.rect.zw - texel * 0.5;
+
+  int radius = int(view.params.y);
+  float lit = 0.0;
+
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      lit += texture(shadow_atlas, vec3(clamp(uv + vec2(x, y) * texel, lo, hi), ndc.z));
+    }
+  }
+
+  return lit / float((2 * radius + 1) * (2 * radius + 1));
+}
+
+// Index of the cascade covering view_depth, light.kind.z when it's past the last one.
+uint shadow_cascade(Light light, float view_depth) {
+  for (uint i = 0; i < light.kind.z; i++) {
+    if (view_depth <= shadow_views[light.kind.y + i].params.z) {
+      return i;
+    }
+  }
+
+  return light.kind.z;
+}
+
+// Cube faces are stored +X, -X, +Y, -Y, +Z, -Z.
+uint shadow_cube_face(vec3 direction) {
+  vec3 a = abs(direction);
+
+  if (a.x >= a.y && a.x >= a.z) {
+    return direction.x > 0.0 ? 0 : 1;
+  }
+
+  if (a.y >= a.z) {
+    return direction.y > 0.0 ? 2 : 3;
+  }
+
+  return direction.z > 0.0 ? 4 : 5;
+}
+
+// 1.0 fully lit, 0.0 fully shadowed.
+float light_shadow(Light light, vec3 world_position, vec3 normal, float view_depth) {
+  if (light.kind.z == 0) {
+    return 1.0;
+  }
+
+  if (light.kind.x == LIGHT_DIRECTIONAL) {
+    uint cascade = shadow_cascade(light, view_depth);
+    return cascade < light.kind.z ? sample_shadow_view(light.kind.y + cascade, world_position, normal) : 1.0;
+  }
+
+  if (light.kind.x == LIGHT_POINT) {
+    uint face = shadow_cube_face(world_position - light.position.xyz);
+    return sample_shadow_view(light.kind.y + face, world_position, normal);
+  }
+
+  return sample_shadow_view(light.kind.y, world_position, normal);
+}
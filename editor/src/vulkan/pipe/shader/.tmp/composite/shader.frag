@@ -0,0 +1,111 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/output.glsl"
+
+layout(set = 0, binding = 0) uniform sampler2D scene_color;
+layout(set = 0, binding = 1) uniform sampler2D bloom_color;
+layout(set = 0, binding = 2) uniform sampler2D grade_lut;
+layout(set = 0, binding = 3) uniform usampler2D object_ids;
+
+// CompositeParams in post.rs.
+layout(push_constant) uniform CompositeParams {
+  OutputParams display;
+  uvec4 effects;  // x: tonemapper, y: bloom, z: vignette, w: color grading
+  vec4 bloom;     // x: intensity
+  vec4 vignette;  // x: intensity, y: smoothness
+  vec4 grade;     // x: strength, y: LUT size
+  uvec4 debug;    // x: overdraw heatmap, y: selection outline
+} post;
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+// 2D strip LUT: `size` slices of size x size, red along x, green along y, blue per slice.
+vec3 apply_lut(vec3 color) {
+  float size = post.grade.y;
+  vec3 encoded = srgb_encode(clamp(color, 0.0, 1.0));
+  float blue = encoded.b * (size - 1.0);
+  float slice = floor(blue);
+
+  vec2 uv = vec2((encoded.r * (size - 1.0) + 0.5) / (size * size), (encoded.g * (size - 1.0) + 0.5) / size);
+  vec3 low = texture(grade_lut, uv + vec2(slice / size, 0.0)).rgb;
+  vec3 high = texture(grade_lut, uv + vec2(min(slice + 1.0, size - 1.0) / size, 0.0)).rgb;
+
+  return srgb_decode(mix(low, high, blue - slice));
+}
+
+// Fragments per pixel: 1 azul, 2 cian, 4 verde, 8 amarillo, 16 o mas rojo.
+vec3 heatmap(float count) {
+  const vec3 COLORS[6] = vec3[](
+    vec3(0.0),
+    vec3(0.0, 0.0, 1.0),
+    vec3(0.0, 1.0, 1.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(1.0, 1.0, 0.0),
+    vec3(1.0, 0.0, 0.0)
+  );
+
+  float t = count < 1.0 ? count : clamp(log2(count) + 1.0, 1.0, 5.0);
+  int i = min(int(t), 4);
+  return mix(COLORS[i], COLORS[i + 1], t - float(i));
+}
+
+// Must match SELECTED_BIT in picking.rs.
+const uint SELECTED_BIT = 0x80000000u;
+const vec3 OUTLINE_COLOR = vec3(1.0, 0.55, 0.1);
+const int OUTLINE_WIDTH = 2;
+
+// Cobertura del contorno: pixeles no seleccionados con algun vecino seleccionado.
+float selection_outline() {
+  ivec2 size = textureSize(object_ids, 0);
+  ivec2 center = ivec2(frag_uv * vec2(size));
+
+  if ((texelFetch(object_ids, center, 0).r & SELECTED_BIT) != 0u) {
+    return 0.0;
+  }
+
+  for (int y = -OUTLINE_WIDTH; y <= OUTLINE_WIDTH; y++) {
+    for (int x = -OUTLINE_WIDTH; x <= OUTLINE_WIDTH; x++) {
+      ivec2 p = clamp(center + ivec2(x, y), ivec2(0), size - 1);
+
+      if ((texelFetch(object_ids, p, 0).r & SELECTED_BIT) != 0u) {
+        return 1.0;
+      }
+    }
+  }
+
+  return 0.0;
+}
+
+void main() {
+  vec3 color = texture(scene_color, frag_uv).rgb;
+
+  if (post.debug.x != 0) {
+    outColor = vec4(heatmap(color.r), 1.0);
+    return;
+  }
+
+  if (post.effects.y != 0) {
+    color += texture(bloom_color, frag_uv).rgb * post.bloom.x;
+  }
+
+  color = tonemap(color * post.display.exposure, post.effects.x, post.display);
+
+  // La LUT trabaja en el rango SDR.
+  if (post.effects.w != 0 && !is_hdr_output(post.display)) {
+    color = mix(color, apply_lut(color), post.grade.x);
+  }
+
+  if (post.effects.z != 0) {
+    float distance = length(frag_uv - 0.5) * 1.41421356;
+    color *= 1.0 - post.vignette.x * smoothstep(1.0 - post.vignette.y, 1.0, distance);
+  }
+
+  if (post.debug.y != 0) {
+    color = mix(color, OUTLINE_COLOR, selection_outline());
+  }
+
+  outColor = vec4(color, 1.0);
+}
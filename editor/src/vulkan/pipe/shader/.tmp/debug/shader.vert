@@ -0,0 +1,15 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/frame.glsl"
+
+// DebugVertex in scene/debug_draw.rs.
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec4 in_color;
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+  frag_color = in_color;
+  gl_Position = frame.view_proj * vec4(in_position, 1.0);
+}
@@ -0,0 +1,67 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/output.glsl"
+
+layout(set = 0, binding = 0) uniform sampler2D display_color;
+
+// FinalParams in post.rs.
+layout(push_constant) uniform FinalParams {
+  OutputParams display;
+  vec4 fxaa; // x: enabled, y: edge threshold, z: edge threshold min, w: strength
+} params;
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_SPAN_MAX = 8.0;
+
+// Luma aproximadamente perceptual, el color llega lineal.
+float luma(vec3 color) {
+  return sqrt(dot(clamp(color, 0.0, 1.0), vec3(0.299, 0.587, 0.114)));
+}
+
+vec3 fxaa(vec3 center) {
+  vec2 texel = 1.0 / vec2(textureSize(display_color, 0));
+
+  float luma_m = luma(center);
+  float luma_nw = luma(texture(display_color, frag_uv + vec2(-1.0, -1.0) * texel).rgb);
+  float luma_ne = luma(texture(display_color, frag_uv + vec2(1.0, -1.0) * texel).rgb);
+  float luma_sw = luma(texture(display_color, frag_uv + vec2(-1.0, 1.0) * texel).rgb);
+  float luma_se = luma(texture(display_color, frag_uv + vec2(1.0, 1.0) * texel).rgb);
+
+  float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+  float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+  if (luma_max - luma_min < max(params.fxaa.z, luma_max * params.fxaa.y)) {
+    return center;
+  }
+
+  vec2 direction = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+  float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+  float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+  direction = clamp(direction * scale, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+  vec3 a = 0.5 * (texture(display_color, frag_uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+                  texture(display_color, frag_uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+  vec3 b = a * 0.5 + 0.25 * (texture(display_color, frag_uv - direction * 0.5).rgb +
+                             texture(display_color, frag_uv + direction * 0.5).rgb);
+
+  float luma_b = luma(b);
+  vec3 filtered = (luma_b < luma_min || luma_b > luma_max) ? a : b;
+
+  return mix(center, filtered, params.fxaa.w);
+}
+
+void main() {
+  vec3 color = texture(display_color, frag_uv).rgb;
+
+  if (params.fxaa.x > 0.0) {
+    color = fxaa(color);
+  }
+
+  outColor = vec4(encode_output(color, params.display), 1.0);
+}
@@ -0,0 +1,158 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/frame.glsl"
+#include "../common/instances.glsl"
+#include "../common/lights.glsl"
+#include "../common/shadows.glsl"
+#include "../common/pbr.glsl"
+#include "../common/environment.glsl"
+
+layout(set = 2, binding = 0) uniform sampler2D albedo_map;
+layout(set = 2, binding = 1) uniform sampler2D normal_map;
+layout(set = 2, binding = 2) uniform sampler2D metallic_roughness_map;
+layout(set = 2, binding = 3) uniform sampler2D occlusion_map;
+layout(set = 2, binding = 4) uniform sampler2D emissive_map;
+
+layout(location = 0) in vec3 frag_world_position;
+layout(location = 1) in vec3 frag_normal;
+layout(location = 2) in vec2 frag_uv;
+layout(location = 3) flat in uint frag_material;
+layout(location = 4) flat in uint frag_object;
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out uint outObject;
+
+const vec3 CASCADE_TINTS[5] = vec3[](
+  vec3(1.0, 0.35, 0.35),
+  vec3(0.35, 1.0, 0.35),
+  vec3(0.35, 0.35, 1.0),
+  vec3(1.0, 1.0, 0.35),
+  vec3(1.0)
+);
+
+const vec3 MIP_TINTS[6] = vec3[](
+  vec3(0.2, 0.4, 1.0),
+  vec3(0.2, 1.0, 0.4),
+  vec3(1.0, 1.0, 0.2),
+  vec3(1.0, 0.6, 0.2),
+  vec3(1.0, 0.2, 0.2),
+  vec3(1.0, 0.2, 1.0)
+);
+
+struct Surface {
+  vec3 N;
+  vec3 geometric_normal;
+  vec3 V;
+  float view_depth;
+  vec3 albedo;
+  float metallic;
+  float roughness;
+};
+
+vec3 shade_light(Light light, Surface s) {
+  float shadow = light_shadow(light, frag_world_position, s.geometric_normal, s.view_depth);
+
+  if (shadow <= 0.0) {
+    return vec3(0.0);
+  }
+
+  if (light.kind.x == LIGHT_DIRECTIONAL) {
+    return brdf(s.N, s.V, -light.direction.xyz, light.color.rgb * shadow, s.albedo, s.metallic, s.roughness);
+  }
+
+  vec3 to_light = light.position.xyz - frag_world_position;
+  float distance = length(to_light);
+  vec3 L = to_light / max(distance, 1e-4);
+  float attenuation = range_attenuation(distance, light.position.w) * shadow;
+
+  if (light.kind.x == LIGHT_SPOT) {
+    attenuation *= smoothstep(light.cone.y, light.cone.x, dot(-L, light.direction.xyz));
+  }
+
+  return brdf(s.N, s.V, L, light.color.rgb * attenuation, s.albedo, s.metallic, s.roughness);
+}
+
+vec3 debug_view_color(vec3 albedo, vec3 normal, vec3 tangent) {
+  switch (frame.debug.x) {
+    case VIEW_MODE_WIREFRAME:
+      return mix(vec3(0.85), albedo, 0.35);
+    case VIEW_MODE_NORMALS:
+      return normal * 0.5 + 0.5;
+    case VIEW_MODE_TANGENTS:
+      return normalize(tangent) * 0.5 + 0.5;
+    case VIEW_MODE_UV_CHECKER: {
+      vec2 cell = floor(frag_uv * 8.0);
+      float checker = mod(cell.x + cell.y, 2.0);
+      vec3 uv_color = vec3(fract(frag_uv), 0.0);
+      return mix(uv_color * 0.4 + 0.1, uv_color * 0.5 + 0.5, checker);
+    }
+    case VIEW_MODE_MIP_LEVEL: {
+      float level = textureQueryLod(albedo_map, frag_uv).x;
+      int mip = min(int(level), 4);
+      vec3 tint = mix(MIP_TINTS[mip], MIP_TINTS[mip + 1], fract(level));
+      return mix(albedo, tint, 0.7);
+    }
+    case VIEW_MODE_OVERDRAW:
+      // Se suma con blending aditivo; el composite lo convierte en heatmap.
+      return vec3(1.0, 0.0, 0.0);
+    case VIEW_MODE_DEPTH: {
+      float view_depth = -(frame.view * vec4(frag_world_position, 1.0)).z;
+      float near = frame.clip.x;
+      float far = frame.clip.y;
+      float depth = log(max(view_depth, near) / near) / log(far / near);
+      return vec3(1.0 - clamp(depth, 0.0, 1.0));
+    }
+    default:
+      return albedo;
+  }
+}
+
+void main() {
+  // Antes de cualquier return: el picking funciona en todos los modos de vista.
+  outObject = frag_object;
+  Material material = materials[frag_material];
+
+  vec4 base_color = material.base_color * texture(albedo_map, frag_uv);
+  vec2 metallic_roughness = texture(metallic_roughness_map, frag_uv).bg;
+  float metallic = clamp(material.params.x * metallic_roughness.x, 0.0, 1.0);
+  float roughness = clamp(material.params.y * metallic_roughness.y, 0.04, 1.0);
+  float occlusion = mix(1.0, texture(occlusion_map, frag_uv).r, material.params.z);
+  vec3 emissive = material.emissive.rgb * texture(emissive_map, frag_uv).rgb;
+
+  vec3 geometric_normal = normalize(frag_normal);
+  vec3 N = geometric_normal;
+  vec3 tangent_normal = texture(normal_map, frag_uv).xyz * 2.0 - 1.0;
+  tangent_normal.xy *= material.params.w;
+  mat3 tbn = cotangent_frame(N, frag_world_position, frag_uv);
+  N = normalize(tbn * tangent_normal);
+
+  if (is_debug_view()) {
+    outColor = vec4(debug_view_color(base_color.rgb, geometric_normal, tbn[0]), 1.0);
+    return;
+  }
+
+  vec3 V = normalize(frame.camera_position.xyz - frag_world_position);
+  float view_depth = -(frame.view * vec4(frag_world_position, 1.0)).z;
+  Surface surface = Surface(N, geometric_normal, V, view_depth, base_color.rgb, metallic, roughness);
+  vec3 color = vec3(0.0);
+
+  for (uint i = 0; i < light_counts.y; i++) {
+    color += shade_light(lights[i], surface);
+  }
+
+  uint cluster = cluster_index(gl_FragCoord.xy, view_depth);
+
+  for (uint i = 0; i < cluster_counts[cluster]; i++) {
+    Light light = lights[cluster_lights[cluster * MAX_LIGHTS_PER_CLUSTER + i]];
+    color += shade_light(light, surface);
+  }
+
+  color += environment_lighting(N, V, base_color.rgb, metallic, roughness) * occlusion + emissive;
+
+  if (frame.debug.x == VIEW_MODE_SHADOW_CASCADES && light_counts.y > 0) {
+    color *= CASCADE_TINTS[min(shadow_cascade(lights[0], view_depth), 4)];
+  }
+
+  outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,28 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/frame.glsl"
+#include "../common/instances.glsl"
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+
+layout(location = 0) out vec3 frag_world_position;
+layout(location = 1) out vec3 frag_normal;
+layout(location = 2) out vec2 frag_uv;
+layout(location = 3) flat out uint frag_material;
+layout(location = 4) flat out uint frag_object;
+
+void main() {
+  // gl_InstanceIndex ya incluye el first_instance del draw command.
+  Instance instance = instances[visible[gl_InstanceIndex]];
+  vec4 world_position = instance.model * vec4(in_position, 1.0);
+
+  gl_Position = frame.view_proj * world_position;
+  frag_world_position = world_position.xyz;
+  frag_normal = transpose(inverse(mat3(instance.model))) * in_normal;
+  frag_uv = in_uv;
+  frag_material = instance.info.x;
+  frag_object = object_id(instance);
+}
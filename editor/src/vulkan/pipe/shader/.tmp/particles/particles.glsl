@@ -0,0 +1,27 @@
+// Must match GpuParticle / GpuEmitter in particles.rs.
+#define MAX_EMITTERS 8
+#define PARTICLES_PER_EMITTER 4096
+#define COLOR_KEYS 8
+
+struct Particle {
+  vec4 position; // xyz, w: age
+  vec4 velocity; // xyz, w: lifetime
+};
+
+struct Emitter {
+  vec4 position;  // xyz, w: spawn radius
+  vec4 velocity;  // xyz, w: velocity spread
+  vec4 gravity;
+  vec4 life_size; // lifetime min/max, size start/end
+  uvec4 spawn;    // first slot, count, seed
+  vec4 colors[COLOR_KEYS];
+};
+
+layout(std430, set = 1, binding = 0) buffer Particles {
+  Particle particles[];
+};
+
+layout(std140, set = 1, binding = 1) uniform Emitters {
+  Emitter emitters[MAX_EMITTERS];
+  uvec4 counts;
+};
@@ -0,0 +1,17 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+layout(location = 0) in vec4 frag_color;
+layout(location = 1) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  float falloff = 1.0 - smoothstep(0.5, 1.0, length(frag_uv));
+
+  if (falloff <= 0.0) {
+    discard;
+  }
+
+  outColor = vec4(frag_color.rgb, frag_color.a * falloff);
+}
@@ -0,0 +1,50 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/frame.glsl"
+#include "particles.glsl"
+
+layout(location = 0) out vec4 frag_color;
+layout(location = 1) out vec2 frag_uv;
+
+vec2 corners[6] = vec2[](
+  vec2(-1.0, -1.0),
+  vec2(1.0, -1.0),
+  vec2(1.0, 1.0),
+  vec2(-1.0, -1.0),
+  vec2(1.0, 1.0),
+  vec2(-1.0, 1.0)
+);
+
+vec4 color_over_life(Emitter emitter, float t) {
+  float key = clamp(t, 0.0, 1.0) * float(COLOR_KEYS - 1);
+  int first = min(int(key), COLOR_KEYS - 2);
+  return mix(emitter.colors[first], emitter.colors[first + 1], key - float(first));
+}
+
+void main() {
+  Particle particle = particles[gl_InstanceIndex];
+  float age = particle.position.w;
+  float lifetime = particle.velocity.w;
+
+  // Particulas muertas: triangulo degenerado fuera del clip space.
+  if (age >= lifetime) {
+    gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+    frag_color = vec4(0.0);
+    frag_uv = vec2(0.0);
+    return;
+  }
+
+  Emitter emitter = emitters[gl_InstanceIndex / PARTICLES_PER_EMITTER];
+  float t = age / lifetime;
+  float size = mix(emitter.life_size.z, emitter.life_size.w, t);
+
+  vec2 corner = corners[gl_VertexIndex];
+  vec3 right = vec3(frame.view[0][0], frame.view[1][0], frame.view[2][0]);
+  vec3 up = vec3(frame.view[0][1], frame.view[1][1], frame.view[2][1]);
+  vec3 position = particle.position.xyz + (corner.x * right + corner.y * up) * size;
+
+  gl_Position = frame.view_proj * vec4(position, 1.0);
+  frag_color = color_over_life(emitter, t);
+  frag_uv = corner;
+}
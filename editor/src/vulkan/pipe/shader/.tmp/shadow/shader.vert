@@ -0,0 +1,18 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/frame.glsl"
+#include "../common/instances.glsl"
+
+layout(location = 0) in vec3 in_position;
+
+layout(push_constant) uniform ShadowPush {
+  mat4 view_proj;
+} shadow;
+
+void main() {
+  // Los draws de sombras no pasan por el culling: se indexa directo en instances.
+  Instance instance = instances[gl_InstanceIndex];
+
+  gl_Position = shadow.view_proj * instance.model * vec4(in_position, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#define LIGHTING_SET 1
+
+#include "../common/frame.glsl"
+#include "../common/pbr.glsl"
+#include "../common/environment.glsl"
+
+layout(location = 0) in vec3 frag_direction;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  if (is_debug_view()) {
+    outColor = vec4(0.0, 0.0, 0.0, 1.0);
+    return;
+  }
+
+  vec3 color = textureLod(environment_map, normalize(frag_direction), frame.environment.z).rgb;
+  outColor = vec4(color * frame.environment.y, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/frame.glsl"
+
+layout(location = 0) out vec3 frag_direction;
+
+// Full-screen triangle on the far plane; the world direction is rebuilt from the inverse matrices.
+void main() {
+  vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+  vec2 ndc = uv * 2.0 - 1.0;
+
+  vec4 view_point = frame.inverse_proj * vec4(ndc, 1.0, 1.0);
+  vec3 view_dir = view_point.xyz / view_point.w;
+  frag_direction = transpose(mat3(frame.view)) * view_dir;
+
+  gl_Position = vec4(ndc, 1.0, 1.0);
+}
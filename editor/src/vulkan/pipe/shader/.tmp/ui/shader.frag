@@ -0,0 +1,27 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/output.glsl"
+
+layout(set = 0, binding = 0) uniform sampler2D ui_texture;
+
+// UiParams in ui.rs.
+layout(push_constant) uniform UiParams {
+  OutputParams display;
+  vec4 screen; // xy: screen size in points, z: 1 for a linear texture
+} params;
+
+layout(location = 0) in vec4 frag_color;
+layout(location = 1) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+  vec4 texel = texture(ui_texture, frag_uv);
+  vec3 rgb = params.screen.z > 0.0 ? texel.rgb : srgb_decode(texel.rgb);
+  vec4 color = frag_color * vec4(rgb, texel.a);
+
+  // La codificacion de salida no es lineal: se aplica al color sin premultiplicar.
+  vec3 straight = color.a > 0.0 ? color.rgb / color.a : vec3(0.0);
+  outColor = vec4(encode_output(straight, params.display) * color.a, color.a);
+}
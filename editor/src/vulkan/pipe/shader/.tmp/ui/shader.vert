@@ -0,0 +1,24 @@
+#version 450
+#extension GL_GOOGLE_include_directive : require
+
+#include "../common/output.glsl"
+
+// UiParams in ui.rs.
+layout(push_constant) uniform UiParams {
+  OutputParams display;
+  vec4 screen; // xy: screen size in points, z: 1 for a linear texture
+} params;
+
+// egui::epaint::Vertex: posiciones en puntos, color sRGB premultiplicado.
+layout(location = 0) in vec2 in_position;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) in vec4 in_color;
+
+layout(location = 0) out vec4 frag_color;
+layout(location = 1) out vec2 frag_uv;
+
+void main() {
+  frag_color = vec4(srgb_decode(in_color.rgb), in_color.a);
+  frag_uv = in_uv;
+  gl_Position = vec4(in_position / params.screen.xy * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,897 @@
+use std::mem::size_of;
+use std::path::PathBuf;
+
+use anyhow::{Ok, Result};
+use log::{info, warn};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+use vulkanalia_sys::Handle;
+
+use super::buffers::{begin_single_time_commands, end_single_time_commands};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_image_descriptor,
+};
+use super::images::{create_image, create_image_view, create_mip_range_view};
+use super::output::{DisplayOutput, OutputParams};
+use super::pipe::{create_graphics_pipeline, BlendMode, PipelineDesc};
+use super::textures::{create_texture, destroy_texture, load_rgba8, Texture};
+use super::utils::bytes::as_bytes;
+use super::view_mode::ViewMode;
+use super::VulkanAppData;
+
+/// Format of the scene color target and the bloom chain.
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const BLOOM_MIPS: u32 = 6;
+/// Entries per channel of the color grading LUT.
+const LUT_SIZE: u32 = 16;
+
+/// Curve that maps the exposed HDR color to the display range. HDR outputs always roll off with
+/// an extended Reinhard towards the display peak unless it's `None`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Tonemapper {
+  None,
+  Reinhard,
+  #[default]
+  Aces,
+  /// Hable's Uncharted 2 curve.
+  Filmic,
+}
+
+impl Tonemapper {
+  pub fn next(self) -> Self {
+    match self {
+      Self::None => Self::Reinhard,
+      Self::Reinhard => Self::Aces,
+      Self::Aces => Self::Filmic,
+      Self::Filmic => Self::None,
+    }
+  }
+
+  /// Value of `TONEMAP_*` in `common/output.glsl`.
+  fn value(self) -> u32 {
+    match self {
+      Self::None => 0,
+      Self::Reinhard => 1,
+      Self::Aces => 2,
+      Self::Filmic => 3,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomSettings {
+  pub enabled: bool,
+  /// Scene luminance where bloom starts.
+  pub threshold: f32,
+  /// Width of the soft transition around `threshold`, relative to it.
+  pub knee: f32,
+  pub intensity: f32,
+  /// Scale of the upsampling filter, in texels of each mip.
+  pub radius: f32,
+}
+
+impl Default for BloomSettings {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      threshold: 1.0,
+      knee: 0.5,
+      intensity: 0.08,
+      radius: 1.0,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FxaaSettings {
+  pub enabled: bool,
+  /// Minimum local contrast, relative to the brightest neighbour, that gets filtered.
+  pub edge_threshold: f32,
+  /// Minimum absolute local contrast that gets filtered, skips dark areas.
+  pub edge_threshold_min: f32,
+  /// Blend between the original (0) and the filtered (1) color.
+  pub strength: f32,
+}
+
+impl Default for FxaaSettings {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      edge_threshold: 0.166,
+      edge_threshold_min: 0.0833,
+      strength: 1.0,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VignetteSettings {
+  pub enabled: bool,
+  /// Darkening at the corners, 1.0 is black.
+  pub intensity: f32,
+  /// Fraction of the distance to the corners the falloff covers.
+  pub smoothness: f32,
+}
+
+impl Default for VignetteSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      intensity: 0.35,
+      smoothness: 0.45,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorGradeSettings {
+  pub enabled: bool,
+  /// 256x16 strip of sixteen 16x16 slices (red along x, green along y, blue per slice) in sRGB.
+  /// `None` uses the identity LUT.
+  pub lut: Option<PathBuf>,
+  /// Blend between the ungraded (0) and graded (1) color.
+  pub strength: f32,
+}
+
+impl Default for ColorGradeSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      lut: None,
+      strength: 1.0,
+    }
+  }
+}
+
+/// Full-screen passes applied to the HDR scene color before it's presented.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostSettings {
+  /// Exposure compensation in stops.
+  pub exposure_ev: f32,
+  pub tonemapper: Tonemapper,
+  pub bloom: BloomSettings,
+  pub fxaa: FxaaSettings,
+  pub vignette: VignetteSettings,
+  pub grade: ColorGradeSettings,
+}
+
+/// Push constants of `composite/shader.frag`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CompositeParams {
+  display: OutputParams,
+  /// x: tonemapper, y: bloom, z: vignette, w: color grading.
+  effects: [u32; 4],
+  /// x: intensity.
+  bloom: [f32; 4],
+  /// x: intensity, y: smoothness.
+  vignette: [f32; 4],
+  /// x: strength, y: LUT size.
+  grade: [f32; 4],
+  /// x: 1 to show the red channel as an overdraw heatmap, y: 1 to outline the selected props.
+  debug: [u32; 4],
+}
+
+/// Push constants of `final/shader.frag`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct FinalParams {
+  display: OutputParams,
+  /// x: enabled, y: edge threshold, z: edge threshold min, w: strength.
+  fxaa: [f32; 4],
+}
+
+/// Push constants of the bloom shaders, x: threshold, y: knee, z: 1 on the prefiltering pass,
+/// w: upsampling radius.
+type BloomParams = [f32; 4];
+
+#[derive(Copy, Clone, Debug, Default)]
+struct BloomMip {
+  view: vk::ImageView,
+  framebuffer: vk::Framebuffer,
+  extent: vk::Extent2D,
+  /// Samples the previous mip (the scene color for mip 0) while downsampling into this one.
+  down_set: vk::DescriptorSet,
+  /// Samples the next mip while upsampling into this one.
+  up_set: vk::DescriptorSet,
+}
+
+/// HDR scene target and the post-processing chain that ends on the swapchain image, or on the
+/// viewport texture: bloom, composite (exposure, tonemapping, grading, vignette) and a final FXAA
+/// and encoding pass.
+#[derive(Clone, Debug, Default)]
+pub struct PostData {
+  pub settings: PostSettings,
+  sampler: vk::Sampler,
+  lut: Texture,
+  lut_path: Option<PathBuf>,
+  single_layout: vk::DescriptorSetLayout,
+  composite_layout: vk::DescriptorSetLayout,
+  single_pipeline_layout: vk::PipelineLayout,
+  composite_pipeline_layout: vk::PipelineLayout,
+  descriptor_pool: vk::DescriptorPool,
+  pub hdr: Texture,
+  ldr: Texture,
+  /// Display-linear result of the final pass while the scene is drawn in a viewport.
+  pub viewport: Texture,
+  bloom_image: vk::Image,
+  bloom_memory: vk::DeviceMemory,
+  bloom_mips: Vec<BloomMip>,
+  bloom_down_pass: vk::RenderPass,
+  bloom_up_pass: vk::RenderPass,
+  composite_pass: vk::RenderPass,
+  present_pass: vk::RenderPass,
+  composite_framebuffer: vk::Framebuffer,
+  present_framebuffers: Vec<vk::Framebuffer>,
+  bloom_down_pipeline: vk::Pipeline,
+  bloom_up_pipeline: vk::Pipeline,
+  composite_pipeline: vk::Pipeline,
+  final_pipeline: vk::Pipeline,
+  composite_set: vk::DescriptorSet,
+  final_set: vk::DescriptorSet,
+}
+
+fn identity_lut() -> Vec<u8> {
+  let max = (LUT_SIZE - 1) as f32;
+  let channel = |v: u32| (v as f32 / max * 255.0).round() as u8;
+
+  (0..LUT_SIZE)
+    .flat_map(|g| (0..LUT_SIZE * LUT_SIZE).map(move |x| (x % LUT_SIZE, g, x / LUT_SIZE)))
+    .flat_map(|(r, g, b)| [channel(r), channel(g), channel(b), 255])
+    .collect()
+}
+
+unsafe fn create_lut(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  path: Option<&PathBuf>,
+) -> Result<Texture> {
+  let (width, height) = (LUT_SIZE * LUT_SIZE, LUT_SIZE);
+  let format = vk::Format::R8G8B8A8_UNORM;
+
+  if let Some(path) = path {
+    match load_rgba8(path) {
+      Result::Ok((w, h, pixels)) if (w, h) == (width, height) => {
+        info!("[+] post -> color grading LUT {}", path.display());
+        return create_texture(instance, device, data, w, h, format, &pixels);
+      }
+      Result::Ok((w, h, _)) => warn!("LUT {} is {}x{}, expected {}x{}.", path.display(), w, h, width, height),
+      Err(e) => warn!("Failed to load LUT {}: {}", path.display(), e),
+    }
+  }
+
+  create_texture(instance, device, data, width, height, format, &identity_lut())
+}
+
+/// Sampler, set and pipeline layouts and the identity LUT; they don't depend on the swapchain.
+pub unsafe fn create_post_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let sampler_info = vk::SamplerCreateInfo::builder()
+    .mag_filter(vk::Filter::LINEAR)
+    .min_filter(vk::Filter::LINEAR)
+    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+    .max_lod(0.0);
+
+  let sampler = device.create_sampler(&sampler_info, None)?;
+
+  let sampled = |binding| {
+    layout_binding(
+      binding,
+      vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+      vk::ShaderStageFlags::FRAGMENT,
+    )
+  };
+  let single_layout = create_descriptor_set_layout(device, &[sampled(0)])?;
+  let composite_layout = create_descriptor_set_layout(device, &[sampled(0), sampled(1), sampled(2), sampled(3)])?;
+
+  let pipeline_layout = |set_layout, size: usize| {
+    let range = vk::PushConstantRange::builder()
+      .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+      .offset(0)
+      .size(size as u32);
+
+    let set_layouts = &[set_layout];
+    let push_constant_ranges = &[range];
+    let info = vk::PipelineLayoutCreateInfo::builder()
+      .set_layouts(set_layouts)
+      .push_constant_ranges(push_constant_ranges);
+
+    device.create_pipeline_layout(&info, None)
+  };
+
+  // Los pases de bloom y el final comparten layout: un sampler y hasta 32 bytes de push constants.
+  let single_pipeline_layout = pipeline_layout(single_layout, size_of::<FinalParams>().max(size_of::<BloomParams>()))?;
+  let composite_pipeline_layout = pipeline_layout(composite_layout, size_of::<CompositeParams>())?;
+
+  let lut = create_lut(instance, device, data, None)?;
+
+  data.post = PostData {
+    settings: data.post.settings.clone(),
+    sampler,
+    lut,
+    single_layout,
+    composite_layout,
+    single_pipeline_layout,
+    composite_pipeline_layout,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+/// Reloads the color grading LUT when `settings.grade.lut` changed.
+pub unsafe fn sync_post_lut(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  if data.post.settings.grade.lut == data.post.lut_path {
+    return Ok(());
+  }
+
+  let path = data.post.settings.grade.lut.clone();
+  let lut = create_lut(instance, device, data, path.as_ref())?;
+
+  // El set del composite apunta a la LUT anterior, que puede estar en uso.
+  device.device_wait_idle()?;
+  destroy_texture(device, &data.post.lut);
+
+  let post = &mut data.post;
+  post.lut = lut;
+  post.lut_path = path;
+
+  if !post.composite_set.is_null() {
+    write_image_descriptor(device, post.composite_set, 2, post.lut.view, post.sampler);
+  }
+
+  Ok(())
+}
+
+pub unsafe fn create_post_render_pass(
+  device: &Device,
+  format: vk::Format,
+  load_op: vk::AttachmentLoadOp,
+  initial_layout: vk::ImageLayout,
+  final_layout: vk::ImageLayout,
+) -> Result<vk::RenderPass> {
+  let color_attachment = vk::AttachmentDescription::builder()
+    .format(format)
+    .samples(vk::SampleCountFlags::_1)
+    .load_op(load_op)
+    .store_op(vk::AttachmentStoreOp::STORE)
+    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .initial_layout(initial_layout)
+    .final_layout(final_layout);
+
+  let color_attachment_ref = vk::AttachmentReference::builder()
+    .attachment(0)
+    .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+  let color_attachments = &[color_attachment_ref];
+  let subpass = vk::SubpassDescription::builder()
+    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+    .color_attachments(color_attachments);
+
+  // Cada pase lee lo que escribio el anterior, y el frame anterior puede seguir leyendo el target.
+  let before = vk::SubpassDependency::builder()
+    .src_subpass(vk::SUBPASS_EXTERNAL)
+    .dst_subpass(0)
+    .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER)
+    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+    .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::FRAGMENT_SHADER)
+    .dst_access_mask(
+      vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::SHADER_READ,
+    );
+
+  let after = vk::SubpassDependency::builder()
+    .src_subpass(0)
+    .dst_subpass(vk::SUBPASS_EXTERNAL)
+    .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+    .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+    .dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+  let attachments = &[color_attachment];
+  let subpasses = &[subpass];
+  let dependencies = &[before, after];
+  let info = vk::RenderPassCreateInfo::builder()
+    .attachments(attachments)
+    .subpasses(subpasses)
+    .dependencies(dependencies);
+
+  Ok(device.create_render_pass(&info, None)?)
+}
+
+pub unsafe fn create_framebuffer(
+  device: &Device,
+  render_pass: vk::RenderPass,
+  view: vk::ImageView,
+  extent: vk::Extent2D,
+) -> Result<vk::Framebuffer> {
+  let attachments = &[view];
+  let info = vk::FramebufferCreateInfo::builder()
+    .render_pass(render_pass)
+    .attachments(attachments)
+    .width(extent.width)
+    .height(extent.height)
+    .layers(1);
+
+  Ok(device.create_framebuffer(&info, None)?)
+}
+
+unsafe fn create_color_target(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  extent: vk::Extent2D,
+) -> Result<Texture> {
+  let (image, memory) = create_image(
+    instance,
+    device,
+    data,
+    extent.width,
+    extent.height,
+    1,
+    HDR_FORMAT,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  let view = create_image_view(device, image, HDR_FORMAT, vk::ImageAspectFlags::COLOR, 1)?;
+
+  Ok(Texture { image, memory, view })
+}
+
+/// Leaves every bloom mip readable, so the composite pass can bind mip 0 while bloom is off.
+unsafe fn clear_bloom_layout(device: &Device, data: &VulkanAppData, image: vk::Image, mip_levels: u32) -> Result<()> {
+  let command_buffer = begin_single_time_commands(device, data)?;
+
+  let subresource = vk::ImageSubresourceRange::builder()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .base_mip_level(0)
+    .level_count(mip_levels)
+    .base_array_layer(0)
+    .layer_count(1);
+
+  let barrier = vk::ImageMemoryBarrier::builder()
+    .old_layout(vk::ImageLayout::UNDEFINED)
+    .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .image(image)
+    .subresource_range(subresource)
+    .src_access_mask(vk::AccessFlags::empty())
+    .dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    vk::PipelineStageFlags::TOP_OF_PIPE,
+    vk::PipelineStageFlags::FRAGMENT_SHADER,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[] as &[vk::BufferMemoryBarrier],
+    &[barrier],
+  );
+
+  end_single_time_commands(device, data, command_buffer)
+}
+
+/// Scene and intermediate targets, bloom chain, render passes, pipelines and descriptor sets,
+/// all sized after the render extent. Must run before the scene framebuffers are created.
+pub unsafe fn create_post_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let extent = data.render_extent();
+
+  let hdr = create_color_target(instance, device, data, extent)?;
+  let ldr = create_color_target(instance, device, data, extent)?;
+  let viewport = match data.viewport {
+    Some(_) => create_color_target(instance, device, data, extent)?,
+    None => Texture::default(),
+  };
+
+  let bloom_extent = vk::Extent2D {
+    width: (extent.width / 2).max(1),
+    height: (extent.height / 2).max(1),
+  };
+  let mip_levels = BLOOM_MIPS.min(bloom_extent.width.max(bloom_extent.height).ilog2() + 1);
+
+  let (bloom_image, bloom_memory) = create_image(
+    instance,
+    device,
+    data,
+    bloom_extent.width,
+    bloom_extent.height,
+    mip_levels,
+    HDR_FORMAT,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  clear_bloom_layout(device, data, bloom_image, mip_levels)?;
+
+  let read_only = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+  let bloom_down_pass = create_post_render_pass(
+    device,
+    HDR_FORMAT,
+    vk::AttachmentLoadOp::DONT_CARE,
+    vk::ImageLayout::UNDEFINED,
+    read_only,
+  )?;
+  let bloom_up_pass = create_post_render_pass(device, HDR_FORMAT, vk::AttachmentLoadOp::LOAD, read_only, read_only)?;
+  let composite_pass = create_post_render_pass(
+    device,
+    HDR_FORMAT,
+    vk::AttachmentLoadOp::DONT_CARE,
+    vk::ImageLayout::UNDEFINED,
+    read_only,
+  )?;
+
+  // Con viewport el pase final escribe una textura que muestra la UI, no la swapchain.
+  let (present_format, present_layout, present_views) = match data.viewport {
+    Some(_) => (HDR_FORMAT, read_only, vec![viewport.view]),
+    None => (
+      data.swapchain_format,
+      vk::ImageLayout::PRESENT_SRC_KHR,
+      data.swapchain_images_views.clone(),
+    ),
+  };
+  let present_pass = create_post_render_pass(
+    device,
+    present_format,
+    vk::AttachmentLoadOp::DONT_CARE,
+    vk::ImageLayout::UNDEFINED,
+    present_layout,
+  )?;
+
+  let mut bloom_mips = vec![];
+
+  for mip in 0..mip_levels {
+    let extent = vk::Extent2D {
+      width: (bloom_extent.width >> mip).max(1),
+      height: (bloom_extent.height >> mip).max(1),
+    };
+    let view = create_mip_range_view(device, bloom_image, HDR_FORMAT, vk::ImageAspectFlags::COLOR, mip, 1)?;
+    // Down y up tienen el mismo formato y attachments: los framebuffers son compatibles con ambos.
+    let framebuffer = create_framebuffer(device, bloom_down_pass, view, extent)?;
+
+    bloom_mips.push(BloomMip {
+      view,
+      framebuffer,
+      extent,
+      ..Default::default()
+    });
+  }
+
+  let composite_framebuffer = create_framebuffer(device, composite_pass, ldr.view, extent)?;
+  let present_framebuffers = present_views
+    .iter()
+    .map(|v| create_framebuffer(device, present_pass, *v, extent))
+    .collect::<Result<Vec<_>>>()?;
+
+  let post = &data.post;
+  let vert = include_bytes!("./pipe/shader/.tmp/fullscreen/vert.spv");
+  let bloom_down = include_bytes!("./pipe/shader/.tmp/bloom_down/frag.spv");
+  let bloom_up = include_bytes!("./pipe/shader/.tmp/bloom_up/frag.spv");
+  let composite = include_bytes!("./pipe/shader/.tmp/composite/frag.spv");
+  let present = include_bytes!("./pipe/shader/.tmp/final/frag.spv");
+
+  let fullscreen = |frag: &[u8], layout, render_pass, blend| {
+    let mut desc = PipelineDesc::new(&vert[..], frag, layout, render_pass, extent);
+    desc.cull_mode = vk::CullModeFlags::NONE;
+    desc.depth_test = false;
+    desc.depth_write = false;
+    desc.blend = blend;
+    desc.dynamic_viewport = true;
+
+    create_graphics_pipeline(device, &desc)
+  };
+
+  let bloom_down_pipeline = fullscreen(
+    &bloom_down[..],
+    post.single_pipeline_layout,
+    bloom_down_pass,
+    BlendMode::Opaque,
+  )?;
+  let bloom_up_pipeline = fullscreen(
+    &bloom_up[..],
+    post.single_pipeline_layout,
+    bloom_up_pass,
+    BlendMode::Additive,
+  )?;
+  let composite_pipeline = fullscreen(
+    &composite[..],
+    post.composite_pipeline_layout,
+    composite_pass,
+    BlendMode::Opaque,
+  )?;
+  let final_pipeline = fullscreen(
+    &present[..],
+    post.single_pipeline_layout,
+    present_pass,
+    BlendMode::Opaque,
+  )?;
+
+  let single_sets = mip_levels * 2 + 1;
+  let pool_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    .descriptor_count(single_sets + 4);
+
+  let pool_sizes = &[pool_size];
+  let pool_info = vk::DescriptorPoolCreateInfo::builder()
+    .pool_sizes(pool_sizes)
+    .max_sets(single_sets + 1);
+
+  let descriptor_pool = device.create_descriptor_pool(&pool_info, None)?;
+
+  let sampler = post.sampler;
+  let single_sets = allocate_descriptor_sets(device, descriptor_pool, post.single_layout, single_sets as usize)?;
+  let composite_set = allocate_descriptor_sets(device, descriptor_pool, post.composite_layout, 1)?[0];
+  let final_set = single_sets[0];
+
+  for (i, mip) in bloom_mips.iter_mut().enumerate() {
+    mip.down_set = single_sets[1 + i * 2];
+    mip.up_set = single_sets[2 + i * 2];
+  }
+
+  for i in 0..bloom_mips.len() {
+    let source = if i == 0 { hdr.view } else { bloom_mips[i - 1].view };
+    write_image_descriptor(device, bloom_mips[i].down_set, 0, source, sampler);
+
+    if let Some(next) = bloom_mips.get(i + 1) {
+      write_image_descriptor(device, bloom_mips[i].up_set, 0, next.view, sampler);
+    }
+  }
+
+  write_image_descriptor(device, composite_set, 0, hdr.view, sampler);
+  write_image_descriptor(device, composite_set, 1, bloom_mips[0].view, sampler);
+  write_image_descriptor(device, composite_set, 2, post.lut.view, sampler);
+  write_image_descriptor(device, composite_set, 3, data.picking.id_view, data.picking.sampler);
+  write_image_descriptor(device, final_set, 0, ldr.view, sampler);
+
+  let post = &mut data.post;
+  post.descriptor_pool = descriptor_pool;
+  post.hdr = hdr;
+  post.ldr = ldr;
+  post.viewport = viewport;
+  post.bloom_image = bloom_image;
+  post.bloom_memory = bloom_memory;
+  post.bloom_mips = bloom_mips;
+  post.bloom_down_pass = bloom_down_pass;
+  post.bloom_up_pass = bloom_up_pass;
+  post.composite_pass = composite_pass;
+  post.present_pass = present_pass;
+  post.composite_framebuffer = composite_framebuffer;
+  post.present_framebuffers = present_framebuffers;
+  post.bloom_down_pipeline = bloom_down_pipeline;
+  post.bloom_up_pipeline = bloom_up_pipeline;
+  post.composite_pipeline = composite_pipeline;
+  post.final_pipeline = final_pipeline;
+  post.composite_set = composite_set;
+  post.final_set = final_set;
+
+  Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn record_fullscreen_pass(
+  device: &Device,
+  command_buffer: vk::CommandBuffer,
+  render_pass: vk::RenderPass,
+  framebuffer: vk::Framebuffer,
+  extent: vk::Extent2D,
+  pipeline: vk::Pipeline,
+  layout: vk::PipelineLayout,
+  set: vk::DescriptorSet,
+  push_constants: &[u8],
+) {
+  let render_area = vk::Rect2D::builder().offset(vk::Offset2D::default()).extent(extent);
+
+  let info = vk::RenderPassBeginInfo::builder()
+    .render_pass(render_pass)
+    .framebuffer(framebuffer)
+    .render_area(render_area);
+
+  let viewport = vk::Viewport::builder()
+    .x(0.0)
+    .y(0.0)
+    .width(extent.width as f32)
+    .height(extent.height as f32)
+    .min_depth(0.0)
+    .max_depth(1.0);
+
+  device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+  device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+  device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+  device.cmd_bind_descriptor_sets(command_buffer, vk::PipelineBindPoint::GRAPHICS, layout, 0, &[set], &[]);
+  device.cmd_push_constants(
+    command_buffer,
+    layout,
+    vk::ShaderStageFlags::FRAGMENT,
+    0,
+    push_constants,
+  );
+  device.cmd_draw(command_buffer, 3, 1, 0, 0);
+  device.cmd_end_render_pass(command_buffer);
+}
+
+/// Records bloom, composite and the final pass into the swapchain image or the viewport texture;
+/// must run after the scene render pass.
+pub unsafe fn record_post(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let post = &data.post;
+  let settings = &post.settings;
+  // Los modos de depuracion muestran datos: sin exposicion ni efectos.
+  let lit = !data.view_mode.is_debug();
+  let exposure = if lit { settings.exposure_ev.exp2() } else { 1.0 };
+  let output = OutputParams::new(data.display_output, &data.present_config.hdr, exposure);
+
+  if lit && settings.bloom.enabled {
+    let bloom = &settings.bloom;
+
+    for (i, mip) in post.bloom_mips.iter().enumerate() {
+      let prefilter = if i == 0 { 1.0 } else { 0.0 };
+      let params: BloomParams = [bloom.threshold, bloom.knee, prefilter, bloom.radius];
+
+      record_fullscreen_pass(
+        device,
+        command_buffer,
+        post.bloom_down_pass,
+        mip.framebuffer,
+        mip.extent,
+        post.bloom_down_pipeline,
+        post.single_pipeline_layout,
+        mip.down_set,
+        as_bytes(&params),
+      );
+    }
+
+    // Cada mip suma el siguiente, ya difuminado, de menor a mayor resolucion.
+    for mip in post.bloom_mips.iter().rev().skip(1) {
+      let params: BloomParams = [bloom.threshold, bloom.knee, 0.0, bloom.radius];
+
+      record_fullscreen_pass(
+        device,
+        command_buffer,
+        post.bloom_up_pass,
+        mip.framebuffer,
+        mip.extent,
+        post.bloom_up_pipeline,
+        post.single_pipeline_layout,
+        mip.up_set,
+        as_bytes(&params),
+      );
+    }
+  }
+
+  let flag = |enabled: bool| enabled as u32;
+  let composite = CompositeParams {
+    display: output,
+    effects: [
+      if lit { settings.tonemapper } else { Tonemapper::None }.value(),
+      flag(lit && settings.bloom.enabled),
+      flag(lit && settings.vignette.enabled),
+      flag(lit && settings.grade.enabled),
+    ],
+    bloom: [settings.bloom.intensity, 0.0, 0.0, 0.0],
+    vignette: [settings.vignette.intensity, settings.vignette.smoothness, 0.0, 0.0],
+    grade: [settings.grade.strength, LUT_SIZE as f32, 0.0, 0.0],
+    debug: [
+      flag(data.view_mode == ViewMode::Overdraw),
+      flag(data.view_mode != ViewMode::Overdraw),
+      0,
+      0,
+    ],
+  };
+
+  record_fullscreen_pass(
+    device,
+    command_buffer,
+    post.composite_pass,
+    post.composite_framebuffer,
+    data.render_extent(),
+    post.composite_pipeline,
+    post.composite_pipeline_layout,
+    post.composite_set,
+    as_bytes(&composite),
+  );
+
+  // La textura del viewport queda lineal: la UI la codifica al dibujarla.
+  let (framebuffer, display) = match data.viewport {
+    Some(_) => (
+      post.present_framebuffers[0],
+      OutputParams {
+        transfer: DisplayOutput::Linear.transfer(),
+        ..output
+      },
+    ),
+    None => (post.present_framebuffers[image_index], output),
+  };
+
+  let fxaa = &settings.fxaa;
+  let present = FinalParams {
+    display,
+    fxaa: [
+      flag(lit && fxaa.enabled) as f32,
+      fxaa.edge_threshold,
+      fxaa.edge_threshold_min,
+      fxaa.strength,
+    ],
+  };
+
+  record_fullscreen_pass(
+    device,
+    command_buffer,
+    post.present_pass,
+    framebuffer,
+    data.render_extent(),
+    post.final_pipeline,
+    post.single_pipeline_layout,
+    post.final_set,
+    as_bytes(&present),
+  );
+}
+
+pub unsafe fn destroy_post_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  let post = &mut data.post;
+
+  for pipeline in [
+    post.bloom_down_pipeline,
+    post.bloom_up_pipeline,
+    post.composite_pipeline,
+    post.final_pipeline,
+  ] {
+    device.destroy_pipeline(pipeline, None);
+  }
+
+  device.destroy_descriptor_pool(post.descriptor_pool, None);
+  post.composite_set = vk::DescriptorSet::null();
+
+  post
+    .present_framebuffers
+    .iter()
+    .for_each(|f| device.destroy_framebuffer(*f, None));
+  device.destroy_framebuffer(post.composite_framebuffer, None);
+
+  for mip in &post.bloom_mips {
+    device.destroy_framebuffer(mip.framebuffer, None);
+    device.destroy_image_view(mip.view, None);
+  }
+
+  for render_pass in [
+    post.bloom_down_pass,
+    post.bloom_up_pass,
+    post.composite_pass,
+    post.present_pass,
+  ] {
+    device.destroy_render_pass(render_pass, None);
+  }
+
+  device.destroy_image(post.bloom_image, None);
+  device.free_memory(post.bloom_memory, None);
+  destroy_texture(device, &post.viewport);
+  destroy_texture(device, &post.ldr);
+  destroy_texture(device, &post.hdr);
+}
+
+pub unsafe fn destroy_post_system(device: &Device, data: &mut VulkanAppData) {
+  let post = &mut data.post;
+
+  destroy_texture(device, &post.lut);
+  device.destroy_pipeline_layout(post.single_pipeline_layout, None);
+  device.destroy_pipeline_layout(post.composite_pipeline_layout, None);
+  device.destroy_descriptor_set_layout(post.single_layout, None);
+  device.destroy_descriptor_set_layout(post.composite_layout, None);
+  device.destroy_sampler(post.sampler, None);
+}
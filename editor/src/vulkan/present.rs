@@ -0,0 +1,224 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use vulkanalia::vk;
+
+use super::output::HdrConfig;
+
+/// Margen que se deja al final de cada frame para esperar con spin en vez de `thread::sleep`.
+const SPIN_THRESHOLD: Duration = Duration::from_micros(1500);
+
+/// FPS caps the editor cycles through at runtime (`None` means uncapped).
+pub const FPS_CAPS: &[Option<u32>] = &[None, Some(30), Some(60), Some(120), Some(144)];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VsyncMode {
+  Fifo,
+  FifoRelaxed,
+  Mailbox,
+  Immediate,
+}
+
+impl VsyncMode {
+  pub fn present_mode(self) -> vk::PresentModeKHR {
+    match self {
+      Self::Fifo => vk::PresentModeKHR::FIFO,
+      Self::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+      Self::Mailbox => vk::PresentModeKHR::MAILBOX,
+      Self::Immediate => vk::PresentModeKHR::IMMEDIATE,
+    }
+  }
+
+  pub fn next(self) -> Self {
+    match self {
+      Self::Fifo => Self::FifoRelaxed,
+      Self::FifoRelaxed => Self::Mailbox,
+      Self::Mailbox => Self::Immediate,
+      Self::Immediate => Self::Fifo,
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PresentConfig {
+  pub vsync: VsyncMode,
+  pub target_fps: Option<u32>,
+  /// Only render when something changed (input, resize, config) instead of every loop iteration.
+  pub redraw_on_change: bool,
+  pub hdr: HdrConfig,
+}
+
+impl Default for PresentConfig {
+  fn default() -> Self {
+    Self {
+      vsync: VsyncMode::Mailbox,
+      target_fps: None,
+      redraw_on_change: false,
+      hdr: HdrConfig::default(),
+    }
+  }
+}
+
+impl PresentConfig {
+  pub fn next_fps_cap(&self) -> Option<u32> {
+    let index = FPS_CAPS.iter().position(|c| *c == self.target_fps).unwrap_or(0);
+    FPS_CAPS[(index + 1) % FPS_CAPS.len()]
+  }
+}
+
+#[derive(Debug)]
+pub struct FrameLimiter {
+  next_frame: Instant,
+}
+
+impl Default for FrameLimiter {
+  fn default() -> Self {
+    Self {
+      next_frame: Instant::now(),
+    }
+  }
+}
+
+impl FrameLimiter {
+  /// Blocks until the next frame slot for `target_fps`. Sleeps most of the interval and spins the
+  /// remainder, since `thread::sleep` alone overshoots by a scheduler tick on most platforms.
+  pub fn wait(&mut self, target_fps: Option<u32>) {
+    let Some(fps) = target_fps.filter(|f| *f > 0) else {
+      self.next_frame = Instant::now();
+      return;
+    };
+
+    let frame_time = Duration::from_secs_f64(1.0 / fps as f64);
+    let now = Instant::now();
+    let deadline = self.schedule(now, frame_time);
+
+    if let Some(remaining) = deadline.checked_duration_since(now) {
+      if remaining > SPIN_THRESHOLD {
+        thread::sleep(remaining - SPIN_THRESHOLD);
+      }
+
+      while Instant::now() < deadline {
+        std::hint::spin_loop();
+      }
+    }
+  }
+
+  /// When the frame asked for at `now` may start, and books the slot after it.
+  fn schedule(&mut self, now: Instant, frame_time: Duration) -> Instant {
+    // Si nos atrasamos mas de un frame no intentamos recuperar, solo reiniciamos el reloj.
+    if self.next_frame + frame_time < now {
+      self.next_frame = now;
+    }
+
+    let deadline = self.next_frame;
+    self.next_frame += frame_time;
+    deadline
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FRAME: Duration = Duration::from_millis(10);
+
+  fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+  }
+
+  #[test]
+  fn fps_caps_cycle_back_to_uncapped() {
+    let mut config = PresentConfig::default();
+    let mut seen = vec![];
+
+    for _ in 0..FPS_CAPS.len() {
+      config.target_fps = config.next_fps_cap();
+      seen.push(config.target_fps);
+    }
+
+    assert_eq!(seen, [Some(30), Some(60), Some(120), Some(144), None]);
+  }
+
+  #[test]
+  fn unknown_fps_caps_restart_the_cycle() {
+    let config = PresentConfig {
+      target_fps: Some(75),
+      ..Default::default()
+    };
+
+    assert_eq!(config.next_fps_cap(), Some(30));
+  }
+
+  #[test]
+  fn vsync_modes_cycle() {
+    let modes = std::iter::successors(Some(VsyncMode::Fifo), |m| Some(m.next())).take(5);
+
+    assert_eq!(
+      modes.collect::<Vec<_>>(),
+      [
+        VsyncMode::Fifo,
+        VsyncMode::FifoRelaxed,
+        VsyncMode::Mailbox,
+        VsyncMode::Immediate,
+        VsyncMode::Fifo,
+      ]
+    );
+  }
+
+  #[test]
+  fn limiter_books_one_slot_per_frame() {
+    let start = Instant::now();
+    let mut limiter = FrameLimiter { next_frame: start };
+
+    assert_eq!(limiter.schedule(start, FRAME), start);
+    // Un frame rapido espera a su hueco, no a `now + FRAME`.
+    assert_eq!(limiter.schedule(start + ms(3), FRAME), start + FRAME);
+    assert_eq!(limiter.schedule(start + ms(12), FRAME), start + 2 * FRAME);
+    assert_eq!(limiter.next_frame, start + 3 * FRAME);
+  }
+
+  #[test]
+  fn limiter_catches_up_less_than_a_frame_late() {
+    let start = Instant::now();
+    let mut limiter = FrameLimiter { next_frame: start };
+
+    // Tarde, pero menos de un frame: el hueco ya paso y no se espera.
+    assert_eq!(limiter.schedule(start + ms(8), FRAME), start);
+    assert_eq!(limiter.next_frame, start + FRAME);
+  }
+
+  #[test]
+  fn limiter_restarts_the_clock_after_a_stall() {
+    let start = Instant::now();
+    let mut limiter = FrameLimiter { next_frame: start };
+
+    assert_eq!(limiter.schedule(start + ms(50), FRAME), start + ms(50));
+    assert_eq!(limiter.next_frame, start + ms(60));
+  }
+
+  #[test]
+  fn uncapped_waits_return_at_once_and_reset_the_clock() {
+    let mut limiter = FrameLimiter {
+      next_frame: Instant::now() + Duration::from_secs(60),
+    };
+    let before = Instant::now();
+
+    limiter.wait(None);
+    limiter.wait(Some(0));
+
+    assert!(before.elapsed() < Duration::from_secs(1));
+    assert!(limiter.next_frame <= Instant::now());
+  }
+
+  #[test]
+  fn capped_waits_take_at_least_a_frame_each() {
+    let mut limiter = FrameLimiter::default();
+    limiter.wait(Some(500));
+    let start = Instant::now();
+
+    limiter.wait(Some(500));
+    limiter.wait(Some(500));
+
+    assert!(start.elapsed() >= ms(3));
+  }
+}
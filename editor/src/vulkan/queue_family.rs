@@ -11,6 +11,9 @@ use super::{utils::sagitario_error::SuitabilityError, VulkanAppData};
 pub struct QueueFamilyIndices {
   pub graphics: u32,
   pub present: u32,
+  /// Where uploads are copied: a family for transfers only if there's one, so they run beside
+  /// the frame, or else the graphics one.
+  pub transfer: u32,
 }
 
 impl QueueFamilyIndices {
@@ -19,9 +22,20 @@ impl QueueFamilyIndices {
 
     let graphics = properties
       .iter()
-      .position(|p| p.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+      .position(|p| {
+        p.queue_flags
+          .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+      })
       .map(|i| i as u32);
 
+    let transfer = properties
+      .iter()
+      .position(|p| {
+        p.queue_flags.contains(vk::QueueFlags::TRANSFER) && !p.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+      })
+      .map(|i| i as u32)
+      .or(graphics);
+
     let mut present = None;
 
     for (index, _properties) in properties.iter().enumerate() {
@@ -31,8 +45,12 @@ impl QueueFamilyIndices {
       }
     }
 
-    if let (Some(graphics), Some(present)) = (graphics, present) {
-      Ok(Self { graphics, present })
+    if let (Some(graphics), Some(present), Some(transfer)) = (graphics, present, transfer) {
+      Ok(Self {
+        graphics,
+        present,
+        transfer,
+      })
     } else {
       Err(anyhow!(SuitabilityError("Missing required queue families.")))
     }
@@ -0,0 +1,554 @@
+use std::mem::size_of;
+
+use anyhow::{anyhow, Ok, Result};
+use cgmath::{ortho, perspective, vec3, vec4, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use log::warn;
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0},
+  Device, Instance,
+};
+
+use super::buffers::{create_host_buffer, destroy_host_buffer, write_memory, HostBuffer};
+use super::geometry::{vertex_attribute_descriptions, vertex_binding_descriptions};
+use super::images::{create_image, create_image_view};
+use super::instancing::{instance_set, record_instance_shadow_draws};
+use super::pipe::{create_graphics_pipeline, PipelineDesc};
+use super::utils::bytes::as_bytes;
+use super::VulkanAppData;
+use crate::scene::camera::{Camera, VULKAN_CLIP_CORRECTION};
+use crate::scene::light::{Light, LightKind, MAX_CASCADES};
+
+pub const SHADOW_ATLAS_SIZE: u32 = 4096;
+pub const MAX_SHADOW_VIEWS: usize = 64;
+/// Extra depth behind each cascade so casters outside the camera frustum still cast.
+const CASTER_MARGIN: f32 = 50.0;
+const SHADOW_NEAR: f32 = 0.05;
+
+const SHADOW_FORMATS: &[vk::Format] = &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM];
+
+/// std430 layout of `ShadowView` in `common/shadows.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct GpuShadowView {
+  view_proj: Matrix4<f32>,
+  /// xy: offset, zw: size, in atlas UV.
+  rect: [f32; 4],
+  /// x: normal bias, y: PCF radius in texels, z: cascade far distance in view depth.
+  params: [f32; 4],
+}
+
+/// Square tile of the shadow atlas, in texels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtlasRect {
+  pub x: u32,
+  pub y: u32,
+  pub size: u32,
+}
+
+/// Shelf packer that hands out atlas tiles row by row.
+#[derive(Copy, Clone, Debug, Default)]
+struct ShelfPacker {
+  x: u32,
+  y: u32,
+  shelf_height: u32,
+}
+
+impl ShelfPacker {
+  fn allocate(&mut self, size: u32) -> Option<AtlasRect> {
+    if self.x + size > SHADOW_ATLAS_SIZE {
+      self.x = 0;
+      self.y += self.shelf_height;
+      self.shelf_height = 0;
+    }
+
+    if self.y + size > SHADOW_ATLAS_SIZE {
+      return None;
+    }
+
+    let rect = AtlasRect {
+      x: self.x,
+      y: self.y,
+      size,
+    };
+
+    self.x += size;
+    self.shelf_height = self.shelf_height.max(size);
+
+    Some(rect)
+  }
+}
+
+/// One depth render into the atlas.
+#[derive(Copy, Clone, Debug)]
+struct ShadowView {
+  rect: AtlasRect,
+  depth_bias: f32,
+  slope_bias: f32,
+  gpu: GpuShadowView,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ShadowFrame {
+  views: HostBuffer,
+  cpu_views: Vec<ShadowView>,
+}
+
+/// Shadow maps of every light packed in one depth atlas: cascades for directional lights, six
+/// faces for point lights and one map for spot lights.
+#[derive(Clone, Debug, Default)]
+pub struct ShadowData {
+  atlas: vk::Image,
+  atlas_memory: vk::DeviceMemory,
+  pub atlas_view: vk::ImageView,
+  pub sampler: vk::Sampler,
+  render_pass: vk::RenderPass,
+  framebuffer: vk::Framebuffer,
+  pipeline_layout: vk::PipelineLayout,
+  pipeline: vk::Pipeline,
+  frames: Vec<ShadowFrame>,
+}
+
+unsafe fn get_shadow_format(instance: &Instance, data: &VulkanAppData) -> Result<vk::Format> {
+  let features = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+
+  SHADOW_FORMATS
+    .iter()
+    .cloned()
+    .find(|f| {
+      let properties = instance.get_physical_device_format_properties(data.physical_device, *f);
+      properties.optimal_tiling_features.contains(features)
+    })
+    .ok_or_else(|| anyhow!("Failed to find supported shadow map format."))
+}
+
+unsafe fn create_shadow_render_pass(device: &Device, format: vk::Format) -> Result<vk::RenderPass> {
+  let depth_attachment = vk::AttachmentDescription::builder()
+    .format(format)
+    .samples(vk::SampleCountFlags::_1)
+    .load_op(vk::AttachmentLoadOp::CLEAR)
+    .store_op(vk::AttachmentStoreOp::STORE)
+    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+  let depth_attachment_ref = vk::AttachmentReference::builder()
+    .attachment(0)
+    .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+  let subpass = vk::SubpassDescription::builder()
+    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+    .depth_stencil_attachment(&depth_attachment_ref);
+
+  // El atlas se comparte entre frames: el anterior puede seguir muestreandolo.
+  let before = vk::SubpassDependency::builder()
+    .src_subpass(vk::SUBPASS_EXTERNAL)
+    .dst_subpass(0)
+    .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+    .src_access_mask(vk::AccessFlags::SHADER_READ)
+    .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+    .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);
+
+  let after = vk::SubpassDependency::builder()
+    .src_subpass(0)
+    .dst_subpass(vk::SUBPASS_EXTERNAL)
+    .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+    .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+    .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+    .dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+  let attachments = &[depth_attachment];
+  let subpasses = &[subpass];
+  let dependencies = &[before, after];
+  let info = vk::RenderPassCreateInfo::builder()
+    .attachments(attachments)
+    .subpasses(subpasses)
+    .dependencies(dependencies);
+
+  Ok(device.create_render_pass(&info, None)?)
+}
+
+/// Atlas, comparison sampler, depth-only render pass and pipeline; they don't depend on the swapchain.
+pub unsafe fn create_shadow_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let format = get_shadow_format(instance, data)?;
+
+  let (atlas, atlas_memory) = create_image(
+    instance,
+    device,
+    data,
+    SHADOW_ATLAS_SIZE,
+    SHADOW_ATLAS_SIZE,
+    1,
+    format,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  let atlas_view = create_image_view(device, atlas, format, vk::ImageAspectFlags::DEPTH, 1)?;
+
+  let sampler_info = vk::SamplerCreateInfo::builder()
+    .mag_filter(vk::Filter::LINEAR)
+    .min_filter(vk::Filter::LINEAR)
+    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+    .compare_enable(true)
+    .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+    .max_lod(0.0);
+
+  let sampler = device.create_sampler(&sampler_info, None)?;
+
+  let render_pass = create_shadow_render_pass(device, format)?;
+
+  let attachments = &[atlas_view];
+  let framebuffer_info = vk::FramebufferCreateInfo::builder()
+    .render_pass(render_pass)
+    .attachments(attachments)
+    .width(SHADOW_ATLAS_SIZE)
+    .height(SHADOW_ATLAS_SIZE)
+    .layers(1);
+
+  let framebuffer = device.create_framebuffer(&framebuffer_info, None)?;
+
+  let view_proj_range = vk::PushConstantRange::builder()
+    .stage_flags(vk::ShaderStageFlags::VERTEX)
+    .offset(0)
+    .size(size_of::<Matrix4<f32>>() as u32);
+
+  let set_layouts = &[data.frame_set_layout, data.instancing.set_layout];
+  let push_constant_ranges = &[view_proj_range];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder()
+    .set_layouts(set_layouts)
+    .push_constant_ranges(push_constant_ranges);
+
+  let pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+
+  let vert = include_bytes!("./pipe/shader/.tmp/shadow/vert.spv");
+  let vertex_bindings = vertex_binding_descriptions();
+  let vertex_attributes = vertex_attribute_descriptions();
+
+  let extent = vk::Extent2D {
+    width: SHADOW_ATLAS_SIZE,
+    height: SHADOW_ATLAS_SIZE,
+  };
+
+  let mut desc = PipelineDesc::new(&vert[..], &[], pipeline_layout, render_pass, extent);
+  desc.vertex_bindings = &vertex_bindings;
+  desc.vertex_attributes = &vertex_attributes;
+  desc.cull_mode = vk::CullModeFlags::NONE;
+  desc.depth_only = true;
+  desc.depth_bias = true;
+  desc.dynamic_viewport = true;
+
+  let pipeline = create_graphics_pipeline(device, &desc)?;
+
+  data.shadows = ShadowData {
+    atlas,
+    atlas_memory,
+    atlas_view,
+    sampler,
+    render_pass,
+    framebuffer,
+    pipeline_layout,
+    pipeline,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+pub unsafe fn create_shadow_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let size = MAX_SHADOW_VIEWS * size_of::<GpuShadowView>();
+
+  data.shadows.frames = (0..data.swapchain_images.len())
+    .map(|_| {
+      let views = create_host_buffer(instance, device, data, size, vk::BufferUsageFlags::STORAGE_BUFFER)?;
+      Ok(ShadowFrame {
+        views,
+        ..Default::default()
+      })
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+  Ok(())
+}
+
+pub fn shadow_views_buffer(data: &VulkanAppData, image_index: usize) -> (vk::Buffer, vk::DeviceSize) {
+  let size = MAX_SHADOW_VIEWS * size_of::<GpuShadowView>();
+  (data.shadows.frames[image_index].views.buffer, size as vk::DeviceSize)
+}
+
+fn light_up(direction: Vector3<f32>) -> Vector3<f32> {
+  if direction.y.abs() > 0.99 {
+    vec3(0.0, 0.0, 1.0)
+  } else {
+    vec3(0.0, 1.0, 0.0)
+  }
+}
+
+/// View-projections of the cascades of a directional light, each with the view depth it ends at.
+fn cascade_matrices(light: &Light, camera: &Camera, aspect: f32) -> Vec<(Matrix4<f32>, f32)> {
+  let settings = light.shadows;
+  let count = settings.cascades.clamp(1, MAX_CASCADES);
+  let near = camera.near;
+  let far = camera.far.min(settings.max_distance).max(near + 0.1);
+  let direction = light.direction.normalize();
+
+  let mut previous = near;
+  let mut cascades = vec![];
+
+  for i in 1..=count {
+    let p = i as f32 / count as f32;
+    let logarithmic = near * (far / near).powf(p);
+    let uniform = near + (far - near) * p;
+    let split = settings.split_lambda * logarithmic + (1.0 - settings.split_lambda) * uniform;
+
+    let projection = camera.projection_range(aspect, previous, split);
+    let inverse = (projection * camera.view()).invert().unwrap_or_else(Matrix4::identity);
+
+    let corners = [
+      (-1.0, -1.0, 0.0),
+      (1.0, -1.0, 0.0),
+      (-1.0, 1.0, 0.0),
+      (1.0, 1.0, 0.0),
+      (-1.0, -1.0, 1.0),
+      (1.0, -1.0, 1.0),
+      (-1.0, 1.0, 1.0),
+      (1.0, 1.0, 1.0),
+    ]
+    .map(|(x, y, z)| {
+      let p = inverse * vec4(x, y, z, 1.0);
+      Point3::from_homogeneous(p)
+    });
+
+    let center = Point3::centroid(&corners);
+    let radius = corners.iter().map(|c| (c - center).magnitude()).fold(0.0, f32::max);
+    // Radio estable para que las cascadas no tiemblen al girar la camara.
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let eye = center - direction * (radius + CASTER_MARGIN);
+    let view = Matrix4::look_at_rh(eye, center, light_up(direction));
+    let mut projection =
+      VULKAN_CLIP_CORRECTION * ortho(-radius, radius, -radius, radius, 0.0, radius * 2.0 + CASTER_MARGIN);
+
+    // Snap al texel para evitar shimmering al mover la camara.
+    let texels = settings.resolution as f32 * 0.5;
+    let origin = (projection * view) * vec4(0.0, 0.0, 0.0, 1.0);
+    let offset_x = ((origin.x * texels).round() - origin.x * texels) / texels;
+    let offset_y = ((origin.y * texels).round() - origin.y * texels) / texels;
+    projection.w.x += offset_x;
+    projection.w.y += offset_y;
+
+    cascades.push((projection * view, split));
+    previous = split;
+  }
+
+  cascades
+}
+
+/// View-projections of the six faces of a point light: +X, -X, +Y, -Y, +Z, -Z.
+fn cube_face_matrices(light: &Light) -> Vec<(Matrix4<f32>, f32)> {
+  let projection =
+    VULKAN_CLIP_CORRECTION * perspective(Deg(90.0), 1.0, SHADOW_NEAR, light.range.max(SHADOW_NEAR * 2.0));
+
+  [
+    (vec3(1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0)),
+    (vec3(-1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0)),
+    (vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0)),
+    (vec3(0.0, -1.0, 0.0), vec3(0.0, 0.0, -1.0)),
+    (vec3(0.0, 0.0, 1.0), vec3(0.0, -1.0, 0.0)),
+    (vec3(0.0, 0.0, -1.0), vec3(0.0, -1.0, 0.0)),
+  ]
+  .iter()
+  .map(|(forward, up)| {
+    let view = Matrix4::look_at_rh(light.position, light.position + forward, *up);
+    (projection * view, 0.0)
+  })
+  .collect()
+}
+
+/// View-projection of a spot light's shadow, covering its outer cone up to `range`.
+pub fn spot_matrix(light: &Light, outer_angle: Deg<f32>) -> (Matrix4<f32>, f32) {
+  let direction = light.direction.normalize();
+  let fov = Deg((outer_angle.0 * 2.0).clamp(1.0, 170.0));
+  let projection = VULKAN_CLIP_CORRECTION * perspective(fov, 1.0, SHADOW_NEAR, light.range.max(SHADOW_NEAR * 2.0));
+  let view = Matrix4::look_at_rh(light.position, light.position + direction, light_up(direction));
+
+  (projection * view, 0.0)
+}
+
+/// Places the shadow views of every shadowed light in the atlas and uploads them for
+/// `image_index`. Returns the `(first view, view count)` of each light in `lights`.
+pub unsafe fn update_shadows(
+  device: &Device,
+  data: &mut VulkanAppData,
+  image_index: usize,
+  lights: &[&Light],
+  camera: &Camera,
+) -> Result<Vec<(u32, u32)>> {
+  let extent = data.render_extent();
+  let aspect = extent.width as f32 / extent.height.max(1) as f32;
+
+  let mut packer = ShelfPacker::default();
+  let mut views: Vec<ShadowView> = vec![];
+  let mut ranges = vec![(0, 0); lights.len()];
+
+  for (i, light) in lights.iter().enumerate() {
+    let settings = light.shadows;
+
+    if !light.enabled || !settings.enabled {
+      continue;
+    }
+
+    let matrices = match light.kind {
+      LightKind::Directional => cascade_matrices(light, camera, aspect),
+      LightKind::Point => cube_face_matrices(light),
+      LightKind::Spot { outer_angle, .. } => vec![spot_matrix(light, outer_angle)],
+    };
+
+    // Todas las vistas de la luz o ninguna.
+    let mut trial = packer;
+    let resolution = settings.resolution.clamp(16, SHADOW_ATLAS_SIZE);
+    let rects = matrices
+      .iter()
+      .map(|_| trial.allocate(resolution))
+      .collect::<Option<Vec<_>>>();
+
+    let Some(rects) = rects.filter(|_| views.len() + matrices.len() <= MAX_SHADOW_VIEWS) else {
+      warn!("The shadow atlas is full, `{}` doesn't cast shadows.", light.name);
+      continue;
+    };
+
+    packer = trial;
+    ranges[i] = (views.len() as u32, matrices.len() as u32);
+
+    for ((view_proj, split), rect) in matrices.into_iter().zip(rects) {
+      let atlas = SHADOW_ATLAS_SIZE as f32;
+
+      views.push(ShadowView {
+        rect,
+        depth_bias: settings.depth_bias,
+        slope_bias: settings.slope_bias,
+        gpu: GpuShadowView {
+          view_proj,
+          rect: [
+            rect.x as f32 / atlas,
+            rect.y as f32 / atlas,
+            rect.size as f32 / atlas,
+            rect.size as f32 / atlas,
+          ],
+          params: [settings.normal_bias, settings.pcf_radius as f32, split, 0.0],
+        },
+      });
+    }
+  }
+
+  let frame = &mut data.shadows.frames[image_index];
+  let gpu_views = views.iter().map(|v| v.gpu).collect::<Vec<_>>();
+
+  write_memory(device, frame.views.memory, &gpu_views)?;
+  frame.cpu_views = views;
+
+  Ok(ranges)
+}
+
+/// Renders every shadow view into its atlas tile; must be outside of a render pass.
+pub unsafe fn record_shadow_pass(
+  device: &Device,
+  data: &VulkanAppData,
+  command_buffer: vk::CommandBuffer,
+  image_index: usize,
+) {
+  let shadows = &data.shadows;
+  let frame = &shadows.frames[image_index];
+
+  let render_area = vk::Rect2D::builder().extent(vk::Extent2D {
+    width: SHADOW_ATLAS_SIZE,
+    height: SHADOW_ATLAS_SIZE,
+  });
+
+  let depth_clear_value = vk::ClearValue {
+    depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+  };
+
+  let clear_values = &[depth_clear_value];
+  let info = vk::RenderPassBeginInfo::builder()
+    .render_pass(shadows.render_pass)
+    .framebuffer(shadows.framebuffer)
+    .render_area(render_area)
+    .clear_values(clear_values);
+
+  device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, shadows.pipeline);
+  device.cmd_bind_descriptor_sets(
+    command_buffer,
+    vk::PipelineBindPoint::GRAPHICS,
+    shadows.pipeline_layout,
+    0,
+    &[data.frame_descriptor_sets[image_index], instance_set(data, image_index)],
+    &[],
+  );
+
+  for view in &frame.cpu_views {
+    let viewport = vk::Viewport::builder()
+      .x(view.rect.x as f32)
+      .y(view.rect.y as f32)
+      .width(view.rect.size as f32)
+      .height(view.rect.size as f32)
+      .min_depth(0.0)
+      .max_depth(1.0);
+
+    let scissor = vk::Rect2D::builder()
+      .offset(vk::Offset2D {
+        x: view.rect.x as i32,
+        y: view.rect.y as i32,
+      })
+      .extent(vk::Extent2D {
+        width: view.rect.size,
+        height: view.rect.size,
+      });
+
+    device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+    device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+    device.cmd_set_depth_bias(command_buffer, view.depth_bias, 0.0, view.slope_bias);
+    device.cmd_push_constants(
+      command_buffer,
+      shadows.pipeline_layout,
+      vk::ShaderStageFlags::VERTEX,
+      0,
+      as_bytes(&view.gpu.view_proj),
+    );
+
+    record_instance_shadow_draws(device, data, command_buffer, image_index);
+  }
+
+  device.cmd_end_render_pass(command_buffer);
+}
+
+pub unsafe fn destroy_shadow_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  data
+    .shadows
+    .frames
+    .iter()
+    .for_each(|f| destroy_host_buffer(device, &f.views));
+}
+
+pub unsafe fn destroy_shadow_system(device: &Device, data: &mut VulkanAppData) {
+  let shadows = &mut data.shadows;
+
+  device.destroy_pipeline(shadows.pipeline, None);
+  device.destroy_pipeline_layout(shadows.pipeline_layout, None);
+  device.destroy_framebuffer(shadows.framebuffer, None);
+  device.destroy_render_pass(shadows.render_pass, None);
+  device.destroy_sampler(shadows.sampler, None);
+  device.destroy_image_view(shadows.atlas_view, None);
+  device.destroy_image(shadows.atlas, None);
+  device.free_memory(shadows.atlas_memory, None);
+}
@@ -1,4 +1,5 @@
 use anyhow::{Ok, Result};
+use log::{info, warn};
 use vulkanalia::{
   vk::{self, DeviceV1_0, HasBuilder, KhrSurfaceExtension, KhrSwapchainExtension},
   Device, Instance,
@@ -6,7 +7,8 @@ use vulkanalia::{
 use vulkanalia_sys::Handle;
 use winit::window::Window;
 
-use super::{queue_family::QueueFamilyIndices, VulkanAppData};
+use super::output::{select_surface_format, DisplayOutput};
+use super::{present::VsyncMode, queue_family::QueueFamilyIndices, VulkanAppData};
 
 #[derive(Clone, Debug)]
 pub struct SwapchainSupport {
@@ -34,11 +36,21 @@ pub unsafe fn create_swapchain(
   let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
   let support = SwapchainSupport::get(instance, data, data.physical_device)?;
 
-  let surface_format = get_swapchain_surface_format(&support.formats);
-  let present_mode = get_swapchain_present_mode(&support.present_modes);
+  let (surface_format, display_output) = get_swapchain_surface_format(&support.formats, data);
+  let present_mode = get_swapchain_present_mode(&support.present_modes, data.present_config.vsync);
   let extent = get_swapchain_extent(window, support.capabilities);
 
+  info!(
+    "[+] create_swapchain -> {:?} {:?} {:?} {:?}",
+    present_mode, surface_format.format, surface_format.color_space, extent
+  );
+
   data.swapchain_format = surface_format.format;
+  data.display_output = display_output;
+
+  if display_output.is_hdr() {
+    info!("[+] create_swapchain -> HDR output ({:?})", display_output);
+  }
   data.swapchain_extent = extent;
 
   let mut image_count = support.capabilities.min_image_count + 1;
@@ -57,6 +69,17 @@ pub unsafe fn create_swapchain(
     vk::SharingMode::EXCLUSIVE
   };
 
+  // TRANSFER_SRC permite leer la imagen presentada (capturas de pantalla).
+  let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+  data.swapchain_capturable = support
+    .capabilities
+    .supported_usage_flags
+    .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+
+  if data.swapchain_capturable {
+    image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+  }
+
   let info = vk::SwapchainCreateInfoKHR::builder()
     .surface(data.surface)
     .min_image_count(image_count)
@@ -64,7 +87,7 @@ pub unsafe fn create_swapchain(
     .image_color_space(surface_format.color_space)
     .image_extent(extent)
     .image_array_layers(1)
-    .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+    .image_usage(image_usage)
     .image_sharing_mode(image_sharing_mode)
     .queue_family_indices(&queue_family_indices)
     .pre_transform(support.capabilities.current_transform)
@@ -79,20 +102,26 @@ pub unsafe fn create_swapchain(
   Ok(())
 }
 
-pub fn get_swapchain_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
-  formats
-    .iter()
-    .cloned()
-    .find(|f| f.format == vk::Format::B8G8R8A8_SRGB && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
-    .unwrap_or_else(|| formats[0])
+pub fn get_swapchain_surface_format(
+  formats: &[vk::SurfaceFormatKHR],
+  data: &VulkanAppData,
+) -> (vk::SurfaceFormatKHR, DisplayOutput) {
+  select_surface_format(formats, data.present_config.hdr.mode, data.swapchain_colorspace_ext)
 }
 
-pub fn get_swapchain_present_mode(present_modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
-  present_modes
-    .iter()
-    .cloned()
-    .find(|m| *m == vk::PresentModeKHR::MAILBOX)
-    .unwrap_or(vk::PresentModeKHR::FIFO)
+/// Picks the present mode requested by `vsync`, falling back to FIFO (the only one the spec guarantees).
+pub fn get_swapchain_present_mode(present_modes: &[vk::PresentModeKHR], vsync: VsyncMode) -> vk::PresentModeKHR {
+  let preferred = vsync.present_mode();
+
+  if present_modes.contains(&preferred) {
+    preferred
+  } else {
+    warn!(
+      "Present mode {:?} not supported by the surface, falling back to FIFO.",
+      preferred
+    );
+    vk::PresentModeKHR::FIFO
+  }
 }
 
 pub fn get_swapchain_extent(window: &Window, capabilities: vk::SurfaceCapabilitiesKHR) -> vk::Extent2D {
@@ -144,3 +173,43 @@ pub unsafe fn create_swapchain_image_views(device: &Device, data: &mut VulkanApp
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ALL: [VsyncMode; 4] = [
+    VsyncMode::Fifo,
+    VsyncMode::FifoRelaxed,
+    VsyncMode::Mailbox,
+    VsyncMode::Immediate,
+  ];
+
+  #[test]
+  fn uses_the_requested_mode_when_supported() {
+    let supported = ALL.map(|v| v.present_mode());
+
+    for vsync in ALL {
+      assert_eq!(get_swapchain_present_mode(&supported, vsync), vsync.present_mode());
+    }
+  }
+
+  #[test]
+  fn falls_back_to_fifo() {
+    let only_fifo = [vk::PresentModeKHR::FIFO];
+    let no_mailbox = [vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::FIFO];
+
+    for vsync in ALL {
+      assert_eq!(get_swapchain_present_mode(&only_fifo, vsync), vk::PresentModeKHR::FIFO);
+    }
+
+    assert_eq!(
+      get_swapchain_present_mode(&no_mailbox, VsyncMode::Mailbox),
+      vk::PresentModeKHR::FIFO
+    );
+    assert_eq!(
+      get_swapchain_present_mode(&no_mailbox, VsyncMode::Immediate),
+      vk::PresentModeKHR::IMMEDIATE
+    );
+  }
+}
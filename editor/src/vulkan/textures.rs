@@ -0,0 +1,282 @@
+use std::path::Path;
+
+use anyhow::{Ok, Result};
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0},
+  Device, Instance,
+};
+
+use super::buffers::{begin_single_time_commands, create_buffer, end_single_time_commands, write_memory};
+use super::images::{create_image, create_image_view};
+use super::VulkanAppData;
+
+/// A sampled 2D image with its full mip chain.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Texture {
+  pub image: vk::Image,
+  pub memory: vk::DeviceMemory,
+  pub view: vk::ImageView,
+}
+
+/// Decodes any format supported by the `image` crate into tightly packed RGBA8.
+pub fn load_rgba8(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+  let image = image::open(path)?.to_rgba8();
+
+  Ok((image.width(), image.height(), image.into_raw()))
+}
+
+/// Uploads RGBA8 `pixels` and generates the mipmaps with linear blits when the format allows it.
+pub unsafe fn create_texture(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  width: u32,
+  height: u32,
+  format: vk::Format,
+  pixels: &[u8],
+) -> Result<Texture> {
+  let properties = instance.get_physical_device_format_properties(data.physical_device, format);
+  let linear_blit = properties
+    .optimal_tiling_features
+    .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
+
+  let mip_levels = if linear_blit {
+    width.max(height).max(1).ilog2() + 1
+  } else {
+    1
+  };
+
+  let (staging_buffer, staging_buffer_memory) = create_buffer(
+    instance,
+    device,
+    data,
+    pixels.len() as vk::DeviceSize,
+    vk::BufferUsageFlags::TRANSFER_SRC,
+    vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+  )?;
+
+  write_memory(device, staging_buffer_memory, pixels)?;
+
+  let (image, memory) = create_image(
+    instance,
+    device,
+    data,
+    width,
+    height,
+    mip_levels,
+    format,
+    vk::ImageTiling::OPTIMAL,
+    vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::TRANSFER_SRC,
+    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+  )?;
+
+  let command_buffer = begin_single_time_commands(device, data)?;
+
+  let subresource_range = |base_mip_level: u32, level_count: u32| vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level,
+    level_count,
+    base_array_layer: 0,
+    layer_count: 1,
+  };
+
+  let barrier = |mip: u32, levels: u32, old: vk::ImageLayout, new: vk::ImageLayout| {
+    let (src_access_mask, dst_access_mask) = match (old, new) {
+      (vk::ImageLayout::UNDEFINED, _) => (vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE),
+      (_, vk::ImageLayout::TRANSFER_SRC_OPTIMAL) => (vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::TRANSFER_READ),
+      (vk::ImageLayout::TRANSFER_SRC_OPTIMAL, _) => (vk::AccessFlags::TRANSFER_READ, vk::AccessFlags::SHADER_READ),
+      _ => (vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::SHADER_READ),
+    };
+
+    vk::ImageMemoryBarrier::builder()
+      .old_layout(old)
+      .new_layout(new)
+      .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+      .image(image)
+      .subresource_range(subresource_range(mip, levels))
+      .src_access_mask(src_access_mask)
+      .dst_access_mask(dst_access_mask)
+  };
+
+  let pipeline_barrier =
+    |src: vk::PipelineStageFlags, dst: vk::PipelineStageFlags, b: vk::ImageMemoryBarrierBuilder| {
+      device.cmd_pipeline_barrier(
+        command_buffer,
+        src,
+        dst,
+        vk::DependencyFlags::empty(),
+        &[] as &[vk::MemoryBarrier],
+        &[] as &[vk::BufferMemoryBarrier],
+        &[b],
+      );
+    };
+
+  pipeline_barrier(
+    vk::PipelineStageFlags::TOP_OF_PIPE,
+    vk::PipelineStageFlags::TRANSFER,
+    barrier(
+      0,
+      mip_levels,
+      vk::ImageLayout::UNDEFINED,
+      vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+    ),
+  );
+
+  let subresource = vk::ImageSubresourceLayers::builder()
+    .aspect_mask(vk::ImageAspectFlags::COLOR)
+    .mip_level(0)
+    .base_array_layer(0)
+    .layer_count(1);
+
+  let region = vk::BufferImageCopy::builder()
+    .buffer_offset(0)
+    .buffer_row_length(0)
+    .buffer_image_height(0)
+    .image_subresource(subresource)
+    .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+    .image_extent(vk::Extent3D {
+      width,
+      height,
+      depth: 1,
+    });
+
+  device.cmd_copy_buffer_to_image(
+    command_buffer,
+    staging_buffer,
+    image,
+    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+    &[region],
+  );
+
+  // Cada nivel se genera a partir del anterior, que pasa a TRANSFER_SRC y despues a lectura.
+  let (mut mip_width, mut mip_height) = (width as i32, height as i32);
+
+  for mip in 1..mip_levels {
+    pipeline_barrier(
+      vk::PipelineStageFlags::TRANSFER,
+      vk::PipelineStageFlags::TRANSFER,
+      barrier(
+        mip - 1,
+        1,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      ),
+    );
+
+    let next_width = (mip_width / 2).max(1);
+    let next_height = (mip_height / 2).max(1);
+
+    let layers = |mip_level: u32| vk::ImageSubresourceLayers {
+      aspect_mask: vk::ImageAspectFlags::COLOR,
+      mip_level,
+      base_array_layer: 0,
+      layer_count: 1,
+    };
+
+    let blit = vk::ImageBlit::builder()
+      .src_offsets([
+        vk::Offset3D { x: 0, y: 0, z: 0 },
+        vk::Offset3D {
+          x: mip_width,
+          y: mip_height,
+          z: 1,
+        },
+      ])
+      .src_subresource(layers(mip - 1))
+      .dst_offsets([
+        vk::Offset3D { x: 0, y: 0, z: 0 },
+        vk::Offset3D {
+          x: next_width,
+          y: next_height,
+          z: 1,
+        },
+      ])
+      .dst_subresource(layers(mip));
+
+    device.cmd_blit_image(
+      command_buffer,
+      image,
+      vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+      image,
+      vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+      &[blit],
+      vk::Filter::LINEAR,
+    );
+
+    pipeline_barrier(
+      vk::PipelineStageFlags::TRANSFER,
+      vk::PipelineStageFlags::FRAGMENT_SHADER,
+      barrier(
+        mip - 1,
+        1,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+      ),
+    );
+
+    mip_width = next_width;
+    mip_height = next_height;
+  }
+
+  pipeline_barrier(
+    vk::PipelineStageFlags::TRANSFER,
+    vk::PipelineStageFlags::FRAGMENT_SHADER,
+    barrier(
+      mip_levels - 1,
+      1,
+      vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+      vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    ),
+  );
+
+  end_single_time_commands(device, data, command_buffer)?;
+
+  device.destroy_buffer(staging_buffer, None);
+  device.free_memory(staging_buffer_memory, None);
+
+  let view = create_image_view(device, image, format, vk::ImageAspectFlags::COLOR, mip_levels)?;
+
+  Ok(Texture { image, memory, view })
+}
+
+/// 1x1 texture filled with `color`, used for material maps that aren't assigned.
+pub unsafe fn create_solid_texture(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  format: vk::Format,
+  color: [u8; 4],
+) -> Result<Texture> {
+  create_texture(instance, device, data, 1, 1, format, &color)
+}
+
+pub unsafe fn create_sampler(instance: &Instance, device: &Device, data: &VulkanAppData) -> Result<vk::Sampler> {
+  let limits = instance.get_physical_device_properties(data.physical_device).limits;
+  let anisotropy = data.features.sampler_anisotropy == vk::TRUE;
+
+  let info = vk::SamplerCreateInfo::builder()
+    .mag_filter(vk::Filter::LINEAR)
+    .min_filter(vk::Filter::LINEAR)
+    .address_mode_u(vk::SamplerAddressMode::REPEAT)
+    .address_mode_v(vk::SamplerAddressMode::REPEAT)
+    .address_mode_w(vk::SamplerAddressMode::REPEAT)
+    .anisotropy_enable(anisotropy)
+    .max_anisotropy(limits.max_sampler_anisotropy.min(16.0))
+    .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+    .unnormalized_coordinates(false)
+    .compare_enable(false)
+    .compare_op(vk::CompareOp::ALWAYS)
+    .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+    .min_lod(0.0)
+    .max_lod(vk::LOD_CLAMP_NONE)
+    .mip_lod_bias(0.0);
+
+  Ok(device.create_sampler(&info, None)?)
+}
+
+pub unsafe fn destroy_texture(device: &Device, texture: &Texture) {
+  device.destroy_image_view(texture.view, None);
+  device.destroy_image(texture.image, None);
+  device.free_memory(texture.memory, None);
+}
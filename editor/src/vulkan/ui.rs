@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+use std::mem::{offset_of, size_of};
+
+use anyhow::{Ok, Result};
+use egui::epaint::{Primitive, Vertex};
+use egui::{ImageData, TextureId, TexturesDelta};
+use log::info;
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder, InstanceV1_0},
+  Device, Instance,
+};
+use vulkanalia_sys::Handle;
+
+use super::buffers::{create_host_buffer, destroy_host_buffer, write_memory, HostBuffer};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_image_descriptor,
+};
+use super::output::OutputParams;
+use super::pipe::{create_graphics_pipeline, BlendMode, PipelineDesc};
+use super::post::{create_framebuffer, create_post_render_pass};
+use super::textures::{create_texture, destroy_texture, Texture};
+use super::utils::bytes::as_bytes;
+use super::VulkanAppData;
+use crate::ui::{UiOutput, VIEWPORT_TEXTURE};
+
+/// Vertices and indices the buffers of each swapchain image start with; they grow when a frame
+/// needs more.
+const INITIAL_UI_VERTICES: usize = 1 << 14;
+const INITIAL_UI_INDICES: usize = 1 << 16;
+/// Textures the UI can hold at once, the font atlas included.
+const MAX_UI_TEXTURES: u32 = 64;
+
+/// Push constants of `ui/shader.vert` and `ui/shader.frag`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct UiParams {
+  display: OutputParams,
+  /// xy: screen size in points, z: 1 if the texture holds display-linear color.
+  screen: [f32; 4],
+}
+
+/// An egui texture, with the pixels kept to patch partial updates in.
+#[derive(Clone, Debug)]
+struct UiTexture {
+  texture: Texture,
+  set: vk::DescriptorSet,
+  size: [usize; 2],
+  pixels: Vec<u8>,
+}
+
+/// A mesh of the draw list, clipped with the scissor.
+#[derive(Copy, Clone, Debug)]
+struct UiDraw {
+  scissor: vk::Rect2D,
+  set: vk::DescriptorSet,
+  /// Samples the viewport texture, which is linear instead of sRGB.
+  linear: bool,
+  first_index: u32,
+  index_count: u32,
+  vertex_offset: i32,
+}
+
+#[derive(Clone, Debug, Default)]
+struct UiFrame {
+  vertices: HostBuffer,
+  indices: HostBuffer,
+  vertex_capacity: usize,
+  index_capacity: usize,
+  /// Screen size in points this frame's draws were laid out for.
+  screen: [f32; 2],
+  draws: Vec<UiDraw>,
+}
+
+/// Editor UI drawn over the presented image: egui textures, a pass on the swapchain images and per
+/// swapchain image vertex and index buffers. While the scene is drawn in a viewport texture the
+/// pass clears the image and the UI covers it, the viewport included.
+#[derive(Clone, Debug, Default)]
+pub struct UiData {
+  sampler: vk::Sampler,
+  set_layout: vk::DescriptorSetLayout,
+  pipeline_layout: vk::PipelineLayout,
+  descriptor_pool: vk::DescriptorPool,
+  textures: HashMap<TextureId, UiTexture>,
+  /// Samples `post.viewport` as `VIEWPORT_TEXTURE`, null without a viewport.
+  viewport_set: vk::DescriptorSet,
+  /// Freed by egui after the last frame was recorded, destroyed on the next sync.
+  pending_free: Vec<TextureId>,
+  pub max_texture_side: usize,
+  render_pass: vk::RenderPass,
+  framebuffers: Vec<vk::Framebuffer>,
+  pipeline: vk::Pipeline,
+  frames: Vec<UiFrame>,
+}
+
+/// Sampler, layouts and the texture pool; they don't depend on the swapchain.
+pub unsafe fn create_ui_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let sampler_info = vk::SamplerCreateInfo::builder()
+    .mag_filter(vk::Filter::LINEAR)
+    .min_filter(vk::Filter::LINEAR)
+    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    .min_lod(0.0)
+    .max_lod(0.0);
+
+  let bindings = &[layout_binding(
+    0,
+    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    vk::ShaderStageFlags::FRAGMENT,
+  )];
+  let set_layout = create_descriptor_set_layout(device, bindings)?;
+
+  let push_constant_range = vk::PushConstantRange::builder()
+    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+    .offset(0)
+    .size(size_of::<UiParams>() as u32);
+
+  let set_layouts = &[set_layout];
+  let push_constant_ranges = &[push_constant_range];
+  let layout_info = vk::PipelineLayoutCreateInfo::builder()
+    .set_layouts(set_layouts)
+    .push_constant_ranges(push_constant_ranges);
+
+  // Cada textura libera su set al destruirse.
+  let pool_size = vk::DescriptorPoolSize::builder()
+    .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+    .descriptor_count(MAX_UI_TEXTURES);
+
+  let pool_sizes = &[pool_size];
+  let pool_info = vk::DescriptorPoolCreateInfo::builder()
+    .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+    .pool_sizes(pool_sizes)
+    .max_sets(MAX_UI_TEXTURES);
+
+  let limits = instance.get_physical_device_properties(data.physical_device).limits;
+
+  let ui = &mut data.ui;
+  ui.sampler = device.create_sampler(&sampler_info, None)?;
+  ui.set_layout = set_layout;
+  ui.pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+  ui.descriptor_pool = device.create_descriptor_pool(&pool_info, None)?;
+  ui.max_texture_side = limits.max_image_dimension_2d as usize;
+
+  Ok(())
+}
+
+/// Render pass and framebuffers on the swapchain images, drawn after the final post pass, plus
+/// the pipeline, the vertex and index buffers and the set of the viewport texture.
+pub unsafe fn create_ui_swapchain_resources(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+) -> Result<()> {
+  let extent = data.swapchain_extent;
+  let present = vk::ImageLayout::PRESENT_SRC_KHR;
+
+  // Con viewport nadie mas escribe la imagen de la swapchain.
+  let (load_op, initial_layout) = match data.viewport {
+    Some(_) => (vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED),
+    None => (vk::AttachmentLoadOp::LOAD, present),
+  };
+  let render_pass = create_post_render_pass(device, data.swapchain_format, load_op, initial_layout, present)?;
+
+  let framebuffers = data
+    .swapchain_images_views
+    .iter()
+    .map(|v| create_framebuffer(device, render_pass, *v, extent))
+    .collect::<Result<Vec<_>>>()?;
+
+  let mut frames = vec![];
+
+  for _ in 0..data.swapchain_images.len() {
+    frames.push(create_ui_frame(
+      instance,
+      device,
+      data,
+      INITIAL_UI_VERTICES,
+      INITIAL_UI_INDICES,
+    )?);
+  }
+
+  let vert = include_bytes!("./pipe/shader/.tmp/ui/vert.spv");
+  let frag = include_bytes!("./pipe/shader/.tmp/ui/frag.spv");
+
+  let vertex_bindings = [vk::VertexInputBindingDescription::builder()
+    .binding(0)
+    .stride(size_of::<Vertex>() as u32)
+    .input_rate(vk::VertexInputRate::VERTEX)
+    .build()];
+
+  let attribute = |location: u32, format: vk::Format, offset: usize| {
+    vk::VertexInputAttributeDescription::builder()
+      .binding(0)
+      .location(location)
+      .format(format)
+      .offset(offset as u32)
+      .build()
+  };
+
+  let vertex_attributes = [
+    attribute(0, vk::Format::R32G32_SFLOAT, offset_of!(Vertex, pos)),
+    attribute(1, vk::Format::R32G32_SFLOAT, offset_of!(Vertex, uv)),
+    attribute(2, vk::Format::R8G8B8A8_UNORM, offset_of!(Vertex, color)),
+  ];
+
+  let mut desc = PipelineDesc::new(&vert[..], &frag[..], data.ui.pipeline_layout, render_pass, extent);
+  desc.vertex_bindings = &vertex_bindings;
+  desc.vertex_attributes = &vertex_attributes;
+  desc.cull_mode = vk::CullModeFlags::NONE;
+  desc.blend = BlendMode::Premultiplied;
+  desc.depth_test = false;
+  desc.depth_write = false;
+  desc.dynamic_viewport = true;
+
+  let viewport_set = match data.viewport {
+    Some(_) => {
+      let ui = &data.ui;
+      let set = allocate_descriptor_sets(device, ui.descriptor_pool, ui.set_layout, 1)?[0];
+      write_image_descriptor(device, set, 0, data.post.viewport.view, ui.sampler);
+      set
+    }
+    None => vk::DescriptorSet::null(),
+  };
+
+  let ui = &mut data.ui;
+  ui.pipeline = create_graphics_pipeline(device, &desc)?;
+  ui.viewport_set = viewport_set;
+  ui.render_pass = render_pass;
+  ui.framebuffers = framebuffers;
+  ui.frames = frames;
+
+  Ok(())
+}
+
+unsafe fn create_ui_frame(
+  instance: &Instance,
+  device: &Device,
+  data: &VulkanAppData,
+  vertex_capacity: usize,
+  index_capacity: usize,
+) -> Result<UiFrame> {
+  Ok(UiFrame {
+    vertices: create_host_buffer(
+      instance,
+      device,
+      data,
+      vertex_capacity * size_of::<Vertex>(),
+      vk::BufferUsageFlags::VERTEX_BUFFER,
+    )?,
+    indices: create_host_buffer(
+      instance,
+      device,
+      data,
+      index_capacity * size_of::<u32>(),
+      vk::BufferUsageFlags::INDEX_BUFFER,
+    )?,
+    vertex_capacity,
+    index_capacity,
+    ..Default::default()
+  })
+}
+
+/// Creates and patches the textures egui asked for and destroys the ones it freed a frame ago.
+/// Call before `update_ui`; `textures` is drained.
+pub unsafe fn sync_ui_textures(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  textures: &mut TexturesDelta,
+) -> Result<()> {
+  if !data.ui.pending_free.is_empty() {
+    device.queue_wait_idle(data.graphics_queue)?;
+
+    for id in std::mem::take(&mut data.ui.pending_free) {
+      if let Some(texture) = data.ui.textures.remove(&id) {
+        destroy_ui_texture(device, &data.ui, &texture)?;
+      }
+    }
+  }
+
+  for (id, delta) in textures.set.drain(..) {
+    let ImageData::Color(image) = &delta.image;
+    let patch = image
+      .pixels
+      .iter()
+      .flat_map(|c| [c.r(), c.g(), c.b(), c.a()])
+      .collect::<Vec<_>>();
+
+    let (size, pixels) = match (delta.pos, data.ui.textures.get(&id)) {
+      (Some([x, y]), Some(old)) => {
+        let mut pixels = old.pixels.clone();
+        let [width, height] = image.size;
+
+        for row in 0..height {
+          let start = ((y + row) * old.size[0] + x) * 4;
+          pixels[start..start + width * 4].copy_from_slice(&patch[row * width * 4..(row + 1) * width * 4]);
+        }
+
+        (old.size, pixels)
+      }
+      (None, _) => (image.size, patch),
+      (Some(_), None) => continue,
+    };
+
+    // `create_texture` espera a que la cola quede libre, asi que la textura anterior ya no se usa.
+    let texture = create_texture(
+      instance,
+      device,
+      data,
+      size[0] as u32,
+      size[1] as u32,
+      vk::Format::R8G8B8A8_UNORM,
+      &pixels,
+    )?;
+
+    let ui = &mut data.ui;
+    let set = match ui.textures.remove(&id) {
+      Some(old) => {
+        destroy_texture(device, &old.texture);
+        old.set
+      }
+      None => {
+        info!("[+] ui -> texture {:?}, {}x{}", id, size[0], size[1]);
+        allocate_descriptor_sets(device, ui.descriptor_pool, ui.set_layout, 1)?[0]
+      }
+    };
+
+    write_image_descriptor(device, set, 0, texture.view, ui.sampler);
+    ui.textures.insert(
+      id,
+      UiTexture {
+        texture,
+        set,
+        size,
+        pixels,
+      },
+    );
+  }
+
+  data.ui.pending_free.append(&mut textures.free);
+
+  Ok(())
+}
+
+unsafe fn destroy_ui_texture(device: &Device, ui: &UiData, texture: &UiTexture) -> Result<()> {
+  destroy_texture(device, &texture.texture);
+  device.free_descriptor_sets(ui.descriptor_pool, &[texture.set])?;
+
+  Ok(())
+}
+
+/// Uploads the tessellated UI for `image_index`, growing its buffers if they are too small.
+pub unsafe fn update_ui(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  image_index: usize,
+  output: &UiOutput,
+) -> Result<()> {
+  let extent = data.swapchain_extent;
+  let scale = output.pixels_per_point;
+  let mut vertices = Vec::<Vertex>::new();
+  let mut indices = Vec::<u32>::new();
+  let mut draws = vec![];
+
+  for clipped in &output.primitives {
+    // Los callbacks de pintado son de otros backends.
+    let Primitive::Mesh(mesh) = &clipped.primitive else {
+      continue;
+    };
+
+    let linear = mesh.texture_id == VIEWPORT_TEXTURE;
+    let set = match data.ui.textures.get(&mesh.texture_id) {
+      _ if linear => data.ui.viewport_set,
+      Some(texture) => texture.set,
+      None => continue,
+    };
+
+    if set.is_null() {
+      continue;
+    }
+
+    // Recorte en pixeles fisicos, dentro de la imagen.
+    let clip = clipped.clip_rect;
+    let min_x = (clip.min.x * scale).round().clamp(0.0, extent.width as f32) as i32;
+    let min_y = (clip.min.y * scale).round().clamp(0.0, extent.height as f32) as i32;
+    let max_x = (clip.max.x * scale).round().clamp(0.0, extent.width as f32) as i32;
+    let max_y = (clip.max.y * scale).round().clamp(0.0, extent.height as f32) as i32;
+
+    if max_x <= min_x || max_y <= min_y || mesh.indices.is_empty() {
+      continue;
+    }
+
+    draws.push(UiDraw {
+      scissor: vk::Rect2D {
+        offset: vk::Offset2D { x: min_x, y: min_y },
+        extent: vk::Extent2D {
+          width: (max_x - min_x) as u32,
+          height: (max_y - min_y) as u32,
+        },
+      },
+      set,
+      linear,
+      first_index: indices.len() as u32,
+      index_count: mesh.indices.len() as u32,
+      vertex_offset: vertices.len() as i32,
+    });
+
+    vertices.extend_from_slice(&mesh.vertices);
+    indices.extend_from_slice(&mesh.indices);
+  }
+
+  let frame = &data.ui.frames[image_index];
+
+  // El fence de esta imagen ya se espero: sus buffers se pueden sustituir.
+  if vertices.len() > frame.vertex_capacity || indices.len() > frame.index_capacity {
+    let vertex_capacity = vertices.len().max(frame.vertex_capacity).next_power_of_two();
+    let index_capacity = indices.len().max(frame.index_capacity).next_power_of_two();
+    let grown = create_ui_frame(instance, device, data, vertex_capacity, index_capacity)?;
+    let old = std::mem::replace(&mut data.ui.frames[image_index], grown);
+    destroy_host_buffer(device, &old.vertices);
+    destroy_host_buffer(device, &old.indices);
+  }
+
+  let frame = &mut data.ui.frames[image_index];
+  write_memory(device, frame.vertices.memory, &vertices)?;
+  write_memory(device, frame.indices.memory, &indices)?;
+
+  frame.screen = [extent.width as f32 / scale, extent.height as f32 / scale];
+  frame.draws = draws;
+
+  Ok(())
+}
+
+/// Draws the UI over the swapchain image in its own pass; must run after `record_post`.
+pub unsafe fn record_ui(device: &Device, data: &VulkanAppData, command_buffer: vk::CommandBuffer, image_index: usize) {
+  let ui = &data.ui;
+  let frame = &ui.frames[image_index];
+
+  if frame.draws.is_empty() && data.viewport.is_none() {
+    return;
+  }
+
+  let extent = data.swapchain_extent;
+  let render_area = vk::Rect2D::builder().offset(vk::Offset2D::default()).extent(extent);
+
+  let clear_values = &[vk::ClearValue {
+    color: vk::ClearColorValue {
+      float32: [0.0, 0.0, 0.0, 1.0],
+    },
+  }];
+
+  let info = vk::RenderPassBeginInfo::builder()
+    .render_pass(ui.render_pass)
+    .framebuffer(ui.framebuffers[image_index])
+    .render_area(render_area)
+    .clear_values(clear_values);
+
+  let viewport = vk::Viewport::builder()
+    .x(0.0)
+    .y(0.0)
+    .width(extent.width as f32)
+    .height(extent.height as f32)
+    .min_depth(0.0)
+    .max_depth(1.0);
+
+  let display = OutputParams::new(data.display_output, &data.present_config.hdr, 1.0);
+
+  device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+  device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, ui.pipeline);
+  device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+  device.cmd_bind_vertex_buffers(command_buffer, 0, &[frame.vertices.buffer], &[0]);
+  device.cmd_bind_index_buffer(command_buffer, frame.indices.buffer, 0, vk::IndexType::UINT32);
+
+  for draw in &frame.draws {
+    let params = UiParams {
+      display,
+      screen: [frame.screen[0], frame.screen[1], draw.linear as u32 as f32, 0.0],
+    };
+
+    device.cmd_push_constants(
+      command_buffer,
+      ui.pipeline_layout,
+      vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+      0,
+      as_bytes(&params),
+    );
+    device.cmd_set_scissor(command_buffer, 0, &[draw.scissor]);
+    device.cmd_bind_descriptor_sets(
+      command_buffer,
+      vk::PipelineBindPoint::GRAPHICS,
+      ui.pipeline_layout,
+      0,
+      &[draw.set],
+      &[],
+    );
+    device.cmd_draw_indexed(
+      command_buffer,
+      draw.index_count,
+      1,
+      draw.first_index,
+      draw.vertex_offset,
+      0,
+    );
+  }
+
+  device.cmd_end_render_pass(command_buffer);
+}
+
+pub unsafe fn destroy_ui_swapchain_resources(device: &Device, data: &mut VulkanAppData) {
+  let ui = &mut data.ui;
+
+  if !ui.viewport_set.is_null() {
+    device.free_descriptor_sets(ui.descriptor_pool, &[ui.viewport_set]).ok();
+    ui.viewport_set = vk::DescriptorSet::null();
+  }
+
+  device.destroy_pipeline(ui.pipeline, None);
+  ui.framebuffers
+    .drain(..)
+    .for_each(|f| device.destroy_framebuffer(f, None));
+  device.destroy_render_pass(ui.render_pass, None);
+
+  for frame in ui.frames.drain(..) {
+    destroy_host_buffer(device, &frame.vertices);
+    destroy_host_buffer(device, &frame.indices);
+  }
+}
+
+pub unsafe fn destroy_ui_system(device: &Device, data: &mut VulkanAppData) {
+  let ui = &mut data.ui;
+
+  // El pool libera los sets de las texturas.
+  for (_, texture) in ui.textures.drain() {
+    destroy_texture(device, &texture.texture);
+  }
+
+  device.destroy_descriptor_pool(ui.descriptor_pool, None);
+  device.destroy_pipeline_layout(ui.pipeline_layout, None);
+  device.destroy_descriptor_set_layout(ui.set_layout, None);
+  device.destroy_sampler(ui.sampler, None);
+}
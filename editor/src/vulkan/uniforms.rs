@@ -0,0 +1,120 @@
+use std::mem::size_of;
+
+use anyhow::{Ok, Result};
+use cgmath::{vec4, Matrix4, SquareMatrix, Vector4};
+use vulkanalia::{vk, Device, Instance};
+
+use super::buffers::{create_buffer, write_memory};
+use super::descriptors::{
+  allocate_descriptor_sets, create_descriptor_set_layout, layout_binding, write_buffer_descriptor,
+};
+use super::environment::PREFILTER_MIPS;
+use super::VulkanAppData;
+use crate::scene::Scene;
+
+/// Per-frame values shared by every shader at `set = 0, binding = 0` (std140).
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct FrameUniforms {
+  pub view: Matrix4<f32>,
+  pub proj: Matrix4<f32>,
+  pub view_proj: Matrix4<f32>,
+  pub inverse_proj: Matrix4<f32>,
+  pub camera_position: Vector4<f32>,
+  /// x: seconds since start, y: delta time, zw: viewport size in pixels.
+  pub time: Vector4<f32>,
+  /// x: near plane, y: far plane.
+  pub clip: Vector4<f32>,
+  /// x: diffuse and specular intensity, y: sky intensity, z: sky blur, w: max lod of the prefiltered map.
+  pub environment: Vector4<f32>,
+  /// x: `ViewMode`.
+  pub debug: [u32; 4],
+}
+
+pub unsafe fn create_frame_descriptor_set_layout(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let stages = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT | vk::ShaderStageFlags::COMPUTE;
+  let bindings = &[layout_binding(0, vk::DescriptorType::UNIFORM_BUFFER, stages)];
+
+  data.frame_set_layout = create_descriptor_set_layout(device, bindings)?;
+
+  Ok(())
+}
+
+pub unsafe fn create_uniform_buffers(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  data.uniform_buffers.clear();
+  data.uniform_buffers_memory.clear();
+
+  for _ in 0..data.swapchain_images.len() {
+    let (uniform_buffer, uniform_buffer_memory) = create_buffer(
+      instance,
+      device,
+      data,
+      size_of::<FrameUniforms>() as u64,
+      vk::BufferUsageFlags::UNIFORM_BUFFER,
+      vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+    )?;
+
+    data.uniform_buffers.push(uniform_buffer);
+    data.uniform_buffers_memory.push(uniform_buffer_memory);
+  }
+
+  Ok(())
+}
+
+pub unsafe fn create_frame_descriptor_sets(device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  data.frame_descriptor_sets = allocate_descriptor_sets(
+    device,
+    data.descriptor_pool,
+    data.frame_set_layout,
+    data.swapchain_images.len(),
+  )?;
+
+  for (set, buffer) in data.frame_descriptor_sets.iter().zip(data.uniform_buffers.iter()) {
+    write_buffer_descriptor(
+      device,
+      *set,
+      0,
+      vk::DescriptorType::UNIFORM_BUFFER,
+      *buffer,
+      size_of::<FrameUniforms>() as u64,
+    );
+  }
+
+  Ok(())
+}
+
+pub unsafe fn update_frame_uniforms(
+  device: &Device,
+  data: &VulkanAppData,
+  image_index: usize,
+  scene: &Scene,
+  time: f32,
+  delta_time: f32,
+) -> Result<()> {
+  let extent = data.render_extent();
+  let aspect = extent.width as f32 / extent.height.max(1) as f32;
+
+  let view = scene.camera.view();
+  let proj = scene.camera.projection(aspect);
+  let position = scene.camera.position;
+  let environment = &scene.environment;
+
+  let uniforms = FrameUniforms {
+    view,
+    proj,
+    view_proj: proj * view,
+    inverse_proj: proj.invert().unwrap_or_else(Matrix4::identity),
+    camera_position: vec4(position.x, position.y, position.z, 1.0),
+    time: vec4(time, delta_time, extent.width as f32, extent.height as f32),
+    clip: vec4(scene.camera.near, scene.camera.far, 0.0, 0.0),
+    environment: vec4(
+      environment.intensity,
+      environment.sky_intensity,
+      environment.sky_blur,
+      (PREFILTER_MIPS - 1) as f32,
+    ),
+    debug: [data.view_mode as u32, 0, 0, 0],
+  };
+
+  write_memory(device, data.uniform_buffers_memory[image_index], &[uniforms])
+}
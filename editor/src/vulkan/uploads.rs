@@ -0,0 +1,490 @@
+use std::mem::{size_of_val, take};
+use std::ptr::copy_nonoverlapping as memcpy;
+
+use anyhow::{Ok, Result};
+use log::info;
+use vulkanalia::{
+  vk::{self, DeviceV1_0, HasBuilder},
+  Device, Instance,
+};
+
+use super::buffers::{create_buffer, get_memory_type_index};
+use super::images::create_image_view;
+use super::queue_family::QueueFamilyIndices;
+use super::textures::{destroy_texture, Texture};
+use super::{VulkanAppData, MAX_FRAMES_IN_FLIGHT};
+
+/// Asset uploads: textures and buffers are created right away, and their data is copied on the
+/// transfer queue with the next frame, all of it through one staging buffer. The frame waits
+/// for the copies on the GPU only, the CPU never does.
+#[derive(Clone, Debug, Default)]
+pub struct UploadData {
+  pool: vk::CommandPool,
+  /// Graphics and transfer families when they differ, the uploaded resources are shared by both.
+  families: Vec<u32>,
+  /// Signaled by the copies of each frame in flight, for its draw to wait on.
+  semaphores: Vec<vk::Semaphore>,
+  pending: Vec<PendingUpload>,
+  in_flight: Vec<UploadBatch>,
+}
+
+/// Data waiting for `flush_uploads`.
+#[derive(Clone, Debug)]
+enum PendingUpload {
+  Buffer {
+    buffer: vk::Buffer,
+    bytes: Vec<u8>,
+  },
+  Image {
+    image: vk::Image,
+    width: u32,
+    height: u32,
+    texel_size: usize,
+    levels: Vec<Vec<u8>>,
+  },
+}
+
+impl PendingUpload {
+  /// Where each copy region of the upload starts in the staging buffer when the upload is
+  /// written from `offset` on, and where the next upload can start. Image regions must start at
+  /// a multiple of the texel size, and of 4 on a transfer-only queue.
+  fn regions(&self, offset: usize) -> (Vec<usize>, usize) {
+    match self {
+      Self::Buffer { bytes, .. } => (vec![offset], offset + bytes.len()),
+      Self::Image { texel_size, levels, .. } => {
+        let alignment = (*texel_size).max(4);
+        let mut offsets = vec![];
+        let mut end = offset;
+
+        for pixels in levels {
+          let start = end.next_multiple_of(alignment);
+          offsets.push(start);
+          end = start + pixels.len();
+        }
+
+        (offsets, end)
+      }
+    }
+  }
+}
+
+/// Copies submitted together, freed once their fence signals.
+#[derive(Copy, Clone, Debug)]
+struct UploadBatch {
+  fence: vk::Fence,
+  command_buffer: vk::CommandBuffer,
+  staging: vk::Buffer,
+  staging_memory: vk::DeviceMemory,
+}
+
+pub unsafe fn create_upload_system(instance: &Instance, device: &Device, data: &mut VulkanAppData) -> Result<()> {
+  let indices = QueueFamilyIndices::get(instance, data, data.physical_device)?;
+
+  let info = vk::CommandPoolCreateInfo::builder()
+    .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+    .queue_family_index(indices.transfer);
+
+  let semaphore_info = vk::SemaphoreCreateInfo::builder();
+  let semaphores = (0..MAX_FRAMES_IN_FLIGHT)
+    .map(|_| device.create_semaphore(&semaphore_info, None))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let families = if indices.transfer == indices.graphics {
+    vec![]
+  } else {
+    info!("[+] uploads -> transfer queue family {}", indices.transfer);
+    vec![indices.graphics, indices.transfer]
+  };
+
+  data.uploads = UploadData {
+    pool: device.create_command_pool(&info, None)?,
+    families,
+    semaphores,
+    ..Default::default()
+  };
+
+  Ok(())
+}
+
+/// Sharing of the uploaded resources: concurrent between the families, so the draws don't need
+/// to take ownership from the transfer queue.
+fn sharing(families: &[u32]) -> vk::SharingMode {
+  if families.is_empty() {
+    vk::SharingMode::EXCLUSIVE
+  } else {
+    vk::SharingMode::CONCURRENT
+  }
+}
+
+/// A sampled texture with `levels` as its mips, filled by the next `flush_uploads`.
+pub unsafe fn upload_texture(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  width: u32,
+  height: u32,
+  format: vk::Format,
+  levels: &[Vec<u8>],
+) -> Result<Texture> {
+  let families = &data.uploads.families;
+  let info = vk::ImageCreateInfo::builder()
+    .image_type(vk::ImageType::_2D)
+    .extent(vk::Extent3D {
+      width,
+      height,
+      depth: 1,
+    })
+    .mip_levels(levels.len() as u32)
+    .array_layers(1)
+    .format(format)
+    .tiling(vk::ImageTiling::OPTIMAL)
+    .initial_layout(vk::ImageLayout::UNDEFINED)
+    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+    .sharing_mode(sharing(families))
+    .queue_family_indices(families)
+    .samples(vk::SampleCountFlags::_1);
+
+  let image = device.create_image(&info, None)?;
+
+  let requirements = device.get_image_memory_requirements(image);
+  let memory_info = vk::MemoryAllocateInfo::builder()
+    .allocation_size(requirements.size)
+    .memory_type_index(get_memory_type_index(
+      instance,
+      data,
+      vk::MemoryPropertyFlags::DEVICE_LOCAL,
+      requirements,
+    )?);
+
+  let memory = device.allocate_memory(&memory_info, None)?;
+  device.bind_image_memory(image, memory, 0)?;
+
+  let view = create_image_view(device, image, format, vk::ImageAspectFlags::COLOR, levels.len() as u32)?;
+
+  // El tamano del texel sale del primer nivel, que tiene uno por pixel.
+  let texel_size = levels.first().map_or(0, Vec::len) / (width as usize * height as usize).max(1);
+
+  data.uploads.pending.push(PendingUpload::Image {
+    image,
+    width,
+    height,
+    texel_size,
+    levels: levels.to_vec(),
+  });
+
+  Ok(Texture { image, memory, view })
+}
+
+/// A device-local buffer holding `values`, filled by the next `flush_uploads`.
+pub unsafe fn upload_buffer<T: Copy>(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  usage: vk::BufferUsageFlags,
+  values: &[T],
+) -> Result<(vk::Buffer, vk::DeviceMemory)> {
+  let size = size_of_val(values);
+  let families = &data.uploads.families;
+  let info = vk::BufferCreateInfo::builder()
+    .size(size as vk::DeviceSize)
+    .usage(usage | vk::BufferUsageFlags::TRANSFER_DST)
+    .sharing_mode(sharing(families))
+    .queue_family_indices(families);
+
+  let buffer = device.create_buffer(&info, None)?;
+
+  let requirements = device.get_buffer_memory_requirements(buffer);
+  let memory_info = vk::MemoryAllocateInfo::builder()
+    .allocation_size(requirements.size)
+    .memory_type_index(get_memory_type_index(
+      instance,
+      data,
+      vk::MemoryPropertyFlags::DEVICE_LOCAL,
+      requirements,
+    )?);
+
+  let memory = device.allocate_memory(&memory_info, None)?;
+  device.bind_buffer_memory(buffer, memory, 0)?;
+
+  let bytes = std::slice::from_raw_parts(values.as_ptr().cast::<u8>(), size).to_vec();
+  data.uploads.pending.push(PendingUpload::Buffer { buffer, bytes });
+
+  Ok((buffer, memory))
+}
+
+/// Destroys a texture from `upload_texture`, dropping its copy if it's still pending.
+pub unsafe fn destroy_uploaded_texture(device: &Device, data: &mut VulkanAppData, texture: &Texture) {
+  let pending = &mut data.uploads.pending;
+  pending.retain(|u| !matches!(u, PendingUpload::Image { image, .. } if *image == texture.image));
+
+  destroy_texture(device, texture);
+}
+
+/// Destroys a buffer from `upload_buffer`, dropping its copy if it's still pending.
+pub unsafe fn destroy_uploaded_buffer(
+  device: &Device,
+  data: &mut VulkanAppData,
+  buffer: vk::Buffer,
+  memory: vk::DeviceMemory,
+) {
+  let pending = &mut data.uploads.pending;
+  pending.retain(|u| !matches!(u, PendingUpload::Buffer { buffer: b, .. } if *b == buffer));
+
+  device.destroy_buffer(buffer, None);
+  device.free_memory(memory, None);
+}
+
+/// Submits the pending copies on the transfer queue. Returns the semaphore they signal, which
+/// the draw of `frame` has to wait on, or `None` if nothing was pending.
+pub unsafe fn flush_uploads(
+  instance: &Instance,
+  device: &Device,
+  data: &mut VulkanAppData,
+  frame: usize,
+) -> Result<Option<vk::Semaphore>> {
+  // Los lotes terminados sueltan su staging.
+  let (done, in_flight) = take(&mut data.uploads.in_flight)
+    .into_iter()
+    .partition::<Vec<_>, _>(|b| device.get_fence_status(b.fence) == Result::Ok(vk::SuccessCode::SUCCESS));
+
+  for batch in done {
+    device.destroy_fence(batch.fence, None);
+    device.free_command_buffers(data.uploads.pool, &[batch.command_buffer]);
+    device.destroy_buffer(batch.staging, None);
+    device.free_memory(batch.staging_memory, None);
+  }
+
+  data.uploads.in_flight = in_flight;
+
+  if data.uploads.pending.is_empty() {
+    return Ok(None);
+  }
+
+  let pending = take(&mut data.uploads.pending);
+  let size = pending.iter().fold(0, |offset, upload| upload.regions(offset).1) as vk::DeviceSize;
+
+  let (staging, staging_memory) = create_buffer(
+    instance,
+    device,
+    data,
+    size,
+    vk::BufferUsageFlags::TRANSFER_SRC,
+    vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+  )?;
+
+  let info = vk::CommandBufferAllocateInfo::builder()
+    .level(vk::CommandBufferLevel::PRIMARY)
+    .command_pool(data.uploads.pool)
+    .command_buffer_count(1);
+
+  let command_buffer = device.allocate_command_buffers(&info)?[0];
+  let info = vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+  device.begin_command_buffer(command_buffer, &info)?;
+
+  let mapped = device
+    .map_memory(staging_memory, 0, size, vk::MemoryMapFlags::empty())?
+    .cast::<u8>();
+  let mut offset = 0;
+
+  for upload in &pending {
+    let (offsets, end) = upload.regions(offset);
+    offset = end;
+
+    match upload {
+      PendingUpload::Buffer { buffer, bytes } => {
+        memcpy(bytes.as_ptr(), mapped.add(offsets[0]), bytes.len());
+
+        let region = vk::BufferCopy::builder()
+          .src_offset(offsets[0] as vk::DeviceSize)
+          .size(bytes.len() as vk::DeviceSize);
+        device.cmd_copy_buffer(command_buffer, staging, *buffer, &[region]);
+      }
+      PendingUpload::Image {
+        image,
+        width,
+        height,
+        levels,
+        ..
+      } => {
+        let range = vk::ImageSubresourceRange {
+          aspect_mask: vk::ImageAspectFlags::COLOR,
+          base_mip_level: 0,
+          level_count: levels.len() as u32,
+          base_array_layer: 0,
+          layer_count: 1,
+        };
+
+        image_barrier(
+          device,
+          command_buffer,
+          *image,
+          range,
+          (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL),
+          (vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER),
+        );
+
+        for (mip, (pixels, &offset)) in levels.iter().zip(&offsets).enumerate() {
+          memcpy(pixels.as_ptr(), mapped.add(offset), pixels.len());
+
+          let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(mip as u32)
+            .base_array_layer(0)
+            .layer_count(1);
+
+          let region = vk::BufferImageCopy::builder()
+            .buffer_offset(offset as vk::DeviceSize)
+            .image_subresource(subresource)
+            .image_extent(vk::Extent3D {
+              width: (width >> mip).max(1),
+              height: (height >> mip).max(1),
+              depth: 1,
+            });
+
+          device.cmd_copy_buffer_to_image(
+            command_buffer,
+            staging,
+            *image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+          );
+        }
+
+        // El frame que lo usa espera al semaforo, que ya hace visibles las escrituras.
+        image_barrier(
+          device,
+          command_buffer,
+          *image,
+          range,
+          (
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+          ),
+          (vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::BOTTOM_OF_PIPE),
+        );
+      }
+    }
+  }
+
+  device.unmap_memory(staging_memory);
+  device.end_command_buffer(command_buffer)?;
+
+  let fence = device.create_fence(&vk::FenceCreateInfo::builder(), None)?;
+  let semaphore = data.uploads.semaphores[frame];
+  let command_buffers = &[command_buffer];
+  let signal_semaphores = &[semaphore];
+  let submit_info = vk::SubmitInfo::builder()
+    .command_buffers(command_buffers)
+    .signal_semaphores(signal_semaphores);
+
+  device.queue_submit(data.transfer_queue, &[submit_info], fence)?;
+
+  data.uploads.in_flight.push(UploadBatch {
+    fence,
+    command_buffer,
+    staging,
+    staging_memory,
+  });
+
+  Ok(Some(semaphore))
+}
+
+unsafe fn image_barrier(
+  device: &Device,
+  command_buffer: vk::CommandBuffer,
+  image: vk::Image,
+  range: vk::ImageSubresourceRange,
+  (old, new): (vk::ImageLayout, vk::ImageLayout),
+  (src, dst): (vk::PipelineStageFlags, vk::PipelineStageFlags),
+) {
+  let (src_access_mask, dst_access_mask) = match old {
+    vk::ImageLayout::UNDEFINED => (vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE),
+    _ => (vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::empty()),
+  };
+
+  let barrier = vk::ImageMemoryBarrier::builder()
+    .old_layout(old)
+    .new_layout(new)
+    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+    .image(image)
+    .subresource_range(range)
+    .src_access_mask(src_access_mask)
+    .dst_access_mask(dst_access_mask);
+
+  device.cmd_pipeline_barrier(
+    command_buffer,
+    src,
+    dst,
+    vk::DependencyFlags::empty(),
+    &[] as &[vk::MemoryBarrier],
+    &[] as &[vk::BufferMemoryBarrier],
+    &[barrier],
+  );
+}
+
+pub unsafe fn destroy_upload_system(device: &Device, data: &mut VulkanAppData) {
+  let uploads = &mut data.uploads;
+
+  for batch in uploads.in_flight.drain(..) {
+    device.destroy_fence(batch.fence, None);
+    device.destroy_buffer(batch.staging, None);
+    device.free_memory(batch.staging_memory, None);
+  }
+
+  uploads
+    .semaphores
+    .iter()
+    .for_each(|s| device.destroy_semaphore(*s, None));
+  device.destroy_command_pool(uploads.pool, None);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn buffer(len: usize) -> PendingUpload {
+    PendingUpload::Buffer {
+      buffer: vk::Buffer::default(),
+      bytes: vec![0; len],
+    }
+  }
+
+  /// `width` by `height` image with its whole mip chain, `texel_size` bytes per texel.
+  fn image(width: u32, height: u32, texel_size: usize) -> PendingUpload {
+    let levels = (0..=width.max(height).ilog2())
+      .map(|mip| ((width >> mip).max(1) * (height >> mip).max(1)) as usize)
+      .map(|texels| vec![0; texels * texel_size])
+      .collect();
+
+    PendingUpload::Image {
+      image: vk::Image::default(),
+      width,
+      height,
+      texel_size,
+      levels,
+    }
+  }
+
+  #[test]
+  fn buffers_are_packed() {
+    assert_eq!(buffer(6).regions(0), (vec![0], 6));
+    assert_eq!(buffer(6).regions(3), (vec![3], 9));
+  }
+
+  #[test]
+  fn images_after_a_buffer_start_aligned() {
+    // Detras de 6 bytes de un buffer: mips de 4x4 en RGBA8 y de 2x2 en RGBA16F.
+    assert_eq!(image(4, 4, 4).regions(6), (vec![8, 72, 88], 92));
+    assert_eq!(image(2, 2, 8).regions(6), (vec![8, 40], 48));
+  }
+
+  #[test]
+  fn every_mip_starts_at_a_multiple_of_4() {
+    // Los niveles de 1 byte por texel dejan el siguiente desalineado.
+    let (offsets, end) = image(2, 1, 1).regions(0);
+
+    assert_eq!(offsets, [0, 4]);
+    assert_eq!(end, 5);
+  }
+}
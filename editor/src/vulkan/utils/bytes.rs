@@ -0,0 +1,12 @@
+use std::mem::size_of;
+use std::slice;
+
+/// Views a `#[repr(C)]` value as raw bytes, e.g. for push constants.
+///
+/// # Safety
+///
+/// `T` must be `#[repr(C)]` (or a primitive or array of them) with no padding bytes, since
+/// every byte of the value is read.
+pub unsafe fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+  slice::from_raw_parts(value as *const T as *const u8, size_of::<T>())
+}
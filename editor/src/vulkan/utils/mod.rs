@@ -1 +1,2 @@
+pub mod bytes;
 pub mod sagitario_error;
@@ -29,7 +29,7 @@ pub unsafe fn validations_layers(entry: &Entry) -> Result<Vec<*const i8>, Error>
     Vec::new()
   };
 
-  return Ok(layers);
+  Ok(layers)
 }
 
 pub extern "system" fn debug_callback(
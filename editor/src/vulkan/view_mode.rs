@@ -0,0 +1,84 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// What the viewport shows instead of (or on top of) the lit scene. The value is sent to the
+/// shaders in `FrameUniforms::debug.x`, keep `common/frame.glsl` in sync.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ViewMode {
+  #[default]
+  Lit = 0,
+  /// Tints every pixel with the directional shadow cascade it samples.
+  ShadowCascades = 1,
+  /// Triangle edges only, needs `fillModeNonSolid`.
+  Wireframe = 2,
+  /// World space vertex normals.
+  Normals = 3,
+  /// World space tangents of the normal mapping frame.
+  Tangents = 4,
+  /// Checker pattern over the UVs, red and green follow u and v.
+  UvChecker = 5,
+  /// Tints the albedo with the mip level it samples.
+  MipLevel = 6,
+  /// Heatmap of how many fragments land on every pixel.
+  Overdraw = 7,
+  /// Logarithmic view depth between the near (white) and far (black) planes.
+  Depth = 8,
+}
+
+impl ViewMode {
+  pub const ALL: &'static [Self] = &[
+    Self::Lit,
+    Self::ShadowCascades,
+    Self::Wireframe,
+    Self::Normals,
+    Self::Tangents,
+    Self::UvChecker,
+    Self::MipLevel,
+    Self::Overdraw,
+    Self::Depth,
+  ];
+
+  pub fn next(self) -> Self {
+    let index = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+    Self::ALL[(index + 1) % Self::ALL.len()]
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Lit => "lit",
+      Self::ShadowCascades => "shadow-cascades",
+      Self::Wireframe => "wireframe",
+      Self::Normals => "normals",
+      Self::Tangents => "tangents",
+      Self::UvChecker => "uv-checker",
+      Self::MipLevel => "mip-level",
+      Self::Overdraw => "overdraw",
+      Self::Depth => "depth",
+    }
+  }
+
+  /// Modes that show data instead of lighting: no sky, bloom, tonemapping or grading.
+  pub fn is_debug(self) -> bool {
+    !matches!(self, Self::Lit | Self::ShadowCascades)
+  }
+}
+
+impl fmt::Display for ViewMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+impl FromStr for ViewMode {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL.iter().copied().find(|m| m.name() == s).ok_or_else(|| {
+      let names = Self::ALL.iter().map(|m| m.name()).collect::<Vec<_>>();
+      anyhow!("Unknown view mode `{}`, expected one of: {}.", s, names.join(", "))
+    })
+  }
+}